@@ -6,9 +6,38 @@ use std::{
 
 use regex::Regex;
 
-use crate::evaluation::{evaluate_and_compare, EvaluationError, Random};
+use crate::{
+    evaluation::{
+        evaluate_and_compare, evaluate_expression, evaluate_operands, EvaluationError, Random,
+        Rounding,
+    },
+    file::is_page_on_path,
+};
 
 pub const GAME_OVER_KEYWORD: &str = "game over";
+/// Records in this category are increased by one every time the player moves to another page
+pub const AUTO_RECORD_CATEGORY: &str = "auto";
+/// Marks a record that isn't shown to the player, either as its category or as the last element of the record
+pub const HIDDEN_RECORD_KEYWORD: &str = "hidden";
+/// Marks a record whose value is kept between adventures, as the last element of the record
+pub const GLOBAL_RECORD_KEYWORD: &str = "global";
+/// Records in this category aren't changed by side effects, their value is computed from an expression whenever they're shown
+pub const DERIVED_RECORD_CATEGORY: &str = "derived";
+/// Side effect key that unlocks the achievement named by its value instead of changing a record
+pub const ACHIEVEMENT_KEYWORD: &str = "achievement";
+/// Side effect value starting with this asks the player for the value, the rest of the value is the question shown to them
+pub const INPUT_KEYWORD: &str = "ask:";
+/// File name of the starting page of adventures created from the example template
+pub const EXAMPLE_PAGE_NAME: &str = "start";
+/// Starting page of adventures created from the example template, showing a condition, a side effect and a way to end the adventure
+pub const EXAMPLE_PAGE: &str = "title: The Beginning
+story: [hero] wakes up in a small room with [gold] gold coins and [health] health. Replace this text with the opening scene of your adventure, keywords in square brackets show current values of records and names.
+choice: Search the room{result: search}
+choice: Open the door{condition: rich}{result: game over}
+condition: rich;[gold];>;0
+result: search;start;gold;1d6";
+/// Header row of records exported as comma separated values
+pub const RECORDS_CSV_HEADER: &str = "name,category,default,hidden,min,max";
 
 /// Describes an error that might have occured during parsing of adventure element
 #[derive(Debug)]
@@ -19,16 +48,46 @@ pub enum ParsingError {
     Invalid(String),
     IncomplatePage(Page),
     MissingRecord(String),
+    InvalidComparison(String),
 }
 /// Holds basic information about adventure, including records, names and path where all the pages can be loaded from
 #[derive(Default, Clone)]
 pub struct Adventure {
     pub title: String,
     pub description: String,
+    pub author: String,
+    pub version: String,
+    pub date: String,
+    /// How results of division in expressions are rounded
+    pub rounding: Rounding,
+    /// Hides values of evaluated tests from the player
+    pub hide_rolls: bool,
+    /// Color of the story text as 0xRRGGBB, None uses the default color
+    pub theme_color: Option<u32>,
+    /// Name of the font used for the story text, None uses the default font
+    pub theme_font: Option<String>,
+    /// Size of the story text, None uses the default size
+    pub theme_size: Option<i32>,
+    /// Color of the game screen background as 0xRRGGBB, None uses the background image
+    pub theme_background: Option<u32>,
+    /// Color of the choice buttons as 0xRRGGBB, None uses the default color
+    pub theme_accent: Option<u32>,
     pub path: String,
     pub start: String,
+    /// Adventures of the same series share their global records, if empty the title is used instead
+    pub series: String,
+    /// Additional named pages the player can begin the adventure from, in the order they were declared
+    pub chapters: Vec<Chapter>,
+    /// Achievements the player can unlock through results, in the order they were declared
+    pub achievements: Vec<Achievement>,
     pub records: HashMap<String, Record>,
     pub names: HashMap<String, Name>,
+    /// Results any page can lead to, a result of the page with the same name is used instead
+    pub results: HashMap<String, StoryResult>,
+    /// Words the spellchecker accepts in story texts of the adventure, like names of places and characters
+    pub dictionary: Vec<String>,
+    /// Keys not recognized by this version of the game with their values, in the order they were read, kept so they're not lost when saving
+    pub unknown_keys: Vec<(String, String)>,
 }
 /// Represents a numeric value that is tracked throughout an adventure
 ///
@@ -38,6 +97,28 @@ pub struct Record {
     pub category: String,
     pub name: String,
     pub value: i32,
+    /// Hidden records work like any other but they're not shown to the player
+    pub hidden: bool,
+    /// Expression the value of a derived record is computed from, empty for other records
+    pub expression: String,
+    /// Global records keep their value between playthroughs and adventures of the same series
+    pub global: bool,
+    /// Smallest value the record can have, None if it isn't limited
+    pub min: Option<i32>,
+    /// Largest value the record can have, None if it isn't limited
+    pub max: Option<i32>,
+}
+/// Named entry point of an adventure, letting the player begin from a page other than the start
+#[derive(Clone, PartialEq, Debug)]
+pub struct Chapter {
+    pub name: String,
+    pub page: String,
+}
+/// Goal of an adventure, unlocked by a result with the achievement side effect and remembered between playthroughs
+#[derive(Clone, PartialEq, Debug)]
+pub struct Achievement {
+    pub id: String,
+    pub description: String,
 }
 /// Represents a string value that is displayable within adventure page story and title
 ///
@@ -48,17 +129,26 @@ pub struct Name {
     pub value: String,
 }
 /// Holds both title and story text for an individual page, as well as choices leading to other pages
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Page {
     pub title: String,
+    /// Labels for organizing pages in the editor, the outline groups pages by them and the page list can be filtered by them.
+    /// They're never shown in the game
+    pub tags: Vec<String>,
     pub story: String,
     pub choices: Vec<Choice>,
+    /// Choices are shown to the player in a random order each time the page is entered
+    pub shuffle: bool,
     pub conditions: HashMap<String, Condition>,
     pub tests: HashMap<String, Test>,
     pub results: HashMap<String, StoryResult>,
+    pub randoms: HashMap<String, RandomResult>,
+    pub switches: HashMap<String, Switch>,
+    /// Keys not recognized by this version of the game with their values, in the order they were read, kept so they're not lost when saving
+    pub unknown_keys: Vec<(String, String)>,
 }
 /// Helper enum for comparing two expressions
-#[derive(Debug, Eq, PartialEq, Default)]
+#[derive(Debug, Eq, PartialEq, Default, Clone)]
 pub enum Comparison {
     #[default]
     Greater,
@@ -71,7 +161,7 @@ pub enum Comparison {
 /// Holds information allowing a story page to transition to another page
 ///
 /// Results can also hold a list of pairs for mutating adventure records and names allowing those to change in reaction to user choice
-#[derive(Debug, Default, PartialEq)]
+#[derive(Debug, Default, PartialEq, Clone)]
 pub struct StoryResult {
     pub name: String,
     pub next_page: String,
@@ -79,7 +169,7 @@ pub struct StoryResult {
     pub side_effects: HashMap<String, String>,
 }
 /// Holds expressions that based on their evaluation and comparison, lead to two different results of a page.
-#[derive(Debug, Default, PartialEq)]
+#[derive(Debug, Default, PartialEq, Clone)]
 pub struct Test {
     pub name: String,
     pub expression_r: String,
@@ -88,18 +178,69 @@ pub struct Test {
     pub success_result: String,
     pub failure_result: String,
 }
+/// Values both expressions of a test evaluated to, shown to the player so they can see what was rolled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TestRoll {
+    pub left: i32,
+    pub right: i32,
+    pub success: bool,
+}
+/// Leads to one of several pages picked at random, pages with higher weight are picked more often
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct RandomResult {
+    pub name: String,
+    /// Pairs of page name and its weight, weights are always above zero
+    pub outcomes: Vec<(String, i32)>,
+}
+/// Range of values the expression of a switch is matched against, either end can be left open
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct ValueRange {
+    pub min: Option<i32>,
+    pub max: Option<i32>,
+}
+/// Leads to one of several results depending on which range the value of an expression falls into
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Switch {
+    pub name: String,
+    pub expression: String,
+    /// Ranges and names of results they lead to, the first range containing the value is used
+    pub branches: Vec<(ValueRange, String)>,
+    /// Result used when the value isn't in any of the ranges, empty if there's none
+    pub default_result: String,
+}
 /// Represents a text available to player as a choice in response to presented story
 ///
-/// The choice have either a test or a result that it points to, allowing progression to a different page
-#[derive(Debug, Default, PartialEq)]
+/// The choice have either a test, a switch, a random result or a result that it points to, allowing progression to a different page
+#[derive(Debug, Default, PartialEq, Clone)]
 pub struct Choice {
     pub text: String,
     pub condition: String,
     pub test: String,
     pub result: String,
+    pub random: String,
+    pub switch: String,
+    /// Choice that can be chosen only once during an adventure
+    pub once: bool,
+}
+/// Kind of a page element a choice refers to by name
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChoiceElement {
+    Condition,
+    Test,
+    Result,
+    Random,
+    Switch,
+}
+/// Element a choice of the page refers to that the page doesn't have
+#[derive(Debug, PartialEq, Clone)]
+pub struct MissingReference {
+    /// Index of the choice in the page
+    pub choice: usize,
+    pub element: ChoiceElement,
+    pub name: String,
 }
 /// Holds two expressions and comparison type used in determining whatever a choice is available to be chosen by the player
-#[derive(Debug, Default, PartialEq)]
+#[derive(Debug, Default, PartialEq, Clone)]
 pub struct Condition {
     pub name: String,
     pub expression_r: String,
@@ -110,17 +251,116 @@ pub struct Condition {
 const REGEX_CONDITION_IN_CHOICE: &str = r"\{\s*condition:\s*(\w+(?:\s|\w)*)\s*\}";
 const REGEX_TEST_IN_CHOICE: &str = r"\{\s*test:\s*(\w+(?:\s|\w)*)\s*\}";
 const REGEX_RESULT_IN_CHOICE: &str = r"\{\s*result:\s*(\w+(?:\s|\w)*)\s*\}";
+const REGEX_RANDOM_IN_CHOICE: &str = r"\{\s*random:\s*(\w+(?:\s|\w)*)\s*\}";
+const REGEX_SWITCH_IN_CHOICE: &str = r"\{\s*switch:\s*(\w+(?:\s|\w)*)\s*\}";
+const REGEX_ONCE_IN_CHOICE: &str = r"\{\s*once\s*\}";
+
+/// Regex matches for keywords in a choice, compiled once and reused for every choice being parsed
+pub struct ChoiceParser {
+    condition: Regex,
+    test: Regex,
+    result: Regex,
+    random: Regex,
+    switch: Regex,
+    once: Regex,
+}
+
+impl ChoiceParser {
+    pub fn new() -> Self {
+        Self {
+            condition: Regex::new(REGEX_CONDITION_IN_CHOICE).unwrap(),
+            test: Regex::new(REGEX_TEST_IN_CHOICE).unwrap(),
+            result: Regex::new(REGEX_RESULT_IN_CHOICE).unwrap(),
+            random: Regex::new(REGEX_RANDOM_IN_CHOICE).unwrap(),
+            switch: Regex::new(REGEX_SWITCH_IN_CHOICE).unwrap(),
+            once: Regex::new(REGEX_ONCE_IN_CHOICE).unwrap(),
+        }
+    }
+}
+
+impl Default for ChoiceParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 /// Creates a Regex match for specified keyword
+///
+/// The keyword is matched literally, any regex metacharacters within it are escaped.
+/// Only whitespace is allowed between the keyword and the brackets, so the keyword has to be the whole bracketed token
 pub fn regex_match_keyword(keyword: &str) -> Result<Regex, regex::Error> {
-    regex::Regex::new(&format!(r"\[\s*({})\s*\]", keyword))
+    regex::Regex::new(&format!(r"\[\s*({})\s*\]", regex::escape(keyword)))
 }
 /// Turns a string into a keyword that can be matched within parts of adventure page text
 pub fn create_keyword(keyword: &str) -> String {
     format!("[{}]", keyword)
 }
-/// Tests if the keyword can be correctly matched in text
+/// Replaces Windows and old Mac line endings with a new line character
+fn normalize_line_endings(text: String) -> String {
+    if text.contains('\r') {
+        return text.replace("\r\n", "\n").replace('\r', "\n");
+    }
+    text
+}
+/// Splits a line that starts with a lower case key followed by a colon, like `title:`, into the key and trimmed value
+///
+/// Returns None if the line doesn't start with a key
+fn split_key_line(line: &str) -> Option<(String, String)> {
+    let (key, value) = line.split_once(':')?;
+    if key.len() > 0 && key.chars().all(|x| x.is_ascii_lowercase() || x == '_') {
+        return Some((key.to_string(), value.trim().to_string()));
+    }
+    None
+}
+/// Keeps a line whose value couldn't be read among unknown keys, so it isn't lost when the file is saved again
+///
+/// It's meant for values that only change how the story looks or plays, those fall back to their defaults instead of failing the whole file
+fn keep_invalid_key(unknown_keys: &mut Vec<(String, String)>, line: &str) {
+    println!("Invalid value, using the default instead: {}", line.trim());
+    if let Some(key) = split_key_line(line) {
+        unknown_keys.push(key);
+    }
+}
+/// Parses a color written as six hexadecimal digits, optionally preceded by #, into 0xRRGGBB
+fn parse_hex_color(text: &str) -> Option<u32> {
+    let text = text.trim().trim_start_matches('#');
+    match u32::from_str_radix(text, 16) {
+        Ok(c) if text.len() == 6 => Some(c),
+        _ => None,
+    }
+}
+/// Returns the question of a side effect value that asks the player for the value, or None if it's a regular value
+pub fn input_prompt(value: &str) -> Option<&str> {
+    value.trim().strip_prefix(INPUT_KEYWORD).map(|x| x.trim())
+}
+/// Splits comma separated list of tags, skipping empty ones
+pub fn parse_tags(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(|x| x.trim().to_string())
+        .filter(|x| x.len() > 0)
+        .collect()
+}
+/// Quotes a value for comma separated values if it contains commas, quotes or line breaks
+fn csv_field(text: &str) -> String {
+    if text.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+/// Characters that mark or separate keywords in text and adventure files, they can't be a part of a keyword
+const KEYWORD_RESERVED_CHARACTERS: [char; 3] = ['[', ']', ';'];
+
+/// Tests if the keyword can be correctly matched in text and saved in adventure files
+///
+/// Keywords can't be empty, start or end with whitespace or contain brackets or semicolons
 pub fn is_keyword_valid(keyword: &str) -> bool {
+    if keyword.len() == 0 || keyword.trim() != keyword {
+        return false;
+    }
+    if keyword.contains(&KEYWORD_RESERVED_CHARACTERS[..]) {
+        return false;
+    }
     if let Ok(r) = regex_match_keyword(keyword) {
         let test = format!("[{}]", keyword);
         return r.is_match(&test);
@@ -143,6 +383,7 @@ impl Display for ParsingError {
             }
             ParsingError::IncomplatePage(p) => write!(f, "The page is incomplete: {:?}", p),
             ParsingError::MissingRecord(p) => write!(f, "Record {} is missing", p),
+            ParsingError::InvalidComparison(c) => write!(f, "{} is not a valid comparison", c),
         }
     }
 }
@@ -156,6 +397,7 @@ impl Adventure {
     /// Note that path can be relative or absolute
     pub fn parse_from_string(text: String, path: String) -> Result<Adventure, ParsingError> {
         let mut adv = Adventure::default();
+        let text = normalize_line_endings(text);
 
         let lines = text.lines();
         let mut flag = 0;
@@ -169,6 +411,79 @@ impl Adventure {
             } else if line.starts_with("start:") {
                 flag = 0;
                 adv.start = line.replacen("start:", "", 1).trim().to_string();
+            } else if line.starts_with("series:") {
+                flag = 0;
+                adv.series = line.replacen("series:", "", 1).trim().to_string();
+            } else if line.starts_with("author:") {
+                flag = 0;
+                adv.author = line.replacen("author:", "", 1).trim().to_string();
+            } else if line.starts_with("version:") {
+                flag = 0;
+                adv.version = line.replacen("version:", "", 1).trim().to_string();
+            } else if line.starts_with("date:") {
+                flag = 0;
+                adv.date = line.replacen("date:", "", 1).trim().to_string();
+            } else if line.starts_with("rounding:") {
+                flag = 0;
+                let text = line.replacen("rounding:", "", 1);
+                match Rounding::from_keyword(&text) {
+                    Some(r) => adv.rounding = r,
+                    None => keep_invalid_key(&mut adv.unknown_keys, line),
+                }
+            } else if line.starts_with("hide_rolls:") {
+                flag = 0;
+                match line.replacen("hide_rolls:", "", 1).trim() {
+                    "true" => adv.hide_rolls = true,
+                    "false" => adv.hide_rolls = false,
+                    _ => keep_invalid_key(&mut adv.unknown_keys, line),
+                }
+            } else if line.starts_with("theme_color:") {
+                flag = 0;
+                match parse_hex_color(&line.replacen("theme_color:", "", 1)) {
+                    Some(c) => adv.theme_color = Some(c),
+                    None => keep_invalid_key(&mut adv.unknown_keys, line),
+                }
+            } else if line.starts_with("theme_background:") {
+                flag = 0;
+                match parse_hex_color(&line.replacen("theme_background:", "", 1)) {
+                    Some(c) => adv.theme_background = Some(c),
+                    None => keep_invalid_key(&mut adv.unknown_keys, line),
+                }
+            } else if line.starts_with("theme_accent:") {
+                flag = 0;
+                match parse_hex_color(&line.replacen("theme_accent:", "", 1)) {
+                    Some(c) => adv.theme_accent = Some(c),
+                    None => keep_invalid_key(&mut adv.unknown_keys, line),
+                }
+            } else if line.starts_with("theme_font:") {
+                flag = 0;
+                let font = line.replacen("theme_font:", "", 1).trim().to_string();
+                if font.len() > 0 {
+                    adv.theme_font = Some(font);
+                }
+            } else if line.starts_with("theme_size:") {
+                flag = 0;
+                match line.replacen("theme_size:", "", 1).trim().parse() {
+                    Ok(s) if s > 0 => adv.theme_size = Some(s),
+                    _ => keep_invalid_key(&mut adv.unknown_keys, line),
+                }
+            } else if line.starts_with("chapter:") {
+                flag = 0;
+                let text = line.replacen("chapter:", "", 1);
+                let chapter = Chapter::parse_from_string(text)?;
+                adv.chapters.push(chapter);
+            } else if line.starts_with("achievement:") {
+                flag = 0;
+                let text = line.replacen("achievement:", "", 1);
+                let achievement = Achievement::parse_from_string(text)?;
+                adv.achievements.push(achievement);
+            } else if line.starts_with("dictionary:") {
+                flag = 0;
+                let text = line.replacen("dictionary:", "", 1);
+                text.split(',')
+                    .map(|x| x.trim())
+                    .filter(|x| x.len() > 0)
+                    .for_each(|x| adv.dictionary.push(x.to_string()));
             } else if line.starts_with("record:") {
                 flag = 0;
                 let text = line.replacen("record:", "", 1);
@@ -179,9 +494,17 @@ impl Adventure {
                 let text = line.replacen("name:", "", 1);
                 let name = Name::parse_from_string(text)?;
                 adv.names.insert(name.keyword.clone(), name);
+            } else if line.starts_with("result:") {
+                flag = 0;
+                let text = line.replacen("result:", "", 1);
+                let result = StoryResult::parse_from_string(text)?;
+                adv.results.insert(result.name.clone(), result);
+            } else if let Some((key, value)) = split_key_line(line).filter(|_| flag != 1) {
+                // keys from newer versions of the format are kept as they are, lines of the description can look like keys too
+                adv.unknown_keys.push((key, value));
             } else {
                 if flag == 1 {
-                    adv.description = adv.description + line;
+                    adv.description = format!("{}\n{}", adv.description, line);
                 }
             }
         }
@@ -193,6 +516,17 @@ impl Adventure {
             return Err(ParsingError::Invalid(text));
         }
     }
+    /// Turns the records into comma separated values with a header row, sorted by category and name
+    pub fn records_to_csv(&self) -> String {
+        let mut records: Vec<&Record> = self.records.values().collect();
+        records.sort_by(|a, b| (&a.category, &a.name).cmp(&(&b.category, &b.name)));
+        let mut csv = RECORDS_CSV_HEADER.to_string();
+        for record in records {
+            csv.push('\n');
+            csv.push_str(&record.to_csv_row());
+        }
+        csv
+    }
     /// Turns the adventure into a string representation that can be either saved to drive or parsed back into adventure
     ///
     /// # Limitations
@@ -202,14 +536,159 @@ impl Adventure {
             "title: {}\ndescription: {}\nstart: {}",
             self.title, self.description, self.start
         );
+        if self.series.len() > 0 {
+            ser = format!("{}\nseries: {}", ser, self.series);
+        }
+        if self.author.len() > 0 {
+            ser = format!("{}\nauthor: {}", ser, self.author);
+        }
+        if self.version.len() > 0 {
+            ser = format!("{}\nversion: {}", ser, self.version);
+        }
+        if self.date.len() > 0 {
+            ser = format!("{}\ndate: {}", ser, self.date);
+        }
+        if self.rounding != Rounding::default() {
+            ser = format!("{}\nrounding: {}", ser, self.rounding);
+        }
+        if self.hide_rolls {
+            ser = format!("{}\nhide_rolls: true", ser);
+        }
+        if let Some(color) = self.theme_color {
+            ser = format!("{}\ntheme_color: #{:06x}", ser, color);
+        }
+        if let Some(font) = &self.theme_font {
+            ser = format!("{}\ntheme_font: {}", ser, font);
+        }
+        if let Some(size) = self.theme_size {
+            ser = format!("{}\ntheme_size: {}", ser, size);
+        }
+        if let Some(color) = self.theme_background {
+            ser = format!("{}\ntheme_background: #{:06x}", ser, color);
+        }
+        if let Some(color) = self.theme_accent {
+            ser = format!("{}\ntheme_accent: #{:06x}", ser, color);
+        }
+        self.chapters
+            .iter()
+            .for_each(|x| ser = format!("{}\nchapter: {}", ser, x.serialize_to_string()));
+        self.achievements
+            .iter()
+            .for_each(|x| ser = format!("{}\nachievement: {}", ser, x.serialize_to_string()));
+        if self.dictionary.len() > 0 {
+            ser = format!("{}\ndictionary: {}", ser, self.dictionary.join(", "));
+        }
         self.records
             .iter()
             .for_each(|x| ser = format!("{}\nrecord: {}", ser, x.1.serialize_to_string()));
         self.names
             .iter()
             .for_each(|x| ser = format!("{}\nname: {}", ser, x.1.serialize_to_string()));
+        self.results
+            .iter()
+            .for_each(|x| ser = format!("{}\nresult: {}", ser, x.1.serialize_to_string()));
+        self.unknown_keys
+            .iter()
+            .for_each(|x| ser = format!("{}\n{}: {}", ser, x.0, x.1));
         ser
     }
+    /// Name global records of the adventure are stored under, so they don't collide with records of unrelated adventures
+    pub fn global_namespace(&self) -> &str {
+        match self.series.len() > 0 {
+            true => &self.series,
+            false => &self.title,
+        }
+    }
+    /// Finds the result the page leads to by name, results of the page take precedence over the shared results
+    pub fn find_result<'a>(&'a self, page: &'a Page, name: &str) -> Option<&'a StoryResult> {
+        page.results.get(name).or_else(|| self.results.get(name))
+    }
+    /// Results the page can lead to, its own together with the shared results it doesn't replace
+    pub fn page_results(&self, page: &Page) -> HashMap<String, StoryResult> {
+        let mut results = self.results.clone();
+        results.extend(page.results.clone());
+        results
+    }
+    /// Names of pages using the shared result, pages that have their own result of the same name aren't included
+    ///
+    /// The list is sorted
+    pub fn pages_using_result(&self, pages: &HashMap<String, Page>, name: &str) -> Vec<String> {
+        let mut using: Vec<String> = pages
+            .iter()
+            .filter(|x| x.1.results.contains_key(name) == false && x.1.uses_result(name))
+            .map(|x| x.0.clone())
+            .collect();
+        using.sort();
+        using
+    }
+    /// Renames a shared result, updating choices, tests and switches of pages that use it
+    ///
+    /// Returns false if there's no such shared result or the new name is taken
+    pub fn rename_result(&mut self, pages: &mut HashMap<String, Page>, old: &str, new: &str) -> bool {
+        if self.results.contains_key(new) {
+            return false;
+        }
+        let mut result = match self.results.remove(old) {
+            Some(r) => r,
+            None => return false,
+        };
+        pages
+            .values_mut()
+            .filter(|x| x.results.contains_key(old) == false)
+            .for_each(|x| x.rename_result_references(old, new));
+        result.name = new.to_string();
+        self.results.insert(new.to_string(), result);
+        true
+    }
+    /// Returns sorted list of categories used by the records, without the empty category
+    pub fn record_categories(&self) -> Vec<String> {
+        let mut categories: Vec<String> = self
+            .records
+            .values()
+            .map(|x| x.category.clone())
+            .filter(|x| x.len() > 0)
+            .collect();
+        categories.sort();
+        categories.dedup();
+        categories
+    }
+    /// Lists records shown to the player with their starting values, one line per category sorted by category
+    ///
+    /// Lines look like `category: name 10, other 5`, records without a category are listed first without a label
+    pub fn records_preview(&self) -> Vec<String> {
+        let mut records: Vec<&Record> = self.records.values().filter(|x| x.is_hidden() == false).collect();
+        records.sort_by(|a, b| (&a.category, &a.name).cmp(&(&b.category, &b.name)));
+        let mut lines: Vec<(String, Vec<String>)> = Vec::new();
+        for record in records {
+            let value = match record.current_value(&self.records, self.rounding) {
+                Ok(v) => v.to_string(),
+                Err(_) => "?".to_string(),
+            };
+            let entry = format!("{} {}", record.name, value);
+            match lines.last_mut() {
+                Some(line) if line.0 == record.category => line.1.push(entry),
+                _ => lines.push((record.category.clone(), vec![entry])),
+            }
+        }
+        lines
+            .into_iter()
+            .map(|(category, entries)| match category.len() {
+                0 => entries.join(", "),
+                _ => format!("{}: {}", category, entries.join(", ")),
+            })
+            .collect()
+    }
+    /// Finds the declared achievement of given id
+    pub fn achievement(&self, id: &str) -> Option<&Achievement> {
+        self.achievements.iter().find(|x| x.id == id)
+    }
+    /// Finds the page the chapter of given name begins at
+    pub fn chapter_page(&self, chapter: &str) -> Option<&String> {
+        self.chapters
+            .iter()
+            .find(|x| x.name == chapter)
+            .map(|x| &x.page)
+    }
     /// Tests if the adventure has bare minimum to be considered as loaded
     pub fn is_bare_minimum(&self) -> bool {
         if self.title.len() < 1 {
@@ -222,6 +701,8 @@ impl Adventure {
         true
     }
     /// Tests if the adventure has bare minimum information to be considered playable
+    ///
+    /// That means the start page is set and its file exists in the adventure folder
     pub fn is_playable(&self) -> bool {
         if self.start.len() == 0 {
             return false;
@@ -230,8 +711,255 @@ impl Adventure {
         if path.exists() == false {
             return false;
         }
+        is_page_on_path(&self.path, &self.start)
+    }
+    /// Adds records and names used by the example page and makes it the start of the adventure
+    ///
+    /// The example page itself needs to be saved as EXAMPLE_PAGE_NAME
+    pub fn add_example(&mut self) {
+        for text in ["gold; items; 0; 0..", "health; stats; 10; 0..10"] {
+            if let Ok(record) = Record::parse_from_string(text.to_string()) {
+                self.records.insert(record.name.clone(), record);
+            }
+        }
+        if let Ok(name) = Name::parse_from_string("hero; The traveler".to_string()) {
+            self.names.insert(name.keyword.clone(), name);
+        }
+        self.start = EXAMPLE_PAGE_NAME.to_string();
+    }
+    /// Checks the adventure together with its pages for problems that would prevent it from being played correctly
+    ///
+    /// pages: map of page file names and pages of the adventure
+    ///
+    /// Returns a sorted list of descriptions of found problems, the list is empty if no problems were found
+    pub fn validate(&self, pages: &HashMap<String, Page>) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.start.len() == 0 {
+            problems.push("Starting page is not set".to_string());
+        } else if pages.contains_key(&self.start) == false {
+            problems.push(format!("Starting page {} doesn't exist", self.start));
+        }
+        for chapter in self.chapters.iter() {
+            if pages.contains_key(&chapter.page) == false {
+                problems.push(format!(
+                    "Chapter {} starts at page {} which doesn't exist",
+                    chapter.name, chapter.page
+                ));
+            }
+        }
+        for record in self.records.values() {
+            if record.is_within_bounds() == false {
+                problems.push(format!(
+                    "Record {} starts at {} which is outside of its bounds {}",
+                    record.name,
+                    record.value,
+                    record.bounds_to_string()
+                ));
+            }
+        }
+        for result in self.results.values() {
+            if result.next_page.trim().len() == 0 {
+                problems.push(format!("Shared result {} has no next page", result.name));
+            } else if pages.contains_key(&result.next_page) == false {
+                problems.push(format!(
+                    "Shared result {} leads to page {} which doesn't exist",
+                    result.name, result.next_page
+                ));
+            }
+        }
+        for page in pages.iter() {
+            for result in page.1.results_without_next_page() {
+                problems.push(format!("Page {}: Result {} has no next page", page.0, result));
+            }
+            for result in page.1.results.values() {
+                if result.next_page.trim().len() > 0 && pages.contains_key(&result.next_page) == false {
+                    problems.push(format!(
+                        "Page {}: Result {} leads to page {} which doesn't exist",
+                        page.0, result.name, result.next_page
+                    ));
+                }
+            }
+            for random in page.1.randoms.values() {
+                for outcome in random.outcomes.iter() {
+                    if pages.contains_key(&outcome.0) == false {
+                        problems.push(format!(
+                            "Page {}: Random {} leads to page {} which doesn't exist",
+                            page.0, random.name, outcome.0
+                        ));
+                    }
+                }
+            }
+            for link in page.1.story_links() {
+                if pages.contains_key(link) == false {
+                    problems.push(format!("Page {}: Story links to page {} which doesn't exist", page.0, link));
+                }
+            }
+            for (switch, result) in page.1.missing_switch_results(&self.results) {
+                problems.push(format!(
+                    "Page {}: Switch {} leads to result {} which doesn't exist",
+                    page.0, switch, result
+                ));
+            }
+            for missing in page.1.missing_references(&self.results) {
+                problems.push(format!("Page {}: {}", page.0, missing));
+            }
+        }
+        problems.append(&mut self.ambiguous_side_effects(pages));
+        problems.sort();
+        problems
+    }
+    /// Results of all pages followed by the shared results, each with a description of where it's from
+    fn all_results<'a>(&'a self, pages: &'a HashMap<String, Page>) -> Vec<(String, &'a StoryResult)> {
+        pages
+            .iter()
+            .flat_map(|(name, page)| page.results.values().map(move |x| (format!("Page {}", name), x)))
+            .chain(self.results.values().map(|x| ("Shared results".to_string(), x)))
+            .collect()
+    }
+    /// Lists side effects of the pages that change a keyword which is both a record and a name
+    ///
+    /// The editor doesn't allow such keywords but older files can have them, the game can't tell which one to change
+    pub fn ambiguous_side_effects(&self, pages: &HashMap<String, Page>) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (location, result) in self.all_results(pages) {
+            for key in result.side_effects.keys() {
+                if self.records.contains_key(key) && self.names.contains_key(key) {
+                    problems.push(format!(
+                        "{}: Result {} changes {} which is both a record and a name",
+                        location, result.name, key
+                    ));
+                }
+            }
+        }
+        problems.sort();
+        problems
+    }
+    /// Evaluates every expression of conditions, tests and side effects of the pages with default values of the records
+    ///
+    /// pages: map of page file names and pages of the adventure
+    ///
+    /// Returns a sorted list of descriptions of expressions that failed to evaluate, the list is empty if all of them can be evaluated
+    pub fn check_expressions(&self, pages: &HashMap<String, Page>) -> Vec<String> {
+        let mut problems = Vec::new();
+        // rolled values don't matter, only whatever the dice can be rolled
+        let mut rand = Random::new(0);
+        let keywords = Regex::new(r"\[\s*(\w+(?:\s|\w)*)\]").unwrap();
+        let mut check = |location: &str, element: String, exp: &str| {
+            // missing records evaluate to 0 so they have to be looked for separately
+            if let Some(c) = keywords
+                .captures_iter(exp)
+                .find(|c| self.records.contains_key(c[1].trim()) == false)
+            {
+                return Some(format!(
+                    "{}: {}: {} isn't a record",
+                    location,
+                    element,
+                    c[1].trim()
+                ));
+            }
+            match evaluate_expression(exp, &self.records, &mut rand, self.rounding) {
+                // records can have other values during play
+                Ok(_) | Err(EvaluationError::DivisionByZero) => None,
+                Err(e) => Some(format!("{}: {}: {}", location, element, e)),
+            }
+        };
+        for page in pages.iter() {
+            let location = format!("Page {}", page.0);
+            for condition in page.1.conditions.values() {
+                let element = format!("Condition {}", condition.name);
+                problems.extend(check(&location, element.clone(), &condition.expression_l));
+                problems.extend(check(&location, element, &condition.expression_r));
+            }
+            for test in page.1.tests.values() {
+                let element = format!("Test {}", test.name);
+                problems.extend(check(&location, element.clone(), &test.expression_l));
+                problems.extend(check(&location, element, &test.expression_r));
+            }
+            for switch in page.1.switches.values() {
+                let element = format!("Switch {}", switch.name);
+                problems.extend(check(&location, element, &switch.expression));
+            }
+        }
+        for record in self.records.values().filter(|x| x.is_derived()) {
+            let element = format!("Record {}", record.name);
+            problems.extend(check("Derived records", element, &record.expression));
+        }
+        for (location, result) in self.all_results(pages) {
+            for side_effect in result.side_effects.iter() {
+                // names are changed to text, only records are evaluated
+                if side_effect.0 == ACHIEVEMENT_KEYWORD {
+                    if self.achievement(side_effect.1.trim()).is_none() {
+                        problems.push(format!(
+                            "{}: Result {} unlocks achievement {} which isn't declared",
+                            location, result.name, side_effect.1
+                        ));
+                    }
+                } else if input_prompt(side_effect.1).is_some() {
+                    // the player provides the value, there's nothing to evaluate
+                } else if self.records.contains_key(side_effect.0) {
+                    let element = format!("Result {} side effect {}", result.name, side_effect.0);
+                    problems.extend(check(&location, element, side_effect.1));
+                } else if self.names.contains_key(side_effect.0) == false {
+                    problems.push(format!(
+                        "{}: Result {} changes {} which isn't a record or a name",
+                        location, result.name, side_effect.0
+                    ));
+                }
+            }
+        }
+        problems.sort();
+        problems
+    }
+    /// Renames a page and updates every reference to it
+    ///
+    /// Results, random outcomes and story links of all pages, including the renamed one, shared results, the starting page and chapters are updated
+    ///
+    /// Returns false if the page doesn't exist or another page already has the new name
+    pub fn rename_page(&mut self, pages: &mut HashMap<String, Page>, old: &str, new: &str) -> bool {
+        if old != new && pages.contains_key(new) {
+            return false;
+        }
+        let page = match pages.remove(old) {
+            Some(p) => p,
+            None => return false,
+        };
+        pages.insert(new.to_string(), page);
+        pages
+            .values_mut()
+            .flat_map(|x| x.results.values_mut())
+            .chain(self.results.values_mut())
+            .filter(|x| x.next_page == old)
+            .for_each(|x| x.next_page = new.to_string());
+        pages
+            .values_mut()
+            .flat_map(|x| x.randoms.values_mut())
+            .flat_map(|x| x.outcomes.iter_mut())
+            .filter(|x| x.0 == old)
+            .for_each(|x| x.0 = new.to_string());
+        pages
+            .values_mut()
+            .for_each(|x| x.rename_story_links(old, new));
+        if self.start == old {
+            self.start = new.to_string();
+        }
+        self.chapters
+            .iter_mut()
+            .filter(|x| x.page == old)
+            .for_each(|x| x.page = new.to_string());
         true
     }
+    /// Renames all occurances of a keyword within side effects of the shared results
+    ///
+    /// Both strings need to be raw keywords as the function will turn them into matchable keywords
+    pub fn rename_keyword_in_results(&mut self, old: &str, new: &str) {
+        let regex = match regex_match_keyword(old) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+        self.results
+            .values_mut()
+            .for_each(|x| x.rename_keyword(&regex, old, new));
+    }
     /// Updates a keyword of a record to a new one
     pub fn update_record(&mut self, old: &str, new: Record) {
         if let Some(_) = self.records.remove(old) {
@@ -248,33 +976,59 @@ impl Adventure {
             println!("Failed to find a name {} to update", old);
         }
     }
+    /// Copies definitions of records and names from another adventure
+    ///
+    /// Keywords already used by a record or a name of this adventure are skipped.
+    /// Returns a sorted list of descriptions of skipped keywords
+    pub fn import_variables(&mut self, other: &Adventure) -> Vec<String> {
+        let mut skipped = Vec::new();
+        for record in other.records.values() {
+            if self.records.contains_key(&record.name) || self.names.contains_key(&record.name) {
+                skipped.push(format!("Record {} is already used in this adventure", record.name));
+            } else {
+                self.records.insert(record.name.clone(), record.clone());
+            }
+        }
+        for name in other.names.values() {
+            if self.records.contains_key(&name.keyword) || self.names.contains_key(&name.keyword) {
+                skipped.push(format!("Name {} is already used in this adventure", name.keyword));
+            } else {
+                self.names.insert(name.keyword.clone(), name.clone());
+            }
+        }
+        skipped.sort();
+        skipped
+    }
 }
-/// Replaces a regex matched string slices within source with a new string slice
+/// Replaces all regex matched string slices within source with a new string slice
 macro_rules! replace_with_regex {
-    ($regex:expr, $source:expr, $new:expr) => {
-        if let Some(cap) = $regex.captures(&$source) {
-            let mut i = 1;
+    ($regex:expr, $source:expr, $new:expr) => {{
+        let ranges: Vec<std::ops::Range<usize>> = $regex
+            .captures_iter(&$source)
+            .filter_map(|c| c.get(1))
+            .map(|c| c.range())
+            .collect();
+        if ranges.len() > 0 {
             let mut buff = $source.clone();
-            while let Some(c) = cap.get(i) {
-                buff.replace_range(c.range(), $new);
-                i += 1;
+            // replacing from the back so the earlier ranges stay valid
+            for range in ranges.into_iter().rev() {
+                buff.replace_range(range, $new);
             }
             $source = buff;
         }
-    };
+    }};
 }
 impl Page {
     /// Parses string into Page. It will return error if the text isn't valid page
     pub fn parse_from_string(text: String) -> Result<Page, ParsingError> {
         // creating empty page to populate
         let mut page = Page::default();
+        let text = normalize_line_endings(text);
 
         // next we break the text into lines and create regex lookups to match and connect parts of the page
         let lines = text.lines();
 
-        let match_condition = Regex::new(REGEX_CONDITION_IN_CHOICE).unwrap();
-        let match_test = Regex::new(REGEX_TEST_IN_CHOICE).unwrap();
-        let match_result = Regex::new(REGEX_RESULT_IN_CHOICE).unwrap();
+        let choice_parser = ChoiceParser::new();
 
         let mut story_line = false;
         for line in lines {
@@ -284,6 +1038,16 @@ impl Page {
                 // matching title by keyword
                 story_line = false;
                 page.title = line.replacen("title:", "", 1).trim().to_string();
+            } else if line.starts_with("tags:") {
+                story_line = false;
+                page.tags = parse_tags(&line.replacen("tags:", "", 1));
+            } else if line.starts_with("shuffle:") {
+                story_line = false;
+                match line.replacen("shuffle:", "", 1).trim() {
+                    "true" => page.shuffle = true,
+                    "false" => page.shuffle = false,
+                    _ => keep_invalid_key(&mut page.unknown_keys, line),
+                }
             } else if line.starts_with("story:") {
                 // same with the story, we set the flag to 1 here to signify that any following line that doesn't match any keyword can be added to story
                 story_line = true;
@@ -291,12 +1055,7 @@ impl Page {
             } else if line.starts_with("choice:") {
                 story_line = false;
                 // Reading choice from the line
-                let cho = Choice::parse_from_string(
-                    line.replacen("choice:", "", 1),
-                    &match_condition,
-                    &match_test,
-                    &match_result,
-                )?;
+                let cho = Choice::parse_from_string(line.replacen("choice:", "", 1), &choice_parser)?;
                 page.choices.push(cho);
             } else if line.starts_with("condition:") {
                 story_line = false;
@@ -315,6 +1074,19 @@ impl Page {
                 // failing the page if result doesn't load correctly, like in other cases
                 let res = StoryResult::parse_from_string(line.replacen("result:", "", 1))?;
                 page.results.insert(res.name.clone(), res);
+            } else if line.starts_with("random:") {
+                story_line = false;
+
+                let ran = RandomResult::parse_from_string(line.replacen("random:", "", 1))?;
+                page.randoms.insert(ran.name.clone(), ran);
+            } else if line.starts_with("switch:") {
+                story_line = false;
+
+                let switch = Switch::parse_from_string(line.replacen("switch:", "", 1))?;
+                page.switches.insert(switch.name.clone(), switch);
+            } else if let Some((key, value)) = split_key_line(line).filter(|_| story_line == false) {
+                // keys from newer versions of the format are kept as they are, lines of the story can look like keys too
+                page.unknown_keys.push((key, value));
             } else if story_line {
                 // adding a line to story if it's immediately after story keyword and doesn't match any other keywords
                 page.story = format!("{}\n{}", page.story, line);
@@ -328,7 +1100,18 @@ impl Page {
     }
     /// Transforms page into a string representation of it, suitable for saving onto drive or parsing back into a page struct
     pub fn serialize_to_string(&self) -> String {
-        let mut ser = format!("title: {}\nstory: {}", self.title, self.story);
+        let mut ser = format!("title: {}", self.title);
+        if self.tags.len() > 0 {
+            ser = format!("{}\ntags: {}", ser, self.tags.join(", "));
+        }
+        if self.shuffle {
+            ser = format!("{}\nshuffle: true", ser);
+        }
+        // written before the story so they aren't read back as a part of it
+        self.unknown_keys
+            .iter()
+            .for_each(|x| ser = format!("{}\n{}: {}", ser, x.0, x.1));
+        ser = format!("{}\nstory: {}", ser, self.story);
         self.choices
             .iter()
             .for_each(|x| ser = format!("{}\nchoice: {}", ser, x.serialize_to_string()));
@@ -341,9 +1124,17 @@ impl Page {
         self.results
             .iter()
             .for_each(|x| ser = format!("{}\nresult: {}", ser, x.1.serialize_to_string()));
+        self.randoms
+            .iter()
+            .for_each(|x| ser = format!("{}\nrandom: {}", ser, x.1.serialize_to_string()));
+        self.switches
+            .iter()
+            .for_each(|x| ser = format!("{}\nswitch: {}", ser, x.1.serialize_to_string()));
         ser
     }
     /// Tests if the page is playable, meaning it has a story text, and a choice that leads somewhere
+    ///
+    /// Result names aren't checked against shared results of the adventure, is_playable_with does that
     pub fn is_playable(&self) -> bool {
         if self.story.len() < 1 {
             return false;
@@ -351,15 +1142,183 @@ impl Page {
         if self.choices.len() < 1 {
             return false;
         }
+        // without results of its own, choices can still lead to a shared result of the adventure
         if self.results.len() < 1 {
             for choice in self.choices.iter() {
-                if choice.is_game_over() == false {
+                if choice.is_game_over() == false && choice.is_random() == false && choice.result.len() == 0 {
                     return false;
                 }
             }
         }
         true
     }
+    /// Tests if the page is playable in the adventure, results chosen by its choices have to exist in the page or among shared results
+    ///
+    /// shared: results of the adventure
+    pub fn is_playable_with(&self, shared: &HashMap<String, StoryResult>) -> bool {
+        self.is_playable()
+            && self.choices.iter().all(|x| {
+                x.result.len() == 0
+                    || x.is_game_over()
+                    || self.results.contains_key(&x.result)
+                    || shared.contains_key(&x.result)
+            })
+    }
+    /// Lists condition, test, result, random result or switch names used by choices of the page that the page doesn't have
+    ///
+    /// shared: results of the adventure, choices can lead to them when the page has no result of the same name
+    pub fn missing_references(&self, shared: &HashMap<String, StoryResult>) -> Vec<MissingReference> {
+        let mut missing = Vec::new();
+        for (i, choice) in self.choices.iter().enumerate() {
+            let references = [
+                (ChoiceElement::Condition, &choice.condition, self.conditions.contains_key(&choice.condition)),
+                (ChoiceElement::Test, &choice.test, self.tests.contains_key(&choice.test)),
+                (
+                    ChoiceElement::Result,
+                    &choice.result,
+                    choice.is_game_over()
+                        || self.results.contains_key(&choice.result)
+                        || shared.contains_key(&choice.result),
+                ),
+                (ChoiceElement::Random, &choice.random, self.randoms.contains_key(&choice.random)),
+                (ChoiceElement::Switch, &choice.switch, self.switches.contains_key(&choice.switch)),
+            ];
+            for (element, name, exists) in references {
+                if name.len() > 0 && exists == false {
+                    missing.push(MissingReference {
+                        choice: i,
+                        element,
+                        name: name.clone(),
+                    });
+                }
+            }
+        }
+        missing
+    }
+    /// Results that switches of the page lead to but neither the page nor the shared results have
+    ///
+    /// Returns pairs of switch and result names, sorted by switch name
+    pub fn missing_switch_results<'a>(&'a self, shared: &HashMap<String, StoryResult>) -> Vec<(&'a String, &'a String)> {
+        let mut missing: Vec<(&String, &String)> = self
+            .switches
+            .values()
+            .flat_map(|x| {
+                x.branches
+                    .iter()
+                    .map(|b| &b.1)
+                    .chain(Some(&x.default_result).filter(|r| r.len() > 0))
+                    .map(move |r| (&x.name, r))
+            })
+            .filter(|x| self.results.contains_key(x.1) == false && shared.contains_key(x.1) == false)
+            .collect();
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+    /// Tests if a choice, test or switch of the page leads to the result
+    pub fn uses_result(&self, name: &str) -> bool {
+        self.choices.iter().any(|x| x.result == name)
+            || self
+                .tests
+                .values()
+                .any(|x| x.success_result == name || x.failure_result == name)
+            || self.switches.values().any(|x| x.uses_result(name))
+    }
+    /// Shared results the page leads to, shared results replaced by a result of the page aren't included
+    pub fn used_shared_results<'a>(
+        &'a self,
+        shared: &'a HashMap<String, StoryResult>,
+    ) -> impl Iterator<Item = &'a StoryResult> + 'a {
+        shared
+            .values()
+            .filter(move |x| self.results.contains_key(&x.name) == false && self.uses_result(&x.name))
+    }
+    /// Changes the result choices, tests and switches of the page lead to
+    pub fn rename_result_references(&mut self, old: &str, new: &str) {
+        self.choices
+            .iter_mut()
+            .filter(|x| x.result == old)
+            .for_each(|x| x.result = new.to_string());
+        for test in self.tests.values_mut() {
+            if test.success_result == old {
+                test.success_result = new.to_string();
+            }
+            if test.failure_result == old {
+                test.failure_result = new.to_string();
+            }
+        }
+        self.switches
+            .values_mut()
+            .for_each(|x| x.rename_result(old, new));
+    }
+    /// Lists results of the page that no choice, test or switch leads to, sorted by name
+    pub fn unused_results(&self) -> Vec<&String> {
+        let mut unused: Vec<&String> = self
+            .results
+            .keys()
+            .filter(|x| {
+                self.choices.iter().all(|c| &c.result != *x)
+                    && self
+                        .tests
+                        .values()
+                        .all(|t| &t.success_result != *x && &t.failure_result != *x)
+                    && self.switches.values().all(|s| s.uses_result(x) == false)
+            })
+            .collect();
+        unused.sort();
+        unused
+    }
+    /// Pages the story links to with [[page|text]] markup, in the order they appear
+    pub fn story_links(&self) -> Vec<&str> {
+        let mut links = Vec::new();
+        let mut rest = self.story.as_str();
+        while let Some(start) = rest.find("[[") {
+            rest = &rest[start..];
+            match rest.find("]]") {
+                Some(close) => {
+                    let inner = &rest[2..close];
+                    links.push(inner.split_once('|').map_or(inner, |x| x.0).trim());
+                    rest = &rest[close + 2..];
+                }
+                None => break,
+            }
+        }
+        links
+    }
+    /// Changes story links leading to the old page to lead to the new one, the text shown to the player stays the same
+    pub fn rename_story_links(&mut self, old: &str, new: &str) {
+        let mut story = String::with_capacity(self.story.len());
+        let mut rest = self.story.as_str();
+        while let Some(start) = rest.find("[[") {
+            story.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let close = match rest.find("]]") {
+                Some(c) => c,
+                None => break,
+            };
+            let inner = &rest[2..close];
+            let (target, label) = inner.split_once('|').unwrap_or((inner, inner));
+            if target.trim() == old {
+                story.push_str(&format!("[[{}|{}]]", new, label));
+            } else {
+                story.push_str(&rest[..close + 2]);
+            }
+            rest = &rest[close + 2..];
+        }
+        story.push_str(rest);
+        self.story = story;
+    }
+    /// Lists results of the page that don't lead to any page, sorted by name
+    pub fn results_without_next_page(&self) -> Vec<&String> {
+        let mut results: Vec<&String> = self
+            .results
+            .values()
+            .filter(|x| x.next_page.trim().len() == 0)
+            .map(|x| &x.name)
+            .collect();
+        results.sort();
+        results
+    }
     /// Tests if provided keyword is present within the page or its subcontents
     ///
     /// The keyword should be a raw text as the function will turn it into a matchable keyword
@@ -390,6 +1349,11 @@ impl Page {
                 return true;
             }
         }
+        for switch in self.switches.values() {
+            if switch.is_keyword_present(keyword) {
+                return true;
+            }
+        }
         for result in self.results.iter() {
             if result.1.is_keyword_present(keyword) {
                 return true;
@@ -417,6 +1381,9 @@ impl Page {
         self.tests
             .iter_mut()
             .for_each(|x| x.1.rename_keyword(&regex, new));
+        self.switches
+            .values_mut()
+            .for_each(|x| replace_with_regex!(regex, x.expression, new));
         self.results
             .iter_mut()
             .for_each(|x| x.1.rename_keyword(&regex, old, new));
@@ -425,7 +1392,7 @@ impl Page {
 
 /// macro that extracts keywords from choice text
 macro_rules! insert_in_choice {
-    ($reg:ident, $target:expr, $source:ident) => {
+    ($reg:expr, $target:expr, $source:ident) => {
         // we start by capturing the keyword through regex
         if let Some(c) = $reg.captures(&$source) {
             // we have two matches here, first is the whole match and second is just the name of matched keyword
@@ -442,18 +1409,19 @@ macro_rules! insert_in_choice {
 impl Choice {
     /// Parses string into Choice.
     ///
-    /// It requires to be supplied with preconfigured Regex matches which capture name of the matched result
-    pub fn parse_from_string(
-        mut text: String,
-        match_condition: &Regex,
-        match_test: &Regex,
-        match_result: &Regex,
-    ) -> Result<Choice, ParsingError> {
+    /// It requires to be supplied with a ChoiceParser, which holds Regex matches capturing names of the matched elements
+    pub fn parse_from_string(mut text: String, parser: &ChoiceParser) -> Result<Choice, ParsingError> {
         let mut choice = Choice::default();
         // we use macros here to extract appropriate keywords into their places.
-        insert_in_choice!(match_condition, choice.condition, text);
-        insert_in_choice!(match_test, choice.test, text);
-        insert_in_choice!(match_result, choice.result, text);
+        insert_in_choice!(parser.condition, choice.condition, text);
+        insert_in_choice!(parser.test, choice.test, text);
+        insert_in_choice!(parser.result, choice.result, text);
+        insert_in_choice!(parser.random, choice.random, text);
+        insert_in_choice!(parser.switch, choice.switch, text);
+        if let Some(m) = parser.once.find(&text) {
+            choice.once = true;
+            text.replace_range(m.range(), "");
+        }
 
         // we finish up by assigning text with keywords extracted and push it into the page
         choice.text = text.trim().to_string();
@@ -469,8 +1437,15 @@ impl Choice {
         if self.condition.len() > 0 {
             ser += &format!("{{condition: {}}}", self.condition);
         }
+        if self.once {
+            ser += "{once}";
+        }
         if self.test.len() > 0 {
             ser += &format!("{{test: {}}}", self.test);
+        } else if self.switch.len() > 0 {
+            ser += &format!("{{switch: {}}}", self.switch);
+        } else if self.random.len() > 0 {
+            ser += &format!("{{random: {}}}", self.random);
         } else if self.result.len() > 0 {
             ser += &format!("{{result: {}}}", self.result);
         } else {
@@ -481,19 +1456,17 @@ impl Choice {
     }
     /// Tests if this choice is valid
     ///
-    /// It will return flase if it doesn't have test or result name
-    /// or if it has both
+    /// It will return flase if it doesn't have test, switch, random or result name
+    /// or if it has more than one of them
     pub fn is_valid(&self) -> bool {
         if self.text.len() < 1 {
             return false;
         }
-        if self.test.len() == 0 && self.result.len() == 0 {
-            return false;
-        }
-        if self.test.len() > 0 && self.result.len() > 0 {
-            return false;
-        }
-        true
+        let targets = [&self.test, &self.result, &self.random, &self.switch]
+            .iter()
+            .filter(|x| x.len() > 0)
+            .count();
+        targets == 1
     }
     /// Tests if this choice always leads to the same result or not
     ///
@@ -501,6 +1474,14 @@ impl Choice {
     pub fn is_constant(&self) -> bool {
         self.result.len() > 0
     }
+    /// Tests if the choice leads to a random result
+    pub fn is_random(&self) -> bool {
+        self.random.len() > 0
+    }
+    /// Tests if the choice leads to a result picked by a switch
+    pub fn is_switch(&self) -> bool {
+        self.switch.len() > 0
+    }
     /// Tests if the choice leads to end of a game
     pub fn is_game_over(&self) -> bool {
         self.result == GAME_OVER_KEYWORD
@@ -524,18 +1505,10 @@ impl Choice {
     }
 }
 impl From<&str> for Comparison {
-    /// Less than or equal is default for anything that doesn't match expected comparisons. Not sure if I should leave it like this or error
+    /// Less than or equal is default for anything that doesn't match expected comparisons.
+    /// It's meant for UI where the comparison comes from a fixed list, parsers should use parse instead
     fn from(item: &str) -> Self {
-        match item.trim() {
-            ">" => Comparison::Greater,
-            ">=" => Comparison::GreaterEqual,
-            "=" => Comparison::Equal,
-            "==" => Comparison::Equal,
-            "!" => Comparison::NotEqual,
-            "!=" => Comparison::NotEqual,
-            "<" => Comparison::Less,
-            _ => Comparison::LessEqual,
-        }
+        Comparison::parse(item).unwrap_or(Comparison::LessEqual)
     }
 }
 impl From<String> for Comparison {
@@ -555,7 +1528,50 @@ impl Display for Comparison {
         }
     }
 }
+impl Display for ChoiceElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChoiceElement::Condition => write!(f, "Condition"),
+            ChoiceElement::Test => write!(f, "Test"),
+            ChoiceElement::Result => write!(f, "Result"),
+            ChoiceElement::Random => write!(f, "Random"),
+            ChoiceElement::Switch => write!(f, "Switch"),
+        }
+    }
+}
+impl Display for MissingReference {
+    /// Choices are referred to by their number in the page, starting from 1
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Choice {}: {} {} doesn't exist", self.choice + 1, self.element, self.name)
+    }
+}
+impl Display for TestRoll {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.success {
+            write!(f, "Rolled {} vs {}, success", self.left, self.right)
+        } else {
+            write!(f, "Rolled {} vs {}, failure", self.left, self.right)
+        }
+    }
+}
 impl Comparison {
+    /// Converts a string into a comparison
+    ///
+    /// # Error
+    /// Returns InvalidComparison error if the string isn't one of recognized comparison operators
+    pub fn parse(item: &str) -> Result<Comparison, ParsingError> {
+        match item.trim() {
+            ">" => Ok(Comparison::Greater),
+            ">=" => Ok(Comparison::GreaterEqual),
+            "=" => Ok(Comparison::Equal),
+            "==" => Ok(Comparison::Equal),
+            "!" => Ok(Comparison::NotEqual),
+            "!=" => Ok(Comparison::NotEqual),
+            "<" => Ok(Comparison::Less),
+            "<=" => Ok(Comparison::LessEqual),
+            x => Err(ParsingError::InvalidComparison(x.to_string())),
+        }
+    }
     /// Performs a test between two values according to the comparison type
     pub fn compare(&self, lhv: i32, rhv: i32) -> bool {
         match self {
@@ -605,7 +1621,7 @@ impl Condition {
         Ok(Condition {
             name: args[0].to_string(),
             expression_l: args[1].to_string(),
-            comparison: Comparison::from(args[2]),
+            comparison: Comparison::parse(args[2])?,
             expression_r: args[3].to_string(),
         })
     }
@@ -621,6 +1637,7 @@ impl Condition {
         &self,
         records: &HashMap<String, Record>,
         rand: &mut Random,
+        rounding: Rounding,
     ) -> Result<bool, EvaluationError> {
         evaluate_and_compare(
             &self.expression_l,
@@ -628,6 +1645,7 @@ impl Condition {
             &self.comparison,
             records,
             rand,
+            rounding,
         )
     }
     /// Tests if a keyword is present within the condition's expressions
@@ -669,7 +1687,7 @@ impl Test {
         Ok(Test {
             name: args[0].to_string(),
             expression_l: args[1].to_string(),
-            comparison: Comparison::from(args[2]),
+            comparison: Comparison::parse(args[2])?,
             expression_r: args[3].to_string(),
             success_result: args[4].to_string(),
             failure_result: args[5].to_string(),
@@ -688,6 +1706,7 @@ impl Test {
         )
     }
     /// Evaluates the expressions within the test and compares them. Then the function returns either a success or failure result name
+    /// along with the values both expressions evaluated to
     ///
     /// # Error
     /// If evaluation fails on either expression, error will be returned instead.
@@ -695,23 +1714,26 @@ impl Test {
         &self,
         records: &HashMap<String, Record>,
         rand: &mut Random,
-    ) -> Result<&String, EvaluationError> {
-        match evaluate_and_compare(
+        rounding: Rounding,
+    ) -> Result<(&String, TestRoll), EvaluationError> {
+        let (left, right) = evaluate_operands(
             &self.expression_l,
             &self.expression_r,
-            &self.comparison,
             records,
             rand,
-        ) {
-            Ok(v) => {
-                if v {
-                    Ok(&self.success_result)
-                } else {
-                    Ok(&self.failure_result)
-                }
-            }
-            Err(e) => Err(e),
-        }
+            rounding,
+        )?;
+        let success = self.comparison.compare(left, right);
+        let roll = TestRoll {
+            left,
+            right,
+            success,
+        };
+        if success {
+            Ok((&self.success_result, roll))
+        } else {
+            Ok((&self.failure_result, roll))
+        }
     }
     /// Tests if a keyword is present in either of expressions of the test
     ///
@@ -802,19 +1824,262 @@ impl StoryResult {
             .for_each(|x| replace_with_regex!(regex, *x.1, new));
     }
 }
+impl RandomResult {
+    /// Parses a string into a RandomResult
+    ///
+    /// # Error
+    /// The string needs to be separated with ; and contain a name followed by at least one pair of page name and weight
+    pub fn parse_from_string(text: String) -> Result<RandomResult, ParsingError> {
+        let (name, outcomes) = match text.split_once(";") {
+            Some(v) => v,
+            None => return Err(ParsingError::IncorrectElementCount(text, 3)),
+        };
+        let name = name.trim().to_string();
+        if name.len() == 0 {
+            return Err(ParsingError::Invalid(text));
+        }
+        let outcomes = RandomResult::parse_outcomes(outcomes)?;
+        Ok(RandomResult { name, outcomes })
+    }
+    /// Parses pairs of page names and weights separated by ; or new lines
+    ///
+    /// # Error
+    /// There needs to be at least one pair and each weight needs to be a number above zero
+    pub fn parse_outcomes(text: &str) -> Result<Vec<(String, i32)>, ParsingError> {
+        let mut args: VecDeque<&str> = text
+            .split(&[';', '\n'][..])
+            .map(|x| x.trim())
+            .filter(|x| x.len() > 0)
+            .collect();
+        if args.len() < 2 {
+            return Err(ParsingError::IncorrectElementCount(text.to_string(), 2));
+        }
+        let mut outcomes = Vec::new();
+        while let Some(page) = args.pop_front() {
+            let weight = match args.pop_front() {
+                Some(w) => w,
+                None => return Err(ParsingError::ElementPairMissing(text.to_string())),
+            };
+            match weight.parse::<i32>() {
+                Ok(w) if w > 0 => outcomes.push((page.to_string(), w)),
+                Ok(_) => return Err(ParsingError::Invalid(text.to_string())),
+                Err(_) => return Err(ParsingError::ValueNaN(weight.to_string())),
+            }
+        }
+        Ok(outcomes)
+    }
+    /// Transforms the RandomResult into a string representation
+    fn serialize_to_string(&self) -> String {
+        let mut ser = self.name.clone();
+        self.outcomes
+            .iter()
+            .for_each(|x| ser = format!("{};{};{}", ser, x.0, x.1));
+        ser
+    }
+    /// Picks one of the pages according to their weights
+    ///
+    /// Returns None if there are no pages to pick from
+    pub fn pick(&self, rand: &mut Random) -> Option<&String> {
+        let total: i32 = self.outcomes.iter().map(|x| x.1).sum();
+        if total < 1 {
+            return None;
+        }
+        let mut roll = rand.die(1, total);
+        for outcome in self.outcomes.iter() {
+            if roll <= outcome.1 {
+                return Some(&outcome.0);
+            }
+            roll -= outcome.1;
+        }
+        None
+    }
+}
+impl ValueRange {
+    /// Parses a range written as min..max, either side can be left out to leave it open, a single number matches only itself
+    ///
+    /// # Error
+    /// Returns error if either side isn't a number or the range is empty
+    pub fn parse_from_string(text: &str) -> Result<ValueRange, ParsingError> {
+        let bound = |x: &str| -> Result<Option<i32>, ParsingError> {
+            match x.trim() {
+                "" => Ok(None),
+                x => match x.parse() {
+                    Ok(v) => Ok(Some(v)),
+                    Err(_) => Err(ParsingError::ValueNaN(x.to_string())),
+                },
+            }
+        };
+        let range = match text.split_once("..") {
+            Some((min, max)) => ValueRange {
+                min: bound(min)?,
+                max: bound(max)?,
+            },
+            None => match bound(text)? {
+                Some(v) => ValueRange {
+                    min: Some(v),
+                    max: Some(v),
+                },
+                None => return Err(ParsingError::Invalid(text.to_string())),
+            },
+        };
+        if let (Some(min), Some(max)) = (range.min, range.max) {
+            if min > max {
+                return Err(ParsingError::Invalid(text.to_string()));
+            }
+        }
+        Ok(range)
+    }
+    /// Tests if the value is within the range, ends included
+    pub fn contains(&self, value: i32) -> bool {
+        self.min.map_or(true, |x| x <= value) && self.max.map_or(true, |x| value <= x)
+    }
+}
+impl Display for ValueRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) if min == max => write!(f, "{}", min),
+            (min, max) => {
+                if let Some(min) = min {
+                    write!(f, "{}", min)?;
+                }
+                write!(f, "..")?;
+                if let Some(max) = max {
+                    write!(f, "{}", max)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+impl Switch {
+    /// Parses a Switch out of a string
+    ///
+    /// # Error
+    /// The string needs to use ; as separator and have a name, an expression and at least one pair of range and result name,
+    /// one more element after the pairs is the default result
+    pub fn parse_from_string(text: String) -> Result<Switch, ParsingError> {
+        let args: Vec<&str> = text
+            .split(";")
+            .map(|x| x.trim())
+            .filter(|x| x.len() > 0)
+            .collect();
+        if args.len() < 4 {
+            return Err(ParsingError::IncorrectElementCount(text, 4));
+        }
+        let pairs = (args.len() - 2) / 2 * 2;
+        let branches = Switch::parse_branches(&args[2..2 + pairs].join(";"))?;
+        Ok(Switch {
+            name: args[0].to_string(),
+            expression: args[1].to_string(),
+            branches,
+            default_result: args.get(2 + pairs).unwrap_or(&"").to_string(),
+        })
+    }
+    /// Parses pairs of value ranges and result names separated by ; or new lines
+    ///
+    /// # Error
+    /// There needs to be at least one pair and each range needs to be valid
+    pub fn parse_branches(text: &str) -> Result<Vec<(ValueRange, String)>, ParsingError> {
+        let mut args: VecDeque<&str> = text
+            .split(&[';', '\n'][..])
+            .map(|x| x.trim())
+            .filter(|x| x.len() > 0)
+            .collect();
+        if args.len() < 2 {
+            return Err(ParsingError::IncorrectElementCount(text.to_string(), 2));
+        }
+        let mut branches = Vec::new();
+        while let Some(range) = args.pop_front() {
+            let result = match args.pop_front() {
+                Some(r) => r,
+                None => return Err(ParsingError::ElementPairMissing(text.to_string())),
+            };
+            branches.push((ValueRange::parse_from_string(range)?, result.to_string()));
+        }
+        Ok(branches)
+    }
+    /// Transforms the switch into a string representation of it
+    fn serialize_to_string(&self) -> String {
+        let mut ser = format!("{};{}", self.name, self.expression);
+        self.branches
+            .iter()
+            .for_each(|x| ser = format!("{};{};{}", ser, x.0, x.1));
+        if self.default_result.len() > 0 {
+            ser = format!("{};{}", ser, self.default_result);
+        }
+        ser
+    }
+    /// Evaluates the expression and returns the name of the result its value leads to, along with the value
+    ///
+    /// The result is None if the value isn't in any of the ranges and there's no default result
+    ///
+    /// # Error
+    /// Returns error if the expression can't be evaluated
+    pub fn evaluate(
+        &self,
+        records: &HashMap<String, Record>,
+        rand: &mut Random,
+        rounding: Rounding,
+    ) -> Result<(Option<&String>, i32), EvaluationError> {
+        let value = evaluate_expression(&self.expression, records, rand, rounding)?;
+        let result = match self.branches.iter().find(|x| x.0.contains(value)) {
+            Some(branch) => Some(&branch.1),
+            None if self.default_result.len() > 0 => Some(&self.default_result),
+            None => None,
+        };
+        Ok((result, value))
+    }
+    /// Tests if any of the branches or the default leads to the result
+    pub fn uses_result(&self, result: &str) -> bool {
+        self.default_result == result || self.branches.iter().any(|x| x.1 == result)
+    }
+    /// Changes the name of a result in the branches and the default
+    pub fn rename_result(&mut self, old: &str, new: &str) {
+        self.branches
+            .iter_mut()
+            .filter(|x| x.1 == old)
+            .for_each(|x| x.1 = new.to_string());
+        if self.default_result == old {
+            self.default_result = new.to_string();
+        }
+    }
+    /// Tests if a keyword is present in the expression of the switch
+    ///
+    /// The string should be a raw keyword, the function will turn it into a matchable keyword
+    pub fn is_keyword_present(&self, keyword: &str) -> bool {
+        match regex_match_keyword(keyword) {
+            Ok(regex) => regex.is_match(&self.expression),
+            Err(_) => false,
+        }
+    }
+}
 impl Record {
     /// Creates a record from a text data.
     pub fn parse_from_string(text: String) -> Result<Record, ParsingError> {
-        let args: Vec<&str> = text
+        let mut args: Vec<&str> = text
             .split(";")
             .map(|x| x.trim())
             .filter(|x| x.len() > 0)
             .collect();
 
+        // flags are always after the value, so they can't be mistaken for a category
+        let mut hidden = false;
+        let mut global = false;
+        let mut bounds = ValueRange { min: None, max: None };
+        while args.len() > 2 {
+            match args.last() {
+                Some(&HIDDEN_RECORD_KEYWORD) => hidden = true,
+                Some(&GLOBAL_RECORD_KEYWORD) => global = true,
+                Some(x) if x.contains("..") => bounds = ValueRange::parse_from_string(x)?,
+                _ => break,
+            }
+            args.pop();
+        }
         let len = args.len();
         let name;
         let category;
         let value;
+        let mut expression = String::new();
         match len {
             1 => {
                 name = args[0].to_string();
@@ -834,7 +2099,10 @@ impl Record {
             3 => {
                 name = args[0].to_string();
                 category = args[1].to_string();
-                if let Ok(n) = args[2].parse() {
+                if category == DERIVED_RECORD_CATEGORY {
+                    value = 0;
+                    expression = args[2].to_string();
+                } else if let Ok(n) = args[2].parse() {
                     value = n;
                 } else {
                     return Err(ParsingError::ValueNaN(text));
@@ -846,11 +2114,95 @@ impl Record {
             name,
             category,
             value,
+            hidden,
+            expression,
+            global,
+            min: bounds.min,
+            max: bounds.max,
         })
     }
     /// Turns the record into a string representation
     fn serialize_to_string(&self) -> String {
-        format!("{};{};{}", self.name, self.category, self.value)
+        let mut ser = match self.is_derived() {
+            true => format!("{};{};{}", self.name, self.category, self.expression),
+            false => format!("{};{};{}", self.name, self.category, self.value),
+        };
+        if self.is_bounded() {
+            ser = format!("{};{}", ser, self.bounds_to_string());
+        }
+        if self.hidden {
+            ser = format!("{};{}", ser, HIDDEN_RECORD_KEYWORD);
+        }
+        if self.global {
+            ser = format!("{};{}", ser, GLOBAL_RECORD_KEYWORD);
+        }
+        ser
+    }
+    /// Turns the record into a row of comma separated values, in order of RECORDS_CSV_HEADER
+    ///
+    /// Derived records have their expression in place of the default value
+    fn to_csv_row(&self) -> String {
+        let bound = |x: Option<i32>| x.map_or(String::new(), |x| x.to_string());
+        let default = match self.is_derived() {
+            true => csv_field(&self.expression),
+            false => self.value.to_string(),
+        };
+        format!(
+            "{},{},{},{},{},{}",
+            csv_field(&self.name),
+            csv_field(&self.category),
+            default,
+            self.hidden,
+            bound(self.min),
+            bound(self.max)
+        )
+    }
+    /// Tests if the value of the record is limited on either side
+    pub fn is_bounded(&self) -> bool {
+        self.min.is_some() || self.max.is_some()
+    }
+    /// Writes the limits of the record as min..max, leaving out the sides that aren't limited
+    pub fn bounds_to_string(&self) -> String {
+        let bound = |x: Option<i32>| x.map_or(String::new(), |x| x.to_string());
+        format!("{}..{}", bound(self.min), bound(self.max))
+    }
+    /// Limits the value to the bounds of the record
+    pub fn bounded(&self, value: i32) -> i32 {
+        let value = self.min.map_or(value, |x| value.max(x));
+        self.max.map_or(value, |x| value.min(x))
+    }
+    /// Tests if the default value of the record is within its bounds, derived records are never out of bounds
+    pub fn is_within_bounds(&self) -> bool {
+        self.is_derived() || self.bounded(self.value) == self.value
+    }
+    /// Tests if the record should be hidden from the player
+    ///
+    /// Records in the hidden category are hidden as well
+    pub fn is_hidden(&self) -> bool {
+        self.hidden || self.category == HIDDEN_RECORD_KEYWORD
+    }
+    /// Tests if the record is automatically increased with every page transition
+    pub fn is_auto(&self) -> bool {
+        self.category == AUTO_RECORD_CATEGORY
+    }
+    /// Tests if the value of the record is computed from its expression instead of being stored
+    pub fn is_derived(&self) -> bool {
+        self.category == DERIVED_RECORD_CATEGORY
+    }
+    /// Value of the record as it's shown to the player, derived records are evaluated from the other records
+    ///
+    /// # Error
+    /// Returns error if the expression of a derived record can't be evaluated
+    pub fn current_value(
+        &self,
+        records: &HashMap<String, Record>,
+        rounding: Rounding,
+    ) -> Result<i32, EvaluationError> {
+        if self.is_derived() == false {
+            return Ok(self.value);
+        }
+        // derived records are meant for formulas, dice in them always roll the same
+        evaluate_expression(&self.expression, records, &mut Random::new(0), rounding)
     }
     /// Convenience function that turns the record value into string
     pub fn value_as_string(&self) -> String {
@@ -886,18 +2238,72 @@ impl Name {
         format!("{};{}", self.keyword, self.value)
     }
 }
+impl Achievement {
+    /// Parses a string into an Achievement
+    ///
+    /// The string needs to be separated with ; and have the id of the achievement followed by an optional description
+    pub fn parse_from_string(text: String) -> Result<Achievement, ParsingError> {
+        let args: Vec<&str> = text
+            .splitn(2, ";")
+            .map(|x| x.trim())
+            .filter(|x| x.len() > 0)
+            .collect();
+
+        if args.len() == 0 {
+            return Err(ParsingError::IncorrectElementCount(text, 2));
+        }
+
+        Ok(Achievement {
+            id: args[0].to_string(),
+            description: match args.len() == 2 {
+                true => args[1].to_string(),
+                false => String::new(),
+            },
+        })
+    }
+    /// Turns the achievement into a string representation
+    fn serialize_to_string(&self) -> String {
+        format!("{};{}", self.id, self.description)
+    }
+}
+impl Chapter {
+    /// Parses a string into a Chapter
+    ///
+    /// The string needs to be separated with ; and have exactly two elements, name of the chapter and its page
+    pub fn parse_from_string(text: String) -> Result<Chapter, ParsingError> {
+        let args: Vec<&str> = text
+            .split(";")
+            .map(|x| x.trim())
+            .filter(|x| x.len() > 0)
+            .collect();
+
+        if args.len() != 2 {
+            return Err(ParsingError::IncorrectElementCount(text, 2));
+        }
+
+        Ok(Chapter {
+            name: args[0].to_string(),
+            page: args[1].to_string(),
+        })
+    }
+    /// Turns the chapter into a string representation
+    fn serialize_to_string(&self) -> String {
+        format!("{};{}", self.name, self.page)
+    }
+}
 
 #[cfg(test)]
 mod tests {
 
     use std::collections::HashMap;
 
-    use regex::Regex;
-
-    use crate::adventure::Comparison;
+    use crate::{adventure::Comparison, evaluation::{EvaluationError, Random, Rounding}};
 
     use super::{
-        regex_match_keyword, Adventure, Choice, Condition, Name, Page, Record, StoryResult, Test,
+        input_prompt, is_keyword_valid, regex_match_keyword, Achievement, Adventure, Chapter, Choice, ChoiceElement, ChoiceParser, Condition,
+        MissingReference, Name, Page, ParsingError, RandomResult,
+        Record, StoryResult, Switch, Test, ValueRange, EXAMPLE_PAGE, EXAMPLE_PAGE_NAME, GAME_OVER_KEYWORD,
+        RECORDS_CSV_HEADER,
     };
 
     #[test]
@@ -906,6 +2312,151 @@ mod tests {
         let rec = Record::parse_from_string(data).unwrap();
         assert_eq!(rec.name, "strength");
         assert_eq!(rec.category, "attributes");
+        assert!(rec.is_auto() == false);
+    }
+    #[test]
+    fn record_hidden_round_trip() {
+        let rec = Record::parse_from_string("secret; ; 5; hidden".to_string()).unwrap();
+        assert_eq!(rec.category, "");
+        assert_eq!(rec.value, 5);
+        assert!(rec.hidden);
+        let rec = Record::parse_from_string(rec.serialize_to_string()).unwrap();
+        assert!(rec.hidden);
+        assert_eq!(rec.value, 5);
+
+        let rec = Record::parse_from_string("turn; auto; 0; hidden".to_string()).unwrap();
+        assert!(rec.is_auto());
+        assert!(rec.is_hidden());
+
+        let rec = Record::parse_from_string("secret; hidden; 42".to_string()).unwrap();
+        assert!(rec.hidden == false);
+        assert!(rec.is_hidden());
+        let rec = Record::parse_from_string("secret; hidden".to_string()).unwrap();
+        assert_eq!(rec.category, "hidden");
+        assert!(rec.is_hidden());
+    }
+    #[test]
+    fn record_bounds() {
+        let rec = Record::parse_from_string("health; stats; 10; 0..20; hidden".to_string()).unwrap();
+        assert_eq!((rec.min, rec.max), (Some(0), Some(20)));
+        assert!(rec.hidden);
+        assert_eq!(rec.bounded(-3), 0);
+        assert_eq!(rec.bounded(25), 20);
+        assert_eq!(rec.bounded(7), 7);
+        assert_eq!(Record::parse_from_string(rec.serialize_to_string()).unwrap(), rec);
+
+        let rec = Record::parse_from_string("debt; 0; ..0".to_string()).unwrap();
+        assert_eq!((rec.min, rec.max), (None, Some(0)));
+        assert_eq!(rec.bounds_to_string(), "..0");
+        assert_eq!(rec.bounded(5), 0);
+        assert!(rec.is_within_bounds());
+
+        let rec = Record::parse_from_string("luck; 30; 1..12".to_string()).unwrap();
+        assert!(rec.is_within_bounds() == false);
+        assert!(Record::parse_from_string("luck; 3; 12..1".to_string()).is_err());
+        assert!(Record::parse_from_string("luck; 3; a..1".to_string()).is_err());
+    }
+    #[test]
+    fn record_negative_round_trip() {
+        let rec = Record::parse_from_string("debt; money; -250".to_string()).unwrap();
+        assert_eq!(rec.category, "money");
+        assert_eq!(rec.value, -250);
+        assert_eq!(rec.value_as_string(), "-250");
+        assert_eq!(Record::parse_from_string(rec.serialize_to_string()).unwrap(), rec);
+
+        let rec = Record::parse_from_string("temperature; -5; -30..-1".to_string()).unwrap();
+        assert_eq!(rec.category, "");
+        assert_eq!(rec.value, -5);
+        assert_eq!((rec.min, rec.max), (Some(-30), Some(-1)));
+        assert!(rec.is_within_bounds());
+        assert_eq!(Record::parse_from_string(rec.serialize_to_string()).unwrap(), rec);
+
+        let rec = Record::parse_from_string("luck; stats; 0".to_string()).unwrap();
+        assert_eq!(rec.value, 0);
+        assert_eq!(Record::parse_from_string(rec.serialize_to_string()).unwrap(), rec);
+    }
+    #[test]
+    fn records_csv() {
+        let mut adventure = Adventure::default();
+        for text in ["gold; items; 10; 0..", "hp; 5", "secret; a, \"b\"; 1; hidden"] {
+            let rec = Record::parse_from_string(text.to_string()).unwrap();
+            adventure.records.insert(rec.name.clone(), rec);
+        }
+        assert_eq!(adventure.record_categories(), vec!["a, \"b\"", "items"]);
+        let derived = Record::parse_from_string("power; derived; [gold] + [hp]".to_string()).unwrap();
+        adventure.records.insert(derived.name.clone(), derived);
+        let csv = adventure.records_to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], RECORDS_CSV_HEADER);
+        assert_eq!(lines[1], "hp,,5,false,,");
+        assert_eq!(lines[2], "secret,\"a, \"\"b\"\"\",1,true,,");
+        assert_eq!(lines[3], "power,derived,[gold] + [hp],false,,");
+        assert_eq!(lines[4], "gold,items,10,false,0,");
+    }
+    #[test]
+    fn records_preview() {
+        let mut adventure = Adventure::default();
+        for text in ["gold; items; 10", "hp; 5", "secret; 1; hidden", "rope; items; 1", "power; derived; [hp]*2"] {
+            let rec = Record::parse_from_string(text.to_string()).unwrap();
+            adventure.records.insert(rec.name.clone(), rec);
+        }
+        assert_eq!(
+            adventure.records_preview(),
+            vec!["hp 5", "derived: power 10", "items: gold 10, rope 1"]
+        );
+        assert!(Adventure::default().records_preview().is_empty());
+    }
+    #[test]
+    fn record_global_round_trip() {
+        let rec = Record::parse_from_string("renown; 0; global".to_string()).unwrap();
+        assert!(rec.global);
+        assert!(rec.is_hidden() == false);
+        let rec = Record::parse_from_string("ally; quests; 1; global; hidden".to_string()).unwrap();
+        assert!(rec.global && rec.hidden);
+        assert_eq!(rec.category, "quests");
+        let rec = Record::parse_from_string(rec.serialize_to_string()).unwrap();
+        assert!(rec.global && rec.hidden);
+        assert_eq!(rec.value, 1);
+        let rec = Record::parse_from_string("ally; global".to_string()).unwrap();
+        assert!(rec.global == false);
+        assert_eq!(rec.category, "global");
+    }
+    #[test]
+    fn record_derived() {
+        let rec = Record::parse_from_string("defense; derived; [armor]+[agility]/2".to_string()).unwrap();
+        assert!(rec.is_derived());
+        assert_eq!(rec.expression, "[armor]+[agility]/2");
+        let rec = Record::parse_from_string(rec.serialize_to_string()).unwrap();
+        assert_eq!(rec.expression, "[armor]+[agility]/2");
+
+        let mut records = HashMap::new();
+        for text in ["armor; 4", "agility; 3"] {
+            let r = Record::parse_from_string(text.to_string()).unwrap();
+            records.insert(r.name.clone(), r);
+        }
+        assert_eq!(rec.current_value(&records, Rounding::Floor), Ok(5));
+        assert_eq!(rec.current_value(&records, Rounding::Ceil), Ok(6));
+        assert_eq!(records["armor"].current_value(&records, Rounding::Floor), Ok(4));
+
+        // derived records can be computed from other derived records, but not from themselves
+        records.insert(rec.name.clone(), rec.clone());
+        let block = Record::parse_from_string("block; derived; [defense]*2".to_string()).unwrap();
+        assert_eq!(block.current_value(&records, Rounding::Floor), Ok(10));
+        let rec = Record::parse_from_string("defense; derived; [block]-1".to_string()).unwrap();
+        records.insert(rec.name.clone(), rec);
+        records.insert(block.name.clone(), block.clone());
+        assert_eq!(
+            block.current_value(&records, Rounding::Floor),
+            Err(EvaluationError::CircularRecord("defense".to_string()))
+        );
+    }
+    #[test]
+    fn record_auto_round_trip() {
+        let rec = Record::parse_from_string("turn; auto; 0".to_string()).unwrap();
+        assert!(rec.is_auto());
+        let rec = Record::parse_from_string(rec.serialize_to_string()).unwrap();
+        assert!(rec.is_auto());
+        assert_eq!(rec.value, 0);
     }
     #[test]
     fn result_parse() {
@@ -923,6 +2474,16 @@ mod tests {
         assert_eq!(res.side_effects.get("strength").unwrap(), "1");
     }
     #[test]
+    fn result_input_parse() {
+        let data = "wager; table; gold; ask: How much gold do you wager?; hero; ask: What's your name?".to_string();
+        let res = StoryResult::parse_from_string(data).unwrap();
+        assert_eq!(input_prompt(&res.side_effects["gold"]), Some("How much gold do you wager?"));
+        assert_eq!(input_prompt(&res.side_effects["hero"]), Some("What's your name?"));
+        let res = StoryResult::parse_from_string(res.serialize_to_string()).unwrap();
+        assert_eq!(input_prompt(&res.side_effects["gold"]), Some("How much gold do you wager?"));
+        assert_eq!(input_prompt("[gold] + 1"), None);
+    }
+    #[test]
     fn test_parse() {
         let data = "bravery; 1d20; <=; [confidence]; proceed; cowardness;".to_string();
         let t = Test::parse_from_string(data).unwrap();
@@ -934,6 +2495,24 @@ mod tests {
         assert_eq!(t.failure_result, "cowardness");
     }
     #[test]
+    fn page_unused_results() {
+        let mut page = Page::default();
+        for text in ["proceed; next", "cowardness; end", "leftover; next", "forgotten; end"] {
+            let res = StoryResult::parse_from_string(text.to_string()).unwrap();
+            page.results.insert(res.name.clone(), res);
+        }
+        let test = Test::parse_from_string("bravery; 1d20; <=; 10; proceed; cowardness;".to_string()).unwrap();
+        page.tests.insert(test.name.clone(), test);
+        assert_eq!(page.unused_results(), vec!["forgotten", "leftover"]);
+
+        page.choices.push(Choice {
+            text: "Look around".to_string(),
+            result: "leftover".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(page.unused_results(), vec!["forgotten"]);
+    }
+    #[test]
     fn condition_parse() {
         let data = "wealth; [wealth]; >=; 1d100+15;".to_string();
         let con = Condition::parse_from_string(data).unwrap();
@@ -960,11 +2539,8 @@ mod tests {
     #[test]
     fn choice_parse_condition_result() {
         let data = "Do something brave! {condition: brave} {result: proceed}".to_string();
-        let match_condition = Regex::new(super::REGEX_CONDITION_IN_CHOICE).unwrap();
-        let match_test = Regex::new(super::REGEX_TEST_IN_CHOICE).unwrap();
-        let match_result = Regex::new(super::REGEX_RESULT_IN_CHOICE).unwrap();
-        let cho =
-            Choice::parse_from_string(data, &match_condition, &match_test, &match_result).unwrap();
+        let parser = ChoiceParser::new();
+        let cho = Choice::parse_from_string(data, &parser).unwrap();
         assert_eq!(cho.text, "Do something brave!");
         assert_eq!(cho.test, "");
         assert_eq!(cho.condition, "brave");
@@ -973,11 +2549,8 @@ mod tests {
     #[test]
     fn choice_parse_test() {
         let data = "Do something brave! { test: bravery }".to_string();
-        let match_condition = Regex::new(super::REGEX_CONDITION_IN_CHOICE).unwrap();
-        let match_test = Regex::new(super::REGEX_TEST_IN_CHOICE).unwrap();
-        let match_result = Regex::new(super::REGEX_RESULT_IN_CHOICE).unwrap();
-        let cho =
-            Choice::parse_from_string(data, &match_condition, &match_test, &match_result).unwrap();
+        let parser = ChoiceParser::new();
+        let cho = Choice::parse_from_string(data, &parser).unwrap();
         assert_eq!(cho.text, "Do something brave!");
         assert_eq!(cho.test, "bravery");
         assert_eq!(cho.condition, "");
@@ -986,28 +2559,195 @@ mod tests {
     #[test]
     fn choice_parse() {
         let data = "Do something brave! { result: proceed }".to_string();
-        let match_condition = Regex::new(super::REGEX_CONDITION_IN_CHOICE).unwrap();
-        let match_test = Regex::new(super::REGEX_TEST_IN_CHOICE).unwrap();
-        let match_result = Regex::new(super::REGEX_RESULT_IN_CHOICE).unwrap();
-        let cho =
-            Choice::parse_from_string(data, &match_condition, &match_test, &match_result).unwrap();
+        let parser = ChoiceParser::new();
+        let cho = Choice::parse_from_string(data, &parser).unwrap();
         assert_eq!(cho.text, "Do something brave!");
         assert_eq!(cho.test, "");
         assert_eq!(cho.condition, "");
         assert_eq!(cho.result, "proceed");
     }
     #[test]
+    fn choice_parse_random() {
+        let data = "Wander around {random: wander}".to_string();
+        let parser = ChoiceParser::new();
+        let cho = Choice::parse_from_string(data, &parser).unwrap();
+        assert_eq!(cho.text, "Wander around");
+        assert_eq!(cho.random, "wander");
+        assert!(cho.once == false);
+        assert!(cho.is_random());
+        assert!(cho.is_constant() == false);
+    }
+    #[test]
+    fn choice_parse_switch() {
+        let data = "Search the chest {switch: loot}".to_string();
+        let parser = ChoiceParser::new();
+        let cho = Choice::parse_from_string(data, &parser).unwrap();
+        assert_eq!(cho.text, "Search the chest");
+        assert_eq!(cho.switch, "loot");
+        assert!(cho.is_switch());
+        assert!(cho.is_valid());
+        assert_eq!(cho.serialize_to_string(), "Search the chest{switch: loot}");
+    }
+    #[test]
+    fn switch_parse() {
+        let switch =
+            Switch::parse_from_string(" loot; 1d6+[luck]; ..2; nothing; 3..5; gold; 6; gem; map".to_string())
+                .unwrap();
+        assert_eq!(switch.name, "loot");
+        assert_eq!(switch.expression, "1d6+[luck]");
+        assert_eq!(
+            switch.branches,
+            vec![
+                (ValueRange { min: None, max: Some(2) }, "nothing".to_string()),
+                (ValueRange { min: Some(3), max: Some(5) }, "gold".to_string()),
+                (ValueRange { min: Some(6), max: Some(6) }, "gem".to_string()),
+            ]
+        );
+        assert_eq!(switch.default_result, "map");
+        assert_eq!(switch.serialize_to_string(), "loot;1d6+[luck];..2;nothing;3..5;gold;6;gem;map");
+
+        let switch = Switch::parse_from_string("loot;[gold];-5..;rich".to_string()).unwrap();
+        assert_eq!(switch.branches[0].0, ValueRange { min: Some(-5), max: None });
+        assert_eq!(switch.default_result, "");
+        assert_eq!(switch.serialize_to_string(), "loot;[gold];-5..;rich");
+
+        assert!(Switch::parse_from_string("loot;1d6;1..3".to_string()).is_err());
+        assert!(Switch::parse_from_string("loot;1d6;5..3;gold".to_string()).is_err());
+        assert!(Switch::parse_from_string("loot;1d6;few;gold".to_string()).is_err());
+        assert!(Switch::parse_branches("1..3; gold\n4..; gem").is_ok());
+        assert!(Switch::parse_branches("1..3; gold\n4..").is_err());
+    }
+    #[test]
+    fn switch_evaluate() {
+        let mut switch = Switch::parse_from_string("loot;[gold];..0;poor;1..9;modest".to_string()).unwrap();
+        let mut records = HashMap::new();
+        let mut gold = Record::parse_from_string("gold".to_string()).unwrap();
+        let mut rand = Random::new(0);
+        let mut pick = |value: i32, switch: &Switch| {
+            gold.value = value;
+            records.insert("gold".to_string(), gold.clone());
+            switch
+                .evaluate(&records, &mut rand, Rounding::Truncate)
+                .unwrap()
+                .0
+                .cloned()
+        };
+        assert_eq!(pick(-3, &switch), Some("poor".to_string()));
+        assert_eq!(pick(0, &switch), Some("poor".to_string()));
+        assert_eq!(pick(9, &switch), Some("modest".to_string()));
+        assert_eq!(pick(10, &switch), None);
+        switch.default_result = "rich".to_string();
+        assert_eq!(pick(10, &switch), Some("rich".to_string()));
+
+        assert!(switch.uses_result("poor"));
+        switch.rename_result("poor", "broke");
+        switch.rename_result("rich", "wealthy");
+        assert!(switch.uses_result("broke") && switch.uses_result("wealthy"));
+        assert!(switch.uses_result("poor") == false);
+    }
+    #[test]
+    fn switch_missing_results() {
+        let mut page = Page::default();
+        for text in ["loot;[gold];..0;poor;1..9;modest;rich", "trade;[gold];1..;sell;haggle"] {
+            let switch = Switch::parse_from_string(text.to_string()).unwrap();
+            page.switches.insert(switch.name.clone(), switch);
+        }
+        for name in ["poor", "sell"] {
+            let result = StoryResult {
+                name: name.to_string(),
+                next_page: "market".to_string(),
+                ..Default::default()
+            };
+            page.results.insert(name.to_string(), result);
+        }
+        let mut shared = HashMap::new();
+        shared.insert(
+            "modest".to_string(),
+            StoryResult {
+                name: "modest".to_string(),
+                next_page: "market".to_string(),
+                ..Default::default()
+            },
+        );
+        let (loot, trade) = ("loot".to_string(), "trade".to_string());
+        let (rich, haggle) = ("rich".to_string(), "haggle".to_string());
+        assert_eq!(
+            page.missing_switch_results(&shared),
+            vec![(&loot, &rich), (&trade, &haggle)]
+        );
+
+        let adventure = Adventure {
+            results: shared,
+            ..Default::default()
+        };
+        let mut pages = HashMap::new();
+        pages.insert("market".to_string(), page);
+        let problems = adventure.validate(&pages);
+        assert!(problems.contains(&"Page market: Switch loot leads to result rich which doesn't exist".to_string()));
+        assert!(problems.contains(&"Page market: Switch trade leads to result haggle which doesn't exist".to_string()));
+    }
+    #[test]
+    fn random_result_parse() {
+        let ran = RandomResult::parse_from_string(" wander; forest; 3; river; 1".to_string()).unwrap();
+        assert_eq!(ran.name, "wander");
+        assert_eq!(
+            ran.outcomes,
+            vec![("forest".to_string(), 3), ("river".to_string(), 1)]
+        );
+        assert!(RandomResult::parse_from_string("wander".to_string()).is_err());
+        assert!(RandomResult::parse_from_string("wander;forest".to_string()).is_err());
+        assert!(RandomResult::parse_from_string("wander;forest;0".to_string()).is_err());
+        assert!(RandomResult::parse_from_string("wander;forest;many".to_string()).is_err());
+        assert_eq!(
+            RandomResult::parse_outcomes("forest; 2\nriver; 5").unwrap(),
+            vec![("forest".to_string(), 2), ("river".to_string(), 5)]
+        );
+    }
+    #[test]
+    fn random_result_pick() {
+        let ran = RandomResult {
+            name: "wander".to_string(),
+            outcomes: vec![("forest".to_string(), 3), ("river".to_string(), 1)],
+        };
+        let mut rand = Random::new(69420);
+        let mut forest = 0;
+        for _ in 0..400 {
+            match ran.pick(&mut rand).unwrap().as_str() {
+                "forest" => forest += 1,
+                "river" => {}
+                _ => assert!(false),
+            }
+        }
+        assert!(forest > 250 && forest < 350);
+        assert_eq!(RandomResult::default().pick(&mut rand), None);
+    }
+    #[test]
+    fn choice_parse_once() {
+        let data = "Open the chest {once} {result: loot}".to_string();
+        let parser = ChoiceParser::new();
+        let cho = Choice::parse_from_string(data, &parser).unwrap();
+        assert_eq!(cho.text, "Open the chest");
+        assert_eq!(cho.result, "loot");
+        assert!(cho.once);
+    }
+    #[test]
     fn choice_valid() {
         let mut cho = Choice {
             text: String::from("Do something brave!"),
             condition: String::new(),
             result: String::from("Proceed"),
             test: String::new(),
+            random: String::new(),
+            switch: String::new(),
+            once: false,
         };
         assert!(cho.is_valid());
         cho.result = String::new();
         cho.test = String::from("bravery");
         assert!(cho.is_valid());
+        cho.test = String::new();
+        cho.random = String::from("wander");
+        assert!(cho.is_valid());
     }
     #[test]
     fn choice_invalid() {
@@ -1016,11 +2756,17 @@ mod tests {
             condition: String::new(),
             result: String::new(),
             test: String::new(),
+            random: String::new(),
+            switch: String::new(),
+            once: false,
         };
         assert!(!cho.is_valid());
         cho.result = String::from("proceed");
         cho.test = String::from("bravery");
         assert!(!cho.is_valid());
+        cho.test = String::new();
+        cho.random = String::from("wander");
+        assert!(!cho.is_valid());
     }
     #[test]
     fn page_parse() {
@@ -1047,6 +2793,51 @@ result: coward; confidence; -1; coward_scene;".to_string();
         }
     }
     #[test]
+    fn tags_parse() {
+        let data = "title: Tagged
+tags: combat,  chapter1 ,, needs-review
+story: Story text
+choice: The end {result: game over}"
+            .to_string();
+        let page = Page::parse_from_string(data).unwrap();
+        assert_eq!(page.tags, vec!["combat", "chapter1", "needs-review"]);
+        assert_eq!(page.story, "Story text");
+        assert_eq!(page.shuffle, false);
+
+        let data = "title: Shuffled
+shuffle: true
+story: Story text
+choice: The end {result: game over}"
+            .to_string();
+        let page = Page::parse_from_string(data).unwrap();
+        assert!(page.shuffle);
+        assert!(page.serialize_to_string().contains("\nshuffle: true\n"));
+        let data = "title: Shuffled
+shuffle: maybe
+story: Story text
+choice: The end {result: game over}"
+            .to_string();
+        let page = Page::parse_from_string(data).unwrap();
+        assert!(page.shuffle == false);
+        assert_eq!(page.unknown_keys, vec![("shuffle".to_string(), "maybe".to_string())]);
+    }
+    #[test]
+    fn crlf_parse() {
+        let data = "title: At the Castle Ruins\r\nstory: First line\r\nSecond line\r\nchoice: Run away! {result: coward}\r\nresult: coward; coward_scene; confidence; -1\r\n".to_string();
+        let page = Page::parse_from_string(data).unwrap();
+        assert_eq!(page.title, "At the Castle Ruins");
+        assert_eq!(page.story, "First line\nSecond line");
+        let res = page.results.get("coward").unwrap();
+        assert_eq!(res.next_page, "coward_scene");
+        assert_eq!(res.side_effects.get("confidence").unwrap(), "-1");
+
+        let data = "title: Damsel\r\nstart: intro\rrecord: confidence; attributes; 2\r\n".to_string();
+        let adventure = Adventure::parse_from_string(data, "damsel".to_string()).unwrap();
+        assert_eq!(adventure.title, "Damsel");
+        assert_eq!(adventure.start, "intro");
+        assert_eq!(adventure.records.get("confidence").unwrap().value, 2);
+    }
+    #[test]
     fn capture_keyword() {
         let data = "this is a test string with a [spaced keyword] that should be captured";
         let regex = regex_match_keyword("spaced keyword").unwrap();
@@ -1058,6 +2849,82 @@ result: coward; confidence; -1; coward_scene;".to_string();
         }
     }
     #[test]
+    fn capture_keyword_with_metacharacters() {
+        let regex = regex_match_keyword("a+b").unwrap();
+        assert!(regex.is_match("the [a+b] keyword"));
+        assert!(regex.is_match("the [ a+b ] keyword"));
+        assert!(regex.is_match("the [aab] keyword") == false);
+        assert!(regex_match_keyword("(unclosed").is_ok());
+    }
+    #[test]
+    fn rename_keyword_all_occurrences() {
+        let mut page = Page {
+            title: "[name] and the dragon".to_string(),
+            story: "[name] entered the cave. The dragon roared at [ name ], but [name] didn't flinch.".to_string(),
+            results: {
+                let mut r = HashMap::new();
+                r.insert(
+                    "brave".to_string(),
+                    StoryResult {
+                        name: "brave".to_string(),
+                        next_page: "next".to_string(),
+                        side_effects: {
+                            let mut se = HashMap::new();
+                            se.insert("title".to_string(), "[name] the [name]slayer".to_string());
+                            se
+                        },
+                    },
+                );
+                r
+            },
+            ..Default::default()
+        };
+        page.rename_keyword("name", "hero");
+        assert_eq!(page.title, "[hero] and the dragon");
+        assert_eq!(
+            page.story,
+            "[hero] entered the cave. The dragon roared at [ hero ], but [hero] didn't flinch."
+        );
+        assert_eq!(
+            page.results.get("brave").unwrap().side_effects.get("title").unwrap(),
+            "[hero] the [hero]slayer"
+        );
+    }
+    #[test]
+    fn keyword_validity() {
+        assert!(is_keyword_valid("gold"));
+        assert!(is_keyword_valid("name of town"));
+        assert!(is_keyword_valid("a;b") == false);
+        assert!(is_keyword_valid("a[b]") == false);
+        assert!(is_keyword_valid("[gold]") == false);
+        assert!(is_keyword_valid("gold]") == false);
+        assert!(is_keyword_valid(" gold") == false);
+        assert!(is_keyword_valid("gold ") == false);
+        assert!(is_keyword_valid("") == false);
+    }
+    #[test]
+    fn keyword_whole_token() {
+        let regex = regex_match_keyword("name").unwrap();
+        assert!(regex.is_match("[name]"));
+        assert!(regex.is_match("[ name ]"));
+        assert!(regex.is_match("[namesake]") == false);
+        assert!(regex.is_match("[name of town]") == false);
+        assert!(regex.is_match("[surname]") == false);
+
+        let mut page = Page {
+            title: "The [namesake]".to_string(),
+            story: "You arrive at [name of town].".to_string(),
+            ..Default::default()
+        };
+        assert!(page.is_keyword_present("name") == false);
+        assert!(page.is_keyword_present("namesake"));
+        assert!(page.is_keyword_present("name of town"));
+
+        page.rename_keyword("name", "hero");
+        assert_eq!(page.title, "The [namesake]");
+        assert_eq!(page.story, "You arrive at [name of town].");
+    }
+    #[test]
     fn adventure_parse() {
         let data = "title: Damsel in Distress
 description: This is a story about a knight who faces a dragon to save the princess
@@ -1083,6 +2950,590 @@ record: stuffed animals; resources;"
         assert_eq!(stuff.category, "resources");
     }
     #[test]
+    fn adventure_parse_multiline_description() {
+        let data = "title: Damsel in Distress
+description: This is a story about a knight
+who faces a dragon to save the princess
+start: at_the_castle_ruins"
+            .to_string();
+        let adventure = Adventure::parse_from_string(data, "damsel".to_string()).unwrap();
+        assert_eq!(
+            adventure.description,
+            "This is a story about a knight\nwho faces a dragon to save the princess"
+        );
+
+        let serialized = adventure.serialize_to_string();
+        let b = Adventure::parse_from_string(serialized, "damsel".to_string()).unwrap();
+        assert_eq!(adventure.description, b.description);
+        assert_eq!(adventure.start, b.start);
+    }
+    #[test]
+    fn adventure_parse_unknown_key() {
+        let data = "title: Damsel in Distress
+description: This is a story about a knight
+author: Purrie
+license: CC-BY
+start: at_the_castle_ruins"
+            .to_string();
+        let adventure = Adventure::parse_from_string(data, "damsel".to_string()).unwrap();
+
+        assert_eq!(adventure.description, "This is a story about a knight");
+        assert_eq!(adventure.author, "Purrie");
+        assert_eq!(adventure.version, "");
+        assert_eq!(adventure.start, "at_the_castle_ruins");
+        assert_eq!(adventure.unknown_keys, vec![("license".to_string(), "CC-BY".to_string())]);
+    }
+    #[test]
+    fn adventure_chapters() {
+        let data = "title: Damsel in Distress
+start: at_the_castle_ruins
+chapter: The Tower; tower_gate
+chapter: Escape; escape"
+            .to_string();
+        let adventure = Adventure::parse_from_string(data, "damsel".to_string()).unwrap();
+        assert_eq!(adventure.chapters.len(), 2);
+        assert_eq!(adventure.chapter_page("The Tower").unwrap(), "tower_gate");
+        assert_eq!(adventure.chapter_page("Escape").unwrap(), "escape");
+        assert!(adventure.chapter_page("Prologue").is_none());
+
+        let adventure =
+            Adventure::parse_from_string(adventure.serialize_to_string(), "damsel".to_string())
+                .unwrap();
+        assert_eq!(adventure.start, "at_the_castle_ruins");
+        assert_eq!(
+            adventure.chapters[0],
+            Chapter {
+                name: "The Tower".to_string(),
+                page: "tower_gate".to_string()
+            }
+        );
+        assert_eq!(adventure.chapters[1].name, "Escape");
+        assert!(Chapter::parse_from_string("Epilogue".to_string()).is_err());
+    }
+    #[test]
+    fn adventure_dictionary() {
+        let data = "title: Damsel in Distress
+start: at_the_castle_ruins
+dictionary: Eldoria, Nesforesoth,
+dictionary: wyvern"
+            .to_string();
+        let adventure = Adventure::parse_from_string(data, "damsel".to_string()).unwrap();
+        assert_eq!(adventure.dictionary, vec!["Eldoria", "Nesforesoth", "wyvern"]);
+        let adventure =
+            Adventure::parse_from_string(adventure.serialize_to_string(), "damsel".to_string())
+                .unwrap();
+        assert_eq!(adventure.dictionary, vec!["Eldoria", "Nesforesoth", "wyvern"]);
+        assert!(adventure.serialize_to_string().contains("dictionary: Eldoria, Nesforesoth, wyvern"));
+    }
+    #[test]
+    fn adventure_achievements() {
+        let data = "title: Damsel in Distress
+start: at_the_castle_ruins
+achievement: rescuer; Saved the princess; without a scratch
+achievement: wanderer"
+            .to_string();
+        let adventure = Adventure::parse_from_string(data, "damsel".to_string()).unwrap();
+        let adventure =
+            Adventure::parse_from_string(adventure.serialize_to_string(), "damsel".to_string())
+                .unwrap();
+        assert_eq!(adventure.achievements.len(), 2);
+        assert_eq!(
+            adventure.achievement("rescuer"),
+            Some(&Achievement {
+                id: "rescuer".to_string(),
+                description: "Saved the princess; without a scratch".to_string()
+            })
+        );
+        assert_eq!(adventure.achievement("wanderer").unwrap().description, "");
+        assert!(adventure.achievement("villain").is_none());
+    }
+    #[test]
+    fn unknown_keys_round_trip() {
+        let data = "title: Tagged
+soundtrack: tavern.ogg
+ambience: rain.ogg
+soundtrack: fire.ogg
+story: Story text
+note: she whispers, \"run\"
+warning: the floor creaks
+choice: The end {result: game over}"
+            .to_string();
+        let page = Page::parse_from_string(data).unwrap();
+        // lines of the story that look like keys stay in the story
+        assert_eq!(page.story, "Story text\nnote: she whispers, \"run\"\nwarning: the floor creaks");
+        let page = Page::parse_from_string(page.serialize_to_string()).unwrap();
+        assert_eq!(page.story, "Story text\nnote: she whispers, \"run\"\nwarning: the floor creaks");
+        let keys = vec![
+            ("soundtrack".to_string(), "tavern.ogg".to_string()),
+            ("ambience".to_string(), "rain.ogg".to_string()),
+            ("soundtrack".to_string(), "fire.ogg".to_string()),
+        ];
+        assert_eq!(page.unknown_keys, keys);
+
+        let data = "title: Damsel in Distress
+cover: cover.png
+start: at_the_castle_ruins"
+            .to_string();
+        let adventure = Adventure::parse_from_string(data, "damsel".to_string()).unwrap();
+        let adventure =
+            Adventure::parse_from_string(adventure.serialize_to_string(), "damsel".to_string())
+                .unwrap();
+        assert_eq!(adventure.unknown_keys, vec![("cover".to_string(), "cover.png".to_string())]);
+
+        let data = "title: Damsel in Distress
+description: A story about a knight
+note: inspired by old tales
+start: at_the_castle_ruins"
+            .to_string();
+        let adventure = Adventure::parse_from_string(data, "damsel".to_string()).unwrap();
+        assert_eq!(adventure.description, "A story about a knight\nnote: inspired by old tales");
+        assert!(adventure.unknown_keys.is_empty());
+    }
+    #[test]
+    fn adventure_invalid_values() {
+        let data = "title: Damsel in Distress
+rounding: sideways
+hide_rolls: sometimes
+theme_color: #12345
+theme_size: big
+start: at_the_castle_ruins"
+            .to_string();
+        let adventure = Adventure::parse_from_string(data, "damsel".to_string()).unwrap();
+        assert_eq!(adventure.rounding, Rounding::default());
+        assert!(adventure.hide_rolls == false);
+        assert_eq!((adventure.theme_color, adventure.theme_size), (None, None));
+        let adventure =
+            Adventure::parse_from_string(adventure.serialize_to_string(), "damsel".to_string())
+                .unwrap();
+        assert_eq!(
+            adventure.unknown_keys,
+            vec![
+                ("rounding".to_string(), "sideways".to_string()),
+                ("hide_rolls".to_string(), "sometimes".to_string()),
+                ("theme_color".to_string(), "#12345".to_string()),
+                ("theme_size".to_string(), "big".to_string()),
+            ]
+        );
+    }
+    #[test]
+    fn comparison_invalid() {
+        assert!(Comparison::parse("=>").is_err());
+        assert_eq!(Comparison::parse("<=").unwrap(), Comparison::LessEqual);
+
+        let data = "bravery; 1d20; =>; [confidence]; proceed; cowardness;".to_string();
+        match Test::parse_from_string(data) {
+            Err(ParsingError::InvalidComparison(c)) => assert_eq!(c, "=>"),
+            _ => assert!(false),
+        }
+        let data = "wealth; [wealth]; <>; 15;".to_string();
+        assert!(Condition::parse_from_string(data).is_err());
+    }
+    #[test]
+    fn adventure_import_variables() {
+        let mut sequel = Adventure::default();
+        let mut original = Adventure::default();
+        for (adventure, records, names) in [
+            (&mut sequel, vec!["gold; 5", "hero; 1"], vec!["ally; Ann"]),
+            (&mut original, vec!["gold; 10", "hp; items; 3", "ally; 1"], vec!["hero; Bob", "town; Ardale"]),
+        ] {
+            for text in records {
+                let rec = Record::parse_from_string(text.to_string()).unwrap();
+                adventure.records.insert(rec.name.clone(), rec);
+            }
+            for text in names {
+                let name = Name::parse_from_string(text.to_string()).unwrap();
+                adventure.names.insert(name.keyword.clone(), name);
+            }
+        }
+        let skipped = sequel.import_variables(&original);
+        assert_eq!(
+            skipped,
+            vec![
+                "Name hero is already used in this adventure",
+                "Record ally is already used in this adventure",
+                "Record gold is already used in this adventure",
+            ]
+        );
+        assert_eq!(sequel.records["gold"].value, 5);
+        assert_eq!(sequel.records["hp"].category, "items");
+        assert_eq!(sequel.names["town"].value, "Ardale");
+        assert_eq!(sequel.names["ally"].value, "Ann");
+        assert!(sequel.names.contains_key("hero") == false);
+    }
+    #[test]
+    fn adventure_validate() {
+        let adventure = Adventure {
+            title: "test".to_string(),
+            start: "intro".to_string(),
+            ..Default::default()
+        };
+        let mut pages = HashMap::new();
+        assert_eq!(adventure.validate(&pages), vec!["Starting page intro doesn't exist"]);
+
+        let mut intro = Page::default();
+        intro.results.insert(
+            "go".to_string(),
+            StoryResult {
+                name: "go".to_string(),
+                next_page: "cave".to_string(),
+                ..Default::default()
+            },
+        );
+        pages.insert("intro".to_string(), intro);
+        assert_eq!(
+            adventure.validate(&pages),
+            vec!["Page intro: Result go leads to page cave which doesn't exist"]
+        );
+
+        pages.insert("cave".to_string(), Page::default());
+        assert!(adventure.validate(&pages).is_empty());
+
+        let mut bounded = adventure.clone();
+        let luck = Record::parse_from_string("luck; 30; 1..12".to_string()).unwrap();
+        bounded.records.insert(luck.name.clone(), luck);
+        assert_eq!(
+            bounded.validate(&pages),
+            vec!["Record luck starts at 30 which is outside of its bounds 1..12"]
+        );
+
+        let mut unfinished = pages.clone();
+        unfinished.get_mut("intro").unwrap().results.insert(
+            "wait".to_string(),
+            StoryResult {
+                name: "wait".to_string(),
+                ..Default::default()
+            },
+        );
+        assert_eq!(unfinished["intro"].results_without_next_page(), vec!["wait"]);
+        assert_eq!(
+            adventure.validate(&unfinished),
+            vec!["Page intro: Result wait has no next page"]
+        );
+
+        let cave = pages.get_mut("cave").unwrap();
+        cave.choices.push(Choice {
+            text: "Leave".to_string(),
+            condition: "lit".to_string(),
+            result: "go".to_string(),
+            ..Default::default()
+        });
+        cave.choices.push(Choice {
+            text: "Give up".to_string(),
+            result: GAME_OVER_KEYWORD.to_string(),
+            ..Default::default()
+        });
+        let missing: Vec<String> = cave
+            .missing_references(&HashMap::new())
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        assert_eq!(
+            missing,
+            vec![
+                "Choice 1: Condition lit doesn't exist",
+                "Choice 1: Result go doesn't exist"
+            ]
+        );
+        assert_eq!(
+            adventure.validate(&pages),
+            vec![
+                "Page cave: Choice 1: Condition lit doesn't exist",
+                "Page cave: Choice 1: Result go doesn't exist"
+            ]
+        );
+    }
+    #[test]
+    fn adventure_dangling_conditions() {
+        let adventure = Adventure {
+            title: "test".to_string(),
+            start: "hall".to_string(),
+            ..Default::default()
+        };
+        let hall = Page::parse_from_string(
+            "title: Hall\nstory: A hall.\nchoice: Open the chest{condition: has_key}{result: game over}\ncondition: has_key;[keys];>;0"
+                .to_string(),
+        )
+        .unwrap();
+        let mut pages = HashMap::new();
+        pages.insert("hall".to_string(), hall.clone());
+        assert!(adventure.validate(&pages).is_empty());
+
+        // the choice copied into a page without the condition
+        let mut cellar = Page::parse_from_string("title: Cellar\nstory: Dark.\nchoice: Wait{result: game over}".to_string()).unwrap();
+        cellar.choices.push(hall.choices[0].clone());
+        pages.insert("cellar".to_string(), cellar);
+        assert_eq!(
+            pages["cellar"].missing_references(&adventure.results),
+            vec![MissingReference {
+                choice: 1,
+                element: ChoiceElement::Condition,
+                name: "has_key".to_string(),
+            }]
+        );
+        assert!(adventure
+            .validate(&pages)
+            .contains(&"Page cellar: Choice 2: Condition has_key doesn't exist".to_string()));
+    }
+    #[test]
+    fn adventure_example_template() {
+        let mut adventure = Adventure {
+            title: "Example".to_string(),
+            ..Default::default()
+        };
+        adventure.add_example();
+        assert_eq!(adventure.start, EXAMPLE_PAGE_NAME);
+        let page = Page::parse_from_string(EXAMPLE_PAGE.to_string()).unwrap();
+        assert_eq!(page.choices.len(), 2);
+        let mut pages = HashMap::new();
+        pages.insert(EXAMPLE_PAGE_NAME.to_string(), page);
+        assert!(adventure.validate(&pages).is_empty());
+        assert!(adventure.check_expressions(&pages).is_empty());
+    }
+    #[test]
+    fn adventure_validate_ambiguous_side_effect() {
+        let mut adventure = Adventure {
+            start: "intro".to_string(),
+            ..Default::default()
+        };
+        adventure.records.insert(
+            "gold".to_string(),
+            Record {
+                category: String::new(),
+                name: "gold".to_string(),
+                value: 0,
+                hidden: false,
+                expression: String::new(),
+                global: false,
+                min: None,
+                max: None,
+            },
+        );
+        let mut intro = Page::default();
+        let mut result = StoryResult {
+            name: "loot".to_string(),
+            next_page: "intro".to_string(),
+            ..Default::default()
+        };
+        result.side_effects.insert("gold".to_string(), "5".to_string());
+        intro.results.insert("loot".to_string(), result);
+        let mut pages = HashMap::new();
+        pages.insert("intro".to_string(), intro);
+        assert!(adventure.validate(&pages).is_empty());
+
+        adventure.names.insert(
+            "gold".to_string(),
+            Name {
+                keyword: "gold".to_string(),
+                value: "Gold".to_string(),
+            },
+        );
+        assert_eq!(
+            adventure.validate(&pages),
+            vec!["Page intro: Result loot changes gold which is both a record and a name"]
+        );
+    }
+    #[test]
+    fn adventure_check_expressions() {
+        let mut adventure = Adventure::default();
+        let rec = Record::parse_from_string("gold; 10".to_string()).unwrap();
+        adventure.records.insert(rec.name.clone(), rec);
+        let mut pages = HashMap::new();
+        let mut page = Page::default();
+        page.conditions.insert(
+            "rich".to_string(),
+            Condition::parse_from_string("rich; [gold] / 0; >; 2d".to_string()).unwrap(),
+        );
+        page.tests.insert(
+            "luck".to_string(),
+            Test::parse_from_string("luck; [gould]; >; 1d6; win; lose".to_string()).unwrap(),
+        );
+        let mut result = StoryResult {
+            name: "win".to_string(),
+            next_page: "cave".to_string(),
+            ..Default::default()
+        };
+        result.side_effects.insert("gold".to_string(), "[gold] + silver".to_string());
+        result.side_effects.insert("silver".to_string(), "1".to_string());
+        result.side_effects.insert("achievement".to_string(), "hero".to_string());
+        page.results.insert("win".to_string(), result);
+        pages.insert("cave".to_string(), page);
+
+        let problems = adventure.check_expressions(&pages);
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+        assert!(problems[0].starts_with("Page cave: Condition rich:"));
+        assert!(problems[1].starts_with("Page cave: Result win changes silver"));
+        assert!(problems[2].starts_with("Page cave: Result win side effect gold:"));
+        assert_eq!(
+            problems[3],
+            "Page cave: Result win unlocks achievement hero which isn't declared"
+        );
+        assert_eq!(problems[4], "Page cave: Test luck: gould isn't a record");
+
+        adventure.achievements.push(Achievement {
+            id: "hero".to_string(),
+            description: String::new(),
+        });
+        assert_eq!(adventure.check_expressions(&pages).len(), 4);
+
+        for text in ["power; derived; [might]*2", "might; derived; [power]+[gold]"] {
+            let rec = Record::parse_from_string(text.to_string()).unwrap();
+            adventure.records.insert(rec.name.clone(), rec);
+        }
+        let problems = adventure.check_expressions(&pages);
+        assert_eq!(problems.len(), 6, "{:?}", problems);
+        assert_eq!(
+            problems[0],
+            "Derived records: Record might: Derived record power is computed from itself"
+        );
+        assert_eq!(
+            problems[1],
+            "Derived records: Record power: Derived record might is computed from itself"
+        );
+    }
+    #[test]
+    fn adventure_shared_results() {
+        let data = "title: Town
+start: road
+result: return to town;town;gold;1
+result: rest;inn"
+            .to_string();
+        let mut adventure = Adventure::parse_from_string(data, "town".to_string()).unwrap();
+        assert_eq!(adventure.results.len(), 2);
+        assert_eq!(adventure.results["return to town"].next_page, "town");
+        assert_eq!(adventure.results["return to town"].side_effects["gold"], "1");
+        let copy = Adventure::parse_from_string(adventure.serialize_to_string(), "town".to_string()).unwrap();
+        assert_eq!(copy.results, adventure.results);
+
+        // a page without results of its own can lead to the shared ones
+        let road = Page::parse_from_string(
+            "title: Road\nstory: A road.\nchoice: Go back{result: return to town}\nchoice: Rest{result: rest}\ntest: tired;1d6;>;3;rest;rest"
+                .to_string(),
+        )
+        .unwrap();
+        let mut town = Page::parse_from_string(
+            "title: Town\nstory: A town.\nchoice: Rest{result: rest}\nresult: rest;town".to_string(),
+        )
+        .unwrap();
+        assert_eq!(adventure.find_result(&road, "rest").unwrap().next_page, "inn");
+        assert_eq!(adventure.find_result(&town, "rest").unwrap().next_page, "town");
+        assert!(adventure.find_result(&town, "sleep").is_none());
+        assert_eq!(adventure.page_results(&town)["rest"].next_page, "town");
+        assert_eq!(adventure.page_results(&town).len(), 2);
+        assert!(road.missing_references(&adventure.results).is_empty());
+        assert_eq!(road.missing_references(&HashMap::new()).len(), 2);
+        assert!(road.is_playable_with(&adventure.results));
+        assert!(road.is_playable_with(&HashMap::new()) == false);
+        town.choices[0].result = "sleep".to_string();
+        assert!(town.is_playable_with(&adventure.results) == false);
+
+        let mut pages = HashMap::new();
+        pages.insert("road".to_string(), road);
+        pages.insert("town".to_string(), town);
+        pages.insert("inn".to_string(), Page::default());
+        assert_eq!(
+            adventure.validate(&pages),
+            vec!["Page town: Choice 1: Result sleep doesn't exist"]
+        );
+        assert_eq!(adventure.pages_using_result(&pages, "rest"), vec!["road"]);
+        assert_eq!(adventure.pages_using_result(&pages, "return to town"), vec!["road"]);
+
+        // pages with a result of the same name keep using their own
+        pages.get_mut("town").unwrap().choices[0].result = "rest".to_string();
+        assert!(adventure.rename_result(&mut pages, "rest", "sleep"));
+        assert!(adventure.rename_result(&mut pages, "sleep", "return to town") == false);
+        assert!(adventure.rename_result(&mut pages, "missing", "nap") == false);
+        assert_eq!(pages["road"].choices[1].result, "sleep");
+        assert_eq!(pages["road"].tests["tired"].success_result, "sleep");
+        assert_eq!(pages["town"].choices[0].result, "rest");
+        assert_eq!(adventure.results["sleep"].name, "sleep");
+
+        adventure.rename_page(&mut pages, "town", "city");
+        assert_eq!(adventure.results["return to town"].next_page, "city");
+        adventure.results.get_mut("sleep").unwrap().next_page = "barn".to_string();
+        adventure.rename_keyword_in_results("gold", "coins");
+        assert_eq!(adventure.results["return to town"].side_effects["coins"], "1");
+        assert_eq!(
+            adventure.validate(&pages),
+            vec!["Shared result sleep leads to page barn which doesn't exist"]
+        );
+    }
+    #[test]
+    fn adventure_rename_page() {
+        let mut adventure = Adventure {
+            title: "test".to_string(),
+            start: "intro".to_string(),
+            chapters: vec![Chapter {
+                name: "Depths".to_string(),
+                page: "cave".to_string(),
+            }],
+            ..Default::default()
+        };
+        let result = |name: &str, next: &str| {
+            (
+                name.to_string(),
+                StoryResult {
+                    name: name.to_string(),
+                    next_page: next.to_string(),
+                    ..Default::default()
+                },
+            )
+        };
+        let mut intro = Page::default();
+        intro.results.extend([result("enter", "cave"), result("wait", "intro")]);
+        let mut cave = Page::default();
+        cave.results.extend([result("back", "intro"), result("stay", "cave")]);
+        cave.randoms.insert(
+            "lost".to_string(),
+            RandomResult {
+                name: "lost".to_string(),
+                outcomes: vec![("cave".to_string(), 1), ("lake".to_string(), 1)],
+            },
+        );
+        let mut lake = Page::default();
+        lake.results.extend([result("swim", "cave")]);
+        lake.story = "Back to the [[cave]], the [[ cave |dark cave]] or [[lake|shore]]. [[cave".to_string();
+        let mut pages = HashMap::new();
+        pages.insert("intro".to_string(), intro);
+        pages.insert("cave".to_string(), cave);
+        pages.insert("lake".to_string(), lake);
+
+        assert!(adventure.rename_page(&mut pages, "cave", "grotto"));
+        assert!(adventure.validate(&pages).is_empty());
+        assert!(pages.contains_key("cave") == false);
+        assert_eq!(pages["intro"].results["enter"].next_page, "grotto");
+        assert_eq!(pages["grotto"].results["stay"].next_page, "grotto");
+        assert_eq!(pages["grotto"].randoms["lost"].outcomes[0].0, "grotto");
+        assert_eq!(pages["lake"].results["swim"].next_page, "grotto");
+        assert_eq!(adventure.chapters[0].page, "grotto");
+        assert_eq!(
+            pages["lake"].story,
+            "Back to the [[grotto|cave]], the [[grotto|dark cave]] or [[lake|shore]]. [[cave"
+        );
+        assert_eq!(pages["lake"].story_links(), vec!["grotto", "grotto", "lake"]);
+
+        assert!(adventure.rename_page(&mut pages, "intro", "start"));
+        assert_eq!(adventure.start, "start");
+        assert_eq!(pages["start"].results["wait"].next_page, "start");
+        assert_eq!(pages["grotto"].results["back"].next_page, "start");
+        assert!(adventure.validate(&pages).is_empty());
+
+        assert!(adventure.rename_page(&mut pages, "missing", "other") == false);
+
+        // renaming to a name of another page leaves both pages as they were
+        assert!(adventure.rename_page(&mut pages, "lake", "grotto") == false);
+        assert!(pages.contains_key("lake"));
+        assert_eq!(pages["grotto"].results["stay"].next_page, "grotto");
+        assert_eq!(pages["lake"].results["swim"].next_page, "grotto");
+        assert!(adventure.rename_page(&mut pages, "lake", "lake"));
+        assert_eq!(pages.len(), 3);
+
+        pages.get_mut("lake").unwrap().story = "Go to the [[tower|tall tower]].".to_string();
+        assert_eq!(
+            adventure.validate(&pages),
+            vec!["Page lake: Story links to page tower which doesn't exist"]
+        );
+    }
+    #[test]
     fn comparison_greater() {
         assert!(Comparison::Greater.compare(20, 10));
     }
@@ -1111,7 +3562,17 @@ record: stuffed animals; resources;"
         let a = Adventure {
             title: "test".to_string(),
             description: "this is a test adventure".to_string(),
+            author: "Tester".to_string(),
+            version: "1.2".to_string(),
+            date: "2022-10-01".to_string(),
             start: "start-page".to_string(),
+            rounding: Rounding::HalfUp,
+            hide_rolls: true,
+            theme_color: Some(0x3a2b1c),
+            theme_font: Some("Times".to_string()),
+            theme_size: Some(18),
+            theme_background: Some(0xf4ecd8),
+            theme_accent: Some(0x8b0000),
             records: {
                 let mut r = HashMap::new();
                 r.insert(
@@ -1120,6 +3581,11 @@ record: stuffed animals; resources;"
                         name: "first".to_string(),
                         category: "".to_string(),
                         value: 1,
+                        hidden: false,
+                        expression: String::new(),
+                        global: false,
+                        min: None,
+                        max: None,
                     },
                 );
                 r.insert(
@@ -1128,6 +3594,11 @@ record: stuffed animals; resources;"
                         name: "second".to_string(),
                         category: "".to_string(),
                         value: 4,
+                        hidden: false,
+                        expression: String::new(),
+                        global: false,
+                        min: None,
+                        max: None,
                     },
                 );
                 r
@@ -1157,7 +3628,17 @@ record: stuffed animals; resources;"
         let b = Adventure::parse_from_string(serialized, "path".to_string()).unwrap();
         assert_eq!(a.title, b.title);
         assert_eq!(a.description, b.description);
+        assert_eq!(a.author, b.author);
+        assert_eq!(a.version, b.version);
+        assert_eq!(a.date, b.date);
         assert_eq!(a.start, b.start);
+        assert_eq!(a.rounding, b.rounding);
+        assert_eq!(a.hide_rolls, b.hide_rolls);
+        assert_eq!(a.theme_color, b.theme_color);
+        assert_eq!(a.theme_font, b.theme_font);
+        assert_eq!(a.theme_size, b.theme_size);
+        assert_eq!(a.theme_background, b.theme_background);
+        assert_eq!(a.theme_accent, b.theme_accent);
         assert_eq!(a.records.get("first"), b.records.get("first"));
         assert_eq!(a.records.get("second"), b.records.get("second"));
         assert_eq!(a.names.get("hero"), b.names.get("hero"));
@@ -1167,6 +3648,7 @@ record: stuffed animals; resources;"
     fn serializing_page() {
         let a = Page {
             title: "test title".to_string(),
+            tags: vec!["chapter one".to_string(), "combat".to_string(), "needs-review".to_string()],
             story: "this is a test story".to_string(),
             choices: {
                 vec![
@@ -1191,6 +3673,17 @@ record: stuffed animals; resources;"
                         result: "result".to_string(),
                         ..Default::default()
                     },
+                    Choice {
+                        text: "Random choice".to_string(),
+                        random: "wander".to_string(),
+                        ..Default::default()
+                    },
+                    Choice {
+                        text: "Once only choice".to_string(),
+                        result: "result".to_string(),
+                        once: true,
+                        ..Default::default()
+                    },
                 ]
             },
             conditions: {
@@ -1245,11 +3738,35 @@ record: stuffed animals; resources;"
                 );
                 r
             },
+            randoms: {
+                let mut r = HashMap::new();
+                r.insert(
+                    "wander".to_string(),
+                    RandomResult {
+                        name: "wander".to_string(),
+                        outcomes: vec![("forest".to_string(), 3), ("river".to_string(), 1)],
+                    },
+                );
+                r
+            },
+            switches: {
+                let mut r = HashMap::new();
+                r.insert(
+                    "loot".to_string(),
+                    Switch::parse_from_string("loot;1d6;..2;failure;3..;success".to_string())
+                        .unwrap(),
+                );
+                r
+            },
+            ..Default::default()
         };
 
         let serialized = a.serialize_to_string();
         let b = Page::parse_from_string(serialized).unwrap();
+        assert_eq!(a.randoms, b.randoms);
+        assert_eq!(a.switches, b.switches);
         assert_eq!(a.title, b.title);
+        assert_eq!(a.tags, b.tags);
         assert_eq!(a.story, b.story);
         assert_eq!(a.choices.len(), b.choices.len());
         a.choices
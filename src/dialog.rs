@@ -4,51 +4,110 @@ use dirs::data_dir;
 use fltk::{
     app,
     browser::SelectBrowser,
-    button::Button,
-    enums::{Key, Shortcut},
+    button::{Button, CheckButton},
+    enums::{CallbackTrigger, Key, Shortcut},
     frame::Frame,
-    input::{Input, IntInput},
+    input::Input,
     menu::Choice,
     prelude::*,
-    text::{TextBuffer, TextEditor},
+    text::{TextBuffer, TextDisplay, TextEditor},
     window::Window, dialog::NativeFileChooser,
 };
 
 use crate::{
-    adventure::{Adventure, Name, Record},
-    file::{is_on_adventure_path, user_paths, save_adventure, PROJECT_PATH_NAME},
+    adventure::{
+        is_keyword_valid, Adventure, Name, Record, ValueRange, AUTO_RECORD_CATEGORY, DERIVED_RECORD_CATEGORY,
+        EXAMPLE_PAGE, EXAMPLE_PAGE_NAME,
+    },
+    file::{
+        is_on_adventure_path, save_adventure, save_page, user_data_paths, user_paths, PROJECT_PATH_NAME,
+    },
+    lang::tr,
+    widgets::escape_menu_text,
 };
 
-/// Displays a simple alert dialog with provided formatable message
+/// Displays an error dialog with provided formatable message, the message can be copied from it
 macro_rules! signal_error {
     ($text:expr, $( $x:expr ), *) => {
-        fltk::dialog::alert(0, 0, &format!($text, $($x),*))
+        $crate::dialog::show_error(&format!($text, $($x),*))
     };
     ($text:expr) => {
-         fltk::dialog::alert(0, 0, $text)
+         $crate::dialog::show_error($text)
     };
 }
 pub(crate) use signal_error;
 
+/// Amount of characters of an error message shown in the error dialog, the rest can still be copied
+const MAX_ERROR_LENGTH: usize = 4000;
+
+/// Shortens the message to at most max characters, noting how much of it was cut off
+fn truncate_message(message: &str, max: usize) -> String {
+    match message.char_indices().nth(max) {
+        Some((end, _)) => format!(
+            "{}\n... ({} {})",
+            &message[..end],
+            message[end..].chars().count(),
+            tr("more characters, use Copy to get the whole message")
+        ),
+        None => message.to_string(),
+    }
+}
+/// Presents a modal dialog with an error message that can be selected or copied to clipboard
+///
+/// Very long messages are shortened in the dialog but copied whole
+pub fn show_error(message: &str) {
+    let mut win = Window::default().with_size(500, 250).with_label(tr("Error"));
+
+    let mut display = TextDisplay::new(10, 10, 480, 190, None);
+    let mut butt_copy = Button::new(10, 210, 80, 30, tr("Copy"));
+    let mut butt_close = Button::new(410, 210, 80, 30, tr("Close"));
+
+    win.end();
+    win.make_modal(true);
+    win.make_resizable(true);
+    win.show();
+
+    let mut buffer = TextBuffer::default();
+    buffer.set_text(&truncate_message(message, MAX_ERROR_LENGTH));
+    display.set_buffer(buffer);
+    display.wrap_mode(fltk::text::WrapMode::AtBounds, 0);
+
+    butt_copy.set_callback({
+        let message = message.to_string();
+        move |_| app::copy(&message)
+    });
+    butt_close.set_callback(|x| {
+        x.window().unwrap().hide();
+    });
+    butt_close.set_shortcut(Shortcut::from_key(Key::Enter));
+    let _ = butt_close.take_focus();
+
+    while win.shown() {
+        app::wait();
+    }
+}
+
 /// Creates and shows a modal dialog that lets user choose an adventure.
 ///
-/// The dialog has additional "New" entry appended that will be returned as equal to provided list's length should it be chosen
-pub fn ask_to_choose_adventure(adventures: &Vec<Adventure>) -> Option<usize> {
+/// If with_new is true, the dialog has additional "New" entry appended that will be returned as equal to provided list's length should it be chosen
+pub fn ask_to_choose_adventure(adventures: &Vec<Adventure>, with_new: bool) -> Option<usize> {
     let mut win = Window::default()
         .with_size(300, 150)
-        .with_label("Choose the Adventure");
+        .with_label(tr("Choose the Adventure"));
 
-    Frame::new(50, 10, 200, 20, "Choose the Adventure");
+    Frame::new(50, 10, 200, 20, tr("Choose the Adventure"));
     let mut chooser = Choice::new(50, 30, 200, 30, None);
-    let mut butt_accept = Button::new(210, 110, 80, 30, "Accept");
-    let mut butt_cancel = Button::new(10, 110, 80, 30, "Cancel");
+    let mut butt_accept = Button::new(210, 110, 80, 30, tr("Accept"));
+    let mut butt_cancel = Button::new(10, 110, 80, 30, tr("Cancel"));
 
     win.end();
     win.make_modal(true);
     win.show();
 
     adventures.iter().for_each(|x| chooser.add_choice(&x.title));
-    chooser.add_choice("New");
+    if with_new {
+        chooser.add_choice(tr("New"));
+    }
     chooser.set_value(0);
 
     let conf = Rc::new(RefCell::new(false));
@@ -81,13 +140,14 @@ pub fn ask_to_choose_adventure(adventures: &Vec<Adventure>) -> Option<usize> {
 pub fn ask_for_new_adventure() -> Option<Adventure> {
     let mut win = Window::default()
         .with_size(500, 250)
-        .with_label("Creating Adventure");
+        .with_label(tr("Creating Adventure"));
 
-    Frame::new(50, 10, 400, 20, "Creating Adventure");
-    let mut sel = SelectBrowser::new(10, 35, 230, 200, "Location");
-    let mut name = TextEditor::new(260, 50, 230, 40, "name");
-    let mut butt_accept = Button::new(410, 210, 80, 30, "Accept");
-    let mut butt_cancel = Button::new(250, 210, 80, 30, "Cancel");
+    Frame::new(50, 10, 400, 20, tr("Creating Adventure"));
+    let mut sel = SelectBrowser::new(10, 35, 230, 200, tr("Location"));
+    let mut name = TextEditor::new(260, 50, 230, 40, tr("name"));
+    let mut template = Choice::new(260, 120, 230, 30, tr("Template"));
+    let mut butt_accept = Button::new(410, 210, 80, 30, tr("Accept"));
+    let mut butt_cancel = Button::new(250, 210, 80, 30, tr("Cancel"));
 
     win.end();
     win.make_modal(true);
@@ -111,9 +171,14 @@ pub fn ask_for_new_adventure() -> Option<Adventure> {
     butt_cancel.set_shortcut(Shortcut::from_key(Key::Escape));
 
     name.set_buffer(TextBuffer::default());
+    template.set_align(fltk::enums::Align::Top);
+    template.add_choice(tr("Empty"));
+    template.add_choice(tr("Example"));
+    template.set_value(0);
+    template.set_tooltip(tr("Example adds a starting page with choices and a few records and names to edit"));
     // new root location not supported yet
     //sel.add("New Root Location");
-    user_paths!("books")
+    user_data_paths("books")
         .iter()
         .for_each(|x| sel.add(x.to_str().unwrap()));
     sel.set_callback(|x| {
@@ -178,11 +243,19 @@ pub fn ask_for_new_adventure() -> Option<Adventure> {
                     return None;
                 }
             }
-            let adventure = Adventure {
+            let mut adventure = Adventure {
                 title,
                 path: dir.to_str().unwrap().to_string(),
                 ..Default::default()
             };
+            if template.value() == 1 {
+                adventure.add_example();
+                save_page(
+                    dir.to_str().unwrap(),
+                    EXAMPLE_PAGE_NAME.to_string(),
+                    EXAMPLE_PAGE.to_string(),
+                );
+            }
             let sa = adventure.serialize_to_string();
             save_adventure(dir.to_str().unwrap(), sa);
             return Some(adventure);
@@ -192,6 +265,71 @@ pub fn ask_for_new_adventure() -> Option<Adventure> {
     }
     return None;
 }
+/// Shows a dialog letting the user pick a folder to use as an additional data folder
+///
+/// Returns None if the user cancelled the dialog
+pub fn ask_for_data_folder() -> Option<PathBuf> {
+    let mut dialog = NativeFileChooser::new(fltk::dialog::FileDialogType::BrowseDir);
+    dialog.set_title(tr("Choose a folder with adventure data, adventures are read from its books folder"));
+    dialog.show();
+    let dir = dialog.filename();
+    if dir.as_os_str().len() == 0 {
+        return None;
+    }
+    Some(dir)
+}
+/// Shows a dialog letting the user choose where to save an archive with the adventure
+///
+/// Returns None if the user cancelled the dialog, the .zip extension is applied if it's missing
+pub fn ask_for_export_path(name: &str) -> Option<PathBuf> {
+    let mut dialog = NativeFileChooser::new(fltk::dialog::FileDialogType::BrowseSaveFile);
+    dialog.set_title(tr("Export the adventure"));
+    dialog.set_filter("*.zip");
+    dialog.set_preset_file(&format!("{}.zip", name));
+    dialog.set_option(fltk::dialog::FileDialogOptions::SaveAsConfirm);
+    dialog.show();
+    let mut path = dialog.filename();
+    if path.as_os_str().len() == 0 {
+        return None;
+    }
+    if path.extension().map_or(true, |x| x != "zip") {
+        path.set_extension("zip");
+    }
+    Some(path)
+}
+/// Shows a dialog letting the user choose where to save records of the adventure as comma separated values
+///
+/// Returns None if the user cancelled the dialog, the .csv extension is applied if it's missing
+pub fn ask_for_csv_path(name: &str) -> Option<PathBuf> {
+    let mut dialog = NativeFileChooser::new(fltk::dialog::FileDialogType::BrowseSaveFile);
+    dialog.set_title(tr("Export the records"));
+    dialog.set_filter("*.csv");
+    dialog.set_preset_file(&format!("{}-records.csv", name));
+    dialog.set_option(fltk::dialog::FileDialogOptions::SaveAsConfirm);
+    dialog.show();
+    let mut path = dialog.filename();
+    if path.as_os_str().len() == 0 {
+        return None;
+    }
+    if path.extension().map_or(true, |x| x != "csv") {
+        path.set_extension("csv");
+    }
+    Some(path)
+}
+/// Shows a dialog letting the user choose an archive with an adventure to import
+///
+/// Returns None if the user cancelled the dialog
+pub fn ask_for_archive() -> Option<PathBuf> {
+    let mut dialog = NativeFileChooser::new(fltk::dialog::FileDialogType::BrowseFile);
+    dialog.set_title(tr("Import an adventure"));
+    dialog.set_filter("*.zip");
+    dialog.show();
+    let path = dialog.filename();
+    if path.as_os_str().len() == 0 {
+        return None;
+    }
+    Some(path)
+}
 /// Creates and shows a modal dialog to the user asking for a text input
 ///
 /// The label will be presented above the input.
@@ -204,14 +342,16 @@ pub fn ask_for_text(label: &str) -> Option<String> {
     let mut win = Window::default().with_size(len, 110).with_label(label);
 
     Frame::new(20, 10, len - 40, 20, None).with_label(label);
-    let input = Input::new(20, 30, len - 40, 30, None);
+    let mut input = Input::new(20, 30, len - 40, 30, None);
 
-    let mut butt_accept = Button::new(len - 90, 70, 80, 30, "Accept");
-    let mut butt_cancel = Button::new(10, 70, 80, 30, "Cancel");
+    let mut butt_accept = Button::new(len - 90, 70, 80, 30, tr("Accept"));
+    let mut butt_cancel = Button::new(10, 70, 80, 30, tr("Cancel"));
 
     win.end();
     win.make_modal(true);
     win.show();
+    // lets the user type right away without clicking on the input first
+    let _ = input.take_focus();
 
     let accept = Rc::new(RefCell::new(false));
 
@@ -239,41 +379,169 @@ pub fn ask_for_text(label: &str) -> Option<String> {
         true => Some(input.value()),
     }
 }
+/// Asks the player to answer a question of the adventure, None if the player cancels
+///
+/// If a number is expected, the player is asked again until they type a whole number
+pub fn ask_for_answer(question: &str, number: bool) -> Option<String> {
+    loop {
+        let answer = ask_for_text(question)?;
+        if number && answer.trim().parse::<i32>().is_err() {
+            signal_error!("{} is not a whole number", answer.trim());
+            continue;
+        }
+        return Some(answer);
+    }
+}
+/// Tests if the keyword typed into a record or name dialog can be accepted, telling the user why if it can't
+fn keyword_accepted(keyword: &str) -> bool {
+    if is_keyword_valid(keyword) {
+        return true;
+    }
+    signal_error!(
+        "The keyword {} is invalid, it can't be empty, start or end with a space, or contain [, ] or ;",
+        keyword
+    );
+    false
+}
+/// Parses bounds of a record written as min..max, empty text means the record isn't limited
+///
+/// Shows an error to the user and returns None if the bounds are invalid or the default value is outside of them
+fn bounds_accepted(bounds: &str, value: &str) -> Option<ValueRange> {
+    let bounds = bounds.trim();
+    if bounds.len() == 0 {
+        return Some(ValueRange { min: None, max: None });
+    }
+    let range = match ValueRange::parse_from_string(bounds) {
+        Ok(r) if bounds.contains("..") => r,
+        _ => {
+            signal_error!(
+                "The bounds {} are invalid, write them as min..max with either side left out if it isn't limited",
+                bounds
+            );
+            return None;
+        }
+    };
+    // derived records have an expression instead of a default value, they aren't limited by their bounds
+    match value.trim().parse() {
+        Ok(v) if range.contains(v) == false => {
+            signal_error!("The default value {} is outside of the bounds {}", v, bounds);
+            None
+        }
+        _ => Some(range),
+    }
+}
+/// Tests if the default value of a record is a whole number, negative numbers included, and signals error to the user if it isn't
+///
+/// Derived records have an expression instead, it's checked when the adventure is played
+fn default_accepted(value: &str, derived: bool) -> bool {
+    if derived || value.trim().len() == 0 || value.trim().parse::<i32>().is_ok() {
+        return true;
+    }
+    signal_error!("The default value {} is not a whole number", value.trim());
+    false
+}
 /// Creates and shows a modal dialog asking user to put data for record creation into it
 ///
 /// If optional record value is provided then the fields are prefilled with data from the record
 ///
+/// categories: categories already used in the adventure, offered for the user to pick from
+///
 /// # Warning
 /// While the function return will always be a valid record, it still needs to be tested for duplicate keyword
-pub fn ask_for_record(record: Option<&Record>) -> Option<Record> {
-    let label = "Insert record data";
+pub fn ask_for_record(record: Option<&Record>, categories: &Vec<String>) -> Option<Record> {
+    let label = tr("Insert record data");
 
-    let mut win = Window::default().with_size(300, 170).with_label(label);
+    let mut win = Window::default().with_size(300, 290).with_label(label);
 
     Frame::new(50, 10, 200, 20, None).with_label(label);
-    let mut name = Input::new(80, 30, 200, 30, "Keyword");
-    let mut category = Input::new(80, 60, 200, 30, "Category");
-    let mut value = IntInput::new(80, 90, 200, 30, "Default");
-    let mut butt_accept = Button::new(210, 130, 80, 30, "Accept");
-    let mut butt_cancel = Button::new(10, 130, 80, 30, "Cancel");
+    let mut name = Input::new(80, 30, 200, 30, tr("Keyword"));
+    let mut category = Choice::new(80, 60, 200, 30, tr("Category"));
+    let mut new_category = Input::new(80, 90, 200, 30, tr("New"));
+    let mut value = Input::new(80, 120, 200, 30, tr("Default"));
+    let mut bounds = Input::new(80, 150, 200, 30, tr("Bounds"));
+    let hidden = CheckButton::new(80, 180, 200, 30, tr("Hidden from the player"));
+    let mut global = CheckButton::new(80, 210, 200, 30, tr("Kept between adventures"));
+    let mut butt_accept = Button::new(210, 250, 80, 30, tr("Accept"));
+    let mut butt_cancel = Button::new(10, 250, 80, 30, tr("Cancel"));
 
     win.end();
     win.make_modal(true);
     win.show();
 
+    // special categories are always offered so they're easy to discover
+    let mut categories = categories.clone();
+    for special in [AUTO_RECORD_CATEGORY, DERIVED_RECORD_CATEGORY] {
+        if categories.iter().any(|x| x == special) == false {
+            categories.push(special.to_string());
+        }
+    }
+    // first entry stands for no category and the last one for a new category typed in by the user
+    category.add_choice(tr("No category"));
+    categories.iter().for_each(|x| category.add_choice(&escape_menu_text(x)));
+    category.add_choice(tr("New category"));
+    category.set_value(0);
+    new_category.deactivate();
+    new_category.set_tooltip(tr("Name of the new category"));
+    category.set_callback({
+        let mut new_category = new_category.clone();
+        let last = categories.len() as i32 + 1;
+        move |c| match c.value() == last {
+            true => {
+                new_category.activate();
+                let _ = new_category.take_focus();
+            }
+            false => new_category.deactivate(),
+        }
+    });
+
     if let Some(rec) = record {
         name.set_value(&rec.name);
-        category.set_value(&rec.category);
-        value.set_value(&rec.value.to_string());
+        if let Some(i) = categories.iter().position(|x| x == &rec.category) {
+            category.set_value(i as i32 + 1);
+        } else if rec.category.len() > 0 {
+            category.set_value(categories.len() as i32 + 1);
+            new_category.set_value(&rec.category);
+            new_category.activate();
+        }
+        match rec.is_derived() {
+            true => value.set_value(&rec.expression),
+            false => value.set_value(&rec.value.to_string()),
+        }
+        if rec.is_bounded() {
+            bounds.set_value(&rec.bounds_to_string());
+        }
+        hidden.set_checked(rec.hidden);
+        global.set_checked(rec.global);
     }
+    let _ = name.take_focus();
+    global.set_tooltip(tr("Global records keep their value between playthroughs and adventures of the same series"));
+    value.set_tooltip(tr("Starting value of the record, it can be negative, or the expression its value is computed from for records in the derived category"));
+    bounds.set_tooltip(tr("Smallest and largest value of the record written as min..max, either side can be left out, empty if the value isn't limited"));
 
     let accept = Rc::new(RefCell::new(false));
 
     butt_accept.set_callback({
         let accept = Rc::clone(&accept);
+        let name = name.clone();
+        let value = value.clone();
+        let bounds = bounds.clone();
+        let category = category.clone();
+        let new_category = new_category.clone();
+        let categories = categories.clone();
+        let last = categories.len() as i32 + 1;
         move |x| {
-            *accept.borrow_mut() = true;
-            x.window().unwrap().hide();
+            let derived = match category.value() {
+                i if i == last => new_category.value().trim() == DERIVED_RECORD_CATEGORY,
+                i if i > 0 => categories[i as usize - 1] == DERIVED_RECORD_CATEGORY,
+                _ => false,
+            };
+            if keyword_accepted(&name.value())
+                && default_accepted(&value.value(), derived)
+                && bounds_accepted(&bounds.value(), &value.value()).is_some()
+            {
+                *accept.borrow_mut() = true;
+                x.window().unwrap().hide();
+            }
         }
     });
     butt_cancel.set_callback({
@@ -292,19 +560,28 @@ pub fn ask_for_record(record: Option<&Record>) -> Option<Record> {
 
     match test {
         true if name.len() > 0 => {
-            let category = category.value();
-            let record = match value.value().parse() {
-                Ok(value) => Record {
-                    name,
-                    category,
-                    value,
-                },
-                Err(_) => Record {
-                    name,
-                    category,
-                    value: 0,
-                },
+            let category = match category.value() {
+                0 => String::new(),
+                i if i as usize <= categories.len() => categories[i as usize - 1].clone(),
+                _ => new_category.value().trim().to_string(),
             };
+            let hidden = hidden.is_checked();
+            let global = global.is_checked();
+            let bounds = ValueRange::parse_from_string(&bounds.value()).unwrap_or(ValueRange { min: None, max: None });
+            let mut record = Record {
+                name,
+                category,
+                value: 0,
+                hidden,
+                expression: String::new(),
+                global,
+                min: bounds.min,
+                max: bounds.max,
+            };
+            match record.is_derived() {
+                true => record.expression = value.value().trim().to_string(),
+                false => record.value = value.value().trim().parse().unwrap_or(0),
+            }
             Some(record)
         }
         _ => None,
@@ -315,15 +592,15 @@ pub fn ask_for_record(record: Option<&Record>) -> Option<Record> {
 /// # Warning
 /// While the function ensures the returned name is valid, it still needs to be tested for duplicate keyword
 pub fn ask_for_name(default: Option<&Name>) -> Option<Name> {
-    let label = "Input name data";
+    let label = tr("Input name data");
     let mut win = Window::default().with_size(300, 150).with_label(label);
 
     Frame::new(50, 10, 200, 20, None).with_label(label);
-    let mut name = Input::new(80, 30, 200, 30, "Keyword");
-    let mut value = Input::new(80, 60, 200, 30, "Default");
+    let mut name = Input::new(80, 30, 200, 30, tr("Keyword"));
+    let mut value = Input::new(80, 60, 200, 30, tr("Default"));
 
-    let mut butt_accept = Button::new(210, 110, 80, 30, "Accept");
-    let mut butt_cancel = Button::new(10, 110, 80, 30, "Cancel");
+    let mut butt_accept = Button::new(210, 110, 80, 30, tr("Accept"));
+    let mut butt_cancel = Button::new(10, 110, 80, 30, tr("Cancel"));
 
     win.end();
     win.make_modal(true);
@@ -333,14 +610,18 @@ pub fn ask_for_name(default: Option<&Name>) -> Option<Name> {
         name.set_value(&val.keyword);
         value.set_value(&val.value);
     }
+    let _ = name.take_focus();
 
     let accept = Rc::new(RefCell::new(false));
 
     butt_accept.set_callback({
         let accept = Rc::clone(&accept);
+        let name = name.clone();
         move |x| {
-            *accept.borrow_mut() = true;
-            x.window().unwrap().hide();
+            if keyword_accepted(&name.value()) {
+                *accept.borrow_mut() = true;
+                x.window().unwrap().hide();
+            }
         }
     });
     butt_cancel.set_callback({
@@ -364,16 +645,65 @@ pub fn ask_for_name(default: Option<&Name>) -> Option<Name> {
         _ => None,
     }
 }
+/// Presents a modal dialog with a scrollable list of lines, like problems found in an adventure
+pub fn show_report(label: &str, lines: &Vec<String>) {
+    let mut win = Window::default().with_size(600, 400).with_label(label);
+
+    let mut display = TextDisplay::new(10, 10, 580, 340, None);
+    let mut butt_close = Button::new(510, 360, 80, 30, tr("Close"));
+
+    win.end();
+    win.make_modal(true);
+    win.make_resizable(true);
+    win.show();
+
+    let mut buffer = TextBuffer::default();
+    buffer.set_text(&lines.join("\n"));
+    display.set_buffer(buffer);
+
+    butt_close.set_callback(|x| {
+        x.window().unwrap().hide();
+    });
+    butt_close.set_shortcut(Shortcut::from_key(Key::Escape));
+
+    while win.shown() {
+        app::wait();
+    }
+}
+/// Creates a large window for writing the text of the buffer, edits show in other editors of the same buffer right away
+///
+/// The window isn't modal so the rest of the program can be used while it's open, closing it only hides it
+pub fn create_text_window(label: &str, buffer: TextBuffer) -> (Window, TextEditor) {
+    let mut win = Window::default().with_size(800, 600).with_label(label);
+
+    let mut editor = TextEditor::new(10, 10, 780, 580, None);
+
+    win.end();
+    win.make_resizable(true);
+
+    editor.set_buffer(buffer);
+    editor.wrap_mode(fltk::text::WrapMode::AtBounds, 0);
+
+    (win, editor)
+}
 /// Presents a simple modal dialog asking to confirm a choice
 pub fn ask_to_confirm(label: &str) -> bool {
+    ask_to_confirm_labeled(label, tr("Yes"), tr("No"))
+}
+/// Presents a simple modal dialog asking to confirm a choice, with buttons labeled after what they do
+///
+/// Returns true if the button with yes_text was pressed
+pub fn ask_to_confirm_labeled(label: &str, yes_text: &str, no_text: &str) -> bool {
     let len = i32::max(fltk::draw::width(label) as i32 + 20, 300);
+    let w_yes = i32::max(fltk::draw::width(yes_text) as i32 + 20, 80);
+    let w_no = i32::max(fltk::draw::width(no_text) as i32 + 20, 80);
 
     let mut win = Window::default().with_size(len, 100).with_label(label);
 
     Frame::new(20, 10, len - 40, 20, None).with_label(label);
 
-    let mut butt_accept = Button::new(len - 100, 60, 80, 30, "Yes");
-    let mut butt_cancel = Button::new(20, 60, 80, 30, "No");
+    let mut butt_accept = Button::new(len - w_yes - 20, 60, w_yes, 30, None).with_label(yes_text);
+    let mut butt_cancel = Button::new(20, 60, w_no, 30, None).with_label(no_text);
 
     win.end();
     win.make_modal(true);
@@ -401,6 +731,97 @@ pub fn ask_to_confirm(label: &str) -> bool {
     }
     conf.take()
 }
+/// Returns entries whose name or description contains the filter, ignoring letter case
+fn filter_entries<'a>(entries: &'a Vec<(String, String)>, filter: &str) -> Vec<&'a (String, String)> {
+    let filter = filter.trim().to_lowercase();
+    entries
+        .iter()
+        .filter(|x| x.0.to_lowercase().contains(&filter) || x.1.to_lowercase().contains(&filter))
+        .collect()
+}
+/// Presents a dialog with a list of entries narrowed down as the user types into a filter field
+///
+/// entries: pairs of names and descriptions shown next to them, the filter matches either
+///
+/// Returns the name of the chosen entry, pressing Enter chooses the selected or the first listed entry
+pub fn ask_for_filtered_choice(label: &str, entries: &Vec<(String, String)>) -> Option<String> {
+    let mut win = Window::default().with_size(500, 400).with_label(label);
+
+    let mut filter = Input::new(10, 10, 480, 30, None);
+    let mut list = SelectBrowser::new(10, 50, 480, 300, None);
+    let mut butt_accept = Button::new(410, 360, 80, 30, tr("Accept"));
+    let mut butt_cancel = Button::new(10, 360, 80, 30, tr("Cancel"));
+
+    win.end();
+    win.make_modal(true);
+    win.make_resizable(true);
+    win.show();
+
+    list.set_column_char('\t');
+    list.set_column_widths(&[240, 240]);
+    // names of the listed entries, in the order they're shown
+    let shown = Rc::new(RefCell::new(Vec::new()));
+    let mut fill = {
+        let mut list = list.clone();
+        let shown = shown.clone();
+        let entries = entries.clone();
+        move |text: &str| {
+            list.clear();
+            let mut shown = shown.borrow_mut();
+            shown.clear();
+            for entry in filter_entries(&entries, text) {
+                list.add(&format!("{}\t@.{}", entry.0, entry.1));
+                shown.push(entry.0.clone());
+            }
+            if shown.len() > 0 {
+                list.select(1);
+            }
+        }
+    };
+    fill("");
+
+    let conf = Rc::new(RefCell::new(false));
+
+    filter.set_trigger(CallbackTrigger::Changed);
+    filter.set_callback({
+        let mut fill = fill.clone();
+        move |x| fill(&x.value())
+    });
+    list.set_callback({
+        let conf = Rc::clone(&conf);
+        move |x| {
+            if app::event_clicks() && x.value() > 0 {
+                *conf.borrow_mut() = true;
+                x.window().unwrap().hide();
+            }
+        }
+    });
+    butt_accept.set_callback({
+        let conf = Rc::clone(&conf);
+        move |x| {
+            *conf.borrow_mut() = true;
+            x.window().unwrap().hide();
+        }
+    });
+    butt_cancel.set_callback(|x| {
+        x.window().unwrap().hide();
+    });
+    butt_accept.set_shortcut(Shortcut::from_key(Key::Enter));
+    butt_cancel.set_shortcut(Shortcut::from_key(Key::Escape));
+    let _ = filter.take_focus();
+
+    while win.shown() {
+        app::wait();
+    }
+    if conf.take() == false {
+        return None;
+    }
+    let shown = shown.borrow();
+    match list.value() {
+        0 => shown.first().cloned(),
+        x => shown.get(x as usize - 1).cloned(),
+    }
+}
 /// Presents a dialog with a dropdown populated with the data from the provided iterator
 ///
 /// Returns an index of chosen element and its name
@@ -422,8 +843,8 @@ where
 
     let mut choice = Choice::new(20, 40, len - 40, 30, None);
 
-    let mut butt_accept = Button::new(len - 100, 80, 80, 30, "Accept");
-    let mut butt_cancel = Button::new(20, 80, 80, 30, "Cancel");
+    let mut butt_accept = Button::new(len - 100, 80, 80, 30, tr("Accept"));
+    let mut butt_cancel = Button::new(20, 80, 80, 30, tr("Cancel"));
 
     win.end();
     win.make_modal(true);
@@ -465,3 +886,32 @@ where
         false => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{filter_entries, truncate_message};
+
+    #[test]
+    fn error_truncation() {
+        assert_eq!(truncate_message("short", 10), "short");
+        assert_eq!(truncate_message("exactly", 7), "exactly");
+        let long = truncate_message("zażółć gęślą", 6);
+        assert!(long.starts_with("zażółć\n... (6 "));
+    }
+    #[test]
+    fn entry_filtering() {
+        let entries = vec![
+            ("cave-entrance".to_string(), "The Dark Cave".to_string()),
+            ("forest".to_string(), "Whispering Woods".to_string()),
+            ("lake".to_string(), String::new()),
+        ];
+        let names = |filter: &str| -> Vec<String> {
+            filter_entries(&entries, filter).iter().map(|x| x.0.clone()).collect()
+        };
+        assert_eq!(names("").len(), 3);
+        assert_eq!(names("CAVE"), vec!["cave-entrance"]);
+        assert_eq!(names(" woods "), vec!["forest"]);
+        assert_eq!(names("e"), vec!["cave-entrance", "forest", "lake"]);
+        assert!(names("castle").is_empty());
+    }
+}
@@ -1,22 +1,39 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf, time::SystemTime};
 
-use fltk::{draw::Rect, group::Group, prelude::*};
+use fltk::{
+    app,
+    draw::Rect,
+    enums::{Event as FltkEvent, EventState, Key},
+    group::Group,
+    prelude::*,
+};
 
 use crate::{
-    adventure::{is_keyword_valid, Adventure, Page},
-    dialog::{ask_for_name, ask_for_record, ask_for_text, ask_to_confirm},
+    adventure::{is_keyword_valid, Adventure, Condition, Page, ParsingError, StoryResult, Test},
+    dialog::{
+        ask_for_archive, ask_for_csv_path, ask_for_export_path, ask_for_filtered_choice, ask_for_name, ask_for_record,
+        ask_for_text, ask_to_choose_adventure, ask_to_confirm, ask_to_confirm_labeled, show_report,
+    },
     file::{
-        capture_pages, is_valid_file_name, read_page, remove_adventure, save_adventure, save_page,
-        signal_error, open_help,
+        capture_adventures, capture_pages, export_adventure, import_adventure, is_page_on_path, is_valid_file_name,
+        page_modified,
+        read_page, rename_page_file, save_adventure, FileError, save_page, save_text, signal_error, open_help,
+        trash_page, user_data_paths,
     },
+    lang::tr,
 };
 
 mod adventure;
 mod choice;
 mod condition;
 mod files;
+mod graph;
+mod random;
+mod raw;
 mod result;
+mod shared;
 mod story;
+mod switch;
 mod test;
 mod variables;
 
@@ -51,17 +68,145 @@ macro_rules! page_mut {
     };
 }
 
-use self::{adventure::AdventureEditor, files::FileList, story::StoryEditor};
+use self::{
+    adventure::AdventureEditor, files::FileList, graph::GraphWindow, shared::SharedResultsWindow,
+    story::StoryEditor,
+};
+
+/// Page element copied in the editor, waiting to be pasted into another page
+enum Copied {
+    Condition(Condition),
+    Test(Test),
+}
+/// How many pages are read from the drive before the UI gets a chance to respond
+const PAGES_PER_CHUNK: usize = 20;
+
+/// State of the edited adventure, used to detect if anything has changed
+///
+/// Only the page that was open when the snapshot was taken is compared, since only that page can change in one step
+struct Snapshot {
+    adventure: String,
+    page_names: Vec<String>,
+    page: String,
+    page_text: Option<String>,
+}
+/// Returns the name if it's free in the map, otherwise the name with the lowest free number appended to it
+fn free_name<T>(map: &HashMap<String, T>, name: &str) -> String {
+    if map.contains_key(name) == false {
+        return name.to_string();
+    }
+    let mut i = 2;
+    loop {
+        let candidate = format!("{}-{}", name, i);
+        if map.contains_key(&candidate) == false {
+            return candidate;
+        }
+        i += 1;
+    }
+}
+/// Tests if a page can't be created with the file name, because a page of the adventure or a page file the editor doesn't know about has it
+///
+/// known: page files the editor has read or written, those are replaced or moved to trash on save
+fn is_page_name_taken(
+    pages: &HashMap<String, Page>,
+    known: &HashMap<String, String>,
+    path: &str,
+    name: &str,
+) -> bool {
+    pages.contains_key(name) || (known.contains_key(name) == false && is_page_on_path(path, name))
+}
+/// Returns the file name if no page has it, otherwise the name with the lowest number that makes it free appended to it
+fn free_page_name(
+    pages: &HashMap<String, Page>,
+    known: &HashMap<String, String>,
+    path: &str,
+    name: &str,
+) -> String {
+    if is_page_name_taken(pages, known, path, name) == false {
+        return name.to_string();
+    }
+    let mut i = 2;
+    loop {
+        let candidate = format!("{}-{}", name, i);
+        if is_page_name_taken(pages, known, path, &candidate) == false {
+            return candidate;
+        }
+        i += 1;
+    }
+}
+/// Names of the pages that use the keyword anywhere in their text, choices, conditions, tests, switches or results, sorted
+fn pages_using_keyword<'a>(pages: impl Iterator<Item = (&'a String, &'a Page)>, keyword: &str) -> Vec<String> {
+    let mut names: Vec<String> = pages
+        .filter(|x| x.1.is_keyword_present(keyword))
+        .map(|x| x.0.clone())
+        .collect();
+    names.sort();
+    names
+}
+/// Describes results, random outcomes and story links of other pages, and shared results, that lead to any of the listed pages
+///
+/// Links between the listed pages themselves aren't included, the list is sorted
+fn links_to_pages(
+    pages: &HashMap<String, Page>,
+    shared: &HashMap<String, StoryResult>,
+    names: &Vec<String>,
+) -> Vec<String> {
+    let mut links: Vec<String> = shared
+        .values()
+        .filter(|x| names.contains(&x.next_page))
+        .map(|x| format!("Shared result {} leads to page {}", x.name, x.next_page))
+        .collect();
+    for (name, page) in pages.iter().filter(|x| names.contains(x.0) == false) {
+        for result in page.results.values() {
+            if names.contains(&result.next_page) {
+                links.push(format!(
+                    "Page {}: Result {} leads to page {}",
+                    name, result.name, result.next_page
+                ));
+            }
+        }
+        for random in page.randoms.values() {
+            for outcome in random.outcomes.iter().filter(|x| names.contains(&x.0)) {
+                links.push(format!(
+                    "Page {}: Random {} leads to page {}",
+                    name, random.name, outcome.0
+                ));
+            }
+        }
+        for link in page.story_links().into_iter().filter(|x| names.iter().any(|n| n == x)) {
+            links.push(format!("Page {}: Story links to page {}", name, link));
+        }
+    }
+    links.sort();
+    links.dedup();
+    links
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Event {
     Save,
+    Return,
+    CloneAdventure,
+    ExportAdventure,
+    ImportAdventure,
+    ExportRecords,
+    AddToDictionary,
+    ImportVariables,
+    CheckExpressions,
+    ShowGraph,
+    /// Plays the adventure from the open page, with the pages as they are in the editor
+    Playtest,
+    QuickOpen,
+    LoadPages,
     RenamePage,
     AddPage,
     RemovePage,
+    RestorePage,
     SelectStartingPage(String),
     OpenMeta,
     OpenPage(String),
+    GoToPage(String),
+    FilterPages,
     AddRecord,
     AddName,
     EditRecord(String),
@@ -73,16 +218,30 @@ pub enum Event {
     RenameCondition,
     AddCondition,
     RemoveCondition,
+    CopyCondition,
+    PasteCondition,
     SaveTest(Option<String>),
     LoadTest(String),
     AddTest,
     RenameTest,
     RemoveTest,
+    CopyTest,
+    PasteTest,
     AddResult,
     RenameResult,
     RemoveResult,
     SaveResult(Option<String>),
     LoadResult(String),
+    SaveRandom(Option<String>),
+    LoadRandom(String),
+    AddRandom,
+    RenameRandom,
+    RemoveRandom,
+    SaveSwitch(Option<String>),
+    LoadSwitch(String),
+    AddSwitch,
+    RenameSwitch,
+    RemoveSwitch,
     SaveSideEffect(Option<String>),
     LoadSideEffect(String),
     AddSideEffectRecord,
@@ -93,6 +252,12 @@ pub enum Event {
     SaveChoice(Option<usize>),
     LoadChoice(usize),
     RefreshResults,
+    RefreshUnusedResults,
+    OpenSharedResults,
+    /// Result event coming from the editor of shared results, changing results of the adventure instead of the page
+    SharedResult(Box<Event>),
+    LoadRawPage,
+    ApplyRawPage,
     ToggleRecords(bool),
     ToggleNames(bool),
     OpenHelp(&'static str),
@@ -110,13 +275,36 @@ pub struct EditorWindow {
     page_editor: StoryEditor,
 
     /// Index of the edited adventure within the main adventure list, None for a new unsaved adventure
-    adventure_index: Option<usize>,
     /// Adventure that is loaded for editing
     adventure: Adventure,
 
     current_page: String,
     /// Map of file name keys and pages on those file names
     pages: HashMap<String, Page>,
+    /// Pages removed last in this session, kept so they can be restored
+    removed_pages: Vec<(String, Page)>,
+    /// Window with the graph of pages, if it was opened
+    graph: Option<GraphWindow>,
+    /// Window editing results shared by all pages, if it was opened
+    shared_results: Option<SharedResultsWindow>,
+    /// Serialized pages as they are stored on the drive, used to only write pages that changed
+    saved_pages: HashMap<String, String>,
+    /// Pages renamed since the last save, in order, as old and new name pairs
+    renamed_pages: Vec<(String, String)>,
+    /// Modification times of page files when they were loaded or last written by the editor
+    page_times: HashMap<String, Option<SystemTime>>,
+    /// File names of pages that are still waiting to be read from the drive
+    pending_pages: Vec<String>,
+    /// Amount of pages the adventure had when loading started, used to show the progress
+    total_pages: usize,
+    /// Condition or test copied from a page
+    copied: Option<Copied>,
+    /// Set when the adventure has changes that have not been saved to drive
+    dirty: bool,
+    /// Set while the adventure is played from the editor, the editor is hidden but still holds its changes
+    playtesting: bool,
+    /// Set when leaving the editor failed because the adventure couldn't be saved
+    keep_open: bool,
 }
 impl EditorWindow {
     // creates a new editor in specified area
@@ -131,7 +319,7 @@ impl EditorWindow {
         let w_editor = area.w - w_file - 5;
         let h_editor = area.h;
 
-        let group = Group::new(area.x, area.y, area.w, area.h, None);
+        let mut group = Group::new(area.x, area.y, area.w, area.h, None);
         let file_list = FileList::new(Rect::from((x_file, y_file, w_file, h_file)));
         let adventure_editor =
             AdventureEditor::new(Rect::from((x_editor, y_editor, w_editor, h_editor)));
@@ -140,6 +328,18 @@ impl EditorWindow {
         group.end();
 
         page_editor.hide();
+        // shortcuts reach the group only while the editor is shown
+        group.handle(|_, ev| match ev {
+            FltkEvent::Shortcut
+                if app::event_state().contains(EventState::Ctrl)
+                    && app::event_key() == Key::from_char('p') =>
+            {
+                let (s, _r) = app::channel();
+                s.send(emit!(Event::QuickOpen));
+                true
+            }
+            _ => false,
+        });
 
         Self {
             group,
@@ -148,51 +348,125 @@ impl EditorWindow {
             page_editor,
             adventure: Adventure::default(),
             pages: HashMap::new(),
-            adventure_index: None,
+            removed_pages: Vec::new(),
+            graph: None,
+            shared_results: None,
+            saved_pages: HashMap::new(),
+            renamed_pages: Vec::new(),
+            page_times: HashMap::new(),
+            pending_pages: Vec::new(),
+            total_pages: 0,
             current_page: String::new(),
+            copied: None,
+            dirty: false,
+            playtesting: false,
+            keep_open: false,
         }
     }
     /// Loads an adventure into editor
     ///
-    /// The function may take some time as it loads in all the pages for editing
-    pub fn load_adventure(&mut self, adventure: &Adventure, index: usize) {
+    /// Pages are read in chunks through LoadPages events so the window stays responsive while they load
+    pub fn load_adventure(&mut self, adventure: &Adventure) {
         self.adventure = adventure.clone();
-        self.adventure_index = Some(index);
         self.pages.clear();
-        let pages = capture_pages(&self.adventure.path);
-        self.file_list.populate_pages(&pages);
+        self.removed_pages.clear();
+        self.saved_pages.clear();
+        self.renamed_pages.clear();
+        self.page_times.clear();
+        self.pending_pages = capture_pages(&self.adventure.path);
+        self.total_pages = self.pending_pages.len();
         self.adventure_editor.load(&self.adventure);
-        for page in pages {
-            match read_page(&adventure.path, &page) {
-                Ok(p) => drop(self.pages.insert(page, p)),
+        self.current_page = String::new();
+        self.refresh_page_groups();
+        self.refresh_page_list();
+        self.dirty = false;
+        self.load_pages();
+    }
+    /// Reads the next chunk of pending pages from the drive
+    ///
+    /// Sends another LoadPages event if there are pages left to read
+    fn load_pages(&mut self) {
+        let count = usize::min(PAGES_PER_CHUNK, self.pending_pages.len());
+        for page in self.pending_pages.drain(..count) {
+            self.page_times
+                .insert(page.clone(), page_modified(&self.adventure.path, &page));
+            let loaded = match read_page(&self.adventure.path, &page) {
+                Ok(p) => p,
                 Err(e) => match e {
                     crate::file::FileError::ParsingFailure(_, p) => match p {
-                        crate::adventure::ParsingError::IncomplatePage(p) => {
-                            drop(self.pages.insert(page, p))
+                        crate::adventure::ParsingError::IncomplatePage(p) => p,
+                        _ => {
+                            signal_error!("Fatal Error while parsing page {:?}: {}", &page, p);
+                            continue;
                         }
-                        _ => signal_error!("Fatal Error while parsing page {:?}: {}", &page, p),
                     },
-                    _ => signal_error!("Fatal Error while loading a page {}: {}", &page, e),
+                    _ => {
+                        signal_error!("Fatal Error while loading a page {}: {}", &page, e);
+                        continue;
+                    }
                 },
             };
+            self.saved_pages
+                .insert(page.clone(), loaded.serialize_to_string());
+            self.pages.insert(page, loaded);
+        }
+        self.file_list
+            .set_progress(self.total_pages - self.pending_pages.len(), self.total_pages);
+        self.refresh_page_groups();
+        self.refresh_page_list();
+        if self.is_loading() {
+            let (sender, _) = app::channel();
+            sender.send(emit!(Event::LoadPages));
+        } else {
+            self.set_starting_page(self.adventure.start.clone());
+            let problems = self.adventure.ambiguous_side_effects(&self.pages);
+            if problems.len() > 0 {
+                signal_error!(
+                    "Warning! Some side effects can't tell a record from a name, rename the record or the name:\n{}",
+                    problems.join("\n")
+                );
+            }
         }
-        self.current_page = String::new();
-        self.set_starting_page(self.adventure.start.clone());
     }
-    /// Returns adventure and its index if it's existing adventure or None if the adventure has not been loaded yet
-    pub fn get_adventure(&self) -> (Adventure, Option<usize>) {
-        (self.adventure.clone(), self.adventure_index)
+    /// Tests if some pages of the adventure are still being read from the drive
+    fn is_loading(&self) -> bool {
+        self.pending_pages.len() > 0
+    }
+    /// Returns the adventure being edited
+    pub fn get_adventure(&self) -> Adventure {
+        self.adventure.clone()
     }
     /// Processes editor events
-    pub fn process(&mut self, ev: Event) {
+    ///
+    /// Returns true if the adventure has been saved to drive
+    pub fn process(&mut self, ev: Event) -> bool {
+        let loading = ev == Event::LoadPages;
+        let before = self.snapshot();
+        let mut saved = false;
         match ev {
-            Event::Save                  => self.save_project(),
+            Event::Save                  => saved = self.save_project(),
+            Event::Return                => return self.save_before_leaving(),
+            Event::CloneAdventure        => self.clone_adventure(),
+            Event::ExportAdventure       => return self.export_adventure(),
+            Event::ImportAdventure       => self.import_adventure(),
+            Event::ExportRecords         => self.export_records(),
+            Event::AddToDictionary       => self.add_to_dictionary(),
+            Event::ImportVariables       => self.import_variables(),
+            Event::CheckExpressions      => self.check_expressions(),
+            Event::ShowGraph             => self.show_graph(),
+            // the game is started by the main loop, taking the adventure from playtest
+            Event::Playtest              => self.sync_ui(),
+            Event::QuickOpen             => self.quick_open(),
+            Event::LoadPages             => self.load_pages(),
             Event::RenamePage            => self.rename_page(),
             Event::AddPage               => self.add_page(),
             Event::RemovePage            => self.remove_page(),
+            Event::RestorePage           => self.restore_page(),
             Event::SelectStartingPage(p) => self.set_starting_page(p),
             Event::OpenMeta              => self.open_adventure(),
             Event::OpenPage(name)        => self.open_page(name),
+            Event::GoToPage(name)        => self.go_to_page(name),
+            Event::FilterPages           => self.refresh_page_list(),
             Event::AddRecord             => self.add_keyword(false),
             Event::AddName               => self.add_keyword(true),
             Event::EditRecord(old)       => self.rename_keyword(true, old),
@@ -213,6 +487,8 @@ impl EditorWindow {
                 .conditions
                 .add(&mut page_mut!(self).conditions),
             Event::RemoveCondition       => self.page_editor.conditions.remove(page_mut!(self)),
+            Event::CopyCondition         => self.copy_condition(),
+            Event::PasteCondition        => self.paste_condition(),
             Event::SaveTest(test)        => self
                 .page_editor
                 .tests
@@ -224,15 +500,44 @@ impl EditorWindow {
             Event::RenameTest            => self.page_editor.tests.rename(page_mut!(self)),
             Event::AddTest               => self.page_editor.tests.add(&mut page_mut!(self)),
             Event::RemoveTest            => self.page_editor.tests.remove(&mut page_mut!(self)),
-            Event::AddResult             => self.page_editor.results.add(&mut page_mut!(self).results, &self.current_page),
-            Event::RenameResult          => self.page_editor.results.rename(page_mut!(self)),
-            Event::RemoveResult          => self.page_editor.results.remove(page_mut!(self)),
+            Event::CopyTest              => self.copy_test(),
+            Event::PasteTest             => self.paste_test(),
+            Event::AddResult             => {
+                self.page_editor.results.add(&mut page_mut!(self).results, &self.current_page);
+                self.page_editor.results.show_unused(page!(self));
+            }
+            Event::RenameResult          => {
+                self.page_editor.results.rename(page_mut!(self));
+                self.page_editor.results.show_unused(page!(self));
+            }
+            Event::RemoveResult          => {
+                self.page_editor.results.remove(page_mut!(self));
+                self.page_editor.results.show_unused(page!(self));
+            }
             Event::SaveResult(res)       => {
                 self.page_editor
                     .results
                     .save(&mut page_mut!(self).results, res, &self.adventure)
             }
             Event::LoadResult(res)       => self.page_editor.results.load(&page!(self).results, res),
+            Event::SaveRandom(ran)       => self
+                .page_editor
+                .randoms
+                .save(&mut page_mut!(self).randoms, ran),
+            Event::LoadRandom(ran)       => self.page_editor.randoms.load(&page!(self).randoms, ran),
+            Event::AddRandom             => self.page_editor.randoms.add(&mut page_mut!(self).randoms, &self.current_page),
+            Event::RenameRandom          => self.page_editor.randoms.rename(page_mut!(self)),
+            Event::RemoveRandom          => self.page_editor.randoms.remove(page_mut!(self)),
+            Event::SaveSwitch(sw)        => {
+                let results = self.adventure.page_results(page!(self));
+                self.page_editor
+                    .switches
+                    .save(&mut page_mut!(self).switches, sw, &results);
+            }
+            Event::LoadSwitch(sw)        => self.page_editor.switches.load(&page!(self).switches, sw),
+            Event::AddSwitch             => self.page_editor.switches.add(page_mut!(self)),
+            Event::RenameSwitch          => self.page_editor.switches.rename(page_mut!(self)),
+            Event::RemoveSwitch          => self.page_editor.switches.remove(page_mut!(self)),
             Event::SaveSideEffect(se)    => {
                 self.page_editor
                     .results
@@ -241,7 +546,7 @@ impl EditorWindow {
             Event::LoadSideEffect(se)    => self
                 .page_editor
                 .results
-                .load_effect(&page!(self).results, se),
+                .load_effect(&page!(self).results, se, &self.adventure),
             Event::AddSideEffectRecord   => self
                 .page_editor
                 .results
@@ -271,19 +576,126 @@ impl EditorWindow {
                 .choices
                 .load_choice(&page!(self).choices, c),
             Event::RefreshResults        => {
-                self.page_editor.choices.refresh_dropdowns(page!(self));
+                let results = self.adventure.page_results(page!(self));
+                self.page_editor
+                    .choices
+                    .refresh_dropdowns(page!(self), &results, &self.pages);
                 self.page_editor
                     .tests
-                    .populate(&page!(self).tests, &page!(self).results);
+                    .populate(&page!(self).tests, &results);
+                self.page_editor
+                    .switches
+                    .populate(&page!(self).switches, &results);
             }
+            Event::RefreshUnusedResults  => self.page_editor.results.show_unused(page!(self)),
+            Event::OpenSharedResults     => self.open_shared_results(),
+            Event::SharedResult(ev)      => self.process_shared_result(*ev),
+            Event::LoadRawPage           => self.load_raw_page(),
+            Event::ApplyRawPage          => self.apply_raw_page(),
             Event::ToggleRecords(f)      => self.page_editor.toggle_record_editor(f),
             Event::ToggleNames(f)        => self.page_editor.toggle_name_editor(f),
             Event::OpenHelp(help)        => open_help(help),
         }
+        // loaded pages are not changes made by the user
+        if saved == false && loading == false && self.changed_since(&before) {
+            self.dirty = true;
+        }
+        saved
+    }
+    /// Captures current state of the adventure and the open page
+    fn snapshot(&self) -> Snapshot {
+        let mut page_names: Vec<String> = self.pages.keys().cloned().collect();
+        page_names.sort();
+        Snapshot {
+            adventure: self.adventure.serialize_to_string(),
+            page_names,
+            page: self.current_page.clone(),
+            page_text: self
+                .pages
+                .get(&self.current_page)
+                .map(|x| x.serialize_to_string()),
+        }
+    }
+    /// Tests if the adventure has changed since the snapshot was taken
+    fn changed_since(&self, snapshot: &Snapshot) -> bool {
+        if self.adventure.serialize_to_string() != snapshot.adventure {
+            return true;
+        }
+        let mut page_names: Vec<&String> = self.pages.keys().collect();
+        page_names.sort();
+        if page_names.into_iter().ne(snapshot.page_names.iter()) {
+            return true;
+        }
+        self.pages.get(&snapshot.page).map(|x| x.serialize_to_string()) != snapshot.page_text
+    }
+    /// Writes values from the open editor into the adventure or the open page
+    ///
+    /// The shared result loaded in its window is saved too
+    fn sync_ui(&mut self) {
+        if self.adventure_editor.active() {
+            self.adventure_editor.save(&mut self.adventure);
+        } else {
+            self.page_editor.save_page(page_mut!(self), &self.adventure);
+        }
+        if let Some(window) = self.shared_results.as_ref().filter(|x| x.shown()) {
+            let mut results = std::mem::take(&mut self.adventure.results);
+            window.results.save(&mut results, None, &self.adventure);
+            self.adventure.results = results;
+        }
+    }
+    /// Tests if the adventure open in the editor has changes that have not been saved to drive
+    ///
+    /// Always false when the editor isn't shown, leaving the editor already asks to save the changes
+    pub fn has_unsaved_changes(&mut self) -> bool {
+        if self.group.visible() == false && self.playtesting == false {
+            return false;
+        }
+        let before = self.snapshot();
+        self.sync_ui();
+        if self.changed_since(&before) {
+            self.dirty = true;
+        }
+        self.dirty
+    }
+    /// Asks the user to save the adventure if it has changed since it was last saved
+    ///
+    /// Returns true if the adventure has been saved, if saving fails the editor is kept open
+    fn save_before_leaving(&mut self) -> bool {
+        self.keep_open = false;
+        let before = self.snapshot();
+        self.sync_ui();
+        if self.changed_since(&before) {
+            self.dirty = true;
+        }
+        if self.dirty
+            && ask_to_confirm_labeled(
+                "The adventure has unsaved changes, do you want to save them?",
+                tr("Save"),
+                tr("Discard"),
+            )
+        {
+            let saved = self.save_project();
+            self.keep_open = saved == false;
+            return saved;
+        }
+        false
+    }
+    /// Tests if the editor can be left after the Return event, it's kept open when the adventure couldn't be saved
+    pub fn can_leave(&self) -> bool {
+        self.keep_open == false
     }
     /// Hides editor UI
     pub fn hide(&mut self) {
         self.group.hide();
+        self.page_editor.hide();
+        if let Some(graph) = self.graph.as_mut() {
+            graph.close();
+        }
+        self.graph = None;
+        if let Some(window) = self.shared_results.as_mut() {
+            window.close();
+        }
+        self.shared_results = None;
     }
     /// Shows editor UI
     pub fn show(&mut self) {
@@ -292,13 +704,17 @@ impl EditorWindow {
         self.adventure_editor.show();
     }
     /// Saves the project into drive
-    fn save_project(&mut self) {
-        // save any unsaved data
-        if self.adventure_editor.active() {
-            self.adventure_editor.save(&mut self.adventure);
-        } else {
-            self.page_editor.save_page(page_mut!(self), &self.adventure);
+    ///
+    /// Returns false if the adventure couldn't be saved or the user cancelled it
+    fn save_project(&mut self) -> bool {
+        if self.is_loading() {
+            signal_error!("The adventure can't be saved until all of its pages are loaded");
+            return false;
         }
+        // save any unsaved data
+        self.sync_ui();
+
+        self.refresh_page_groups();
 
         // serializing data
         let adv_ser = self.adventure.serialize_to_string();
@@ -308,13 +724,439 @@ impl EditorWindow {
             .map(|x| (x.0.clone(), x.1.serialize_to_string()))
             .collect();
 
-        // clearing the adventure's folder
-        remove_adventure(&self.adventure.path);
+        let changed = self.changed_on_drive(&pages_ser);
+        if changed.len() > 0
+            && ask_to_confirm(&format!(
+                "These pages were changed outside of the editor since they were loaded:\n{}\nDo you want to replace them with the pages from the editor?",
+                changed.join("\n")
+            )) == false
+        {
+            return false;
+        }
+
+        // renaming files first so pages keep their files and only changed content is written
+        let mut renames = std::mem::take(&mut self.renamed_pages).into_iter();
+        while let Some((old, new)) = renames.next() {
+            match rename_page_file(&self.adventure.path, &old, &new) {
+                // pages that were never saved don't have a file to rename
+                Ok(_) | Err(FileError::FileNonExistent(_)) => {
+                    if let Some(saved) = self.saved_pages.remove(&old) {
+                        self.saved_pages.insert(new.clone(), saved);
+                    }
+                    if let Some(time) = self.page_times.remove(&old) {
+                        self.page_times.insert(new, time);
+                    }
+                }
+                Err(e) => {
+                    signal_error!("Page {} could not be renamed to {}: {}", old, new, e);
+                    // later renames may depend on this one so they're all kept for the next save
+                    self.renamed_pages.push((old, new));
+                    self.renamed_pages.extend(renames);
+                    break;
+                }
+            }
+        }
+        // pages waiting for a rename aren't written so they don't end up in two files
+        let pending = |x: &String| self.renamed_pages.iter().any(|r| &r.0 == x || &r.1 == x);
+
+        // removed pages are moved to trash so they can be recovered
+        for page in capture_pages(&self.adventure.path) {
+            if self.pages.contains_key(&page) == false && pending(&page) == false {
+                trash_page(&self.adventure.path, &page);
+            }
+        }
 
         // Saving the serialized adventures into the folder
         save_adventure(&self.adventure.path, adv_ser);
+        let mut written = HashMap::new();
         for page in pages_ser {
-            save_page(&self.adventure.path, page.0, page.1);
+            if pending(&page.0) {
+                continue;
+            }
+            if self.saved_pages.get(&page.0) != Some(&page.1) {
+                save_page(&self.adventure.path, page.0.clone(), page.1.clone());
+                self.page_times
+                    .insert(page.0.clone(), page_modified(&self.adventure.path, &page.0));
+            }
+            written.insert(page.0, page.1);
+        }
+        self.page_times
+            .retain(|x, _| written.contains_key(x) || self.renamed_pages.iter().any(|r| &r.0 == x));
+        self.saved_pages
+            .retain(|x, _| self.renamed_pages.iter().any(|r| &r.0 == x));
+        self.saved_pages.extend(written);
+
+        if self.renamed_pages.len() > 0 {
+            return false;
+        }
+        self.dirty = false;
+
+        let problems = self.adventure.validate(&self.pages);
+        if problems.len() > 0 {
+            signal_error!(
+                "Warning! The adventure has been saved but it will not play correctly:\n{}",
+                problems.join("\n")
+            );
+        }
+        true
+    }
+    /// Lists page files that were changed on the drive since the editor loaded or wrote them
+    ///
+    /// Only files that saving would replace or move to trash are listed
+    fn changed_on_drive(&self, pages_ser: &HashMap<String, String>) -> Vec<String> {
+        let mut changed: Vec<String> = capture_pages(&self.adventure.path)
+            .into_iter()
+            .filter(|x| match self.page_times.get(x) {
+                Some(time) => *time != page_modified(&self.adventure.path, x),
+                None => true,
+            })
+            .filter(|x| {
+                let renamed = self.renamed_pages.iter().any(|r| &r.0 == x || &r.1 == x);
+                let removed = self.pages.contains_key(x) == false;
+                renamed || removed || pages_ser.get(x) != self.saved_pages.get(x)
+            })
+            .collect();
+        changed.sort();
+        changed
+    }
+    /// Copies selected condition of the current page so it can be pasted into another page
+    fn copy_condition(&mut self) {
+        self.page_editor
+            .conditions
+            .save(&mut page_mut!(self).conditions, None);
+        if let Some(c) = self.page_editor.conditions.copy(&page!(self).conditions) {
+            self.copied = Some(Copied::Condition(c));
+        }
+    }
+    /// Pastes copied condition into the current page
+    ///
+    /// If the page already has a condition with the same name, the user is asked to paste it under a new name
+    fn paste_condition(&mut self) {
+        let mut condition = match &self.copied {
+            Some(Copied::Condition(c)) => c.clone(),
+            _ => {
+                signal_error!("Copy a Condition before pasting it");
+                return;
+            }
+        };
+        let conditions = &mut page_mut!(self).conditions;
+        let name = free_name(conditions, &condition.name);
+        if name != condition.name
+            && ask_to_confirm(&format!(
+                "Condition {} already exists in this page, paste it as {}?",
+                condition.name, name
+            )) == false
+        {
+            return;
+        }
+        condition.name = name;
+        self.page_editor.conditions.paste(conditions, condition);
+    }
+    /// Copies selected test of the current page so it can be pasted into another page
+    fn copy_test(&mut self) {
+        self.page_editor.tests.save(&mut page_mut!(self).tests, None);
+        if let Some(t) = self.page_editor.tests.copy(&page!(self).tests) {
+            self.copied = Some(Copied::Test(t));
+        }
+    }
+    /// Pastes copied test into the current page
+    ///
+    /// If the page already has a test with the same name, the user is asked to paste it under a new name
+    fn paste_test(&mut self) {
+        let mut test = match &self.copied {
+            Some(Copied::Test(t)) => t.clone(),
+            _ => {
+                signal_error!("Copy a Test before pasting it");
+                return;
+            }
+        };
+        let page = page_mut!(self);
+        let name = free_name(&page.tests, &test.name);
+        if name != test.name
+            && ask_to_confirm(&format!(
+                "Test {} already exists in this page, paste it as {}?",
+                test.name, name
+            )) == false
+        {
+            return;
+        }
+        test.name = name;
+        if self.adventure.find_result(page, &test.success_result).is_none()
+            || self.adventure.find_result(page, &test.failure_result).is_none()
+        {
+            signal_error!(
+                "Test {} leads to results that don't exist in this page, select its results before saving",
+                test.name
+            );
+        }
+        self.page_editor.tests.paste(&mut page.tests, test);
+    }
+    /// Saves a copy of the adventure with all of its pages under a new title and folder
+    ///
+    /// The copy includes changes that haven't been saved yet, the edited adventure stays loaded in the editor
+    fn clone_adventure(&mut self) {
+        if self.is_loading() {
+            signal_error!("The adventure can't be copied until all of its pages are loaded");
+            return;
+        }
+        let title = match ask_for_text("Enter a title for the copy of the adventure") {
+            Some(t) if t.trim().len() > 0 => t.trim().to_string(),
+            _ => return,
+        };
+        let folder = title.to_lowercase().replace(" ", "-");
+        if is_valid_file_name(&folder) == false {
+            signal_error!("The folder name {} is invalid", folder);
+            return;
+        }
+        let mut path = PathBuf::from(&self.adventure.path);
+        path.pop();
+        path.push(&folder);
+        if path.exists() {
+            signal_error!("Cannot copy the adventure because {:?} already exists", path);
+            return;
+        }
+        let path = match path.to_str() {
+            Some(p) => p.to_string(),
+            None => {
+                signal_error!("Cannot copy the adventure into {:?}", path);
+                return;
+            }
+        };
+
+        self.sync_ui();
+        let mut adventure = self.adventure.clone();
+        adventure.title = title;
+        adventure.path = path;
+        save_adventure(&adventure.path, adventure.serialize_to_string());
+        for page in self.pages.iter() {
+            save_page(&adventure.path, page.0.clone(), page.1.serialize_to_string());
+        }
+        let (sender, _) = app::channel();
+        sender.send(crate::game::Event::AddAdventure(adventure));
+    }
+    /// Writes the saved adventure files into a zip archive chosen by the user
+    ///
+    /// Unsaved changes are not exported, so the user is asked to save them first.
+    /// Returns true if the adventure has been saved
+    fn export_adventure(&mut self) -> bool {
+        if self.is_loading() {
+            signal_error!("The adventure can't be exported until all of its pages are loaded");
+            return false;
+        }
+        let saved = self.save_before_leaving();
+        let folder = PathBuf::from(&self.adventure.path);
+        let name = folder
+            .file_name()
+            .and_then(|x| x.to_str())
+            .unwrap_or("adventure")
+            .to_string();
+        if let Some(target) = ask_for_export_path(&name) {
+            if let Err(e) = export_adventure(&self.adventure.path, &target) {
+                signal_error!("Could not export the adventure: {}", e);
+            }
+        }
+        saved
+    }
+    /// Writes records of the adventure into a comma separated values file chosen by the user
+    fn export_records(&mut self) {
+        let name = match self.adventure.title.trim() {
+            "" => "adventure".to_string(),
+            t => t.to_lowercase().replace(" ", "-"),
+        };
+        if let Some(target) = ask_for_csv_path(&name) {
+            if let Err(e) = save_text(&target, &self.adventure.records_to_csv()) {
+                signal_error!("Could not export the records: {}", e);
+            }
+        }
+    }
+    /// Adds the word under the cursor in the story text to words the spellchecker accepts in the adventure
+    fn add_to_dictionary(&mut self) {
+        let word = match self.page_editor.word_at_cursor() {
+            Some(w) => w,
+            None => {
+                signal_error!("Place the cursor on a word of the story text to add it to the dictionary");
+                return;
+            }
+        };
+        if self.adventure.dictionary.contains(&word) == false {
+            self.adventure.dictionary.push(word);
+        }
+        self.page_editor.set_dictionary(&self.adventure.dictionary);
+    }
+    /// Copies records and names from an adventure chosen by the user into the edited one
+    ///
+    /// Only definitions are copied, pages of the other adventure are left alone
+    fn import_variables(&mut self) {
+        let adventures: Vec<Adventure> = capture_adventures()
+            .into_iter()
+            .filter(|x| x.path != self.adventure.path)
+            .collect();
+        if adventures.len() == 0 {
+            signal_error!("There are no other adventures to import records and names from");
+            return;
+        }
+        let other = match ask_to_choose_adventure(&adventures, false) {
+            Some(i) => &adventures[i],
+            None => return,
+        };
+        let skipped = self.adventure.import_variables(other);
+        self.adventure_editor.clear_variables(true);
+        self.adventure_editor.clear_variables(false);
+        self.page_editor.clear_variables(true);
+        self.page_editor.clear_variables(false);
+        self.adventure.names.iter().for_each(|x| {
+            self.adventure_editor.add_name(&x.1);
+            self.page_editor.add_name(&x.1);
+        });
+        self.adventure.records.iter().for_each(|x| {
+            self.adventure_editor.add_record(&x.1);
+            self.page_editor.add_record(&x.1);
+        });
+        if skipped.len() > 0 {
+            show_report(tr("Skipped records and names"), &skipped);
+        }
+    }
+    /// Evaluates expressions of all pages and shows the user the ones that fail
+    fn check_expressions(&mut self) {
+        if self.is_loading() {
+            signal_error!("The adventure can't be checked until all of its pages are loaded");
+            return;
+        }
+        self.sync_ui();
+        let mut problems = self.adventure.check_expressions(&self.pages);
+        if problems.len() == 0 {
+            problems.push(tr("No problems found").to_string());
+        }
+        show_report(tr("Expression check"), &problems);
+    }
+    /// Lets the user find a page by its file name or title and opens it
+    fn quick_open(&mut self) {
+        let mut entries: Vec<(String, String)> = self
+            .pages
+            .iter()
+            .map(|x| (x.0.clone(), x.1.title.clone()))
+            .collect();
+        if entries.len() == 0 {
+            return;
+        }
+        entries.sort();
+        if let Some(name) = ask_for_filtered_choice(tr("Open Page"), &entries) {
+            self.go_to_page(name);
+        }
+    }
+    /// Opens the window for editing results shared by all pages
+    fn open_shared_results(&mut self) {
+        if self.is_loading() {
+            signal_error!("Shared results can't be edited until all pages are loaded");
+            return;
+        }
+        self.sync_ui();
+        let window = self.shared_results.get_or_insert_with(SharedResultsWindow::new);
+        window.show(&self.adventure.results, &self.pages);
+    }
+    /// Processes a result event of the shared results window
+    ///
+    /// Adding, renaming and removing shared results can change what choices of the open page lead to, so the page is reloaded
+    fn process_shared_result(&mut self, ev: Event) {
+        if self.shared_results.is_none() {
+            return;
+        }
+        let changes_pages = matches!(ev, Event::AddResult | Event::RenameResult | Event::RemoveResult);
+        if changes_pages {
+            self.sync_ui();
+        }
+        let editor = &mut self.shared_results.as_mut().unwrap().results;
+        match ev {
+            Event::RenameResult => editor.rename_shared(&mut self.adventure, &mut self.pages),
+            Event::RemoveResult => editor.remove_shared(&mut self.adventure, &self.pages),
+            ev => {
+                // records and names of the adventure are needed while its results are changed
+                let mut results = std::mem::take(&mut self.adventure.results);
+                match ev {
+                    Event::AddResult            => editor.add(&mut results, &self.adventure.start),
+                    Event::SaveResult(res)      => editor.save(&mut results, res, &self.adventure),
+                    Event::LoadResult(res)      => editor.load(&results, res),
+                    Event::SaveSideEffect(se)   => editor.save_effect(&mut results, &self.adventure, se),
+                    Event::LoadSideEffect(se)   => editor.load_effect(&results, se, &self.adventure),
+                    Event::AddSideEffectRecord  => editor.add_record(&mut results, &self.adventure.records),
+                    Event::AddSideEffectName    => editor.add_name(&mut results, &self.adventure.names),
+                    Event::RemoveSideEffect     => editor.remove_effect(&mut results),
+                    _ => println!("Error: {:?} is not an event of shared results", ev),
+                }
+                self.adventure.results = results;
+            }
+        }
+        if changes_pages && self.adventure_editor.active() == false && self.pages.contains_key(&self.current_page) {
+            self.load_page();
+        }
+    }
+    /// Opens a window showing how pages of the adventure link to each other
+    fn show_graph(&mut self) {
+        if self.is_loading() {
+            signal_error!("The graph can't be shown until all pages are loaded");
+            return;
+        }
+        self.sync_ui();
+        if let Some(mut old) = self.graph.take() {
+            old.close();
+        }
+        self.graph = Some(GraphWindow::new(&self.pages, &self.adventure.results, &self.adventure.start));
+    }
+    /// Hides the editor to play the adventure from the open page, or from the starting page when no page is open
+    ///
+    /// Returns the adventure, its pages as they are in the editor and the page to start from.
+    /// Changes in the editor need to be synced by the Playtest event first
+    pub fn playtest(&mut self) -> Option<(Adventure, HashMap<String, Page>, String)> {
+        if self.is_loading() {
+            signal_error!("The adventure can't be played until all pages are loaded");
+            return None;
+        }
+        let page = match self.adventure_editor.active() {
+            true => self.adventure.start.clone(),
+            false => self.current_page.clone(),
+        };
+        if self.pages.contains_key(&page) == false {
+            signal_error!("Page {} doesn't exist, the adventure can't be played from it", page);
+            return None;
+        }
+        if let Some(mut graph) = self.graph.take() {
+            graph.close();
+        }
+        if let Some(mut window) = self.shared_results.take() {
+            window.close();
+        }
+        self.playtesting = true;
+        self.group.hide();
+        Some((self.adventure.clone(), self.pages.clone(), page))
+    }
+    /// Shows the editor again after playing the adventure from it, as it was left
+    pub fn resume(&mut self) {
+        self.playtesting = false;
+        self.group.show();
+    }
+    /// Unpacks an adventure from a zip archive chosen by the user into the adventures folder
+    fn import_adventure(&mut self) {
+        let archive = match ask_for_archive() {
+            Some(a) => a,
+            None => return,
+        };
+        let books = match user_data_paths("books").into_iter().next() {
+            Some(b) => b,
+            None => {
+                signal_error!("Could not find a folder to import the adventure into");
+                return;
+            }
+        };
+        if let Err(e) = std::fs::create_dir_all(&books) {
+            signal_error!("Error creating a directory: {}", e);
+            return;
+        }
+        match import_adventure(&archive, &books) {
+            Ok(adventure) => {
+                let (sender, _) = app::channel();
+                sender.send(crate::game::Event::AddAdventure(adventure));
+            }
+            Err(e) => signal_error!("Could not import the adventure: {}", e),
         }
     }
     /// Opens page editor and loads page by filename into it
@@ -329,7 +1171,44 @@ impl EditorWindow {
         self.adventure_editor.hide();
 
         self.current_page = name;
+        self.refresh_page_groups();
         self.load_page();
+        self.warn_missing_references();
+    }
+    /// Opens a page by name and selects it in the file list
+    ///
+    /// Does nothing if the page doesn't exist
+    fn go_to_page(&mut self, name: String) {
+        if self.pages.contains_key(&name) == false {
+            return;
+        }
+        self.file_list.select_line(&name);
+        self.open_page(name);
+    }
+    /// Refreshes page outline and tag filter to reflect changes in pages
+    fn refresh_page_groups(&mut self) {
+        self.file_list.populate_outline(&self.pages);
+        if self.file_list.populate_tags(&self.pages) {
+            self.refresh_page_list();
+        } else {
+            self.file_list.update_summaries(&self.pages, &self.adventure.results);
+        }
+    }
+    /// Fills the page list with pages that match selected tag filter
+    fn refresh_page_list(&mut self) {
+        let tag = self.file_list.selected_tag();
+        let mut pages: Vec<(&String, &Page)> = self
+            .pages
+            .iter()
+            .filter(|x| match &tag {
+                Some(t) => x.1.tags.contains(t),
+                None => true,
+            })
+            .collect();
+        pages.sort_by(|a, b| a.0.cmp(b.0));
+        self.file_list.populate_pages(&pages, &self.adventure.results);
+        self.file_list.mark_line("", &self.adventure.start);
+        self.file_list.select_line(&self.current_page);
     }
     /// Loads current page into UI
     fn load_page(&mut self) {
@@ -340,49 +1219,163 @@ impl EditorWindow {
         self.page_editor
             .conditions
             .populate_conditions(&page.conditions);
-        self.page_editor.tests.populate(&page.tests, &page.results);
+        self.page_editor
+            .tests
+            .set_defaults(&self.adventure.records, self.adventure.rounding);
+        let results = self.adventure.page_results(page);
+        self.page_editor.tests.populate(&page.tests, &results);
         self.page_editor
             .results
             .populate(&page.results, &self.pages);
-        self.page_editor.choices.populate_dropdowns(&page);
+        self.page_editor.results.show_unused(page);
+        self.page_editor.randoms.populate(&page.randoms);
+        self.page_editor.switches.populate(&page.switches, &results);
+        self.page_editor.choices.populate_dropdowns(&page, &results, &self.pages);
         self.page_editor.choices.populate_choices(&page.choices);
+        self.page_editor.raw.load(page);
 
         self.page_editor.show();
     }
+    /// Warns the author about choices of the current page that use elements the page doesn't have
+    fn warn_missing_references(&self) {
+        let missing = match self.pages.get(&self.current_page) {
+            Some(p) => p.missing_references(&self.adventure.results),
+            None => return,
+        };
+        if missing.len() > 0 {
+            signal_error!(
+                "Warning! Some choices of page {} use elements that don't exist:\n{}",
+                self.current_page,
+                missing.iter().map(|x| x.to_string()).collect::<Vec<String>>().join("\n")
+            );
+        }
+    }
+    /// Fills the raw page editor with the current page, including changes made in other editors
+    fn load_raw_page(&mut self) {
+        self.page_editor.save_page(page_mut!(self), &self.adventure);
+        self.page_editor.raw.load(page!(self));
+    }
+    /// Parses the text of the raw page editor and replaces the current page with it
+    ///
+    /// If the text can't be parsed, the error is shown and the page is left unchanged
+    fn apply_raw_page(&mut self) {
+        let page = match Page::parse_from_string(self.page_editor.raw.text()) {
+            Ok(p) => p,
+            Err(ParsingError::IncomplatePage(p)) => p,
+            Err(e) => {
+                signal_error!("The page could not be applied: {}", e);
+                return;
+            }
+        };
+        *page_mut!(self) = page;
+        self.refresh_page_groups();
+        self.load_page();
+        self.warn_missing_references();
+    }
     /// Removes currently selected page
     fn remove_page(&mut self) {
         if self.adventure_editor.active() {
             return;
         }
-        if ask_to_confirm(&format!(
-            "Are you sure you want to remove {} page?",
-            self.current_page
-        )) {
-            self.pages.remove(&self.current_page);
-            self.file_list.remove_line();
+        // pages that aren't loaded yet could still lead to the removed pages
+        if self.is_loading() {
+            signal_error!("Pages can't be removed until all pages are loaded");
+            return;
+        }
+        let names = self.file_list.selected_pages();
+        if names.len() == 0 {
+            return;
+        }
+        // the editor may hold links that aren't in the page yet
+        if let Some(page) = self.pages.get_mut(&self.current_page) {
+            self.page_editor.save_page(page, &self.adventure);
+        }
+        let links = links_to_pages(&self.pages, &self.adventure.results, &names);
+        if links.len() > 0 {
+            signal_error!(
+                "Cannot remove pages other pages lead to, change those links first:\n{}",
+                links.join("\n")
+            );
+            return;
+        }
+        let question = match names.len() {
+            1 => format!("Are you sure you want to remove {} page?", names[0]),
+            n => format!("Are you sure you want to remove {} selected pages?", n),
+        };
+        if ask_to_confirm_labeled(&question, tr("Delete"), tr("Keep")) {
+            self.removed_pages = names
+                .iter()
+                .filter_map(|x| self.pages.remove(x).map(|p| (x.clone(), p)))
+                .collect();
+            self.file_list.remove_lines(&names);
+            self.refresh_page_groups();
             self.open_adventure();
         }
     }
+    /// Brings back pages removed last in this session
+    fn restore_page(&mut self) {
+        if self.removed_pages.len() == 0 {
+            signal_error!("There is no removed page to restore");
+            return;
+        }
+        if let Some((name, _)) = self.removed_pages.iter().find(|x| self.pages.contains_key(&x.0)) {
+            signal_error!("Cannot restore page {} because a page with that name exists", name);
+            return;
+        }
+        let name = self.removed_pages[0].0.clone();
+        self.pages.extend(self.removed_pages.drain(..));
+        self.refresh_page_groups();
+        self.refresh_page_list();
+        self.go_to_page(name);
+    }
     /// Renames currently selected page
     ///
     /// It also updates all references to the page name
     fn rename_page(&mut self) {
+        // links in pages that aren't loaded yet wouldn't be updated
+        if self.is_loading() {
+            signal_error!("Pages can't be renamed until all pages are loaded");
+            return;
+        }
         if let Some(name) =
             ask_for_text(&format!("Enter a new name for page {}", self.current_page))
         {
-            let name = name.to_lowercase().replace(" ", "-");
+            let mut name = name.to_lowercase().replace(" ", "-");
             if is_valid_file_name(&name) == false {
                 signal_error!("The file name {} is invalid", name);
                 return;
             }
-            if let Some(page) = self.pages.remove(&self.current_page) {
-                self.pages
-                    .iter_mut()
-                    .map(|x| x.1.results.iter_mut().filter(|x| x.1.next_page == self.current_page))
-                    .for_each(|x| x.for_each(|x| x.1.next_page = name.clone()));
+            if name == self.current_page {
+                return;
+            }
+            // pages still being loaded are only on the drive, so it's checked too
+            if is_page_name_taken(&self.pages, &self.saved_pages, &self.adventure.path, &name) {
+                let free = free_page_name(&self.pages, &self.saved_pages, &self.adventure.path, &name);
+                if ask_to_confirm(&format!(
+                    "The page {} already exists, do you want to name the page {} instead?",
+                    name, free
+                )) == false
+                {
+                    return;
+                }
+                name = free;
+            }
+            // the editor may hold changes that still refer to the old name
+            if let Some(page) = self.pages.get_mut(&self.current_page) {
+                self.page_editor.save_page(page, &self.adventure);
+            }
+            if self
+                .adventure
+                .rename_page(&mut self.pages, &self.current_page, &name)
+            {
                 self.file_list.rename_selected(&name);
-                self.pages.insert(name.clone(), page);
+                self.file_list.mark_line("", &self.adventure.start);
+                self.renamed_pages
+                    .push((self.current_page.clone(), name.clone()));
                 self.current_page = name;
+                self.refresh_page_groups();
+                // reloading so result dropdowns list the new page name
+                self.load_page();
             }
         }
     }
@@ -394,6 +1387,11 @@ impl EditorWindow {
                 signal_error!("The file name {} is invalid", file_name);
                 return;
             }
+            // pages still being loaded are only on the drive, so it's checked too
+            if is_page_name_taken(&self.pages, &self.saved_pages, &self.adventure.path, &file_name) {
+                signal_error!("The page {} already exists, choose a different name", file_name);
+                return;
+            }
             let page = Page {
                 title: name,
                 ..Default::default()
@@ -420,6 +1418,7 @@ impl EditorWindow {
         if let Some(mut cur_page) = self.pages.get_mut(&self.current_page) {
             self.page_editor.save_page(&mut cur_page, &self.adventure);
         }
+        self.refresh_page_groups();
         self.adventure_editor.load(&self.adventure);
         self.page_editor.hide();
         self.adventure_editor.show();
@@ -447,7 +1446,7 @@ impl EditorWindow {
                 }
             }
         } else {
-            if let Some(rec) = ask_for_record(None) {
+            if let Some(rec) = ask_for_record(None, &self.adventure.record_categories()) {
                 if is_keyword_valid(&rec.name) {
                     if self.adventure.records.contains_key(&rec.name) {
                         signal_error!("The keyword {} is already present", rec.name);
@@ -468,6 +1467,11 @@ impl EditorWindow {
     }
     /// Renames a keyword in the adventure
     fn rename_keyword(&mut self, is_record: bool, old: String) {
+        // pages that aren't loaded yet would keep the old keyword
+        if self.is_loading() {
+            signal_error!("Records and names can't be changed until all pages are loaded");
+            return;
+        }
         // saving unsaved page edits
         if self.adventure_editor.active() == false {
             let mut page = page_mut!(self);
@@ -495,7 +1499,7 @@ impl EditorWindow {
                     return;
                 }
             };
-            if let Some(new_rec) = ask_for_record(Some(rec)) {
+            if let Some(new_rec) = ask_for_record(Some(rec), &self.adventure.record_categories()) {
                 if is_keyword_valid(&new_rec.name) == false {
                     signal_error!("Keyword {} is invalid, use only regular letters", new_rec.name);
                     return;
@@ -510,6 +1514,7 @@ impl EditorWindow {
                 self.pages
                     .iter_mut()
                     .for_each(|x| x.1.rename_keyword(&old, &new_rec.name));
+                self.adventure.rename_keyword_in_results(&old, &new_rec.name);
                 self.adventure.update_record(&old, new_rec);
             }
         } else {
@@ -536,6 +1541,7 @@ impl EditorWindow {
                 self.pages
                     .iter_mut()
                     .for_each(|x| x.1.rename_keyword(&old, &new_nam.keyword));
+                self.adventure.rename_keyword_in_results(&old, &new_nam.keyword);
                 self.adventure.update_name(&old, new_nam);
             }
         }
@@ -547,54 +1553,188 @@ impl EditorWindow {
             self.load_page();
         }
     }
-    /// Removes a keyword from adventure
+    /// Removes a record or a name from the adventure after the user confirms it
+    ///
+    /// Keywords used in any page or shared result can't be removed, pages removed in this session are only mentioned in the question
     fn remove_keyword(&mut self, name: String, is_name: bool) {
-        if is_name {
-            let keyword = match self.adventure.names.get(&name) {
-                Some(k) => k,
+        // pages that aren't loaded yet could still use the keyword
+        if self.is_loading() {
+            signal_error!("Records and names can't be removed until all pages are loaded");
+            return;
+        }
+        let (keyword, kind) = match is_name {
+            true => match self.adventure.names.get(&name) {
+                Some(k) => (k.keyword.clone(), "name"),
                 None => return,
-            };
-            for p in self.pages.iter() {
-                if p.1.is_keyword_present(&keyword.keyword) {
-                    signal_error!(
-                        "Cannot remove the record {} as it is used in at least one of pages",
-                        name
-                    );
-                    return;
-                }
-            }
-            if ask_to_confirm(&format!("Are you sure you want to remove {}?", name)) {
-                self.adventure.names.remove(&name);
-                self.adventure_editor.clear_variables(true);
-                self.page_editor.clear_variables(true);
-                self.adventure.names.iter().for_each(|x| {
-                    self.adventure_editor.add_name(&x.1);
-                    self.page_editor.add_name(&x.1);
-                });
-            }
-        } else {
-            let keyword = match self.adventure.records.get(&name) {
-                Some(k) => k,
+            },
+            false => match self.adventure.records.get(&name) {
+                Some(k) => (k.name.clone(), "record"),
                 None => return,
-            };
-            for p in self.pages.iter() {
-                if p.1.is_keyword_present(&keyword.name) {
-                    signal_error!(
-                        "Cannot remove the record {} as it is used in at least one of pages",
-                        name
-                    );
-                    return;
-                }
-            }
-            if ask_to_confirm(&format!("Are you sure you want to remove {}?", name)) {
-                self.adventure.records.remove(&name);
-                self.adventure_editor.clear_variables(false);
-                self.page_editor.clear_variables(false);
-                self.adventure.records.iter().for_each(|x| {
-                    self.adventure_editor.add_record(&x.1);
-                    self.page_editor.add_record(&x.1);
-                });
+            },
+        };
+        // the page editor may hold text that isn't in the page yet
+        if self.adventure_editor.active() == false {
+            if let Some(page) = self.pages.get_mut(&self.current_page) {
+                self.page_editor.save_page(page, &self.adventure);
             }
         }
+        let using = pages_using_keyword(self.pages.iter(), &keyword);
+        if using.len() > 0 {
+            signal_error!(
+                "Cannot remove the {} {} because it is used in pages: {}",
+                kind,
+                name,
+                using.join(", ")
+            );
+            return;
+        }
+        if self.adventure.results.values().any(|x| x.is_keyword_present(&keyword)) {
+            signal_error!("Cannot remove the {} {} because it is used in a shared result", kind, name);
+            return;
+        }
+        let removed = pages_using_keyword(self.removed_pages.iter().map(|x| (&x.0, &x.1)), &keyword);
+        let question = match removed.len() {
+            0 => format!("Are you sure you want to remove the {} {}?", kind, name),
+            _ => format!(
+                "Are you sure you want to remove the {} {}?\nRemoved pages {} use it and will refer to a missing {} if restored",
+                kind,
+                name,
+                removed.join(", "),
+                kind
+            ),
+        };
+        if ask_to_confirm_labeled(&question, tr("Delete"), tr("Keep")) == false {
+            return;
+        }
+        self.adventure_editor.clear_variables(is_name);
+        self.page_editor.clear_variables(is_name);
+        if is_name {
+            self.adventure.names.remove(&name);
+            self.adventure.names.iter().for_each(|x| {
+                self.adventure_editor.add_name(&x.1);
+                self.page_editor.add_name(&x.1);
+            });
+        } else {
+            self.adventure.records.remove(&name);
+            self.adventure.records.iter().for_each(|x| {
+                self.adventure_editor.add_record(&x.1);
+                self.page_editor.add_record(&x.1);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs::remove_dir_all};
+
+    use crate::adventure::{Page, RandomResult, StoryResult};
+
+    use super::{free_page_name, is_page_name_taken, links_to_pages, pages_using_keyword};
+
+    #[test]
+    fn page_name_collision() {
+        let root = std::env::temp_dir().join("adventure-book-page-collision");
+        let _ = remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("cave.txt"), "title: Cave").unwrap();
+        std::fs::write(root.join("removed.txt"), "title: Removed").unwrap();
+        let path = root.to_str().unwrap();
+
+        let mut pages = HashMap::new();
+        pages.insert("intro".to_string(), Page::default());
+        let mut known = HashMap::new();
+        known.insert("removed".to_string(), String::new());
+
+        let taken = |name: &str| is_page_name_taken(&pages, &known, path, name);
+        assert!(taken("intro"));
+        assert!(taken("cave"));
+        assert!(taken("removed") == false);
+        assert!(taken("lake") == false);
+
+        assert_eq!(free_page_name(&pages, &known, path, "lake"), "lake");
+        assert_eq!(free_page_name(&pages, &known, path, "removed"), "removed");
+        std::fs::write(root.join("cave-2.txt"), "title: Cave").unwrap();
+        pages.insert("intro-2".to_string(), Page::default());
+        assert_eq!(free_page_name(&pages, &known, path, "cave"), "cave-3");
+        assert_eq!(free_page_name(&pages, &known, path, "intro"), "intro-3");
+        let _ = remove_dir_all(&root);
+    }
+    #[test]
+    fn links_to_removed_pages() {
+        let mut pages = HashMap::new();
+        let mut intro = Page::default();
+        intro.results.insert(
+            "enter".to_string(),
+            StoryResult {
+                name: "enter".to_string(),
+                next_page: "cave".to_string(),
+                ..Default::default()
+            },
+        );
+        pages.insert("intro".to_string(), intro);
+        let mut cave = Page::default();
+        cave.randoms.insert(
+            "slip".to_string(),
+            RandomResult {
+                name: "slip".to_string(),
+                outcomes: vec![("lake".to_string(), 1), ("intro".to_string(), 1)],
+            },
+        );
+        pages.insert("cave".to_string(), cave);
+        pages.insert("lake".to_string(), Page::default());
+
+        let mut shared = HashMap::new();
+        let names = vec!["cave".to_string(), "lake".to_string()];
+        assert_eq!(links_to_pages(&pages, &shared, &names), vec!["Page intro: Result enter leads to page cave"]);
+        let names = vec!["intro".to_string(), "cave".to_string(), "lake".to_string()];
+        assert!(links_to_pages(&pages, &shared, &names).is_empty());
+        assert_eq!(
+            links_to_pages(&pages, &shared, &vec!["lake".to_string()]),
+            vec!["Page cave: Random slip leads to page lake"]
+        );
+
+        shared.insert(
+            "swim".to_string(),
+            StoryResult {
+                name: "swim".to_string(),
+                next_page: "lake".to_string(),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            links_to_pages(&pages, &shared, &vec!["lake".to_string()]),
+            vec!["Page cave: Random slip leads to page lake", "Shared result swim leads to page lake"]
+        );
+
+        pages.get_mut("intro").unwrap().story = "You see a [[lake|pond]].".to_string();
+        assert_eq!(
+            links_to_pages(&pages, &shared, &vec!["lake".to_string()]),
+            vec![
+                "Page cave: Random slip leads to page lake",
+                "Page intro: Story links to page lake",
+                "Shared result swim leads to page lake"
+            ]
+        );
+    }
+
+    #[test]
+    fn keyword_users() {
+        let mut pages = HashMap::new();
+        let page = |story: &str| Page {
+            story: story.to_string(),
+            ..Default::default()
+        };
+        pages.insert("market".to_string(), page("You have [gold] coins"));
+        pages.insert("bank".to_string(), page("The vault holds [gold] and [silver]"));
+        pages.insert("road".to_string(), page("A long road"));
+
+        assert_eq!(pages_using_keyword(pages.iter(), "gold"), vec!["bank", "market"]);
+        assert_eq!(pages_using_keyword(pages.iter(), "silver"), vec!["bank"]);
+        assert!(pages_using_keyword(pages.iter(), "gol").is_empty());
+
+        let removed = vec![("road".to_string(), page("[silver] glints"))];
+        let using = pages_using_keyword(removed.iter().map(|x| (&x.0, &x.1)), "silver");
+        assert_eq!(using, vec!["road"]);
     }
 }
@@ -3,10 +3,14 @@ use fltk::{
     draw::Rect,
     group::Group,
     prelude::*,
-    text::{TextBuffer, TextEditor}, button::Button,
+    text::{TextBuffer, TextEditor}, button::{Button, CheckButton}, frame::Frame,
 };
 
-use crate::adventure::{Adventure, Record, Name};
+use crate::{
+    adventure::{Adventure, Record, Name},
+    evaluation::Rounding,
+    lang::tr,
+};
 
 use super::{variables::VariableEditor, help, highlight_color};
 
@@ -17,6 +21,11 @@ use super::{variables::VariableEditor, help, highlight_color};
 pub struct AdventureEditor {
     group: Group,
     title: TextEditor,
+    author: TextEditor,
+    version: TextEditor,
+    date: TextEditor,
+    rounding: fltk::menu::Choice,
+    hide_rolls: CheckButton,
     description: TextEditor,
     records: VariableEditor,
     names: VariableEditor,
@@ -29,15 +38,23 @@ impl AdventureEditor {
 
         let x_title = area.x;
         let y_title = area.y + font_size;
-        let w_title = area.w;
+        let w_hide_rolls = area.w / 4 - 5;
+        let w_title = area.w - w_hide_rolls - 5;
         let h_title = font_size + 4;
+        let x_hide_rolls = x_title + w_title + 5;
+
+        let y_meta = y_title + h_title + font_size;
+        let w_meta = area.w / 4 - 5;
+        let x_version = area.x + w_meta + 5;
+        let x_date = x_version + w_meta + 5;
+        let x_rounding = x_date + w_meta + 5;
 
         let x_desc = area.x;
-        let y_desc = y_title + h_title + font_size;
+        let y_desc = y_meta + h_title + font_size;
         let w_desc = area.w;
-        let h_desc = area.h / 2;
+        let h_desc = area.h / 2 - h_title - font_size;
 
-        let x_help = x_title + w_title - font_size * 2;
+        let x_help = x_title + area.w - font_size * 2;
         let y_help = y_desc - font_size;
         let w_help = font_size;
         let h_help = w_help;
@@ -51,8 +68,20 @@ impl AdventureEditor {
         let nam_area = Rect::new(area.x + rec_area.w, rec_area.y, rec_area.w, rec_area.h);
 
         let group = Group::new(area.x, area.y, area.w, area.h, None);
-        let mut title = TextEditor::new(x_title, y_title, w_title, h_title, "Title");
-        let mut description = TextEditor::new(x_desc, y_desc, w_desc, h_desc, "Description");
+        let mut title = TextEditor::new(x_title, y_title, w_title, h_title, tr("Title"));
+        let mut hide_rolls = CheckButton::new(
+            x_hide_rolls,
+            y_title,
+            w_hide_rolls,
+            h_title,
+            tr("Hide test rolls"),
+        );
+        let mut author = TextEditor::new(x_title, y_meta, w_meta, h_title, tr("Author"));
+        let mut version = TextEditor::new(x_version, y_meta, w_meta, h_title, tr("Version"));
+        let mut date = TextEditor::new(x_date, y_meta, w_meta, h_title, tr("Date"));
+        let mut rounding = fltk::menu::Choice::new(x_rounding, y_meta, w_meta, h_title, None);
+        Frame::new(x_rounding, y_meta + h_title, w_meta, font_size, tr("Division Rounding"));
+        let mut description = TextEditor::new(x_desc, y_desc, w_desc, h_desc, tr("Description"));
         let mut help = Button::new(x_help, y_help, w_help, h_help, "?");
 
         let records = VariableEditor::new(rec_area, true);
@@ -60,6 +89,13 @@ impl AdventureEditor {
         group.end();
 
         title.set_buffer(TextBuffer::default());
+        author.set_buffer(TextBuffer::default());
+        version.set_buffer(TextBuffer::default());
+        date.set_buffer(TextBuffer::default());
+        rounding.add_choice(&Rounding::as_choice());
+        rounding.set_value(0);
+        rounding.set_tooltip(tr("How results of division in expressions are rounded to whole numbers"));
+        hide_rolls.set_tooltip(tr("Don't show players the values rolled in tests of their choices"));
         description.set_buffer(TextBuffer::default());
         description.wrap_mode(fltk::text::WrapMode::AtBounds, 0);
 
@@ -71,6 +107,11 @@ impl AdventureEditor {
         Self {
             group,
             title,
+            author,
+            version,
+            date,
+            rounding,
+            hide_rolls,
             description,
             records,
             names,
@@ -92,6 +133,12 @@ impl AdventureEditor {
     fn set_title(&mut self, title: &str) {
         self.title.buffer().as_mut().unwrap().set_text(&title);
     }
+    /// Sets author, version and date into their editors
+    fn set_details(&mut self, author: &str, version: &str, date: &str) {
+        self.author.buffer().as_mut().unwrap().set_text(author);
+        self.version.buffer().as_mut().unwrap().set_text(version);
+        self.date.buffer().as_mut().unwrap().set_text(date);
+    }
     /// Sets text into adventure description editor
     fn set_description(&mut self, description: &str) {
         self.description
@@ -121,6 +168,9 @@ impl AdventureEditor {
     /// Loads adventure information into UI
     pub fn load(&mut self, adventure: &Adventure) {
         self.set_title(&adventure.title);
+        self.set_details(&adventure.author, &adventure.version, &adventure.date);
+        self.rounding.set_value(adventure.rounding.to_index());
+        self.hide_rolls.set_checked(adventure.hide_rolls);
         self.set_description(&adventure.description);
         self.records.clear();
         for rec in adventure.records.iter() {
@@ -136,6 +186,13 @@ impl AdventureEditor {
     pub fn save(&self, adventure: &mut Adventure) {
         adventure.title = self.title.buffer().as_ref().unwrap().text();
         adventure.description = self.description.buffer().as_ref().unwrap().text();
+        adventure.author = self.author.buffer().as_ref().unwrap().text().trim().to_string();
+        adventure.version = self.version.buffer().as_ref().unwrap().text().trim().to_string();
+        adventure.date = self.date.buffer().as_ref().unwrap().text().trim().to_string();
+        if let Some(r) = self.rounding.choice() {
+            adventure.rounding = Rounding::from_keyword(&r).unwrap_or_default();
+        }
+        adventure.hide_rolls = self.hide_rolls.is_checked();
         // saving only those because records and names are saved through their own controls
     }
 }
@@ -1,7 +1,9 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
 use fltk::{
     app,
     browser::SelectBrowser,
-    button::Button,
+    button::{Button, CheckButton},
     draw::Rect,
     frame::Frame,
     group::Group,
@@ -12,25 +14,48 @@ use fltk::{
 type Dropdown = fltk::menu::Choice;
 
 use crate::{
-    adventure::{Choice, Page, GAME_OVER_KEYWORD},
+    adventure::{Choice, Page, StoryResult, GAME_OVER_KEYWORD},
     dialog::ask_to_confirm,
     editor::{emit, help, variables::variable_receiver, Event, highlight_color},
+    file::signal_error,
     icons::BIN_ICON,
+    lang::tr,
 };
 
 /// Editor for customizing choices for a page
 ///
 /// Displays a list of choices for the page
-/// It has a text editor for the choice text, and drop downs for choosing condition, test and result for each choice
+/// It has a text editor for the choice text, and drop downs for choosing condition, test, random result, switch and result for each choice
 pub struct ChoiceEditor {
     selector: SelectBrowser,
     text: TextEditor,
     condition: Dropdown,
     test: Dropdown,
     result: Dropdown,
+    random: Dropdown,
+    switch: Dropdown,
+    once: CheckButton,
+    /// Page wide flag, it stays visible when no choice is selected
+    shuffle: CheckButton,
     condition_label: Frame,
     test_label: Frame,
     result_label: Frame,
+    random_label: Frame,
+    switch_label: Frame,
+    /// Descriptions of where each result of the page leads, keyed by result name
+    targets: Rc<RefCell<HashMap<String, String>>>,
+}
+
+/// Updates the result dropdown label to tell where the chosen result leads
+fn show_result_target(label: &mut Frame, targets: &HashMap<String, String>, result: Option<String>) {
+    match result.and_then(|x| targets.get(&x)) {
+        Some(target) => label.set_label(&format!("{} ({})", tr("Result"), target)),
+        None => label.set_label(tr("Result")),
+    }
+    // frame has no box of its own so the old text has to be cleared by the group
+    if let Some(mut parent) = label.parent() {
+        parent.redraw();
+    }
 }
 
 impl ChoiceEditor {
@@ -38,7 +63,7 @@ impl ChoiceEditor {
     pub fn new(area: Rect) -> Self {
         let font_size = app::font_size();
 
-        let group = Group::new(area.x, area.y, area.w, area.h, "Choices");
+        let group = Group::new(area.x, area.y, area.w, area.h, tr("Choices"));
 
         let x_selector = area.x;
         let y_selector = area.y;
@@ -59,36 +84,52 @@ impl ChoiceEditor {
         let y_menu_condition = area.y + h_menu;
         let y_menu_test = y_menu_condition + h_menu * 2;
         let y_menu_result = y_menu_test + h_menu * 2;
+        let y_menu_random = y_menu_result + h_menu * 2;
+        let y_menu_switch = y_menu_random + h_menu * 2;
 
         let x_text = x_menu;
-        let y_text = y_menu_result + h_menu * 2;
+        let y_text = y_menu_switch + h_menu * 2;
         let w_text = w_menu;
         let h_text = h_menu;
+        let y_once = y_text + h_text + h_menu;
+        let y_shuffle = area.y + area.h - h_menu;
 
         let mut selector = SelectBrowser::new(
             x_selector,
             y_selector,
             w_selector,
             h_selector,
-            "Choices in this page",
+            tr("Choices in this page"),
         );
         let mut butt_add = Button::new(x_butt_add, y_butt, w_butt, h_butt, "@+");
         let mut butt_rem = Button::new(x_butt_rem, y_butt, w_butt, h_butt, None);
         let mut help = Button::new(x_butt_help, y_butt, w_butt, h_butt, "?");
 
-        let mut text = TextEditor::new(x_text, y_text, w_text, h_text, "Choice Text");
+        let mut text = TextEditor::new(x_text, y_text, w_text, h_text, tr("Choice Text"));
+        let mut once = CheckButton::new(x_text, y_once, w_text, h_menu, tr("Only once"));
+        let mut shuffle = CheckButton::new(x_text, y_shuffle, w_text, h_menu, tr("Shuffle choices"));
         let condition_label = Frame::new(
             x_menu,
             y_menu_condition - font_size,
             w_menu,
             h_menu,
-            "Condition",
+            tr("Condition"),
         );
         let condition = Dropdown::new(x_menu, y_menu_condition, w_menu, h_menu, None);
-        let test_label = Frame::new(x_menu, y_menu_test - font_size, w_menu, h_menu, "Test");
+        let test_label = Frame::new(x_menu, y_menu_test - font_size, w_menu, h_menu, tr("Test"));
         let mut test = Dropdown::new(x_menu, y_menu_test, w_menu, h_menu, None);
-        let result_label = Frame::new(x_menu, y_menu_result - font_size, w_menu, h_menu, "Result");
+        let result_label = Frame::new(x_menu, y_menu_result - font_size, w_menu, h_menu, tr("Result"));
         let mut result = Dropdown::new(x_menu, y_menu_result, w_menu, h_menu, None);
+        let random_label = Frame::new(
+            x_menu,
+            y_menu_random - font_size,
+            w_menu,
+            h_menu,
+            tr("Random Result"),
+        );
+        let mut random = Dropdown::new(x_menu, y_menu_random, w_menu, h_menu, None);
+        let switch_label = Frame::new(x_menu, y_menu_switch - font_size, w_menu, h_menu, tr("Switch"));
+        let mut switch = Dropdown::new(x_menu, y_menu_switch, w_menu, h_menu, None);
         group.end();
 
         let mut bin = SvgImage::from_data(BIN_ICON).unwrap();
@@ -96,6 +137,8 @@ impl ChoiceEditor {
         butt_rem.set_image(Some(bin));
 
         text.set_buffer(TextBuffer::default());
+        once.set_tooltip(tr("The choice becomes unavailable after the player chooses it"));
+        shuffle.set_tooltip(tr("Choices of this page are shown in a random order each time the player enters it"));
 
         let (s, _r) = app::channel();
         butt_add.emit(s.clone(), emit!(Event::AddChoice));
@@ -126,23 +169,62 @@ impl ChoiceEditor {
                 }
             }
         });
+        // a choice can lead to only one of test, result, random result or switch, selecting one clears the others
         test.set_callback({
             let mut result = result.clone();
+            let mut random = random.clone();
+            let mut switch = switch.clone();
+            let mut result_label = result_label.clone();
             move |x| {
                 if x.value() >= 0 {
-                    if result.value() >= 0 {
-                        result.set_value(-1);
-                    }
+                    result.set_value(-1);
+                    random.set_value(-1);
+                    switch.set_value(-1);
+                    result_label.set_label(tr("Result"));
                 }
             }
         });
+        let targets = Rc::new(RefCell::new(HashMap::new()));
         result.set_callback({
             let mut test = test.clone();
+            let mut random = random.clone();
+            let mut switch = switch.clone();
+            let mut result_label = result_label.clone();
+            let targets = targets.clone();
             move |x| {
                 if x.value() >= 0 {
-                    if test.value() >= 0 {
-                        test.set_value(-1);
-                    }
+                    test.set_value(-1);
+                    random.set_value(-1);
+                    switch.set_value(-1);
+                }
+                show_result_target(&mut result_label, &targets.borrow(), x.choice());
+            }
+        });
+        random.set_callback({
+            let mut test = test.clone();
+            let mut result = result.clone();
+            let mut switch = switch.clone();
+            let mut result_label = result_label.clone();
+            move |x| {
+                if x.value() >= 0 {
+                    test.set_value(-1);
+                    result.set_value(-1);
+                    switch.set_value(-1);
+                    result_label.set_label(tr("Result"));
+                }
+            }
+        });
+        switch.set_callback({
+            let mut test = test.clone();
+            let mut result = result.clone();
+            let mut random = random.clone();
+            let mut result_label = result_label.clone();
+            move |x| {
+                if x.value() >= 0 {
+                    test.set_value(-1);
+                    result.set_value(-1);
+                    random.set_value(-1);
+                    result_label.set_label(tr("Result"));
                 }
             }
         });
@@ -155,9 +237,16 @@ impl ChoiceEditor {
             test,
             condition,
             result,
+            random,
+            switch,
+            once,
+            shuffle,
             condition_label,
             test_label,
             result_label,
+            random_label,
+            switch_label,
+            targets,
         }
     }
     /// Hides controls
@@ -170,6 +259,11 @@ impl ChoiceEditor {
         self.test.hide();
         self.result_label.hide();
         self.result.hide();
+        self.random_label.hide();
+        self.random.hide();
+        self.switch_label.hide();
+        self.switch.hide();
+        self.once.hide();
         self.text.hide();
     }
     /// Displays controls
@@ -182,12 +276,32 @@ impl ChoiceEditor {
         self.test.show();
         self.result_label.show();
         self.result.show();
+        self.random_label.show();
+        self.random.show();
+        self.switch_label.show();
+        self.switch.show();
+        self.once.show();
         self.text.show();
     }
+    /// Loads the page flag for showing choices in random order
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        self.shuffle.set_checked(shuffle);
+    }
+    /// Tests if choices of the page are meant to be shown in random order
+    pub fn is_shuffled(&self) -> bool {
+        self.shuffle.is_checked()
+    }
     /// Clears and readds elements to dropdown menus, refreshing available choices
     ///
-    /// The function will attempt to reload previously selected choice
-    pub fn populate_dropdowns(&mut self, page: &Page) {
+    /// results: results of the page together with the shared results of the adventure
+    ///
+    /// Titles of pages are used to show where the chosen result leads
+    pub fn populate_dropdowns(
+        &mut self,
+        page: &Page,
+        results: &HashMap<String, StoryResult>,
+        pages: &HashMap<String, Page>,
+    ) {
         self.condition.clear();
         self.condition.add_choice(" ");
         page.conditions
@@ -196,16 +310,38 @@ impl ChoiceEditor {
         self.test.clear();
         page.tests.iter().for_each(|x| self.test.add_choice(x.0));
         self.result.clear();
-        page.results
-            .iter()
-            .for_each(|x| self.result.add_choice(x.0));
+        results.iter().for_each(|x| self.result.add_choice(x.0));
         self.result.add_choice(GAME_OVER_KEYWORD);
+        let mut targets = self.targets.borrow_mut();
+        targets.clear();
+        for (name, result) in results.iter() {
+            let title = match pages.get(&result.next_page) {
+                Some(p) if p.title.len() > 0 => p.title.clone(),
+                _ => result.next_page.clone(),
+            };
+            targets.insert(name.clone(), format!("{} {}", tr("leads to"), title));
+        }
+        targets.insert(GAME_OVER_KEYWORD.to_string(), tr("ends the adventure").to_string());
+        drop(targets);
+        self.random.clear();
+        page.randoms
+            .iter()
+            .for_each(|x| self.random.add_choice(x.0));
+        self.switch.clear();
+        page.switches
+            .iter()
+            .for_each(|x| self.switch.add_choice(x.0));
     }
     /// Refreshes dropdowns and selected choice
     ///
     /// This is used to load changes from other editors when going back to choice tab
-    pub fn refresh_dropdowns(&mut self, page: &Page) {
-        self.populate_dropdowns(page);
+    pub fn refresh_dropdowns(
+        &mut self,
+        page: &Page,
+        results: &HashMap<String, StoryResult>,
+        pages: &HashMap<String, Page>,
+    ) {
+        self.populate_dropdowns(page, results, pages);
         // reloading the previously selected choice
         let selected = self.selector.value();
         if selected > 0 {
@@ -283,27 +419,38 @@ impl ChoiceEditor {
         }
     }
     /// Event response that saves currently selected element to the list
+    ///
+    /// A choice using a condition the page doesn't have isn't saved until another condition is chosen for it
     pub fn save_choice(&self, choices: &mut Vec<Choice>, index: Option<usize>) {
         // determining the selected element
-        let choice = match index {
-            Some(v) => match choices.get_mut(v) {
-                Some(c) => c,
-                None => return,
-            },
+        let index = match index {
+            Some(v) => v,
             None => match self.selector.value() {
                 0 => return,
-                x => match choices.get_mut((x - 1) as usize) {
-                    Some(c) => c,
-                    None => return,
-                },
+                x => (x - 1) as usize,
             },
         };
-        // saving the data
-        choice.text = self.text.buffer().as_ref().unwrap().text();
-        choice.condition = match self.condition.choice() {
+        let choice = match choices.get_mut(index) {
+            Some(c) => c,
+            None => return,
+        };
+        // the list only has conditions of the page, one copied from another page can't be selected in it
+        let condition = match self.condition.choice() {
             Some(text) if text != " " => text,
-            _ => String::new(),
+            Some(_) => String::new(),
+            None => choice.condition.clone(),
         };
+        if condition.len() > 0 && self.condition.find_index(&condition) < 0 {
+            signal_error!(
+                "Choice {} could not be saved because the page doesn't have Condition {}, choose another condition for it",
+                index + 1,
+                condition
+            );
+            return;
+        }
+        // saving the data
+        choice.text = self.text.buffer().as_ref().unwrap().text();
+        choice.condition = condition;
         choice.test = match self.test.choice() {
             Some(text) => text,
             None => String::new(),
@@ -312,6 +459,15 @@ impl ChoiceEditor {
             Some(text) => text,
             None => String::new(),
         };
+        choice.random = match self.random.choice() {
+            Some(text) => text,
+            None => String::new(),
+        };
+        choice.switch = match self.switch.choice() {
+            Some(text) => text,
+            None => String::new(),
+        };
+        choice.once = self.once.is_checked();
     }
     /// Event response that loads a choice on index into UI
     pub fn load_choice(&mut self, choices: &Vec<Choice>, index: usize) {
@@ -323,6 +479,7 @@ impl ChoiceEditor {
             }
         };
         self.text.buffer().as_mut().unwrap().set_text(&choice.text);
+        self.once.set_checked(choice.once);
         if choice.condition.len() != 0 {
             let index = self.condition.find_index(&choice.condition);
             self.condition.set_value(index);
@@ -335,9 +492,35 @@ impl ChoiceEditor {
             self.test.set_value(index);
             self.result.set_value(-1);
             self.result.redraw();
+            self.random.set_value(-1);
+            self.random.redraw();
+            self.switch.set_value(-1);
+            self.switch.redraw();
+        } else if choice.random.len() != 0 {
+            let index = self.random.find_index(&choice.random);
+            self.random.set_value(index);
+            self.test.set_value(-1);
+            self.test.redraw();
+            self.result.set_value(-1);
+            self.result.redraw();
+            self.switch.set_value(-1);
+            self.switch.redraw();
+        } else if choice.switch.len() != 0 {
+            let index = self.switch.find_index(&choice.switch);
+            self.switch.set_value(index);
+            self.test.set_value(-1);
+            self.test.redraw();
+            self.result.set_value(-1);
+            self.result.redraw();
+            self.random.set_value(-1);
+            self.random.redraw();
         } else {
+            self.random.set_value(-1);
+            self.random.redraw();
             self.test.set_value(-1);
             self.test.redraw();
+            self.switch.set_value(-1);
+            self.switch.redraw();
 
             if choice.result.len() != 0 {
                 let index = self.result.find_index(&choice.result);
@@ -348,6 +531,12 @@ impl ChoiceEditor {
                 self.result.redraw();
             }
         }
+        let result = if choice.test.len() == 0 && choice.random.len() == 0 && choice.switch.len() == 0 {
+            self.result.choice()
+        } else {
+            None
+        };
+        show_result_target(&mut self.result_label, &self.targets.borrow(), result);
         self.show_controls();
     }
 }
@@ -17,6 +17,7 @@ use crate::{
     dialog::{ask_for_text, ask_to_confirm},
     editor::{variables::variable_receiver, highlight_color},
     file::signal_error,
+    lang::tr,
     widgets::find_item,
 };
 
@@ -38,7 +39,7 @@ pub struct ConditionEditor {
 impl ConditionEditor {
     /// Creates UI for editing Conditions
     pub fn new(area: Rect) -> Self {
-        let group = Group::new(area.x, area.y, area.w, area.h, "Conditions");
+        let group = Group::new(area.x, area.y, area.w, area.h, tr("Conditions"));
 
         let font_size = app::font_size();
 
@@ -55,6 +56,8 @@ impl ConditionEditor {
         let x_mod = x_add + w_butt;
         let x_rem = x_selector + w_selector - w_butt;
         let x_help = x_mod + w_butt * 2;
+        let x_copy = x_help + w_butt * 2;
+        let x_paste = x_copy + w_butt * 3;
 
         let marging_column = 20;
         let x_second_column = area.x + w_selector + marging_column;
@@ -68,26 +71,28 @@ impl ConditionEditor {
         let y_exp2 = y_comp + h_line * 2;
 
         let mut selector =
-            SelectBrowser::new(x_selector, y_selector, w_selector, h_selector, "Conditions");
+            SelectBrowser::new(x_selector, y_selector, w_selector, h_selector, tr("Conditions"));
         let mut add = Button::new(x_add, y_butt, w_butt, h_butt, "@+");
         let mut ren = Button::new(x_mod, y_butt, w_butt, h_butt, None);
         let mut rem = Button::new(x_rem, y_butt, w_butt, h_butt, None);
         let mut help = Button::new(x_help, y_butt, w_butt, h_butt, "?");
+        let mut copy = Button::new(x_copy, y_butt, w_butt * 3, h_butt, tr("Copy"));
+        let mut paste = Button::new(x_paste, y_butt, w_butt * 3, h_butt, tr("Paste"));
 
-        let name = Frame::new(x_second_column, y_name, w_second_column, h_line, "Name");
+        let name = Frame::new(x_second_column, y_name, w_second_column, h_line, tr("Name"));
         let mut expression_left = TextEditor::new(
             x_second_column,
             y_exp,
             w_second_column,
             h_line,
-            "Left side expression",
+            tr("Left side expression"),
         );
         let mut expression_right = TextEditor::new(
             x_second_column,
             y_exp2,
             w_second_column,
             h_line,
-            "Right side expression",
+            tr("Right side expression"),
         );
         let mut comparison = fltk::menu::Choice::new(
             x_second_column + w_second_column / 4,
@@ -128,6 +133,12 @@ impl ConditionEditor {
         add.emit(sender.clone(), emit!(Event::AddCondition));
         ren.emit(sender.clone(), emit!(Event::RenameCondition));
         rem.emit(sender.clone(), emit!(Event::RemoveCondition));
+        copy.emit(sender.clone(), emit!(Event::CopyCondition));
+        paste.emit(sender.clone(), emit!(Event::PasteCondition));
+        copy.set_label_size(font_size - 4);
+        paste.set_label_size(font_size - 4);
+        copy.set_tooltip(tr("Copies the selected Condition so it can be pasted into another page"));
+        paste.set_tooltip(tr("Pastes the copied Condition into this page"));
         help.emit(sender, help!("condition"));
         help.set_frame(fltk::enums::FrameType::RoundUpBox);
         help.set_color(highlight_color!());
@@ -269,6 +280,20 @@ impl ConditionEditor {
         self.selector.do_callback();
         conditions.insert(name, cond);
     }
+    /// Returns a copy of the selected condition
+    pub fn copy(&self, conditions: &HashMap<String, Condition>) -> Option<Condition> {
+        conditions.get(&self.selected()).cloned()
+    }
+    /// Adds a condition copied from another page into the collection and selects it
+    ///
+    /// The condition's name must not be already used in the collection
+    pub fn paste(&mut self, conditions: &mut HashMap<String, Condition>, condition: Condition) {
+        let name = condition.name.clone();
+        conditions.insert(name.clone(), condition);
+        self.selector.add(&name);
+        self.selector.select(self.selector.size());
+        self.selector.do_callback();
+    }
     /// Event rezponse that renames selected condition
     ///
     /// Conditions are page specific and this function will update any links to reflect the new condition name
@@ -1,22 +1,78 @@
+use std::collections::HashMap;
+
 use fltk::{
-    app, browser::SelectBrowser, button::Button, draw::Rect, group::Group, image::SvgImage,
-    prelude::*
+    app, browser::MultiBrowser, button::Button, draw::Rect, group::Group, image::SvgImage,
+    misc::Progress, prelude::*,
+    tree::{Tree, TreeReason},
 };
 
 use crate::{
+    adventure::{Page, StoryResult},
     icons::{BIN_ICON, GEAR_ICON, STAR_ICON},
-    widgets::find_item,
+    lang::tr,
+    widgets::{escape_menu_text, escape_tree_text},
 };
 
 use super::{emit, help, Event, highlight_color};
 
+const ALL_PAGES: &str = "All Pages";
+/// Separates page name from its summary in the page list
+const SUMMARY_SEPARATOR: char = '\t';
+/// Browser formatting that draws the summary of an incomplete page in red
+const INCOMPLETE_FORMAT: &str = "@C1";
+
+/// Returns page name from a line of the page list
+fn line_page(line: &str) -> String {
+    line.split(SUMMARY_SEPARATOR).next().unwrap_or_default().to_string()
+}
+/// Creates a compact description of how many elements the page has, like (3 choices, 1 test)
+///
+/// Pages that aren't playable yet are marked as incomplete, results: shared results of the adventure
+fn page_summary(page: &Page, results: &HashMap<String, StoryResult>) -> String {
+    let counts = [
+        (page.choices.len(), "choice", "choices"),
+        (page.conditions.len(), "condition", "conditions"),
+        (page.tests.len(), "test", "tests"),
+        (page.results.len(), "result", "results"),
+        (page.randoms.len(), "random", "randoms"),
+        (page.switches.len(), "switch", "switches"),
+    ];
+    let parts: Vec<String> = counts
+        .iter()
+        .filter(|x| x.0 > 0)
+        .map(|x| match x.0 {
+            1 => format!("1 {}", x.1),
+            n => format!("{} {}", n, x.2),
+        })
+        .collect();
+    let summary = if parts.len() > 0 {
+        format!("({})", parts.join(", "))
+    } else {
+        "(empty)".to_string()
+    };
+    if page.is_playable_with(results) {
+        summary
+    } else {
+        format!("{}{} incomplete", INCOMPLETE_FORMAT, summary)
+    }
+}
+
 /// Displays the list of files in adventure
 ///
 /// It displays both adventure metadata and each page
 /// It also has buttons for adding and removing pages,
 /// or deleting the whole adventure, adding a new one or loading existing
+/// Pages can also be viewed as an outline where they're grouped by their tags
+/// Several pages can be selected in the list with ctrl or shift to remove them together
 pub struct FileList {
-    page_list: SelectBrowser,
+    page_list: MultiBrowser,
+    /// Page marked with a star as the starting page
+    start: String,
+    outline: Tree,
+    tag_filter: fltk::menu::Choice,
+    /// Tags offered by the filter, in the order of its items after the one showing all pages
+    tags: Vec<String>,
+    progress: Progress,
 }
 
 impl FileList {
@@ -28,39 +84,91 @@ impl FileList {
         let x_column_1 = area.x + 5;
         let x_column_2 = area.x + area.w / 2 + 5;
         let w_whole = area.w - 10;
-        let w_column = area.w / 2 - 10;
+        let w_quarter = area.w / 4 - 5;
+        let x_export = x_column_2;
+        let x_import = x_export + w_quarter;
         let h_line = font_size + font_size / 2;
         let y_first_line = area.y;
         let y_second_line = y_first_line + h_line + 2;
-        let y_third_line = y_second_line + h_line + 2;
-        let h_selector = area.h - h_line * 3 - 4;
+        let y_filter = y_second_line + h_line + 2;
+        let y_third_line = y_filter + h_line + 2;
+        let h_selector = area.h - h_line * 4 - 6;
         let y_controls = y_third_line + h_selector;
         let w_controls = font_size;
         let h_controls = font_size;
         let x_add = x_column_1;
         let x_rename = x_add + w_controls;
         let x_help = x_rename + w_controls * 2;
+        let x_outline = x_help + w_controls * 2;
+        let x_restore = x_outline + w_controls * 2;
+        let x_graph = x_restore + w_controls * 2;
+        let x_play = x_graph + w_controls * 2;
         let x_remove = x_column_1 + w_whole - w_controls;
         let x_start = x_remove - w_controls;
 
-        let mut butt_bac = Button::new(x_column_1, y_first_line, w_column, h_line, "Return");
-        let mut butt_sav = Button::new(x_column_2, y_first_line, w_column, h_line, "Save");
+        let mut butt_bac = Button::new(x_column_1, y_first_line, w_quarter, h_line, tr("Return"));
+        let mut butt_sav = Button::new(
+            x_column_1 + w_quarter,
+            y_first_line,
+            w_quarter,
+            h_line,
+            tr("Save"),
+        );
+        let mut butt_exp = Button::new(x_export, y_first_line, w_quarter, h_line, tr("Export"));
+        let mut butt_imp = Button::new(x_import, y_first_line, w_quarter, h_line, tr("Import"));
         let mut butt_add = Button::new(x_add, y_controls, w_controls, h_controls, "@+");
         let mut butt_rem = Button::new(x_remove, y_controls, w_controls, h_controls, None);
         let mut butt_ren = Button::new(x_rename, y_controls, w_controls, h_controls, None);
         let mut butt_str = Button::new(x_start, y_controls, w_controls, h_controls, None);
         let mut help = Button::new(x_help, y_controls, w_controls, h_controls, "?");
+        let mut butt_out = Button::new(x_outline, y_controls, w_controls, h_controls, "@menu");
+        let mut butt_res = Button::new(x_restore, y_controls, w_controls, h_controls, "@undo");
+        let mut butt_graph = Button::new(x_graph, y_controls, w_controls, h_controls, "@-->");
+        let mut butt_play = Button::new(x_play, y_controls, w_controls, h_controls, "@>");
+        let w_clone = font_size * 4;
         let mut adventure_meta = Button::new(
             x_column_1,
             y_second_line,
-            w_whole,
+            w_whole - w_clone * 3 - 15,
+            h_line,
+            tr("Adventure Metadata"),
+        );
+        let mut butt_shared = Button::new(
+            x_column_1 + w_whole - w_clone * 3 - 10,
+            y_second_line,
+            w_clone,
+            h_line,
+            tr("Results"),
+        );
+        let mut butt_check = Button::new(
+            x_column_1 + w_whole - w_clone * 2 - 5,
+            y_second_line,
+            w_clone,
             h_line,
-            "Adventure Metadata",
+            tr("Check"),
         );
+        let mut butt_clone = Button::new(
+            x_column_1 + w_whole - w_clone,
+            y_second_line,
+            w_clone,
+            h_line,
+            tr("Copy"),
+        );
+        let mut tag_filter = fltk::menu::Choice::new(x_column_1, y_filter, w_whole, h_line, None);
         let mut page_list =
-            SelectBrowser::new(x_column_1, y_third_line, w_whole, h_selector, "Pages");
+            MultiBrowser::new(x_column_1, y_third_line, w_whole, h_selector, tr("Pages"));
+        page_list.set_column_char(SUMMARY_SEPARATOR);
+        page_list.set_column_widths(&[w_whole / 2, w_whole / 2]);
+        let mut outline = Tree::new(x_column_1, y_third_line, w_whole, h_selector, None);
+        let mut progress = Progress::new(x_column_1, y_third_line, w_whole, h_line, None);
         group.end();
 
+        progress.set_selection_color(highlight_color!());
+        progress.hide();
+
+        outline.set_show_root(false);
+        outline.hide();
+
         let (s, _r) = app::channel();
 
         let mut gear = SvgImage::from_data(GEAR_ICON).unwrap();
@@ -74,11 +182,17 @@ impl FileList {
         butt_ren.set_image(Some(gear));
         butt_str.set_image(Some(star));
 
-        butt_bac.emit(s.clone(), crate::game::Event::DisplayMainMenu);
+        butt_bac.emit(s.clone(), emit!(Event::Return));
         butt_sav.emit(s.clone(), emit!(Event::Save));
         butt_add.emit(s.clone(), emit!(Event::AddPage));
         butt_rem.emit(s.clone(), emit!(Event::RemovePage));
         butt_ren.emit(s.clone(), emit!(Event::RenamePage));
+        butt_res.emit(s.clone(), emit!(Event::RestorePage));
+        butt_res.set_tooltip(tr("Restore the last removed pages"));
+        butt_graph.emit(s.clone(), emit!(Event::ShowGraph));
+        butt_graph.set_tooltip(tr("Show how pages link to each other"));
+        butt_play.emit(s.clone(), emit!(Event::Playtest));
+        butt_play.set_tooltip(tr("Play the adventure from the open page, without saving it"));
         help.emit(s.clone(), help!("pages-explorer"));
         help.set_color(highlight_color!());
         help.set_frame(fltk::enums::FrameType::RoundUpBox);
@@ -86,56 +200,241 @@ impl FileList {
             let fl = page_list.clone();
             let s = s.clone();
             move |_| {
-                if let Some(page) = fl.selected_text() {
-                    s.send(emit!(Event::SelectStartingPage(page)));
+                if let Some(line) = fl.selected_text() {
+                    s.send(emit!(Event::SelectStartingPage(line_page(&line))));
+                }
+            }
+        });
+        butt_out.set_tooltip(tr("Toggle outline"));
+        butt_out.set_callback({
+            let mut list = page_list.clone();
+            let mut outline = outline.clone();
+            move |_| {
+                if outline.visible() {
+                    outline.hide();
+                    list.show();
+                } else {
+                    list.hide();
+                    outline.show();
                 }
             }
         });
         adventure_meta.emit(s.clone(), emit!(Event::OpenMeta));
+        butt_shared.emit(s.clone(), emit!(Event::OpenSharedResults));
+        butt_shared.set_tooltip(tr("Edit results shared by all pages of the adventure"));
+        butt_clone.emit(s.clone(), emit!(Event::CloneAdventure));
+        butt_clone.set_tooltip(tr("Saves a copy of the adventure under a new title"));
+        butt_check.emit(s.clone(), emit!(Event::CheckExpressions));
+        butt_check.set_tooltip(tr("Looks for expressions that can't be evaluated in all pages"));
+        butt_exp.emit(s.clone(), emit!(Event::ExportAdventure));
+        butt_exp.set_tooltip(tr("Saves the adventure into a zip archive that can be shared"));
+        butt_imp.emit(s.clone(), emit!(Event::ImportAdventure));
+        butt_imp.set_tooltip(tr("Adds an adventure shared as a zip archive to your adventures"));
+        tag_filter.set_tooltip(tr("Filter pages by tag"));
+        tag_filter.add_choice(&escape_menu_text(tr(ALL_PAGES)));
+        tag_filter.set_value(0);
+        tag_filter.emit(s.clone(), emit!(Event::FilterPages));
+        outline.set_callback({
+            let s = s.clone();
+            move |t| {
+                if t.callback_reason() != TreeReason::Selected {
+                    return;
+                }
+                // items with children are tags, only leaves are pages
+                if let Some(item) = t.callback_item() {
+                    if item.has_children() {
+                        return;
+                    }
+                    if let Some(page) = item.label() {
+                        s.send(emit!(Event::GoToPage(page)));
+                    }
+                }
+            }
+        });
         page_list.set_callback(move |x| {
-            if let Some(text) = x.selected_text() {
-                s.send(emit!(Event::OpenPage(text)));
+            // clicking with ctrl can also unselect a line, which shouldn't open it
+            let line = x.value();
+            if line > 0 && x.selected(line) {
+                if let Some(text) = x.text(line) {
+                    s.send(emit!(Event::OpenPage(line_page(&text))));
+                }
             }
         });
 
-        Self { page_list }
+        Self {
+            page_list,
+            start: String::new(),
+            outline,
+            tag_filter,
+            tags: Vec::new(),
+            progress,
+        }
+    }
+    /// Shows how many pages have been loaded, the indicator is hidden once all pages are loaded
+    pub fn set_progress(&mut self, loaded: usize, total: usize) {
+        if loaded >= total {
+            self.progress.hide();
+            return;
+        }
+        self.progress.set_maximum(total as f64);
+        self.progress.set_value(loaded as f64);
+        self.progress
+            .set_label(&format!("{} {}/{}", tr("Loading pages"), loaded, total));
+        self.progress.show();
     }
-    /// Fills the selection widget with page names
-    pub fn populate_pages(&mut self, pages: &Vec<String>) {
+    /// Fills the selection widget with page names and summaries of their contents
+    pub fn populate_pages(&mut self, pages: &Vec<(&String, &Page)>, results: &HashMap<String, StoryResult>) {
         self.page_list.clear();
-        for text in pages {
-            self.page_list.add(&text);
+        for page in pages {
+            self.page_list.add(&format!(
+                "{}{}{}",
+                page.0,
+                SUMMARY_SEPARATOR,
+                page_summary(page.1, results)
+            ));
         }
     }
-    /// Removes selected line from the file list
-    pub fn remove_line(&mut self) {
-        let selection = self.page_list.value();
-        if selection > 0 {
-            self.page_list.remove(selection);
+    /// Updates summaries of the listed pages to reflect changes in their contents
+    pub fn update_summaries(&mut self, pages: &HashMap<String, Page>, results: &HashMap<String, StoryResult>) {
+        let mut n = 1;
+        while let Some(line) = self.page_list.text(n) {
+            let name = line_page(&line);
+            if let Some(page) = pages.get(&name) {
+                let text = format!("{}{}{}", name, SUMMARY_SEPARATOR, page_summary(page, results));
+                if text != line {
+                    self.page_list.set_text(n, &text);
+                }
+            }
+            n += 1;
         }
     }
+    /// Returns index of the line showing the page, or None if the page isn't listed
+    fn find_page(&self, page: &str) -> Option<i32> {
+        let mut n = 1;
+        while let Some(line) = self.page_list.text(n) {
+            if line_page(&line) == page {
+                return Some(n);
+            }
+            n += 1;
+        }
+        None
+    }
+    /// Fills the outline with page names grouped by their tags
+    ///
+    /// Pages with several tags are listed under each of them, pages without tags are placed at the root of the outline
+    pub fn populate_outline(&mut self, pages: &HashMap<String, Page>) {
+        self.outline.clear();
+        let mut keys: Vec<&String> = pages.keys().collect();
+        keys.sort();
+        for key in keys {
+            let name = escape_tree_text(key);
+            for tag in pages[key].tags.iter() {
+                self.outline.add(&format!("{}/{}", escape_tree_text(tag), name));
+            }
+            if pages[key].tags.len() == 0 {
+                self.outline.add(&name);
+            }
+        }
+        self.outline.redraw();
+    }
+    /// Fills the tag filter with all tags used in the pages
+    ///
+    /// Selected tag is kept if it's still used, otherwise the filter is reset to show all pages.
+    /// Returns true if the filter has changed
+    pub fn populate_tags(&mut self, pages: &HashMap<String, Page>) -> bool {
+        let selected = self.selected_tag();
+        let mut tags: Vec<&String> = pages.iter().flat_map(|x| x.1.tags.iter()).collect();
+        tags.sort();
+        tags.dedup();
+
+        self.tag_filter.clear();
+        self.tag_filter.add_choice(&escape_menu_text(tr(ALL_PAGES)));
+        self.tag_filter.set_value(0);
+        for (i, tag) in tags.iter().enumerate() {
+            self.tag_filter.add_choice(&escape_menu_text(tag));
+            if selected.as_ref() == Some(tag) {
+                self.tag_filter.set_value(i as i32 + 1);
+            }
+        }
+        self.tags = tags.into_iter().cloned().collect();
+        selected != self.selected_tag()
+    }
+    /// Returns the tag pages are filtered by, or None if all pages are shown
+    pub fn selected_tag(&self) -> Option<String> {
+        match self.tag_filter.value() {
+            i if i > 0 => self.tags.get(i as usize - 1).cloned(),
+            _ => None,
+        }
+    }
+    /// Returns names of all selected pages in the order they're listed
+    pub fn selected_pages(&self) -> Vec<String> {
+        (1..=self.page_list.size())
+            .filter(|x| self.page_list.selected(*x))
+            .filter_map(|x| self.page_list.text(x))
+            .map(|x| line_page(&x))
+            .collect()
+    }
+    /// Removes lines of the pages from the file list
+    pub fn remove_lines(&mut self, pages: &Vec<String>) {
+        for page in pages {
+            if let Some(x) = self.find_page(page) {
+                self.page_list.remove(x);
+            }
+        }
+    }
+    /// Selects the line, unselecting all other lines
+    fn select_only(&mut self, line: i32) {
+        // the browser can't unselect a line, so other selected lines are replaced with new ones
+        let mut replaced = false;
+        for x in 1..=self.page_list.size() {
+            if x != line && self.page_list.selected(x) {
+                let text = self.page_list.text(x).unwrap_or_default();
+                self.page_list.remove(x);
+                self.page_list.insert(x, &text);
+                replaced = true;
+            }
+        }
+        if replaced {
+            // replaced line may have been the one marked with a star
+            let start = self.start.clone();
+            self.mark_line("", &start);
+        }
+        self.page_list.select(line);
+    }
     /// Marks a selected line with a star, taking the star away from the previous line
     pub fn mark_line(&mut self, previous: &str, new: &str) {
-        if let Some(x) = find_item(&self.page_list, previous) {
+        self.start = new.to_string();
+        if let Some(x) = self.find_page(previous) {
             self.page_list.set_icon::<SvgImage>(x, None);
         }
-        if let Some(x) = find_item(&self.page_list, new) {
+        if let Some(x) = self.find_page(new) {
             let mut star = SvgImage::from_data(STAR_ICON).unwrap();
             let font_size = app::font_size();
             star.scale(font_size, font_size, false, true);
             self.page_list.set_icon(x, Some(star));
         }
     }
-    /// Renames the selected line to a new name
+    /// Renames the selected line to a new name, keeping its summary
     pub fn rename_selected(&mut self, new_name: &str) {
         let x = self.page_list.value();
         if x > 0 {
-            self.page_list.set_text(x, new_name);
+            let line = self.page_list.text(x).unwrap_or_default();
+            let text = match line.split_once(SUMMARY_SEPARATOR) {
+                Some((_, summary)) => format!("{}{}{}", new_name, SUMMARY_SEPARATOR, summary),
+                None => new_name.to_string(),
+            };
+            self.page_list.set_text(x, &text);
+        }
+    }
+    /// Selects a line by its page name without triggering the callback
+    pub fn select_line(&mut self, text: &str) {
+        if let Some(x) = self.find_page(text) {
+            self.select_only(x);
         }
     }
     ///Adds a new line and selects it
     pub fn add_line(&mut self, text: &str) {
         self.page_list.add(text);
-        self.page_list.select(self.page_list.size());
+        self.select_only(self.page_list.size());
     }
 }
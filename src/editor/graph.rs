@@ -0,0 +1,278 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use fltk::{
+    app,
+    draw::{self, Rect},
+    enums::{Align, Color, Event as FltkEvent, FrameType},
+    group::Scroll,
+    prelude::*,
+    widget::Widget,
+    window::Window,
+};
+
+use crate::{
+    adventure::{Page, StoryResult},
+    lang::tr,
+};
+
+use super::{emit, highlight_color, Event};
+
+const NODE_WIDTH: i32 = 150;
+const NODE_HEIGHT: i32 = 30;
+/// Horizontal distance between left edges of neighbouring layers
+const LAYER_SPACING: i32 = 220;
+/// Vertical distance between top edges of nodes in the same layer
+const NODE_SPACING: i32 = 50;
+const MARGIN: i32 = 20;
+/// Length of the arrow head drawn at the end of a link
+const ARROW_SIZE: f64 = 8.0;
+
+/// Names of pages the page leads to through its results, shared results it uses, random outcomes and story links
+fn page_links<'a>(page: &'a Page, shared: &'a HashMap<String, StoryResult>) -> Vec<&'a str> {
+    let mut links: Vec<&str> = page
+        .results
+        .values()
+        .map(|x| x.next_page.as_str())
+        .chain(page.used_shared_results(shared).map(|x| x.next_page.as_str()))
+        .chain(
+            page.randoms
+                .values()
+                .flat_map(|x| x.outcomes.iter().map(|o| o.0.as_str())),
+        )
+        .chain(page.story_links())
+        .collect();
+    links.sort();
+    links.dedup();
+    links
+}
+/// Splits pages into layers by the smallest amount of links it takes to reach them from the starting page
+///
+/// Pages in each layer are sorted by name, pages that can't be reached aren't included
+fn layer_pages(
+    pages: &HashMap<String, Page>,
+    shared: &HashMap<String, StoryResult>,
+    start: &str,
+) -> Vec<Vec<String>> {
+    let mut layers: Vec<Vec<String>> = Vec::new();
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    if pages.contains_key(start) {
+        visited.insert(start.to_string());
+        queue.push_back((start.to_string(), 0));
+    }
+    while let Some((name, depth)) = queue.pop_front() {
+        if layers.len() <= depth {
+            layers.push(Vec::new());
+        }
+        for link in page_links(&pages[&name], shared) {
+            if pages.contains_key(link) && visited.insert(link.to_string()) {
+                queue.push_back((link.to_string(), depth + 1));
+            }
+        }
+        layers[depth].push(name);
+    }
+    layers.iter_mut().for_each(|x| x.sort());
+    layers
+}
+/// Draws an arrow from one point to another, with its head at the end point
+fn draw_arrow(from: (i32, i32), to: (i32, i32)) {
+    draw::draw_line(from.0, from.1, to.0, to.1);
+    let angle = ((to.1 - from.1) as f64).atan2((to.0 - from.0) as f64);
+    let side = |turn: f64| {
+        (
+            to.0 - (ARROW_SIZE * (angle + turn).cos()) as i32,
+            to.1 - (ARROW_SIZE * (angle + turn).sin()) as i32,
+        )
+    };
+    let (left, right) = (side(0.4), side(-0.4));
+    draw::draw_polygon(to.0, to.1, left.0, left.1, right.0, right.1);
+}
+
+/// Window showing pages of the adventure as boxes connected by the links between them
+///
+/// The graph is a snapshot of the pages at the time it was opened, clicking a page opens it in the editor
+pub struct GraphWindow {
+    window: Window,
+}
+
+impl GraphWindow {
+    /// Creates and shows the graph of the pages, laid out in columns by distance from the starting page
+    ///
+    /// shared: results shared by all pages of the adventure
+    pub fn new(pages: &HashMap<String, Page>, shared: &HashMap<String, StoryResult>, start: &str) -> Self {
+        let mut layers = layer_pages(pages, shared, start);
+        // pages that can't be reached are shown in one more column at the end
+        let reachable = layers.len();
+        let mut unreachable: Vec<String> = pages
+            .keys()
+            .filter(|x| layers.iter().all(|l| l.contains(x) == false))
+            .cloned()
+            .collect();
+        if unreachable.len() > 0 {
+            unreachable.sort();
+            layers.push(unreachable);
+        }
+
+        // positions of nodes relative to the canvas
+        let mut nodes = HashMap::new();
+        for (column, layer) in layers.iter().enumerate() {
+            for (row, name) in layer.iter().enumerate() {
+                let x = MARGIN + column as i32 * LAYER_SPACING;
+                let y = MARGIN + row as i32 * NODE_SPACING;
+                nodes.insert(name.clone(), (Rect::new(x, y, NODE_WIDTH, NODE_HEIGHT), column));
+            }
+        }
+        let mut edges = Vec::new();
+        for (name, page) in pages.iter() {
+            for link in page_links(page, shared) {
+                if link != name.as_str() && nodes.contains_key(link) {
+                    edges.push((name.clone(), link.to_string()));
+                }
+            }
+        }
+        let w_canvas = MARGIN * 2 + (layers.len() as i32 - 1).max(0) * LAYER_SPACING + NODE_WIDTH;
+        let h_canvas = MARGIN * 2
+            + (layers.iter().map(|x| x.len()).max().unwrap_or(1) as i32 - 1).max(0) * NODE_SPACING
+            + NODE_HEIGHT;
+
+        let mut window = Window::default()
+            .with_size(800, 600)
+            .with_label(tr("Page Graph"));
+        let scroll = Scroll::new(0, 0, 800, 600, None);
+        let mut canvas = Widget::new(0, 0, w_canvas.max(800), h_canvas.max(600), None);
+        scroll.end();
+        window.end();
+        window.make_resizable(true);
+
+        canvas.draw({
+            let nodes = nodes.clone();
+            let start = start.to_string();
+            move |w| {
+                draw::draw_rect_fill(w.x(), w.y(), w.w(), w.h(), Color::Background2);
+                draw::set_draw_color(Color::Dark3);
+                for (from, to) in edges.iter() {
+                    let (a, b) = (&nodes[from].0, &nodes[to].0);
+                    let from = (w.x() + a.x + a.w, w.y() + a.y + a.h / 2);
+                    let to = (w.x() + b.x, w.y() + b.y + b.h / 2);
+                    draw_arrow(from, to);
+                }
+                draw::set_font(draw::font(), app::font_size());
+                for (name, (rect, column)) in nodes.iter() {
+                    let color = if *name == start {
+                        highlight_color!()
+                    } else if *column >= reachable {
+                        // pages the player can't get to
+                        Color::Light1
+                    } else {
+                        Color::Background
+                    };
+                    let (x, y) = (w.x() + rect.x, w.y() + rect.y);
+                    draw::draw_box(FrameType::UpBox, x, y, rect.w, rect.h, color);
+                    draw::set_draw_color(Color::Foreground);
+                    draw::push_clip(x + 2, y, rect.w - 4, rect.h);
+                    draw::draw_text2(name, x + 4, y, rect.w - 8, rect.h, Align::Left | Align::Inside);
+                    draw::pop_clip();
+                }
+            }
+        });
+        canvas.handle(move |w, ev| match ev {
+            FltkEvent::Push => {
+                let (x, y) = (app::event_x() - w.x(), app::event_y() - w.y());
+                let hit = nodes.iter().find(|(_, (r, _))| {
+                    x >= r.x && x < r.x + r.w && y >= r.y && y < r.y + r.h
+                });
+                if let Some((name, _)) = hit {
+                    let (s, _r) = app::channel();
+                    s.send(emit!(Event::GoToPage(name.clone())));
+                }
+                true
+            }
+            _ => false,
+        });
+        canvas.set_tooltip(tr(
+            "Pages connected by results and random outcomes, click a page to open it",
+        ));
+        window.show();
+
+        GraphWindow { window }
+    }
+    /// Closes the graph window
+    pub fn close(&mut self) {
+        self.window.hide();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::adventure::{Choice, Page, RandomResult, StoryResult};
+
+    use super::layer_pages;
+
+    #[test]
+    fn page_layers() {
+        let page = |links: &[&str]| {
+            let mut page = Page::default();
+            for link in links {
+                page.results.insert(
+                    link.to_string(),
+                    StoryResult {
+                        name: link.to_string(),
+                        next_page: link.to_string(),
+                        ..Default::default()
+                    },
+                );
+            }
+            page
+        };
+        let mut pages = HashMap::new();
+        pages.insert("intro".to_string(), page(&["cave", "forest", "game over"]));
+        pages.insert("cave".to_string(), page(&["intro", "lake"]));
+        let mut forest = page(&[]);
+        forest.randoms.insert(
+            "lost".to_string(),
+            RandomResult {
+                name: "lost".to_string(),
+                outcomes: vec![("lake".to_string(), 1), ("forest".to_string(), 1)],
+            },
+        );
+        pages.insert("forest".to_string(), forest);
+        pages.insert("lake".to_string(), page(&[]));
+        pages.insert("secret".to_string(), page(&["intro"]));
+        pages.insert("attic".to_string(), page(&[]));
+
+        let mut shared = HashMap::new();
+        let layers = layer_pages(&pages, &shared, "intro");
+        assert_eq!(
+            layers,
+            vec![
+                vec!["intro".to_string()],
+                vec!["cave".to_string(), "forest".to_string()],
+                vec!["lake".to_string()],
+            ]
+        );
+        assert!(layer_pages(&pages, &shared, "missing").is_empty());
+
+        // the lake leads to the attic through a shared result
+        shared.insert(
+            "climb".to_string(),
+            StoryResult {
+                name: "climb".to_string(),
+                next_page: "attic".to_string(),
+                ..Default::default()
+            },
+        );
+        pages.get_mut("lake").unwrap().choices.push(Choice {
+            result: "climb".to_string(),
+            ..Default::default()
+        });
+        let layers = layer_pages(&pages, &shared, "intro");
+        assert_eq!(layers.last(), Some(&vec!["attic".to_string()]));
+
+        // story links lead to pages as well
+        pages.get_mut("attic").unwrap().story = "A hidden [[ secret |door]].".to_string();
+        let layers = layer_pages(&pages, &shared, "intro");
+        assert_eq!(layers.last(), Some(&vec!["secret".to_string()]));
+    }
+}
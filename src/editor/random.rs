@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use fltk::{
+    app,
+    browser::SelectBrowser,
+    button::Button,
+    draw::Rect,
+    frame::Frame,
+    group::Group,
+    image::SvgImage,
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+};
+
+use crate::{
+    adventure::{Page, RandomResult},
+    dialog::{ask_for_text, ask_to_confirm},
+    editor::highlight_color,
+    file::signal_error,
+    icons::{BIN_ICON, GEAR_ICON},
+    lang::tr,
+    widgets::find_item,
+};
+
+use super::{emit, help, Event};
+
+/// Random result editor
+///
+/// Lists random results by name
+/// Each random result is edited as a list of pages and their weights, one page per line
+pub struct RandomEditor {
+    selector: SelectBrowser,
+    name: Frame,
+    outcomes: TextEditor,
+}
+
+impl RandomEditor {
+    /// Creates UI for editing random results
+    pub fn new(area: Rect) -> Self {
+        let group = Group::new(area.x, area.y, area.w, area.h, tr("Random"));
+
+        let font_size = app::font_size();
+
+        let x_selector = area.x;
+        let y_selector = area.y;
+        let w_selector = area.w / 3;
+        let h_selector = area.h - font_size;
+
+        let y_butt = y_selector + h_selector;
+        let w_butt = font_size;
+        let h_butt = font_size;
+
+        let x_add = x_selector;
+        let x_ren = x_add + w_butt;
+        let x_rem = x_selector + w_selector - w_butt;
+        let x_help = x_ren + w_butt * 2;
+
+        let column_margin = 20;
+        let x_second_column = area.x + w_selector + column_margin;
+        let w_second_column = area.w - w_selector - column_margin * 2;
+
+        let h_line = font_size + font_size / 2;
+
+        let y_name = y_selector + font_size;
+        let y_outcomes = y_name + h_line * 2;
+        let h_outcomes = area.h - y_outcomes + area.y - font_size * 2;
+
+        let mut selector =
+            SelectBrowser::new(x_selector, y_selector, w_selector, h_selector, tr("Random"));
+        let mut add = Button::new(x_add, y_butt, w_butt, h_butt, "@+");
+        let mut ren = Button::new(x_ren, y_butt, w_butt, h_butt, None);
+        let mut rem = Button::new(x_rem, y_butt, w_butt, h_butt, None);
+        let mut help = Button::new(x_help, y_butt, w_butt, h_butt, "?");
+
+        let name = Frame::new(x_second_column, y_name, w_second_column, h_line, tr("Name"));
+        let mut outcomes = TextEditor::new(
+            x_second_column,
+            y_outcomes,
+            w_second_column,
+            h_outcomes,
+            tr("Pages and their weights"),
+        );
+        group.end();
+
+        let mut gear = SvgImage::from_data(GEAR_ICON).unwrap();
+        let mut bin = SvgImage::from_data(BIN_ICON).unwrap();
+        gear.scale(w_butt, h_butt, false, true);
+        bin.scale(w_butt, h_butt, false, true);
+        ren.set_image(Some(gear));
+        rem.set_image(Some(bin));
+
+        let (sender, _r) = app::channel();
+
+        selector.set_callback({
+            let sender = sender.clone();
+            let mut selected = 0;
+            move |x| {
+                let new = x.value();
+                if new != selected {
+                    if selected > 0 {
+                        if let Some(last) = x.text(selected) {
+                            sender.send(emit!(Event::SaveRandom(Some(last))));
+                        }
+                    }
+                    if let Some(new_s) = x.selected_text() {
+                        sender.send(emit!(Event::LoadRandom(new_s)));
+                    }
+                    selected = new;
+                }
+            }
+        });
+        add.emit(sender.clone(), emit!(Event::AddRandom));
+        ren.emit(sender.clone(), emit!(Event::RenameRandom));
+        rem.emit(sender.clone(), emit!(Event::RemoveRandom));
+        help.emit(sender, help!("random"));
+        help.set_frame(fltk::enums::FrameType::RoundUpBox);
+        help.set_color(highlight_color!());
+
+        outcomes.set_buffer(TextBuffer::default());
+        outcomes.set_tooltip(tr("One page per line, followed by ; and its weight, for example: forest; 3"));
+
+        Self {
+            selector,
+            name,
+            outcomes,
+        }
+    }
+    /// Returns name of the loaded random result, or empty string if nothing is loaded
+    fn selected(&self) -> String {
+        if let Some(t) = self.selector.selected_text() {
+            return t;
+        }
+        String::new()
+    }
+    /// Loads a random result into the editor
+    fn load_ui(&mut self, random: &RandomResult) {
+        self.name.set_label(&random.name);
+        let text: Vec<String> = random
+            .outcomes
+            .iter()
+            .map(|x| format!("{}; {}", x.0, x.1))
+            .collect();
+        self.outcomes
+            .buffer()
+            .as_mut()
+            .unwrap()
+            .set_text(&text.join("\n"));
+        if let Some(n) = find_item(&self.selector, &random.name) {
+            self.selector.select(n);
+            self.selector.do_callback();
+        }
+        self.show_controls();
+    }
+    /// Fills the selector with a new set of random results
+    ///
+    /// The old entries are removed and the first of the new entries is loaded if there's any
+    pub fn populate(&mut self, randoms: &HashMap<String, RandomResult>) {
+        self.selector.clear();
+        self.selector.select(0);
+        self.selector.do_callback();
+        let mut names: Vec<&String> = randoms.keys().collect();
+        names.sort();
+        names.iter().for_each(|x| self.selector.add(x));
+        match names.first() {
+            Some(n) => self.load_ui(&randoms[*n]),
+            None => self.hide_controls(),
+        }
+    }
+    /// Shows the part of editor responsible for customizing random result
+    fn show_controls(&mut self) {
+        self.name.show();
+        self.outcomes.show();
+    }
+    /// Hides the part of editor responsible for customizing random result
+    fn hide_controls(&mut self) {
+        self.name.hide();
+        self.outcomes.hide();
+        self.name.set_label("");
+        self.outcomes.buffer().as_mut().unwrap().set_text("");
+    }
+    /// Event response that adds a new random result to the page
+    ///
+    /// The new random result leads back to the page it's created in until it's edited
+    pub fn add(&mut self, randoms: &mut HashMap<String, RandomResult>, page_name: &String) {
+        let name = match ask_for_text("Insert name for the new Random Result") {
+            Some(n) if n.len() > 0 => n,
+            _ => return,
+        };
+        if randoms.contains_key(&name) {
+            signal_error!("Cannot add {} because it already exists!", name);
+            return;
+        }
+        let random = RandomResult {
+            name: name.clone(),
+            outcomes: vec![(page_name.clone(), 1)],
+        };
+        randoms.insert(name.clone(), random);
+        self.selector.add(&name);
+        self.selector.select(self.selector.size());
+        self.selector.do_callback();
+    }
+    /// Event response that renames selected random result
+    ///
+    /// It also updates the name in choices of the page
+    pub fn rename(&mut self, page: &mut Page) {
+        let selected = self.selected();
+        let name = match ask_for_text(&format!("Insert new name for {} Random Result", &selected)) {
+            Some(n) if n.len() > 0 => n,
+            _ => return,
+        };
+        if page.randoms.contains_key(&name) {
+            signal_error!("Cannot rename to {} because it already exists!", name);
+            return;
+        }
+
+        if let Some(mut random) = page.randoms.remove(&selected) {
+            page.choices
+                .iter_mut()
+                .filter(|x| x.random == selected)
+                .for_each(|x| x.random = name.clone());
+            let n = self.selector.value();
+            self.selector.set_text(n, &name);
+            self.name.set_label(&name);
+            random.name = name.clone();
+            page.randoms.insert(name, random);
+        }
+    }
+    /// Event response that removes selected random result from the page
+    ///
+    /// It fails and shows error to an user if the random result is used in a choice
+    pub fn remove(&mut self, page: &mut Page) {
+        let selected = self.selected();
+        if page.randoms.contains_key(&selected) == false {
+            return;
+        }
+        if page.choices.iter().any(|x| x.random == selected) {
+            signal_error!(
+                "Cannot remove Random Result {} because it's used in one or more of Page's Choices",
+                selected
+            );
+            return;
+        }
+        if ask_to_confirm(&format!(
+            "Are you sure you want to remove {} Random Result?",
+            &selected
+        )) {
+            page.randoms.remove(&selected);
+            self.populate(&page.randoms);
+        }
+    }
+    /// Event response that loads specified random result into UI
+    pub fn load(&mut self, randoms: &HashMap<String, RandomResult>, random: String) {
+        if let Some(r) = randoms.get(&random) {
+            self.load_ui(r);
+        } else {
+            println!(
+                "Warning! Attempted to load a random result that doesn't exist: {}",
+                random
+            );
+        }
+    }
+    /// Event response that saves a random result into the collection
+    ///
+    /// If name isn't specified, currently selected random result is saved
+    pub fn save(&self, randoms: &mut HashMap<String, RandomResult>, random: Option<String>) {
+        let random = match random {
+            Some(s) => s,
+            None => self.selected(),
+        };
+        if let Some(r) = randoms.get_mut(&random) {
+            let text = self.outcomes.buffer().as_ref().unwrap().text();
+            match RandomResult::parse_outcomes(&text) {
+                Ok(o) => r.outcomes = o,
+                Err(e) => signal_error!("Random Result {} could not be saved: {}", random, e),
+            }
+        }
+    }
+}
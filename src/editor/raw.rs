@@ -0,0 +1,63 @@
+use fltk::{
+    app,
+    button::Button,
+    draw::Rect,
+    group::Group,
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+};
+
+use crate::{adventure::Page, editor::highlight_color, lang::tr};
+
+use super::{emit, help, Event};
+
+/// Editor of the page's text form
+///
+/// Shows the page the way it's saved on the drive, applying the text parses it back into the page
+pub struct RawEditor {
+    text: TextEditor,
+}
+
+impl RawEditor {
+    /// Creates UI for editing the page as text
+    pub fn new(area: Rect) -> Self {
+        let group = Group::new(area.x, area.y, area.w, area.h, tr("Raw"));
+
+        let font_size = app::font_size();
+
+        let h_text = area.h - font_size * 2;
+        let y_butt = area.y + h_text + font_size / 2;
+        let w_apply = font_size * 5;
+        let x_apply = area.x + area.w - w_apply;
+        let x_help = x_apply - font_size * 2;
+
+        let mut text = TextEditor::new(area.x, area.y, area.w, h_text, None);
+        let mut apply = Button::new(x_apply, y_butt, w_apply, font_size + 4, tr("Apply"));
+        let mut help = Button::new(x_help, y_butt, font_size, font_size, "?");
+        group.end();
+
+        text.set_buffer(TextBuffer::default());
+        text.set_text_font(fltk::enums::Font::Courier);
+
+        let (sender, _) = app::channel();
+        apply.emit(sender.clone(), emit!(Event::ApplyRawPage));
+        apply.set_tooltip(tr("Replaces the page with the text, the text is kept if it has errors"));
+        help.emit(sender, help!("raw"));
+        help.set_frame(fltk::enums::FrameType::RoundUpBox);
+        help.set_color(highlight_color!());
+
+        Self { text }
+    }
+    /// Fills the editor with the text form of the page
+    pub fn load(&mut self, page: &Page) {
+        self.text
+            .buffer()
+            .as_mut()
+            .unwrap()
+            .set_text(&page.serialize_to_string());
+    }
+    /// Returns the edited text
+    pub fn text(&self) -> String {
+        self.text.buffer().as_ref().unwrap().text()
+    }
+}
@@ -1,11 +1,11 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use fltk::{
     app,
     browser::SelectBrowser,
     button::Button,
     draw::Rect,
-    enums::Color,
+    enums::{Align, Color},
     frame::Frame,
     group::Group,
     image::SvgImage,
@@ -14,15 +14,36 @@ use fltk::{
 };
 
 use crate::{
-    adventure::{Adventure, Name, Page, Record, StoryResult},
+    adventure::{input_prompt, Adventure, Name, Page, Record, StoryResult},
     dialog::{ask_for_choice, ask_for_text, ask_to_confirm},
-    evaluation::{evaluate_expression, Random},
+    evaluation::{evaluate_expression, Random, Rounding},
     file::signal_error,
     icons::{BIN_ICON, GEAR_ICON},
+    lang::tr,
 };
 
 use super::{emit, help, Event, highlight_color};
 
+/// Records and rounding the loaded record side effect is previewed with, None when a name side effect is loaded
+type PreviewRecords = Rc<RefCell<Option<(HashMap<String, Record>, Rounding)>>>;
+
+/// Describes what the side effect expression of a record evaluates to with starting values of the records
+///
+/// Dice are rolled with a fixed seed, so the shown value is only one of the possible rolls
+fn preview_side_effect(expression: &str, records: &HashMap<String, Record>, rounding: Rounding) -> String {
+    let expression = expression.trim();
+    if expression.len() == 0 {
+        return String::new();
+    }
+    if input_prompt(expression).is_some() {
+        return tr("The player enters the value").to_string();
+    }
+    match evaluate_expression(expression, records, &mut Random::new(69), rounding) {
+        Ok(v) => format!("{} {:+}", tr("With starting values:"), v),
+        Err(e) => format!("{} {}", tr("Invalid:"), e),
+    }
+}
+
 /// Widgets for customizing results of the page
 ///
 /// Lists available results for the page
@@ -35,15 +56,22 @@ pub struct ResultEditor {
     effect: Frame,
     next_page: fltk::menu::Choice,
     effect_value: TextEditor,
+    /// Shows what the expression of a record side effect evaluates to, updated as it's typed
+    preview: Frame,
+    preview_records: PreviewRecords,
     next_page_label: Frame,
+    butt_go: Button,
     butt_rec: Button,
     butt_nam: Button,
+    unused: Frame,
 }
 
 impl ResultEditor {
     /// Creates UI for result editor
-    pub fn new(area: Rect) -> Self {
-        let group = Group::new(area.x, area.y, area.w, area.h, "Results");
+    ///
+    /// shared: the editor changes results shared by all pages of the adventure instead of results of the open page
+    pub fn new(area: Rect, shared: bool) -> Self {
+        let group = Group::new(area.x, area.y, area.w, area.h, tr("Results"));
 
         let font_size = app::font_size();
 
@@ -73,6 +101,7 @@ impl ResultEditor {
         // result manipulation widgets
         let y_name = y_results + font_size;
         let y_page = y_name + h_line * 2;
+        let y_unused = y_page + h_line + margin2;
 
         // controls for selector buttons
         let y_butt_result = y_results + h_result;
@@ -91,9 +120,9 @@ impl ResultEditor {
         let y_exp = y_butt + h_line * 2;
 
         let mut select_result =
-            SelectBrowser::new(x_column_1, y_results, w_column_1, h_result, "Results");
+            SelectBrowser::new(x_column_1, y_results, w_column_1, h_result, tr("Results"));
         let mut select_mod =
-            SelectBrowser::new(x_column_1, y_mods, w_column_1, h_mods, "Modifications");
+            SelectBrowser::new(x_column_1, y_mods, w_column_1, h_mods, tr("Modifications"));
 
         let mut butt_add_result = Button::new(x_add, y_butt_result, w_butt, h_butt, "@+");
         let mut butt_ren_result = Button::new(x_ren, y_butt_result, w_butt, h_butt, None);
@@ -101,25 +130,40 @@ impl ResultEditor {
         let mut butt_rem_effect = Button::new(x_rem, y_butt_mod, w_butt, h_butt, None); // no add or rename because the names are constant and you add in other controls
         let mut help = Button::new(x_help, y_butt_result, w_butt, h_butt, "?");
 
-        let name = Frame::new(x_column_2, y_name, w_column_2, h_line, "Name");
+        let name = Frame::new(x_column_2, y_name, w_column_2, h_line, tr("Name"));
         let next_page_label = Frame::new(
             x_column_2,
             y_page - font_size,
             w_column_2,
             h_line,
-            "Next Page",
+            tr("Next Page"),
+        );
+        let next_page =
+            fltk::menu::Choice::new(x_column_2, y_page, w_column_2 - h_line, h_line, None);
+        let mut butt_go = Button::new(
+            x_column_2 + w_column_2 - h_line,
+            y_page,
+            h_line,
+            h_line,
+            "@->",
         );
-        let next_page = fltk::menu::Choice::new(x_column_2, y_page, w_column_2, h_line, None);
 
+        let mut unused = Frame::new(x_column_2, y_unused, w_column_2, h_line, None);
         let effect = Frame::new(x_column_2, y_effect, w_column_2, h_line, None);
-        let mut butt_rec = Button::new(x_column_3, y_butt, w_column_3, h_line, "Add Record");
-        let mut butt_nam = Button::new(x_column_4, y_butt, w_column_3, h_line, "Add Name");
+        let mut butt_rec = Button::new(x_column_3, y_butt, w_column_3, h_line, tr("Add Record"));
+        let mut butt_nam = Button::new(x_column_4, y_butt, w_column_3, h_line, tr("Add Name"));
         let mut expression =
-            TextEditor::new(x_column_2, y_exp, w_column_2, h_line, "Value expression");
+            TextEditor::new(x_column_2, y_exp, w_column_2, h_line, tr("Value expression"));
+        let mut preview = Frame::new(x_column_2, y_exp + h_line + margin2, w_column_2, h_line, None);
 
         group.end();
 
         let (sender, _r) = app::channel();
+        // shared results are edited through the same events, wrapped so they don't change the open page
+        let wrap = move |ev: Event| match shared {
+            true => Event::SharedResult(Box::new(ev)),
+            false => ev,
+        };
 
         select_result.set_callback({
             let sender = sender.clone();
@@ -132,12 +176,12 @@ impl ResultEditor {
                 // Blocking save when nothing is loaded in
                 if x.size() > 0 {
                     if let Some(old) = &old_result {
-                        sender.send(emit!(Event::SaveResult(Some(old.clone()))));
+                        sender.send(emit!(wrap(Event::SaveResult(Some(old.clone())))));
                     }
                 }
                 if let Some(text) = new {
                     old_result = Some(text.clone());
-                    sender.send(emit!(Event::LoadResult(text)));
+                    sender.send(emit!(wrap(Event::LoadResult(text))));
                 } else {
                     old_result = None;
                 }
@@ -155,12 +199,12 @@ impl ResultEditor {
                 // This prevents attempt to save right after the selector is repopulated on new result
                 if x.size() > 0 {
                     if let Some(old) = &old_result {
-                        sender.send(emit!(Event::SaveSideEffect(Some(old.clone()))))
+                        sender.send(emit!(wrap(Event::SaveSideEffect(Some(old.clone())))))
                     }
                 }
                 if let Some(text) = new {
                     old_result = Some(text.clone());
-                    sender.send(emit!(Event::LoadSideEffect(text)));
+                    sender.send(emit!(wrap(Event::LoadSideEffect(text))));
                 } else {
                     old_result = None;
                 }
@@ -173,15 +217,25 @@ impl ResultEditor {
             let sel = select_result.clone();
             move |_| {
                 if sel.size() > 0 {
-                    sender.send(emit!(Event::SaveResult(None)));
+                    sender.send(emit!(wrap(Event::SaveResult(None))));
                 }
-                sender.send(emit!(Event::AddResult));
+                sender.send(emit!(wrap(Event::AddResult)));
             }
         });
-        butt_ren_result.emit(sender.clone(), emit!(Event::RenameResult));
-        butt_rem_result.emit(sender.clone(), emit!(Event::RemoveResult));
-        butt_rem_effect.emit(sender.clone(), emit!(Event::RemoveSideEffect));
+        butt_ren_result.emit(sender.clone(), emit!(wrap(Event::RenameResult)));
+        butt_rem_result.emit(sender.clone(), emit!(wrap(Event::RemoveResult)));
+        butt_rem_effect.emit(sender.clone(), emit!(wrap(Event::RemoveSideEffect)));
         help.emit(sender.clone(), help!("result"));
+        butt_go.set_tooltip(tr("Go to page"));
+        butt_go.set_callback({
+            let sender = sender.clone();
+            let next_page = next_page.clone();
+            move |_| {
+                if let Some(page) = next_page.choice() {
+                    sender.send(emit!(Event::GoToPage(page)));
+                }
+            }
+        });
         help.set_frame(fltk::enums::FrameType::RoundUpBox);
         help.set_color(highlight_color!());
         butt_rec.set_callback({
@@ -189,21 +243,42 @@ impl ResultEditor {
             let sel = select_mod.clone();
             move |_| {
                 if sel.size() > 0 {
-                    sender.send(emit!(Event::SaveSideEffect(None)));
+                    sender.send(emit!(wrap(Event::SaveSideEffect(None))));
                 }
-                sender.send(emit!(Event::AddSideEffectRecord));
+                sender.send(emit!(wrap(Event::AddSideEffectRecord)));
             }
         });
         butt_nam.set_callback({
             let sel = select_mod.clone();
             move |_| {
                 if sel.size() > 0 {
-                    sender.send(emit!(Event::SaveSideEffect(None)));
+                    sender.send(emit!(wrap(Event::SaveSideEffect(None))));
                 }
-                sender.send(emit!(Event::AddSideEffectName));
+                sender.send(emit!(wrap(Event::AddSideEffectName)));
             }
         });
-        expression.set_buffer(TextBuffer::default());
+        let preview_records: PreviewRecords = Rc::new(RefCell::new(None));
+        let mut buffer = TextBuffer::default();
+        buffer.add_modify_callback({
+            let buffer = buffer.clone();
+            let mut preview = preview.clone();
+            let preview_records = Rc::clone(&preview_records);
+            move |_, _, _, _, _| {
+                let label = match preview_records.borrow().as_ref() {
+                    Some((records, rounding)) => preview_side_effect(&buffer.text(), records, *rounding),
+                    None => String::new(),
+                };
+                preview.set_label(&label);
+                // frame has no box of its own so the old text has to be cleared by the group
+                if let Some(mut parent) = preview.parent() {
+                    parent.redraw();
+                }
+            }
+        });
+        expression.set_buffer(buffer);
+        preview.set_align(Align::Left | Align::Inside | Align::Clip);
+        unused.set_label_color(Color::Red);
+        unused.set_align(Align::Left | Align::Inside | Align::Clip);
 
         let mut gear = SvgImage::from_data(GEAR_ICON).unwrap();
         let mut bin = SvgImage::from_data(BIN_ICON).unwrap();
@@ -221,9 +296,13 @@ impl ResultEditor {
             effect,
             next_page,
             next_page_label,
+            butt_go,
             butt_rec,
             butt_nam,
             effect_value: expression,
+            preview,
+            preview_records,
+            unused,
         }
     }
     /// Returns selected result or None if the list is empty or there's nothing selected
@@ -241,6 +320,7 @@ impl ResultEditor {
         res: &str,
         se: Option<String>,
         records: &HashMap<String, Record>,
+        rounding: Rounding,
     ) -> Option<String> {
         let se = match se {
             None => match self.selected_side_effect() {
@@ -251,6 +331,8 @@ impl ResultEditor {
         };
         let value = self.effect_value.buffer().unwrap().text();
         match value.trim().to_string() {
+            // the player provides the value when the result is chosen
+            x if input_prompt(&x).is_some() => Some(x),
             x if is_record && x.len() == 0 => {
                 signal_error!(
                     "Warning! A record cannot be empty, expression for {} in {} will be set to 1",
@@ -265,7 +347,7 @@ impl ResultEditor {
             }
             x if is_record => {
                 let mut r = Random::new(69);
-                match evaluate_expression(&x, records, &mut r) {
+                match evaluate_expression(&x, records, &mut r, rounding) {
                     Ok(_) => Some(x),
                     Err(er) => match &er {
                         crate::evaluation::EvaluationError::DivisionByZero => {
@@ -285,6 +367,14 @@ impl ResultEditor {
                             signal_error!("Warning! Expression of {} is invalid. {}", &se, er);
                             None
                         }
+                        crate::evaluation::EvaluationError::UnbalancedBrackets(_) => {
+                            signal_error!("Warning! Expression of {} is invalid. {}", &se, er);
+                            None
+                        }
+                        crate::evaluation::EvaluationError::CircularRecord(_) => {
+                            signal_error!("Warning! Expression of {} is invalid. {}", &se, er);
+                            None
+                        }
                     },
                 }
             }
@@ -297,6 +387,13 @@ impl ResultEditor {
         self.selector_effects.select(self.selector_effects.size());
         self.selector_effects.do_callback();
     }
+    /// Returns a check telling whatever the result shown in the editor has its next page chosen
+    ///
+    /// It's meant for callbacks that can't reach the editor, like switching tabs
+    pub fn next_page_check(&self) -> impl Fn() -> bool {
+        let next_page = self.next_page.clone();
+        move || next_page.visible() == false || next_page.value() >= 0
+    }
     /// Loads result into the editor
     fn load_result(&mut self, res: &StoryResult) {
         let mut i = 0;
@@ -373,6 +470,7 @@ impl ResultEditor {
         self.name.show();
         self.next_page_label.show();
         self.next_page.show();
+        self.butt_go.show();
         self.butt_nam.show();
         self.butt_rec.show();
     }
@@ -381,6 +479,7 @@ impl ResultEditor {
         self.name.hide();
         self.next_page_label.hide();
         self.next_page.hide();
+        self.butt_go.hide();
         self.butt_nam.hide();
         self.butt_rec.hide();
         self.effect.hide();
@@ -390,11 +489,13 @@ impl ResultEditor {
     fn show_effects(&mut self) {
         self.effect.show();
         self.effect_value.show();
+        self.preview.show();
     }
     /// Hides the side effect editor UI
     fn hide_effects(&mut self) {
         self.effect.hide();
         self.effect_value.hide();
+        self.preview.hide();
     }
     /// tests if a side effect already exists in the story result
     fn contains_side_effect(&self, name: &str) -> bool {
@@ -411,6 +512,38 @@ impl ResultEditor {
     fn has_side_effects(&self) -> bool {
         self.selector_effects.size() > 0
     }
+    /// Warns about results of the page that no choice or test leads to, as the player can never get them,
+    /// and results without a next page, which fail when the player gets them
+    pub fn show_unused(&mut self, page: &Page) {
+        let mut notes = Vec::new();
+        let unused = page.unused_results();
+        if unused.len() > 0 {
+            let names: Vec<&str> = unused.iter().map(|x| x.as_str()).collect();
+            notes.push(format!(
+                "{} {}",
+                tr("Not used by any choice or test:"),
+                names.join(", ")
+            ));
+        }
+        let unfinished = page.results_without_next_page();
+        if unfinished.len() > 0 {
+            let names: Vec<&str> = unfinished.iter().map(|x| x.as_str()).collect();
+            notes.push(format!("{} {}", tr("No next page:"), names.join(", ")));
+        }
+        if notes.len() > 0 {
+            self.unused.set_label(&notes.join("; "));
+            self.unused.set_tooltip(tr(
+                "These results can't happen or can't be followed, use them in a choice or a test and choose their next page, or remove them",
+            ));
+        } else {
+            self.unused.set_label("");
+            self.unused.set_tooltip("");
+        }
+        // frame has no box of its own so the old text has to be cleared by the group
+        if let Some(mut parent) = self.unused.parent() {
+            parent.redraw();
+        }
+    }
     /// Populates result UI with pages and results
     ///
     /// This function ensures the loading is performed in correct order
@@ -469,8 +602,9 @@ impl ResultEditor {
                 let value = match self.evaluate_correct_side_effect_value(
                     is_record,
                     &result.name,
-                    Some(se.clone()),
+                            Some(se.clone()),
                     &adventure.records,
+                    adventure.rounding,
                 ) {
                     Some(x) => x,
                     None => {
@@ -530,6 +664,13 @@ impl ResultEditor {
             );
             return;
         }
+        if page.switches.values().any(|x| x.uses_result(&selected)) {
+            signal_error!(
+                "Result {} is used in a switch! Cannot remove used result",
+                selected
+            );
+            return;
+        }
         if ask_to_confirm(&format!("Are you sure you want to remove {}?", &selected)) {
             page.results.remove(&selected);
             // no need to call populate_side_effects as it is expected of populate_results to do it
@@ -538,7 +679,7 @@ impl ResultEditor {
     }
     /// Event response that renames currently selected result
     ///
-    /// It will also update its name when used in choices and tests
+    /// It will also update its name when used in choices, tests and switches
     pub fn rename(&mut self, page: &mut Page) {
         let selected = match self.selected_result() {
             Some(s) => s,
@@ -551,18 +692,7 @@ impl ResultEditor {
 
         if let Some(mut res) = page.results.remove(&selected) {
             // updating the name in other parts of the page
-            page.choices
-                .iter_mut()
-                .filter(|x| x.result == selected)
-                .for_each(|x| x.result = name.clone());
-            for el in page.tests.iter_mut() {
-                if el.1.success_result == selected {
-                    el.1.success_result = name.clone();
-                }
-                if el.1.failure_result == selected {
-                    el.1.failure_result = name.clone();
-                }
-            }
+            page.rename_result_references(&selected, &name);
             let sel = self.selector_results.value();
             self.selector_results.set_text(sel, &name);
             self.name.set_label(&name);
@@ -570,6 +700,48 @@ impl ResultEditor {
             page.results.insert(name, res);
         }
     }
+    /// Event response that removes currently selected shared result from the adventure
+    ///
+    /// It fails and shows error to an user if a page without its own result of the same name uses it
+    pub fn remove_shared(&mut self, adventure: &mut Adventure, pages: &HashMap<String, Page>) {
+        let selected = match self.selected_result() {
+            Some(s) => s,
+            None => return,
+        };
+        let using = adventure.pages_using_result(pages, &selected);
+        if using.len() > 0 {
+            signal_error!(
+                "Result {} is used in pages {}! Cannot remove used result",
+                selected,
+                using.join(", ")
+            );
+            return;
+        }
+        if ask_to_confirm(&format!("Are you sure you want to remove {}?", &selected)) {
+            adventure.results.remove(&selected);
+            self.populate_results(&adventure.results);
+        }
+    }
+    /// Event response that renames currently selected shared result
+    ///
+    /// Choices, tests and switches of pages that use it are updated
+    pub fn rename_shared(&mut self, adventure: &mut Adventure, pages: &mut HashMap<String, Page>) {
+        let selected = match self.selected_result() {
+            Some(s) => s,
+            None => return,
+        };
+        let name = match ask_for_text(&format!("Input a new name for {}", &selected)) {
+            Some(n) if n.len() > 0 => n,
+            _ => return,
+        };
+        if adventure.rename_result(pages, &selected, &name) == false {
+            signal_error!("Cannot rename to {} because it already exists", name);
+            return;
+        }
+        let sel = self.selector_results.value();
+        self.selector_results.set_text(sel, &name);
+        self.name.set_label(&name);
+    }
     /// Event response that saves effect data from UI into specified side effect
     ///
     /// If no name is provided then it will save into currently selected side effect
@@ -614,6 +786,7 @@ impl ResultEditor {
             &res.name,
             Some(se.clone()),
             &adventure.records,
+            adventure.rounding,
         ) {
             Some(x) => x,
             None => {
@@ -627,7 +800,9 @@ impl ResultEditor {
         res.side_effects.insert(se, value);
     }
     /// Event response that loads a side effect by name into the UI
-    pub fn load_effect(&mut self, results: &HashMap<String, StoryResult>, se: String) {
+    ///
+    /// Expressions of record side effects are previewed with starting values of the adventure's records
+    pub fn load_effect(&mut self, results: &HashMap<String, StoryResult>, se: String, adventure: &Adventure) {
         let selected = match self.selected_result() {
             Some(res) => match results.get(&res) {
                 Some(r) => r,
@@ -661,6 +836,10 @@ impl ResultEditor {
                 }
             }
             self.effect.set_label(&se);
+            *self.preview_records.borrow_mut() = match adventure.records.contains_key(&se) {
+                true => Some((adventure.records.clone(), adventure.rounding)),
+                false => None,
+            };
             self.effect_value.buffer().unwrap().set_text(v);
             self.show_effects();
         } else {
@@ -687,7 +866,7 @@ impl ResultEditor {
             }
         };
         if let Some(choice) = ask_for_choice(
-            "Select Record to add",
+            tr("Select Record to add"),
             records
                 .iter()
                 .filter(|x| !self.contains_side_effect(x.0))
@@ -717,7 +896,7 @@ impl ResultEditor {
             }
         };
         if let Some(choice) = ask_for_choice(
-            "Select Name to add",
+            tr("Select Name to add"),
             names
                 .iter()
                 .filter(|x| !self.contains_side_effect(x.0))
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use fltk::{app, draw::Rect, prelude::*, window::Window};
+
+use crate::{
+    adventure::{Page, StoryResult},
+    lang::tr,
+};
+
+use super::{emit, result::ResultEditor, Event};
+
+const WINDOW_WIDTH: i32 = 800;
+const WINDOW_HEIGHT: i32 = 500;
+const MARGIN: i32 = 20;
+
+/// Window for editing results shared by all pages of the adventure
+///
+/// Choices, tests and switches of any page can lead to the shared results, unless the page has its own result of the same name
+pub struct SharedResultsWindow {
+    window: Window,
+    pub results: ResultEditor,
+}
+
+impl SharedResultsWindow {
+    /// Creates the window, it needs to be filled with populate before it's shown
+    pub fn new() -> Self {
+        let font_size = app::font_size();
+        let mut window = Window::default()
+            .with_size(WINDOW_WIDTH, WINDOW_HEIGHT)
+            .with_label(tr("Shared Results"));
+        let results = ResultEditor::new(
+            Rect::new(
+                MARGIN,
+                MARGIN + font_size,
+                WINDOW_WIDTH - MARGIN * 2,
+                WINDOW_HEIGHT - MARGIN * 2 - font_size,
+            ),
+            true,
+        );
+        window.end();
+
+        // changes of the loaded result are kept when the window is closed
+        window.set_callback(|w| {
+            let (s, _r) = app::channel();
+            s.send(emit!(Event::SharedResult(Box::new(Event::SaveResult(None)))));
+            w.hide();
+        });
+
+        SharedResultsWindow { window, results }
+    }
+    /// Fills the window with shared results and shows it
+    pub fn show(&mut self, results: &HashMap<String, StoryResult>, pages: &HashMap<String, Page>) {
+        self.results.populate(results, pages);
+        self.window.show();
+    }
+    /// Tests if the window is shown
+    pub fn shown(&self) -> bool {
+        self.window.shown()
+    }
+    /// Closes the window
+    pub fn close(&mut self) {
+        self.window.hide();
+    }
+}
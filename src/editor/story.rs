@@ -1,21 +1,60 @@
+use std::{cell::RefCell, rc::Rc};
+
 use fltk::{
     app,
     draw::Rect,
     group::{Group, Tabs},
     prelude::*,
-    text::{TextBuffer, TextEditor}, frame::Frame, enums::Align, button::Button,
+    text::{StyleTableEntryExt, TextAttr, TextBuffer, TextEditor}, frame::Frame, enums::{Align, Color}, button::Button,
+    window::Window,
 };
 
 use crate::{
-    adventure::{Adventure, Page, Name, Record},
+    adventure::{parse_tags, Adventure, Page, Name, Record},
+    dialog::create_text_window,
     editor::{variables::variable_receiver, help, highlight_color},
+    file::signal_error,
+    lang::tr,
+    spelling,
 };
 
 use super::{
-    choice::ChoiceEditor, condition::ConditionEditor, emit, result::ResultEditor, test::TestEditor,
-    variables::VariableEditor, Event,
+    choice::ChoiceEditor, condition::ConditionEditor, emit, random::RandomEditor,
+    raw::RawEditor, result::ResultEditor, switch::SwitchEditor, test::TestEditor, variables::VariableEditor, Event,
 };
 
+// positions of the editor tabs, in the order they're created, since their labels are translated
+const TAB_CHOICES: i32 = 1;
+const TAB_RESULTS: i32 = 2;
+const TAB_TESTS: i32 = 3;
+const TAB_SWITCHES: i32 = 4;
+const TAB_RANDOM: i32 = 5;
+const TAB_CONDITIONS: i32 = 6;
+const TAB_RAW: i32 = 7;
+
+/// Average amount of words read in a minute, used to estimate reading time
+const WORDS_PER_MINUTE: usize = 200;
+
+/// Creates a label with word count and rough reading time of the text
+fn word_count_label(text: &str) -> String {
+    let words = text.split_whitespace().count();
+    let minutes = (words + WORDS_PER_MINUTE - 1) / WORDS_PER_MINUTE;
+    match words {
+        1 => "1 word, under a minute to read".to_string(),
+        n if minutes < 2 => format!("{} words, under a minute to read", n),
+        n => format!("{} words, about {} minutes to read", n, minutes),
+    }
+}
+
+/// Creates style buffer text for the story, marking misspelled words with style B and the rest with style A
+fn spelling_styles(text: &str, dictionary: &Vec<String>) -> String {
+    let mut styles = vec![b'A'; text.len()];
+    for word in spelling::misspelled(text, dictionary) {
+        styles[word].fill(b'B');
+    }
+    String::from_utf8(styles).unwrap_or_default()
+}
+
 /// Edits page's title and story text
 ///
 /// Aside from text editors, it has quick insert buttons for inserting records and names into the text
@@ -23,13 +62,21 @@ pub struct StoryEditor {
     group: Group,
     page_name: Frame,
     title: TextEditor,
+    tags: TextEditor,
     story: TextEditor,
+    /// Separate window editing the story text, created the first time it's opened
+    popout: Rc<RefCell<Option<Window>>>,
+    /// Words of the adventure dictionary, accepted by the spellchecker in addition to the loaded dictionary
+    dictionary: Rc<RefCell<Vec<String>>>,
     records: VariableEditor,
     names: VariableEditor,
     pub choices: ChoiceEditor,
     pub conditions: ConditionEditor,
     pub tests: TestEditor,
     pub results: ResultEditor,
+    pub randoms: RandomEditor,
+    pub switches: SwitchEditor,
+    pub raw: RawEditor,
 }
 
 impl StoryEditor {
@@ -55,7 +102,10 @@ impl StoryEditor {
         let children = Rect::from(tabs.client_area());
 
         let y_title = children.y + font_size;
+        let w_title = children.w / 4 * 3 - 5;
         let h_title = font_size + 4;
+        let x_tags = children.x + w_title + 5;
+        let w_tags = children.w - w_title - 5;
         let y_story = y_title + h_title + font_size;
         let h_story = children.h - h_title - font_size * 2;
 
@@ -64,16 +114,35 @@ impl StoryEditor {
         let w_help = font_size;
         let h_help = w_help;
 
-        let text_page = Group::new(children.x, children.y, children.w, children.h, "Page");
-        let mut title = TextEditor::new(children.x, y_title, children.w, h_title, "Title");
-        let mut story = TextEditor::new(children.x, y_story, children.w, h_story, "Story Text");
+        let text_page = Group::new(children.x, children.y, children.w, children.h, tr("Page"));
+        let mut title = TextEditor::new(children.x, y_title, w_title, h_title, tr("Title"));
+        let mut tags = TextEditor::new(x_tags, y_title, w_tags, h_title, tr("Tags"));
+        let mut story = TextEditor::new(children.x, y_story, children.w, h_story, tr("Story Text"));
         let mut help = Button::new(x_help, y_help, w_help, h_help, "?");
+        let mut butt_word = Button::new(children.x, y_help, font_size * 8, h_help, tr("Add to Dictionary"));
+        let mut butt_popout = Button::new(
+            children.x + font_size * 8 + 5,
+            y_help,
+            font_size * 5,
+            h_help,
+            tr("Pop Out"),
+        );
+        let mut word_count = Frame::new(
+            children.x + children.w / 2,
+            y_help,
+            children.w / 2 - w_help * 3,
+            h_help,
+            None,
+        );
         text_page.end();
 
         let choices = ChoiceEditor::new(children);
-        let results = ResultEditor::new(children);
+        let results = ResultEditor::new(children, false);
         let tests = TestEditor::new(children);
+        let switches = SwitchEditor::new(children);
+        let randoms = RandomEditor::new(children);
         let conditions = ConditionEditor::new(children);
+        let raw = RawEditor::new(children);
 
         tabs.end();
 
@@ -92,55 +161,121 @@ impl StoryEditor {
 
         page_name.set_align(Align::Inside.union(Align::Right));
         title.set_buffer(TextBuffer::default());
-        story.set_buffer(TextBuffer::default());
+        tags.set_buffer(TextBuffer::default());
+        tags.set_tooltip(tr("Comma separated list of tags, pages are grouped by them in the outline, they're never shown in the game"));
+        let mut story_buffer = TextBuffer::default();
+        let dictionary = Rc::new(RefCell::new(Vec::new()));
+        let style_buffer = TextBuffer::default();
+        word_count.set_align(Align::Inside.union(Align::Right));
+        word_count.set_label(&word_count_label(""));
+        story_buffer.add_modify_callback({
+            let buffer = story_buffer.clone();
+            let mut style_buffer = style_buffer.clone();
+            let dictionary = dictionary.clone();
+            move |_, _, _, _, _| {
+                let text = buffer.text();
+                word_count.set_label(&word_count_label(&text));
+                style_buffer.set_text(&spelling_styles(&text, &dictionary.borrow()));
+            }
+        });
+        story.set_buffer(story_buffer);
+        let style = |color, attr| StyleTableEntryExt {
+            color,
+            font: story.text_font(),
+            size: story.text_size(),
+            attr,
+            bgcolor: Color::Background2,
+        };
+        story.set_highlight_data_ext(
+            style_buffer,
+            vec![style(Color::Foreground, TextAttr::None), style(Color::Red, TextAttr::Spelling)],
+        );
         story.wrap_mode(fltk::text::WrapMode::AtBounds, 0);
 
+        let popout = Rc::new(RefCell::new(None));
+        butt_popout.set_tooltip(tr("Opens the story text in a larger window, edits show in the page right away"));
+        butt_popout.set_callback({
+            let popout = Rc::clone(&popout);
+            let buffer = story.buffer().unwrap();
+            move |_| {
+                let mut popout = popout.borrow_mut();
+                let window = popout.get_or_insert_with(|| {
+                    let (window, mut editor) = create_text_window(tr("Story Text"), buffer.clone());
+                    variable_receiver!(editor);
+                    window
+                });
+                window.show();
+            }
+        });
+
         let (sender, _) = app::channel();
-        help.emit(sender, help!("story"));
+        help.emit(sender.clone(), help!("story"));
+        butt_word.emit(sender, emit!(Event::AddToDictionary));
+        butt_word.set_tooltip(tr("Stops marking the word under the cursor as misspelled in this adventure"));
+        if spelling::is_available() == false {
+            butt_word.hide();
+        }
         help.set_frame(fltk::enums::FrameType::RoundUpBox);
         help.set_color(highlight_color!());
 
         tabs.set_callback({
-            let mut old_select = "Choices".to_string();
+            let mut old_select = TAB_CHOICES;
+            let next_page_chosen = results.next_page_check();
             move |x| {
                 let (s, _r) = app::channel();
-                // saving data from editors on tab switch
-                match old_select.as_str() {
-                    "Choices" => s.send(emit!(Event::SaveChoice(None))),
-                    "Conditions" => s.send(emit!(Event::SaveCondition(None))),
-                    "Tests" => s.send(emit!(Event::SaveTest(None))),
-                    "Results" => {
-                        s.send(emit!(Event::SaveResult(None)));
+                // a result without a next page would fail when the player gets it, so the tab can't be left until it's chosen
+                if old_select == TAB_RESULTS && next_page_chosen() == false {
+                    signal_error!("Choose the next page of the result before leaving the Results tab");
+                    if let Some(results) = x.child(TAB_RESULTS).and_then(|c| c.as_group()) {
+                        let _ = x.set_value(&results);
                     }
-                    "Page" => {}
-                    _ => unreachable!(),
+                    return;
+                }
+                // saving data from editors on tab switch, raw text is only saved when it's applied
+                match old_select {
+                    TAB_CHOICES => s.send(emit!(Event::SaveChoice(None))),
+                    TAB_CONDITIONS => s.send(emit!(Event::SaveCondition(None))),
+                    TAB_TESTS => s.send(emit!(Event::SaveTest(None))),
+                    TAB_SWITCHES => s.send(emit!(Event::SaveSwitch(None))),
+                    TAB_RANDOM => s.send(emit!(Event::SaveRandom(None))),
+                    TAB_RESULTS => s.send(emit!(Event::SaveResult(None))),
+                    _ => {}
                 }
                 if let Some(new_select) = x.value() {
-                    let new_select = new_select.label();
-                    match new_select.as_str() {
-                        "Choices" => {
+                    let new_select = x.find(&new_select);
+                    match new_select {
+                        TAB_CHOICES => {
                             s.send(emit!(Event::RefreshResults));
                             s.send(emit!(Event::ToggleNames(true)));
                             s.send(emit!(Event::ToggleRecords(true)));
                         }
-                        "Conditions" => {
+                        TAB_CONDITIONS => {
                             s.send(emit!(Event::ToggleNames(false)));
                             s.send(emit!(Event::ToggleRecords(true)));
                         }
-                        "Tests" => {
+                        TAB_TESTS | TAB_SWITCHES => {
                             s.send(emit!(Event::RefreshResults));
                             s.send(emit!(Event::ToggleNames(false)));
                             s.send(emit!(Event::ToggleRecords(true)));
                         }
-                        "Results" => {
+                        TAB_RANDOM => {
+                            s.send(emit!(Event::ToggleNames(false)));
+                            s.send(emit!(Event::ToggleRecords(false)));
+                        }
+                        TAB_RESULTS => {
+                            s.send(emit!(Event::RefreshUnusedResults));
                             s.send(emit!(Event::ToggleNames(true)));
                             s.send(emit!(Event::ToggleRecords(true)));
                         }
-                        "Page" => {
+                        TAB_RAW => {
+                            s.send(emit!(Event::LoadRawPage));
+                            s.send(emit!(Event::ToggleNames(false)));
+                            s.send(emit!(Event::ToggleRecords(false)));
+                        }
+                        _ => {
                             s.send(emit!(Event::ToggleNames(true)));
                             s.send(emit!(Event::ToggleRecords(true)));
                         }
-                        _ => unreachable!(),
                     }
                     old_select = new_select;
                 }
@@ -154,18 +289,27 @@ impl StoryEditor {
             group,
             page_name,
             title,
+            tags,
             story,
+            popout,
+            dictionary,
             records,
             names,
             choices,
             conditions,
             tests,
             results,
+            randoms,
+            switches,
+            raw,
         }
     }
-    /// Hides the editor
+    /// Hides the editor, closing the story window if it's open
     pub fn hide(&mut self) {
         self.group.hide();
+        if let Some(window) = self.popout.borrow_mut().as_mut() {
+            window.hide();
+        }
     }
     /// Shows and redraws the editor
     pub fn show(&mut self) {
@@ -175,8 +319,15 @@ impl StoryEditor {
     /// Loads a page and list of records and names into editor
     pub fn load_page(&mut self, page: &Page, page_name: &String, adventure: &Adventure) {
         self.page_name.set_label(page_name);
+        *self.dictionary.borrow_mut() = adventure.dictionary.clone();
         self.title.buffer().as_mut().unwrap().set_text(&page.title);
+        self.tags
+            .buffer()
+            .as_mut()
+            .unwrap()
+            .set_text(&page.tags.join(", "));
         self.story.buffer().as_mut().unwrap().set_text(&page.story);
+        self.choices.set_shuffle(page.shuffle);
 
         self.records.clear();
         for rec in adventure.records.iter() {
@@ -187,14 +338,34 @@ impl StoryEditor {
             self.names.add_name(nam.1, true);
         }
     }
+    /// Replaces words accepted by the spellchecker and checks the story text again
+    pub fn set_dictionary(&mut self, words: &Vec<String>) {
+        *self.dictionary.borrow_mut() = words.clone();
+        let text = self.story.buffer().as_ref().unwrap().text();
+        if let Some(mut styles) = self.story.style_buffer() {
+            styles.set_text(&spelling_styles(&text, words));
+        }
+        self.story.redraw();
+    }
+    /// Returns the word of the story text the cursor is in, if any
+    pub fn word_at_cursor(&self) -> Option<String> {
+        let text = self.story.buffer().as_ref().unwrap().text();
+        let position = self.story.insert_position().max(0) as usize;
+        spelling::word_at(&text, position).map(|x| x.to_string())
+    }
     /// Saves the data from the editor into the provided page
     pub fn save_page(&self, page: &mut Page, adventure: &Adventure) {
         page.title = self.title.buffer().as_ref().unwrap().text();
+        page.tags = parse_tags(&self.tags.buffer().as_ref().unwrap().text());
         page.story = self.story.buffer().as_ref().unwrap().text();
+        page.shuffle = self.choices.is_shuffled();
         self.choices.save_choice(&mut page.choices, None);
         self.conditions.save(&mut page.conditions, None);
         self.tests.save(&mut page.tests, None);
+        let results = adventure.page_results(page);
+        self.switches.save(&mut page.switches, None, &results);
         self.results.save(&mut page.results, None, adventure);
+        self.randoms.save(&mut page.randoms, None);
     }
     /// Toggles the display of records UI
     pub fn toggle_record_editor(&mut self, on: bool) {
@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+
+use fltk::{
+    app,
+    browser::SelectBrowser,
+    button::Button,
+    draw::Rect,
+    frame::Frame,
+    group::Group,
+    image::SvgImage,
+    prelude::*,
+    text::{TextBuffer, TextEditor},
+};
+
+use crate::{
+    adventure::{Page, StoryResult, Switch, ValueRange},
+    dialog::{ask_for_text, ask_to_confirm},
+    editor::{highlight_color, variables::variable_receiver},
+    file::signal_error,
+    icons::{BIN_ICON, GEAR_ICON},
+    lang::tr,
+    widgets::find_item,
+};
+
+use super::{emit, help, Event};
+
+/// Entry of the default result dropdown meaning the switch has no default result
+const NO_DEFAULT: &str = " ";
+
+/// Widgets for editing switches
+///
+/// Lists switches in page by name
+/// Each switch is edited as an expression and a list of value ranges and results they lead to, one range per line
+/// A drop down picks the result used when the value isn't in any of the ranges
+pub struct SwitchEditor {
+    selector: SelectBrowser,
+    name: Frame,
+    expression: TextEditor,
+    branches: TextEditor,
+    default_result: fltk::menu::Choice,
+    default_label: Frame,
+}
+
+impl SwitchEditor {
+    /// Creates UI for editing switches of a page
+    pub fn new(area: Rect) -> Self {
+        let group = Group::new(area.x, area.y, area.w, area.h, tr("Switches"));
+
+        let font_size = app::font_size();
+
+        let x_selector = area.x;
+        let y_selector = area.y;
+        let w_selector = area.w / 3;
+        let h_selector = area.h - font_size;
+
+        let y_butt = y_selector + h_selector;
+        let w_butt = font_size;
+        let h_butt = w_butt;
+
+        let x_add = x_selector;
+        let x_ren = x_add + w_butt;
+        let x_rem = x_selector + w_selector - w_butt;
+        let x_help = x_ren + w_butt * 2;
+
+        let column_margin = 20;
+        let x_second_column = x_selector + w_selector + column_margin;
+        let w_second_column = area.w - w_selector - column_margin * 2;
+        let h_line = font_size + font_size / 2;
+
+        let y_name = y_selector + font_size;
+        let y_exp = y_name + h_line * 2;
+        let y_branches = y_exp + h_line * 2;
+        let y_default = area.y + area.h - h_line * 2;
+        let h_branches = y_default - y_branches - font_size * 2;
+
+        let mut selector =
+            SelectBrowser::new(x_selector, y_selector, w_selector, h_selector, tr("Switches"));
+
+        let mut add = Button::new(x_add, y_butt, w_butt, h_butt, "@+");
+        let mut ren = Button::new(x_ren, y_butt, w_butt, h_butt, None);
+        let mut rem = Button::new(x_rem, y_butt, w_butt, h_butt, None);
+        let mut help = Button::new(x_help, y_butt, w_butt, h_butt, "?");
+
+        let name = Frame::new(x_second_column, y_name, w_second_column, h_line, tr("Name"));
+        let mut expression = TextEditor::new(
+            x_second_column,
+            y_exp,
+            w_second_column,
+            h_line,
+            tr("Expression"),
+        );
+        let mut branches = TextEditor::new(
+            x_second_column,
+            y_branches,
+            w_second_column,
+            h_branches,
+            tr("Value ranges and their results"),
+        );
+        let default_label = Frame::new(
+            x_second_column,
+            y_default - font_size,
+            w_second_column,
+            h_line,
+            tr("Otherwise"),
+        );
+        let default_result = fltk::menu::Choice::new(
+            x_second_column,
+            y_default,
+            w_second_column,
+            h_line,
+            None,
+        );
+        group.end();
+
+        let (sender, _r) = app::channel();
+
+        add.emit(sender.clone(), emit!(Event::AddSwitch));
+        ren.emit(sender.clone(), emit!(Event::RenameSwitch));
+        rem.emit(sender.clone(), emit!(Event::RemoveSwitch));
+        help.emit(sender.clone(), help!("switch"));
+        help.set_frame(fltk::enums::FrameType::RoundUpBox);
+        help.set_color(highlight_color!());
+
+        selector.set_callback({
+            let mut selected = 0;
+            move |x| {
+                let new = x.value();
+                if selected != new {
+                    if selected > 0 {
+                        if let Some(switch) = x.text(selected) {
+                            sender.send(emit!(Event::SaveSwitch(Some(switch))));
+                        }
+                    }
+                    if let Some(new) = x.selected_text() {
+                        sender.send(emit!(Event::LoadSwitch(new)));
+                    }
+                    selected = new;
+                }
+            }
+        });
+
+        let mut gear = SvgImage::from_data(GEAR_ICON).unwrap();
+        let mut bin = SvgImage::from_data(BIN_ICON).unwrap();
+        gear.scale(font_size, font_size, false, true);
+        bin.scale(font_size, font_size, false, true);
+        ren.set_image(Some(gear));
+        rem.set_image(Some(bin));
+
+        expression.set_buffer(TextBuffer::default());
+        branches.set_buffer(TextBuffer::default());
+        branches.set_tooltip(tr(
+            "One range per line, followed by ; and a result, for example: 1..3; failure",
+        ));
+
+        variable_receiver!(expression);
+
+        Self {
+            selector,
+            name,
+            expression,
+            branches,
+            default_result,
+            default_label,
+        }
+    }
+    /// Loads provided switch into UI
+    fn load_ui(&mut self, switch: &Switch) {
+        self.name.set_label(&switch.name);
+        if let Some(i) = find_item(&self.selector, &switch.name) {
+            self.selector.select(i);
+            self.selector.do_callback();
+        }
+        self.expression
+            .buffer()
+            .as_mut()
+            .unwrap()
+            .set_text(&switch.expression);
+        let text: Vec<String> = switch
+            .branches
+            .iter()
+            .map(|x| format!("{}; {}", x.0, x.1))
+            .collect();
+        self.branches
+            .buffer()
+            .as_mut()
+            .unwrap()
+            .set_text(&text.join("\n"));
+        let default = match switch.default_result.len() {
+            0 => NO_DEFAULT,
+            _ => &switch.default_result,
+        };
+        let index = self.default_result.find_index(default);
+        self.default_result.set_value(index);
+        self.show_controls();
+    }
+    /// Fills the UI with data to edit the switches
+    pub fn populate(
+        &mut self,
+        switches: &HashMap<String, Switch>,
+        results: &HashMap<String, StoryResult>,
+    ) {
+        self.default_result.set_value(-1);
+        self.default_result.clear();
+        self.default_result.add_choice(NO_DEFAULT);
+        let mut results: Vec<&String> = results.keys().collect();
+        results.sort();
+        results.iter().for_each(|x| self.default_result.add_choice(x));
+        self.selector.clear();
+        self.selector.do_callback();
+        let mut names: Vec<&String> = switches.keys().collect();
+        names.sort();
+        names.iter().for_each(|x| self.selector.add(x));
+        match names.first() {
+            Some(n) => self.load_ui(&switches[*n]),
+            None => self.hide_controls(),
+        }
+    }
+    /// Hides controls customizing the switch
+    fn hide_controls(&mut self) {
+        self.name.hide();
+        self.expression.hide();
+        self.branches.hide();
+        self.default_result.hide();
+        self.default_label.hide();
+        self.name.set_label("");
+        self.expression.buffer().unwrap().set_text("");
+        self.branches.buffer().unwrap().set_text("");
+        self.default_result.set_value(-1);
+    }
+    /// Shows controls for switch customization
+    fn show_controls(&mut self) {
+        self.name.show();
+        self.expression.show();
+        self.branches.show();
+        self.default_result.show();
+        self.default_label.show();
+    }
+    /// Returns text of currently selected item, or None if nothing is selected
+    fn selected(&self) -> Option<String> {
+        self.selector.selected_text()
+    }
+    /// Event response that saves the switch into the page collection
+    ///
+    /// Switch to save can be specified by name, or if it is None, currently selected switch will be saved.
+    /// Branches leading to results that aren't among the provided results are refused
+    pub fn save(
+        &self,
+        switches: &mut HashMap<String, Switch>,
+        switch: Option<String>,
+        results: &HashMap<String, StoryResult>,
+    ) {
+        let switch = match switch.or_else(|| self.selected()) {
+            Some(s) => s,
+            None => return,
+        };
+        if let Some(s) = switches.get_mut(&switch) {
+            let text = self.branches.buffer().as_ref().unwrap().text();
+            let branches = match Switch::parse_branches(&text) {
+                Ok(b) => b,
+                Err(e) => {
+                    signal_error!("Switch {} could not be saved: {}", switch, e);
+                    return;
+                }
+            };
+            if let Some(missing) = branches.iter().find(|x| results.contains_key(&x.1) == false) {
+                signal_error!(
+                    "Switch {} could not be saved because result {} doesn't exist, choose another result for it",
+                    switch,
+                    missing.1
+                );
+                return;
+            }
+            s.branches = branches;
+            s.expression = self.expression.buffer().unwrap().text();
+            s.default_result = match self.default_result.choice() {
+                Some(text) if text != NO_DEFAULT => text,
+                _ => String::new(),
+            };
+        }
+    }
+    /// Event response that loads a switch by name into UI
+    pub fn load(&mut self, switches: &HashMap<String, Switch>, switch: String) {
+        if let Some(switch) = switches.get(&switch) {
+            self.load_ui(switch);
+        }
+    }
+    /// Event response renaming currently selected switch
+    ///
+    /// It also updates the switch in choices in the page
+    pub fn rename(&mut self, page: &mut Page) {
+        let selected = match self.selected() {
+            Some(s) => s,
+            None => {
+                println!("Error: Could not rename a switch. No switch selected");
+                return;
+            }
+        };
+        let name = match ask_for_text(&format!("Insert new name for {} Switch", &selected)) {
+            Some(n) if n.len() > 0 => n,
+            _ => return,
+        };
+        if page.switches.contains_key(&name) {
+            signal_error!("Cannot rename to {} because it already exists", name);
+            return;
+        }
+
+        if let Some(mut switch) = page.switches.remove(&selected) {
+            page.choices
+                .iter_mut()
+                .filter(|x| x.switch == selected)
+                .for_each(|x| x.switch = name.clone());
+
+            let i = self.selector.value();
+            self.selector.set_text(i, &name);
+            self.name.set_label(&name);
+            switch.name = name.clone();
+            page.switches.insert(name, switch);
+        }
+    }
+    /// Event response that adds a new switch to the page
+    ///
+    /// It will fail if there isn't at least one result present in the page
+    pub fn add(&mut self, page: &mut Page) {
+        if page.results.len() < 1 {
+            signal_error!("You need to add a result before you can add a switch");
+            return;
+        }
+        let name = match ask_for_text("Insert name for new Switch") {
+            Some(n) if n.len() > 0 => n,
+            _ => return,
+        };
+        if page.switches.contains_key(&name) {
+            signal_error!("Cannot add {} because it already exists", name);
+            return;
+        }
+        // the new switch splits a die roll between the first and the last result, to be edited later
+        let mut results: Vec<&String> = page.results.keys().collect();
+        results.sort();
+        let switch = Switch {
+            name: name.clone(),
+            expression: "1d6".to_string(),
+            branches: vec![
+                (
+                    ValueRange {
+                        min: None,
+                        max: Some(3),
+                    },
+                    results[0].clone(),
+                ),
+                (
+                    ValueRange {
+                        min: Some(4),
+                        max: None,
+                    },
+                    results[results.len() - 1].clone(),
+                ),
+            ],
+            default_result: String::new(),
+        };
+        self.selector.add(&name);
+        page.switches.insert(name, switch);
+        self.selector.select(self.selector.size());
+        self.selector.do_callback();
+    }
+    /// Event response that removes a selected switch from the page
+    ///
+    /// It fails and shows error to an user if the switch is used in a choice
+    pub fn remove(&mut self, page: &mut Page) {
+        let selected = match self.selected() {
+            Some(s) => s,
+            None => {
+                println!("Error: Tried to remove selected switch but found no selection");
+                return;
+            }
+        };
+        if page.switches.contains_key(&selected) == false {
+            return;
+        }
+        if page.choices.iter().any(|x| x.switch == selected) {
+            signal_error!(
+                "Cannot remove Switch {} because it's used in one or more of Page's Choices",
+                selected
+            );
+            return;
+        }
+
+        if ask_to_confirm(&format!(
+            "Are you sure you want to delete {} Switch?",
+            &selected
+        )) {
+            page.switches.remove(&selected);
+            self.populate(&page.switches, &page.results);
+        }
+    }
+}
@@ -1,10 +1,11 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use fltk::{
     app,
     browser::SelectBrowser,
     button::Button,
     draw::Rect,
+    enums::{Align, Color},
     frame::Frame,
     group::Group,
     image::SvgImage,
@@ -13,11 +14,13 @@ use fltk::{
 };
 
 use crate::{
-    adventure::{Comparison, Page, StoryResult, Test},
+    adventure::{Comparison, Page, Record, StoryResult, Test},
     dialog::{ask_for_text, ask_to_confirm},
     editor::{variables::variable_receiver, highlight_color},
+    evaluation::{compare_ranges, expression_range, ExpressionRange, Rounding},
     file::signal_error,
     icons::{BIN_ICON, GEAR_ICON},
+    lang::tr,
     widgets::find_item,
 };
 
@@ -38,12 +41,73 @@ pub struct TestEditor {
     success_label: Frame,
     failure: fltk::menu::Choice,
     failure_label: Frame,
+    /// Shows values the expressions can evaluate to
+    ranges: Frame,
+    /// Starting values of records and rounding of the adventure, used to find the ranges
+    defaults: Rc<RefCell<(HashMap<String, Record>, Rounding)>>,
+}
+
+/// Finds the range of an expression, empty expressions have none
+fn find_range(
+    exp: &str,
+    defaults: &(HashMap<String, Record>, Rounding),
+) -> Option<Result<ExpressionRange, String>> {
+    if exp.trim().len() == 0 {
+        return None;
+    }
+    Some(expression_range(exp, &defaults.0, defaults.1).map_err(|e| e.to_string()))
+}
+/// Shows ranges of both expressions of a test, and whatever the comparison can ever go both ways
+fn show_ranges(
+    label: &mut Frame,
+    left: &str,
+    right: &str,
+    comparison: Option<String>,
+    defaults: &(HashMap<String, Record>, Rounding),
+) {
+    let (left, right) = match (find_range(left, defaults), find_range(right, defaults)) {
+        (Some(l), Some(r)) => (l, r),
+        _ => {
+            label.set_label("");
+            return;
+        }
+    };
+    let describe = |x: &Result<ExpressionRange, String>| match x {
+        Ok(r) => r.to_string(),
+        Err(e) => e.clone(),
+    };
+    let mut text = format!(
+        "{} {}, {} {}",
+        tr("left:"),
+        describe(&left),
+        tr("right:"),
+        describe(&right)
+    );
+    let outcome = match (&left, &right, comparison) {
+        (Ok(l), Ok(r), Some(c)) => compare_ranges(l, r, &Comparison::from(c)),
+        _ => None,
+    };
+    match outcome {
+        Some(true) => text = format!("{} - {}", text, tr("always true")),
+        Some(false) => text = format!("{} - {}", text, tr("always false")),
+        None => {}
+    }
+    if outcome.is_some() || left.is_err() || right.is_err() {
+        label.set_label_color(Color::Red);
+    } else {
+        label.set_label_color(Color::Foreground);
+    }
+    label.set_label(&text);
+    // the label is drawn over the group, so old text is only cleared by redrawing the group
+    if let Some(mut parent) = label.parent() {
+        parent.redraw();
+    }
 }
 
 impl TestEditor {
     /// Creates UI for editing tests of a page
     pub fn new(area: Rect) -> Self {
-        let group = Group::new(area.x, area.y, area.w, area.h, "Tests");
+        let group = Group::new(area.x, area.y, area.w, area.h, tr("Tests"));
 
         let font_size = app::font_size();
 
@@ -60,6 +124,8 @@ impl TestEditor {
         let x_ren = x_add + w_butt;
         let x_rem = x_selector + w_selector - w_butt;
         let x_help = x_ren + w_butt * 2;
+        let x_copy = x_help + w_butt * 2;
+        let x_paste = x_copy + w_butt * 3;
 
         let column_margin = 20;
         let x_second_column = x_selector + w_selector + column_margin;
@@ -77,27 +143,29 @@ impl TestEditor {
         let w_comp = w_second_column / 2;
 
         let mut selector =
-            SelectBrowser::new(x_selector, y_selector, w_selector, h_selector, "Tests");
+            SelectBrowser::new(x_selector, y_selector, w_selector, h_selector, tr("Tests"));
 
         let mut add = Button::new(x_add, y_butt, w_butt, h_butt, "@+");
         let mut ren = Button::new(x_ren, y_butt, w_butt, h_butt, None);
         let mut rem = Button::new(x_rem, y_butt, w_butt, h_butt, None);
         let mut help = Button::new(x_help, y_butt, w_butt, h_butt, "?");
+        let mut copy = Button::new(x_copy, y_butt, w_butt * 3, h_butt, tr("Copy"));
+        let mut paste = Button::new(x_paste, y_butt, w_butt * 3, h_butt, tr("Paste"));
 
-        let name = Frame::new(x_second_column, y_name, w_second_column, h_line, "Name");
+        let name = Frame::new(x_second_column, y_name, w_second_column, h_line, tr("Name"));
         let mut expression_left = TextEditor::new(
             x_second_column,
             y_exp,
             w_second_column,
             h_line,
-            "Left side expression",
+            tr("Left side expression"),
         );
         let mut expression_right = TextEditor::new(
             x_second_column,
             y_exp2,
             w_second_column,
             h_line,
-            "Right side expression",
+            tr("Right side expression"),
         );
         let mut comparison = fltk::menu::Choice::new(x_comp, y_comp, w_comp, h_line, None);
         let success_label = Frame::new(
@@ -105,7 +173,7 @@ impl TestEditor {
             y_result_success - font_size,
             w_second_column,
             h_line,
-            "On Success",
+            tr("On Success"),
         );
         let success = fltk::menu::Choice::new(
             x_second_column,
@@ -119,7 +187,7 @@ impl TestEditor {
             y_result_failure - font_size,
             w_second_column,
             h_line,
-            "On Failure",
+            tr("On Failure"),
         );
         let failure = fltk::menu::Choice::new(
             x_second_column,
@@ -128,6 +196,7 @@ impl TestEditor {
             h_line,
             None,
         );
+        let mut ranges = Frame::new(x_second_column, y_exp2 + h_line, w_second_column, h_line, None);
         group.end();
 
         let (sender, _r) = app::channel();
@@ -135,6 +204,12 @@ impl TestEditor {
         add.emit(sender.clone(), emit!(Event::AddTest));
         ren.emit(sender.clone(), emit!(Event::RenameTest));
         rem.emit(sender.clone(), emit!(Event::RemoveTest));
+        copy.emit(sender.clone(), emit!(Event::CopyTest));
+        paste.emit(sender.clone(), emit!(Event::PasteTest));
+        copy.set_label_size(font_size - 4);
+        paste.set_label_size(font_size - 4);
+        copy.set_tooltip(tr("Copies the selected Test so it can be pasted into another page"));
+        paste.set_tooltip(tr("Pastes the copied Test into this page"));
         help.emit(sender.clone(), help!("test"));
         help.set_frame(fltk::enums::FrameType::RoundUpBox);
         help.set_color(highlight_color!());
@@ -164,11 +239,45 @@ impl TestEditor {
         ren.set_image(Some(gear));
         rem.set_image(Some(bin));
 
-        expression_left.set_buffer(TextBuffer::default());
-        expression_right.set_buffer(TextBuffer::default());
+        let mut buffer_left = TextBuffer::default();
+        let mut buffer_right = TextBuffer::default();
         comparison.add_choice(&Comparison::as_choice());
         comparison.set_value(0);
 
+        // ranges are found again whenever any part of the test changes
+        let defaults = Rc::new(RefCell::new((HashMap::new(), Rounding::default())));
+        let mut update = {
+            let buffer_left = buffer_left.clone();
+            let buffer_right = buffer_right.clone();
+            let comparison = comparison.clone();
+            let mut ranges = ranges.clone();
+            let defaults = defaults.clone();
+            move || {
+                show_ranges(
+                    &mut ranges,
+                    &buffer_left.text(),
+                    &buffer_right.text(),
+                    comparison.choice(),
+                    &defaults.borrow(),
+                )
+            }
+        };
+        buffer_left.add_modify_callback({
+            let mut update = update.clone();
+            move |_, _, _, _, _| update()
+        });
+        buffer_right.add_modify_callback({
+            let mut update = update.clone();
+            move |_, _, _, _, _| update()
+        });
+        comparison.set_callback(move |_| update());
+        expression_left.set_buffer(buffer_left);
+        expression_right.set_buffer(buffer_right);
+        ranges.set_align(Align::Inside.union(Align::Left));
+        ranges.set_tooltip(tr(
+            "Values the expressions can have with records at their starting values",
+        ));
+
         variable_receiver!(expression_left);
         variable_receiver!(expression_right);
 
@@ -182,8 +291,25 @@ impl TestEditor {
             success_label,
             failure,
             failure_label,
+            ranges,
+            defaults,
         }
     }
+    /// Sets starting values of records and rounding used to find ranges of the expressions
+    pub fn set_defaults(&mut self, records: &HashMap<String, Record>, rounding: Rounding) {
+        *self.defaults.borrow_mut() = (records.clone(), rounding);
+        self.refresh_ranges();
+    }
+    /// Shows ranges of the expressions currently in the editor
+    fn refresh_ranges(&mut self) {
+        show_ranges(
+            &mut self.ranges,
+            &self.expression_left.buffer().unwrap().text(),
+            &self.expression_right.buffer().unwrap().text(),
+            self.comparison.choice(),
+            &self.defaults.borrow(),
+        );
+    }
     /// Loads provided test into UI
     fn load_ui(&mut self, test: &Test) {
         self.name.set_label(&test.name);
@@ -202,6 +328,7 @@ impl TestEditor {
             .unwrap()
             .set_text(&test.expression_r);
         self.comparison.set_value(test.comparison.to_index());
+        self.refresh_ranges();
         let mut i = 0;
         self.success.set_value(-1);
         if self.success.size() > 0 {
@@ -263,6 +390,7 @@ impl TestEditor {
         self.failure.hide();
         self.success_label.hide();
         self.failure_label.hide();
+        self.ranges.hide();
         self.expression_left.buffer().unwrap().set_text("");
         self.expression_right.buffer().unwrap().set_text("");
         self.name.set_label("");
@@ -280,6 +408,7 @@ impl TestEditor {
         self.failure.show();
         self.success_label.show();
         self.failure_label.show();
+        self.ranges.show();
     }
     /// Returns text of currently selected item, or None if nothing is selected
     fn selected(&self) -> Option<String> {
@@ -318,6 +447,20 @@ impl TestEditor {
             self.load_ui(test);
         }
     }
+    /// Returns a copy of the selected test
+    pub fn copy(&self, tests: &HashMap<String, Test>) -> Option<Test> {
+        tests.get(&self.selected()?).cloned()
+    }
+    /// Adds a test copied from another page into the collection and selects it
+    ///
+    /// The test's name must not be already used in the collection
+    pub fn paste(&mut self, tests: &mut HashMap<String, Test>, test: Test) {
+        let name = test.name.clone();
+        tests.insert(name.clone(), test);
+        self.selector.add(&name);
+        self.selector.select(self.selector.size());
+        self.selector.do_callback();
+    }
     /// Event response renaming currently selected test
     ///
     /// It also updates the test in choices in the page
@@ -13,6 +13,7 @@ type HandleEvent = fltk::enums::Event;
 use crate::{
     adventure::{create_keyword, Name, Record},
     icons::{BIN_ICON, GEAR_ICON},
+    lang::tr,
 };
 
 use super::{emit, help, highlight_color, Event};
@@ -66,11 +67,19 @@ impl VariableEditor {
         let (s, _r) = app::channel();
 
         if is_record {
-            button.set_label("Add Record");
+            button.set_label(tr("Add Record"));
             button.emit(s.clone(), emit!(Event::AddRecord));
+            let x_export = x_help + w_help + 10;
+            let w_export = (area.x + area.w - x_export) / 2;
+            let mut export = Button::new(x_export, y, w_export, h, tr("Export CSV"));
+            export.set_tooltip(tr("Saves the records into a file that can be opened in a spreadsheet"));
+            export.emit(s.clone(), emit!(Event::ExportRecords));
+            let mut import = Button::new(x_export + w_export, y, w_export, h, tr("Import"));
+            import.set_tooltip(tr("Copies records and names from another adventure"));
+            import.emit(s.clone(), emit!(Event::ImportVariables));
             help.emit(s, help!("variable-record"));
         } else {
-            button.set_label("Add Name");
+            button.set_label(tr("Add Name"));
             button.emit(s.clone(), emit!(Event::AddName));
             help.emit(s, help!("variable-name"));
         }
@@ -94,7 +103,8 @@ impl VariableEditor {
     /// variable: Name to display in the editor
     /// extra: Extra part of the label shown in brackets
     /// inserter: Whatever to create a quick insert button for text editors or not
-    fn add_line(&mut self, variable: &String, extra: &String, inserter: bool) {
+    /// dimmed: Whatever to draw the label with inactive color, used for records hidden from the player
+    fn add_line(&mut self, variable: &String, extra: &String, inserter: bool, dimmed: bool) {
         let child_count = self.children;
 
         let mut x = self.scroll.x();
@@ -135,6 +145,10 @@ impl VariableEditor {
         let mut label = Frame::new(x, y, w, h, None);
         label.set_frame(FrameType::EngravedFrame);
         label.set_label(variable);
+        if dimmed {
+            label.set_label_color(label.label_color().inactive());
+            label.set_tooltip(tr("Hidden from the player"));
+        }
 
         let mut extra_label = Frame::new(x, y, w, h, None);
         extra_label.set_align(Align::Inside.union(Align::Left));
@@ -188,13 +202,14 @@ impl VariableEditor {
     pub fn add_record(&mut self, record: &Record, inserter: bool) {
         let extra = match record.category.as_str() {
             "" => record.value_as_string(),
+            _ if record.is_derived() => format!("{}, {}", record.category, record.expression),
             x => format!("{}, {}", x, record.value_as_string()),
         };
-        self.add_line(&record.name, &extra, inserter);
+        self.add_line(&record.name, &extra, inserter, record.is_hidden());
     }
     /// Creates a new line with all the necessary controls for the Name
     pub fn add_name(&mut self, name: &Name, inserter: bool) {
-        self.add_line(&name.keyword, &name.value, inserter);
+        self.add_line(&name.keyword, &name.value, inserter, false);
     }
     /// Displays the editor
     pub fn show(&mut self) {
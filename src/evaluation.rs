@@ -13,6 +13,10 @@ pub enum EvaluationError {
     NotANumber(String),
     InvalidDieExpression(String),
     MissingDicePoolEvaluator(String),
+    /// Record keyword is missing its opening or closing square bracket
+    UnbalancedBrackets(String),
+    /// Derived record is computed from itself, directly or through other derived records
+    CircularRecord(String),
 }
 
 impl Display for EvaluationError {
@@ -30,17 +34,98 @@ impl Display for EvaluationError {
                 "{} is not a valid dice pool expression, use something like 4d6p4",
                 n
             ),
+            EvaluationError::UnbalancedBrackets(n) => {
+                write!(f, "{} has a square bracket without its pair", n)
+            }
+            EvaluationError::CircularRecord(n) => {
+                write!(f, "Derived record {} is computed from itself", n)
+            }
+        }
+    }
+}
+/// Determines how results of division are rounded to whole numbers
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// Drops the fractional part, rounding toward zero
+    #[default]
+    Truncate,
+    Floor,
+    Ceil,
+    /// Rounds to the nearest number, halves are rounded up
+    HalfUp,
+}
+impl Rounding {
+    /// Converts a keyword used in adventure metadata into rounding mode
+    ///
+    /// Returns None if the keyword isn't recognized
+    pub fn from_keyword(keyword: &str) -> Option<Rounding> {
+        match keyword.trim() {
+            "truncate" => Some(Rounding::Truncate),
+            "floor" => Some(Rounding::Floor),
+            "ceil" => Some(Rounding::Ceil),
+            "round-half-up" => Some(Rounding::HalfUp),
+            _ => None,
+        }
+    }
+    /// Returns a string suitable to use in FLTK Choice widget
+    pub fn as_choice() -> String {
+        "truncate|floor|ceil|round-half-up".to_string()
+    }
+    /// Converts the rounding to a number usable for indexing values in FLTK Choice widget
+    pub fn to_index(&self) -> i32 {
+        match self {
+            Rounding::Truncate => 0,
+            Rounding::Floor => 1,
+            Rounding::Ceil => 2,
+            Rounding::HalfUp => 3,
+        }
+    }
+    /// Divides two numbers, rounding the result according to the mode
+    ///
+    /// The divisor must not be zero
+    pub fn divide(&self, l: i32, r: i32) -> i32 {
+        let exact = l as f64 / r as f64;
+        match self {
+            Rounding::Truncate => l / r,
+            Rounding::Floor => exact.floor() as i32,
+            Rounding::Ceil => exact.ceil() as i32,
+            Rounding::HalfUp => (exact + 0.5).floor() as i32,
+        }
+    }
+}
+impl Display for Rounding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rounding::Truncate => write!(f, "truncate"),
+            Rounding::Floor => write!(f, "floor"),
+            Rounding::Ceil => write!(f, "ceil"),
+            Rounding::HalfUp => write!(f, "round-half-up"),
         }
     }
 }
 /// Evaluates expression into a number, taking care of randomness and record evaluation
 ///
+/// Results of division are rounded according to the rounding mode
+///
+/// Derived records are evaluated from their expressions, dice in them always roll the same
+///
 /// # Errors
 /// If the expression can't be evaluated or contains undefined records or calculations then an error will be returned instead.
 pub fn evaluate_expression(
     exp: &str,
     records: &HashMap<String, Record>,
     rand: &mut Random,
+    rounding: Rounding,
+) -> Result<i32, EvaluationError> {
+    evaluate_deriving(exp, records, rand, rounding, &[])
+}
+/// Evaluates expression into a number while derived records listed in deriving are being evaluated
+fn evaluate_deriving(
+    exp: &str,
+    records: &HashMap<String, Record>,
+    rand: &mut Random,
+    rounding: Rounding,
+    deriving: &[&str],
 ) -> Result<i32, EvaluationError> {
     // before we start processing the expression, we need to go through it in search of brackets, so those are processed first.
     // best way to do it is to use recursion, this should also handle nested brackets.
@@ -50,22 +135,28 @@ pub fn evaluate_expression(
         while let Some(c) = reg.captures(&exp) {
             let whole = c.get(0).unwrap();
             let part = c.get(1).unwrap();
-            let ev = evaluate_expression(part.as_str(), records, rand)?;
+            let ev = evaluate_deriving(part.as_str(), records, rand, rounding, deriving)?;
             exp.replace_range(whole.range(), &ev.to_string());
         }
     }
-    let tokens: Vec<&str> = exp
-        .split_inclusive(&['+', '-', '*', '/'][..])
-        .map(|x| x.trim())
-        .collect();
+    let tokens: Vec<&str> = split_operators(&exp).into_iter().map(|x| x.trim()).collect();
     // this function evaluates name of a record into its value, it defaults to 0 on records not found
     // Although, record not found should probably result in an error instead of 0
     let eval_rec = |x: &str| {
         let expected = x.replace("[", "").replace("]", "");
-        if let Some(v) = records.get(&expected) {
-            return v.value_as_string();
+        match records.get(&expected) {
+            Some(v) if v.is_derived() => {
+                if deriving.contains(&expected.as_str()) {
+                    return Err(EvaluationError::CircularRecord(expected));
+                }
+                let mut path = deriving.to_vec();
+                path.push(&expected);
+                evaluate_deriving(&v.expression, records, &mut Random::new(0), rounding, &path)
+                    .map(|x| x.to_string())
+            }
+            Some(v) => Ok(v.value_as_string()),
+            None => Ok("0".to_string()),
         }
-        return "0".to_string();
     };
     // This closure turns a die expression into evaluated form <i32> based on parameters
     let mut eval_die = |x: &str, typ: char, pool: Option<char>| {
@@ -100,6 +191,12 @@ pub fn evaluate_expression(
             }
         }
 
+        // rolling needs at least one die with at least one side, and pools a threshold of at least one
+        // the highest roll also has to fit into a number
+        if r.iter().any(|x| *x < 1) || r[0].checked_mul(r[1]).is_none() {
+            return Err(EvaluationError::InvalidDieExpression(x.to_string()));
+        }
+
         // matching types to dice rolls
         match typ {
             'd' => match pool {
@@ -146,15 +243,21 @@ pub fn evaluate_expression(
     for tok in tokens {
         if tok == "-" {
             // special handing of -1 situations, like in expression 1d20*-1
-            ops.push((-1, '*', 2));
+            // it binds tighter than other operations so 10/-2 divides by -2 instead of dividing by -1 and multiplying by 2
+            ops.push((-1, '*', 3));
             continue;
         }
         let mut exp: String;
         let op;
         let op_priority;
+        // an empty token comes from an expression that is empty or ends with an operator followed by spaces
+        let last = match tok.chars().last() {
+            Some(c) => c,
+            None => return Err(EvaluationError::NotANumber(tok.to_string())),
+        };
         // First we asses what operation the token needs to perform
         // If the token doesn't have an operation, then it's assumed it's the last token
-        match tok.chars().last().unwrap() {
+        match last {
             '+' => {
                 op = '+';
                 op_priority = 1;
@@ -184,11 +287,13 @@ pub fn evaluate_expression(
         }
 
         // test if the token has a record name in it, and turn it into a number
-        while exp.contains('[') {
-            let start = exp.find(|x| x == '[').unwrap();
-            let end = exp.find(|x| x == ']').unwrap();
+        while let Some(start) = exp.find('[') {
+            let end = match exp[start..].find(']') {
+                Some(e) => start + e,
+                None => return Err(EvaluationError::UnbalancedBrackets(tok.to_string())),
+            };
             let val = &exp[start..=end];
-            let ev = eval_rec(val);
+            let ev = eval_rec(val)?;
             exp.replace_range(start..=end, &ev);
         }
 
@@ -201,8 +306,11 @@ pub fn evaluate_expression(
                 .collect();
             loop {
                 // we take first and next expression to evaluate together
-                let mut this = split_exp.pop_front().unwrap();
-                let mut next = split_exp.pop_front().unwrap();
+                // a roll followed by l or h needs another roll to be compared with, like 1d20l1d20
+                let (mut this, mut next) = match (split_exp.pop_front(), split_exp.pop_front()) {
+                    (Some(t), Some(n)) => (t, n),
+                    _ => return Err(EvaluationError::InvalidDieExpression(tok.to_string())),
+                };
                 // last char of the expression is the evaluation type
                 let hi_or_lo = this.chars().last().unwrap();
                 let hi_or_lo_next = next.chars().last().unwrap();
@@ -246,16 +354,19 @@ pub fn evaluate_expression(
         }
     }
 
-    // going through operations, evaluating one at a time until only one remains or we encounter error
+    if ops.len() == 0 {
+        return Err(EvaluationError::NotANumber(exp));
+    }
+    // going through operations left to right, evaluating one at a time until only one remains or we encounter error
     let mut i = 0;
     loop {
         // if it's the only operation remaining, then we have the result
         if ops.len() == 1 {
             return Ok(ops[0].0);
         }
-        // if we reached the end of the operations, we wrap around and start from the beginning
+        // the last value has no operation so it can always be calculated, reaching it means the expression ends with an operator
         if i == ops.len() - 1 {
-            i = 0;
+            return Err(EvaluationError::NotANumber(exp));
         }
         // peeking at operations to see if we can calculate something
         let l = &ops[i];
@@ -270,19 +381,44 @@ pub fn evaluate_expression(
                     if r.0 == 0 {
                         return Err(EvaluationError::DivisionByZero);
                     }
-                    (l.0 / r.0, r.1, r.2)
+                    (rounding.divide(l.0, r.0), r.1, r.2)
                 }
                 _ => unreachable!(),
             };
             ops.remove(i + 1);
             ops.remove(i);
             ops.insert(i, r);
+            // the result may now be calculated with the operation before it, like 1-2*3+4 after the multiplication
+            i = i.saturating_sub(1);
         } else {
             // there's mismatch in priorities, so we skip to the next operation
             i += 1;
         }
     }
 }
+/// Splits the expression after each operator, keeping the operator at the end of the token
+///
+/// Record keywords in square brackets are never split, so keywords can contain spaces and operators
+fn split_operators(exp: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut in_keyword = false;
+    for (i, c) in exp.char_indices() {
+        match c {
+            '[' => in_keyword = true,
+            ']' => in_keyword = false,
+            '+' | '-' | '*' | '/' if in_keyword == false => {
+                tokens.push(&exp[start..=i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < exp.len() {
+        tokens.push(&exp[start..]);
+    }
+    tokens
+}
 /// Evaluates two expressions and compares them to each other.
 ///
 /// If any of the expressions can't be evaluated, error is returned
@@ -292,22 +428,158 @@ pub fn evaluate_and_compare(
     comp: &Comparison,
     records: &HashMap<String, Record>,
     rand: &mut Random,
+    rounding: Rounding,
 ) -> Result<bool, EvaluationError> {
+    let (l, r) = evaluate_operands(lhe, rhe, records, rand, rounding)?;
+    return Ok(comp.compare(l, r));
+}
+/// Evaluates both expressions of a comparison and returns their values without comparing them
+///
+/// The left expression is evaluated first, so random values are generated in the same order as in evaluate_and_compare
+pub fn evaluate_operands(
+    lhe: &str,
+    rhe: &str,
+    records: &HashMap<String, Record>,
+    rand: &mut Random,
+    rounding: Rounding,
+) -> Result<(i32, i32), EvaluationError> {
     let l;
     let r;
-    match evaluate_expression(lhe, records, rand) {
+    match evaluate_expression(lhe, records, rand, rounding) {
         Ok(v) => l = v,
         Err(e) => return Err(e),
     }
-    match evaluate_expression(rhe, records, rand) {
+    match evaluate_expression(rhe, records, rand, rounding) {
         Ok(v) => r = v,
         Err(e) => return Err(e),
     }
-    return Ok(comp.compare(l, r));
+    return Ok((l, r));
+}
+/// Lowest and highest values an expression can evaluate to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpressionRange {
+    pub min: i32,
+    pub max: i32,
+    /// Exploding dice can roll above the highest value
+    pub open: bool,
+}
+impl ExpressionRange {
+    /// Highest value the expression can reach, without a limit if the range is open
+    fn upper(&self) -> i32 {
+        if self.open {
+            i32::MAX
+        } else {
+            self.max
+        }
+    }
+}
+impl Display for ExpressionRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.min == self.max && self.open == false {
+            return write!(f, "{}", self.min);
+        }
+        write!(f, "{}–{}", self.min, self.max)?;
+        if self.open {
+            write!(f, "+")?;
+        }
+        Ok(())
+    }
+}
+/// Most rolls an expression can have for every combination of their lowest and highest values to be tried
+///
+/// Expressions with more rolls are only evaluated with all rolls at their lowest and all at their highest
+const MAX_RANGE_ROLLS: u32 = 12;
+
+/// Finds the lowest and highest values the expression can evaluate to, with records at their current values
+///
+/// Every combination of lowest and highest values of the rolls is evaluated, which gives the exact range as long as each roll only ever raises or lowers the result.
+/// Exploding dice are counted without explosions and make the range open.
+///
+/// # Errors
+/// Returns error if the expression can't be evaluated, division by zero is only returned if no combination of rolls avoids it
+pub fn expression_range(
+    exp: &str,
+    records: &HashMap<String, Record>,
+    rounding: Rounding,
+) -> Result<ExpressionRange, EvaluationError> {
+    // the first pass counts the rolls, so it's known how many combinations there are
+    let mut rand = Random::extremes(0);
+    let _ = evaluate_expression(exp, records, &mut rand, rounding);
+    // without a count only the lowest and highest values of all rolls are tried
+    let (rolls, open) = rand
+        .extremes
+        .map_or((u32::MAX, false), |e| (e.rolls, e.exploding));
+    let combinations: Vec<u32> = if rolls > MAX_RANGE_ROLLS {
+        vec![0, u32::MAX]
+    } else {
+        (0..1u32 << rolls).collect()
+    };
+
+    let mut range: Option<(i32, i32)> = None;
+    for picks in combinations {
+        let mut rand = Random::extremes(picks);
+        match evaluate_expression(exp, records, &mut rand, rounding) {
+            Ok(v) => range = Some(range.map_or((v, v), |r| (r.0.min(v), r.1.max(v)))),
+            Err(EvaluationError::DivisionByZero) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    match range {
+        Some((min, max)) => Ok(ExpressionRange { min, max, open }),
+        None => Err(EvaluationError::DivisionByZero),
+    }
+}
+/// Tells if comparing values from the two ranges always gives the same answer
+///
+/// Returns None if the answer depends on the rolled values
+pub fn compare_ranges(
+    l: &ExpressionRange,
+    r: &ExpressionRange,
+    comp: &Comparison,
+) -> Option<bool> {
+    let corners = [
+        (l.min, r.min),
+        (l.min, r.upper()),
+        (l.upper(), r.min),
+        (l.upper(), r.upper()),
+    ];
+    match comp {
+        // values in between the extremes can be equal even if none of the extremes are
+        Comparison::Equal | Comparison::NotEqual => {
+            if l.min > r.upper() || r.min > l.upper() {
+                Some(*comp == Comparison::NotEqual)
+            } else if corners.iter().all(|x| x.0 == x.1) {
+                Some(*comp == Comparison::Equal)
+            } else {
+                None
+            }
+        }
+        _ => {
+            let results: Vec<bool> = corners.iter().map(|x| comp.compare(x.0, x.1)).collect();
+            if results.iter().all(|x| *x) {
+                Some(true)
+            } else if results.iter().all(|x| *x == false) {
+                Some(false)
+            } else {
+                None
+            }
+        }
+    }
 }
 /// Provides various functionality for generating random semi-predictable numbers
 pub struct Random {
     generator: StdRng,
+    /// When set, rolls give their lowest or highest value instead of a random one
+    extremes: Option<Extremes>,
+}
+/// Choices of lowest or highest values for consecutive rolls, used to find ranges of expressions
+#[derive(Clone, Copy)]
+struct Extremes {
+    /// One bit for each roll, set bits give the highest value
+    picks: u32,
+    rolls: u32,
+    /// Whatever any of the rolls were exploding dice
+    exploding: bool,
 }
 impl Random {
     /// Creates a new random number generator from seed
@@ -316,8 +588,33 @@ impl Random {
     pub fn new(seed: u64) -> Self {
         Self {
             generator: StdRng::seed_from_u64(seed),
+            extremes: None,
         }
     }
+    /// Creates a generator that gives rolls their lowest or highest values as chosen by bits of picks
+    ///
+    /// Rolls past the 32nd are highest only if all bits are set
+    fn extremes(picks: u32) -> Self {
+        Self {
+            generator: StdRng::seed_from_u64(0),
+            extremes: Some(Extremes {
+                picks,
+                rolls: 0,
+                exploding: false,
+            }),
+        }
+    }
+    /// Returns the lowest or highest value for the next roll, or None if rolls are random
+    fn extreme(&mut self, min: i32, max: i32) -> Option<i32> {
+        let e = self.extremes.as_mut()?;
+        let high = if e.rolls < 32 {
+            (e.picks >> e.rolls) & 1 == 1
+        } else {
+            e.picks == u32::MAX
+        };
+        e.rolls += 1;
+        Some(if high { max } else { min })
+    }
     /// Generates a random values between 'amount' and 'amount' times 'sides', simulating rolling that many dice
     ///
     /// # Error
@@ -327,6 +624,9 @@ impl Random {
         assert!(sides > 0);
         let min = amount;
         let max = amount * sides;
+        if let Some(v) = self.extreme(min, max) {
+            return v;
+        }
         self.generator.gen_range(min..=max)
     }
     /// Generates a random value 'amount' times in range of 1 to 'sides' and counts how many of those are at or above 'threshold' and returns that count
@@ -337,6 +637,9 @@ impl Random {
         assert!(sides > 0);
         assert!(amount > 0);
         assert!(threshold > 0);
+        if let Some(v) = self.extreme(0, amount) {
+            return v;
+        }
         let mut res = 0;
         for _ in 0..amount {
             if self.die(1, sides) >= threshold {
@@ -353,6 +656,9 @@ impl Random {
         assert!(sides > 0);
         assert!(amount > 0);
         assert!(threshold > 0);
+        if let Some(v) = self.extreme(0, amount) {
+            return v;
+        }
 
         let mut res = 0;
         for _ in 0..amount {
@@ -369,6 +675,12 @@ impl Random {
     pub fn die_explode(&mut self, amount: i32, sides: i32) -> i32 {
         assert!(amount > 0);
         assert!(sides > 0);
+        if let Some(e) = self.extremes.as_mut() {
+            e.exploding = true;
+        }
+        if let Some(v) = self.extreme(amount, amount * sides) {
+            return v;
+        }
 
         let mut counter = 0;
         for _ in 0..amount {
@@ -391,7 +703,10 @@ mod tests {
 
     use crate::adventure::{Comparison, Record};
 
-    use super::{evaluate_and_compare, evaluate_expression, Random};
+    use super::{
+        compare_ranges, evaluate_and_compare, evaluate_expression, expression_range, EvaluationError,
+        Random, Rounding,
+    };
 
     #[test]
     fn evex_dice_regular() {
@@ -406,7 +721,7 @@ mod tests {
         });
         assert_eq!(
             test.die(1, 4),
-            evaluate_expression("1d4", &records, &mut rand).unwrap()
+            evaluate_expression("1d4", &records, &mut rand, Rounding::Truncate).unwrap()
         );
     }
     #[test]
@@ -423,7 +738,7 @@ mod tests {
 
         assert_eq!(
             test.die(4, 6),
-            evaluate_expression("[strength]d6", &records, &mut rand).unwrap()
+            evaluate_expression("[strength]d6", &records, &mut rand, Rounding::Truncate).unwrap()
         );
     }
     #[test]
@@ -440,7 +755,7 @@ mod tests {
 
         assert_eq!(
             test.die(6, 4),
-            evaluate_expression("6d[strength]", &records, &mut rand).unwrap()
+            evaluate_expression("6d[strength]", &records, &mut rand, Rounding::Truncate).unwrap()
         );
     }
     #[test]
@@ -451,7 +766,7 @@ mod tests {
         let records = HashMap::<String, Record>::new();
         assert_eq!(
             test.pool(2, 6, 4),
-            evaluate_expression("2d6p4", &records, &mut rand).unwrap()
+            evaluate_expression("2d6p4", &records, &mut rand, Rounding::Truncate).unwrap()
         );
     }
     #[test]
@@ -462,7 +777,7 @@ mod tests {
         let records = HashMap::<String, Record>::new();
         assert_eq!(
             test.pool_reverse(2, 6, 4),
-            evaluate_expression("2d6q4", &records, &mut rand).unwrap()
+            evaluate_expression("2d6q4", &records, &mut rand, Rounding::Truncate).unwrap()
         );
     }
     #[test]
@@ -473,7 +788,7 @@ mod tests {
         let records = HashMap::<String, Record>::new();
         assert_eq!(
             test.die_explode(2, 6),
-            evaluate_expression("2x6", &records, &mut rand).unwrap()
+            evaluate_expression("2x6", &records, &mut rand, Rounding::Truncate).unwrap()
         );
     }
     #[test]
@@ -484,7 +799,7 @@ mod tests {
         let records = HashMap::<String, Record>::new();
         assert_eq!(
             test.die(1, 10) + 5,
-            evaluate_expression("1d10+5", &records, &mut rand).unwrap()
+            evaluate_expression("1d10+5", &records, &mut rand, Rounding::Truncate).unwrap()
         );
     }
     #[test]
@@ -495,7 +810,7 @@ mod tests {
         let records = HashMap::<String, Record>::new();
         assert_eq!(
             test.die(2, 4) / 2,
-            evaluate_expression("2d4/2", &records, &mut rand).unwrap()
+            evaluate_expression("2d4/2", &records, &mut rand, Rounding::Truncate).unwrap()
         );
     }
     #[test]
@@ -506,7 +821,7 @@ mod tests {
         let records = HashMap::<String, Record>::new();
         assert_eq!(
             test.die(1, 4) * test.die(1, 4),
-            evaluate_expression("1d4*1d4", &records, &mut rand).unwrap()
+            evaluate_expression("1d4*1d4", &records, &mut rand, Rounding::Truncate).unwrap()
         );
     }
     #[test]
@@ -517,7 +832,7 @@ mod tests {
         let records = HashMap::<String, Record>::new();
         assert_eq!(
             i32::min(test.die(1, 20), test.die(1, 20)),
-            evaluate_expression("1d20l1d20", &records, &mut rand).unwrap()
+            evaluate_expression("1d20l1d20", &records, &mut rand, Rounding::Truncate).unwrap()
         );
     }
     #[test]
@@ -528,7 +843,7 @@ mod tests {
         let records = HashMap::<String, Record>::new();
         assert_eq!(
             i32::max(test.die(1, 20), test.die(1, 20)),
-            evaluate_expression("1d20h1d20", &records, &mut rand).unwrap()
+            evaluate_expression("1d20h1d20", &records, &mut rand, Rounding::Truncate).unwrap()
         );
     }
     #[test]
@@ -538,7 +853,7 @@ mod tests {
 
         let records = HashMap::<String, Record>::new();
         assert_eq!(
-            evaluate_expression("1d20+5*2/3-1", &records, &mut rand).unwrap(),
+            evaluate_expression("1d20+5*2/3-1", &records, &mut rand, Rounding::Truncate).unwrap(),
             test.die(1, 20) + 5 * 2 / 3 - 1
         );
     }
@@ -549,7 +864,7 @@ mod tests {
 
         let records = HashMap::<String, Record>::new();
         assert_eq!(
-            evaluate_expression("1d20*-1", &records, &mut rand).unwrap(),
+            evaluate_expression("1d20*-1", &records, &mut rand, Rounding::Truncate).unwrap(),
             test.die(1, 20) * -1
         );
     }
@@ -558,15 +873,154 @@ mod tests {
         let mut rand = Random::new(69420);
         let records = HashMap::<String, Record>::new();
 
-        let ev = evaluate_expression("2 - 5", &records, &mut rand);
+        let ev = evaluate_expression("2 - 5", &records, &mut rand, Rounding::Truncate);
         assert_eq!(ev, Ok(-3));
     }
     #[test]
+    fn evaluate_left_to_right() {
+        let mut rand = Random::new(69420);
+        let records = HashMap::<String, Record>::new();
+        let cases = [
+            ("8/4/2", 1),
+            ("6/2*3", 9),
+            ("2*3/6", 1),
+            ("12/2/3*4", 8),
+            ("10-2-3", 5),
+            ("10-2+3", 11),
+            ("1-2*3+4", -1),
+            ("20-6/2-1", 16),
+            ("2+3*4-5*2", 4),
+            ("10/-2", -5),
+            ("10/-2*3", -15),
+            ("1--2", 3),
+        ];
+        for (exp, expected) in cases {
+            assert_eq!(
+                evaluate_expression(exp, &records, &mut rand, Rounding::Truncate),
+                Ok(expected),
+                "{}",
+                exp
+            );
+        }
+        assert!(evaluate_expression("3+4*", &records, &mut rand, Rounding::Truncate).is_err());
+    }
+    #[test]
+    fn evaluate_negative_records() {
+        let mut rand = Random::new(69420);
+        let mut test = Random::new(69420);
+        let mut records = HashMap::<String, Record>::new();
+        for (name, value) in [("debt", -3), ("gold", 4)] {
+            let mut r = Record::parse_from_string(name.to_string()).unwrap();
+            r.value = value;
+            records.insert(name.to_string(), r);
+        }
+        let cases = [
+            ("[debt]", -3),
+            ("[debt]*-1", 3),
+            ("[debt] * - 1", 3),
+            ("-[debt]", 3),
+            ("-[gold]", -4),
+            ("5-[debt]", 8),
+            ("5 - [debt] * 2", 11),
+            ("[debt]-5", -8),
+            ("[debt]*[debt]", 9),
+            ("[gold]/[debt]", -1),
+            ("10/[debt]*-1", 3),
+            ("[gold]*-[debt]", 12),
+            ("([debt]-1)*-1", 4),
+            ("5-([debt]*2)", 11),
+            ("[debt]h2", 2),
+            ("[debt]l2", -3),
+        ];
+        for (exp, expected) in cases {
+            assert_eq!(
+                evaluate_expression(exp, &records, &mut rand, Rounding::Truncate),
+                Ok(expected),
+                "{}",
+                exp
+            );
+        }
+        assert_eq!(
+            evaluate_expression("1d20*[debt]", &records, &mut rand, Rounding::Truncate),
+            Ok(test.die(1, 20) * -3)
+        );
+        assert!(evaluate_expression("[debt]d6", &records, &mut rand, Rounding::Truncate).is_err());
+    }
+    #[test]
+    fn evaluate_multi_word_records() {
+        let mut rand = Random::new(69420);
+        let mut test = Random::new(69420);
+        let mut records = HashMap::<String, Record>::new();
+        for (name, value) in [("stuffed animals", 3), ("half-orc", 2), ("a/b test", 4)] {
+            let mut r = Record::parse_from_string(name.to_string()).unwrap();
+            r.value = value;
+            records.insert(name.to_string(), r);
+        }
+        let cases = [
+            ("[stuffed animals]+1", 4),
+            ("1 + [stuffed animals] * 2", 7),
+            ("([stuffed animals]+1)*2", 8),
+            ("[half-orc]", 2),
+            ("[half-orc]-[stuffed animals]", -1),
+            ("-[half-orc]*[a/b test]", -8),
+            ("[a/b test]/[half-orc]", 2),
+            ("[stuffed animals]h[a/b test]", 4),
+        ];
+        for (exp, expected) in cases {
+            assert_eq!(
+                evaluate_expression(exp, &records, &mut rand, Rounding::Truncate),
+                Ok(expected),
+                "{}",
+                exp
+            );
+        }
+        assert_eq!(
+            evaluate_expression("[stuffed animals]d6", &records, &mut rand, Rounding::Truncate),
+            Ok(test.die(3, 6))
+        );
+        assert_eq!(
+            evaluate_expression("[half-orc]d6+[stuffed animals]", &records, &mut rand, Rounding::Truncate),
+            Ok(test.die(2, 6) + 3)
+        );
+    }
+    #[test]
+    fn evaluate_partial_input() {
+        let mut rand = Random::new(69420);
+        let mut records = HashMap::<String, Record>::new();
+        records.insert("gold".to_string(), Record::parse_from_string("gold".to_string()).unwrap());
+        // expressions as they are while being typed must give errors instead of panicking
+        let cases = [
+            ("[", EvaluationError::UnbalancedBrackets("[".to_string())),
+            ("[gold", EvaluationError::UnbalancedBrackets("[gold".to_string())),
+            ("2*][gold", EvaluationError::UnbalancedBrackets("][gold".to_string())),
+            ("1d20l", EvaluationError::InvalidDieExpression("1d20l".to_string())),
+            ("3+1d6h", EvaluationError::InvalidDieExpression("1d6h".to_string())),
+            ("", EvaluationError::NotANumber(String::new())),
+            ("   ", EvaluationError::NotANumber(String::new())),
+            ("1+ ", EvaluationError::NotANumber(String::new())),
+        ];
+        for (exp, expected) in cases {
+            assert_eq!(
+                evaluate_expression(exp, &records, &mut rand, Rounding::Truncate),
+                Err(expected),
+                "{}",
+                exp
+            );
+        }
+        assert_eq!(
+            evaluate_expression("100000d100000", &records, &mut rand, Rounding::Truncate),
+            Err(EvaluationError::InvalidDieExpression("100000d100000".to_string()))
+        );
+        assert!(expression_range("1d20l", &records, Rounding::Truncate).is_err());
+        assert!(expression_range("[gold", &records, Rounding::Truncate).is_err());
+        assert!(expression_range("", &records, Rounding::Truncate).is_err());
+    }
+    #[test]
     fn evaluate_brackets() {
         let mut rand = Random::new(69420);
         let records = HashMap::<String, Record>::new();
 
-        let ev = evaluate_expression("5 * (4 + 1)", &records, &mut rand).unwrap();
+        let ev = evaluate_expression("5 * (4 + 1)", &records, &mut rand, Rounding::Truncate).unwrap();
         let res = 5 * (4 + 1);
         assert_eq!(ev, res);
     }
@@ -576,7 +1030,7 @@ mod tests {
         let records = HashMap::<String, Record>::new();
         let val = "5 * (4 + 1 * (1 + 1) / (20 - (3 * 2)))".to_string();
 
-        let val: i32 = evaluate_expression(&val, &records, &mut rand).unwrap();
+        let val: i32 = evaluate_expression(&val, &records, &mut rand, Rounding::Truncate).unwrap();
         let comp = 5 * (4 + 1 * (1 + 1) / (20 - (3 * 2)));
         assert_eq!(val, comp);
     }
@@ -586,7 +1040,7 @@ mod tests {
         let records = HashMap::<String, Record>::new();
         let val = "5 * (2 - 3)".to_string();
 
-        let val = evaluate_expression(&val, &records, &mut rand).unwrap();
+        let val = evaluate_expression(&val, &records, &mut rand, Rounding::Truncate).unwrap();
         assert_eq!(val, -5);
     }
     #[test]
@@ -600,11 +1054,16 @@ mod tests {
                 category: String::new(),
                 name: "strength".to_string(),
                 value: 13,
+                hidden: false,
+                expression: String::new(),
+                global: false,
+                min: None,
+                max: None,
             },
         );
         let val = "1d20 + ([strength] - 10) / 2";
 
-        let val = evaluate_expression(val, &records, &mut rand).unwrap();
+        let val = evaluate_expression(val, &records, &mut rand, Rounding::Truncate).unwrap();
         assert_eq!(val, test.die(1, 20) + (13 - 10) / 2);
     }
     #[test]
@@ -639,9 +1098,70 @@ mod tests {
         let records = HashMap::<String, Record>::new();
 
         for _ in 0..10 {
-            let c = evaluate_and_compare("1d20", "1d10", &Comparison::Less, &records, &mut rand)
+            let c = evaluate_and_compare("1d20", "1d10", &Comparison::Less, &records, &mut rand, Rounding::Truncate)
                 .unwrap();
             assert_eq!(c, test.die(1, 20) < test.die(1, 10));
         }
     }
+    #[test]
+    fn expression_ranges() {
+        let mut records = HashMap::<String, Record>::new();
+        records.insert("strength".to_string(), {
+            let mut r = Record::parse_from_string("strength".to_string()).unwrap();
+            r.value = 3;
+            r
+        });
+        let range = |exp: &str| expression_range(exp, &records, Rounding::Truncate).unwrap();
+
+        assert_eq!(range("1d20").to_string(), "1–20");
+        assert_eq!(range("10").to_string(), "10");
+        assert_eq!(range("2d6+[strength]").to_string(), "5–15");
+        assert_eq!(range("1d6-1d6").to_string(), "-5–5");
+        assert_eq!(range("[strength]d6*-1").to_string(), "-18–-3");
+        assert_eq!(range("1d20l1d20").to_string(), "1–20");
+        assert_eq!(range("4d6p5").to_string(), "0–4");
+        assert_eq!(range("10/(1d2-1)").to_string(), "10");
+        let exploding = range("2x6");
+        assert!(exploding.open);
+        assert_eq!(exploding.to_string(), "2–12+");
+        assert!(expression_range("1d", &records, Rounding::Truncate).is_err());
+        assert!(expression_range("1d0", &records, Rounding::Truncate).is_err());
+
+        let compare = |l: &str, c: Comparison, r: &str| compare_ranges(&range(l), &range(r), &c);
+        assert_eq!(compare("1d6", Comparison::Greater, "10"), Some(false));
+        assert_eq!(compare("1d20", Comparison::Greater, "10"), None);
+        assert_eq!(compare("1d6", Comparison::GreaterEqual, "1"), Some(true));
+        assert_eq!(compare("1d6", Comparison::Equal, "3"), None);
+        assert_eq!(compare("1d6", Comparison::NotEqual, "10"), Some(true));
+        assert_eq!(compare("5", Comparison::Equal, "[strength]+2"), Some(true));
+        assert_eq!(compare("1x6", Comparison::Less, "10"), None);
+    }
+    #[test]
+    fn division_rounding() {
+        let mut rand = Random::new(69420);
+        let mut records = HashMap::<String, Record>::new();
+        records.insert("debt".to_string(), {
+            let mut r = Record::parse_from_string("debt".to_string()).unwrap();
+            r.value = -5;
+            r
+        });
+        let mut divide = |exp: &str, rounding: Rounding| {
+            evaluate_expression(exp, &records, &mut rand, rounding).unwrap()
+        };
+
+        assert_eq!(divide("5/2", Rounding::Truncate), 2);
+        assert_eq!(divide("5/2", Rounding::Floor), 2);
+        assert_eq!(divide("5/2", Rounding::Ceil), 3);
+        assert_eq!(divide("5/2", Rounding::HalfUp), 3);
+
+        assert_eq!(divide("[debt]/2", Rounding::Truncate), -2);
+        assert_eq!(divide("[debt]/2", Rounding::Floor), -3);
+        assert_eq!(divide("[debt]/2", Rounding::Ceil), -2);
+        assert_eq!(divide("[debt]/2", Rounding::HalfUp), -2);
+
+        assert_eq!(divide("7/3", Rounding::HalfUp), 2);
+        assert_eq!(divide("8/3", Rounding::HalfUp), 3);
+        assert_eq!(Rounding::from_keyword("round-half-up"), Some(Rounding::HalfUp));
+        assert_eq!(Rounding::from_keyword("nearest"), None);
+    }
 }
@@ -1,14 +1,18 @@
-use dirs::{cache_dir, data_dir};
+use dirs::{cache_dir, config_dir, data_dir};
 use fltk::app;
 use fltk::image::PngImage;
 
 use crate::adventure::*;
+use crate::lang::DEFAULT_LANGUAGE;
 
 pub(crate) use crate::dialog::signal_error;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
-use std::fs::{create_dir_all, read_dir, remove_dir_all, remove_file, File};
-use std::io::{Read, Write};
+use std::fs::{create_dir_all, read_dir, remove_dir_all, remove_file, rename, File};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use std::vec::Vec;
 
 #[derive(Debug)]
@@ -19,8 +23,50 @@ pub enum FileError {
     CannotStringifyPathBuff(PathBuf),
     NoAdventureOnPath(PathBuf),
     FileNonExistent(PathBuf),
+    ArchiveFailure(PathBuf, String),
 }
 pub const PROJECT_PATH_NAME: &str = "adventure-book";
+/// Folder within an adventure where removed pages are moved to
+pub const TRASH_FOLDER: &str = ".trash";
+/// Seed adventures are started with unless the player chooses another one
+pub const DEFAULT_SEED: u64 = 69420;
+
+thread_local! {
+    /// Pages read during the play, keyed by path of their adventure and their name
+    static PAGE_CACHE: RefCell<HashMap<(String, String), Page>> = RefCell::new(HashMap::new());
+    /// Additional data folders from settings, read once and refreshed whenever settings are saved
+    static ROOTS: RefCell<Option<Vec<PathBuf>>> = RefCell::new(None);
+}
+/// Player preferences that are kept between runs of the program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// Reveals story text character by character instead of all at once
+    pub typewriter: bool,
+    /// Additional data folders added by the player, searched after the built in ones
+    pub roots: Vec<PathBuf>,
+    /// Makes the window cover the whole screen
+    pub fullscreen: bool,
+    /// Reads story text aloud
+    pub speech: bool,
+    /// Language of the interface
+    pub language: String,
+    /// Seed adventures are started with, None uses a different seed each time
+    ///
+    /// Adventures are started with the default seed unless the player changes it
+    pub seed: Option<u64>,
+}
+/// Values of global records that are kept between adventures
+///
+/// Values are keyed by namespace of the adventure they belong to and name of the record, so unrelated adventures don't overwrite each other's records
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GlobalRecords {
+    values: HashMap<(String, String), i32>,
+}
+/// Achievements the player has unlocked, keyed by title of their adventure and their id
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Achievements {
+    unlocked: HashSet<(String, String)>,
+}
 /// Expected paths where adventure data is stored for user created content on windows
 #[cfg(target_os = "windows")]
 macro_rules! user_paths {
@@ -132,34 +178,100 @@ impl Display for FileError {
             FileError::FileNonExistent(p) => {
                 write!(f, "File doesn't exist: {}", p.to_str().unwrap())
             }
+            FileError::ArchiveFailure(p, e) => {
+                write!(f, "Could not process archive {}: {}", p.to_str().unwrap(), e)
+            }
         }
     }
 }
+/// Tests if the path can be used as an additional data folder
+pub fn is_valid_root(path: &Path) -> bool {
+    path.is_dir() && read_dir(path).is_ok()
+}
+/// Keeps additional data folders from settings to be used by data paths, reporting those that can't be read
+fn remember_roots(roots: &Vec<PathBuf>) {
+    for root in roots.iter().filter(|x| is_valid_root(x) == false) {
+        println!("Data folder {:?} is missing or can't be read", root);
+    }
+    ROOTS.with(|x| *x.borrow_mut() = Some(roots.clone()));
+}
+/// Returns subfolders of the additional data folders from settings, folders that can't be read are skipped
+fn root_paths(folder: &str) -> Vec<PathBuf> {
+    if ROOTS.with(|x| x.borrow().is_none()) {
+        remember_roots(&load_settings().roots);
+    }
+    ROOTS.with(|x| {
+        x.borrow()
+            .iter()
+            .flatten()
+            .filter(|x| is_valid_root(x))
+            .map(|x| x.join(folder))
+            .collect()
+    })
+}
+/// Expected paths where adventure and core program data is stored, including data folders added by the player
+pub fn data_paths(folder: &str) -> Vec<PathBuf> {
+    all_paths!(folder)
+        .into_iter()
+        .chain(root_paths(folder))
+        .collect()
+}
+/// Expected paths where user created content is stored, including data folders added by the player
+pub fn user_data_paths(folder: &str) -> Vec<PathBuf> {
+    user_paths!(folder)
+        .into_iter()
+        .chain(root_paths(folder))
+        .collect()
+}
 /// Iterates over folders with adventure data and collects all possible adventures to run
 pub fn capture_adventures() -> Vec<Adventure> {
+    capture_adventures_with_progress(|_, _| {})
+}
+/// Collects all possible adventures to run, reporting progress after each adventure folder is processed
+///
+/// progress: called with the number of processed folders and the number of all found folders
+pub fn capture_adventures_with_progress<F: FnMut(usize, usize)>(mut progress: F) -> Vec<Adventure> {
     let mut ret = Vec::<Adventure>::new();
 
-    // going over the paths
-    for path in all_paths!("books") {
-        // reading all the directories on path
-        if let Ok(it) = read_dir(path) {
-            // going over directories, those are adventure folders
-            for dir in it {
-                // reading the folder data if it opens correctly
-                if let Ok(dir) = dir {
-                    // capturing the path to adventure metadata file
-                    let path = dir.path();
-                    match load_adventure(path) {
-                        Err(e) => signal_error!("{}", e),
-                        Ok(adventure) => ret.push(adventure),
-                    }
-                }
-            }
+    // folders are found first so the progress can be reported against their total
+    let folders: Vec<PathBuf> = data_paths("books")
+        .iter()
+        .flat_map(|x| find_adventure_folders(x))
+        .collect();
+    let total = folders.len();
+    for (i, path) in folders.into_iter().enumerate() {
+        match load_adventure(path) {
+            // folders without an adventure file aren't adventures, only broken adventures are worth alerting about
+            Err(FileError::NoAdventureOnPath(p)) => println!("Skipping {:?}, it isn't an adventure", p),
+            Err(e) => signal_error!("{}", e),
+            Ok(adventure) => ret.push(adventure),
         }
+        progress(i + 1, total);
     }
 
     ret
 }
+/// Collects paths to adventure folders within the path, at any depth
+///
+/// Folders identified as adventures aren't searched further,
+/// symbolic links are only followed if they lead directly to an adventure
+fn find_adventure_folders(path: &Path) -> Vec<PathBuf> {
+    let mut ret = Vec::new();
+    let it = match read_dir(path) {
+        Ok(it) => it,
+        Err(_) => return ret,
+    };
+    for dir in it.flatten() {
+        let path = dir.path();
+        if is_adventure_on_path(&path) {
+            ret.push(path);
+        } else if dir.file_type().map_or(false, |x| x.is_dir()) {
+            ret.append(&mut find_adventure_folders(&path));
+        }
+    }
+    ret.sort();
+    ret
+}
 /// Loads adventure from provided path or returns nothing if path doesn't contain an adventure
 pub fn load_adventure(path: PathBuf) -> Result<Adventure, FileError> {
     let mut path = path;
@@ -200,19 +312,27 @@ pub fn is_adventure_on_path(path: &PathBuf) -> bool {
     }
     p.exists()
 }
+/// Tests if a page file exists within the adventure folder
+///
+/// path: adventure path, should be the same as stored in adventure struct
+/// name: name of the page, the extension is applied automatically
+pub fn is_page_on_path(path: &str, name: &str) -> bool {
+    let mut p = PathBuf::from(path);
+    p.push(name);
+    p.set_extension("txt");
+    p.exists()
+}
 /// Tests if the path is within a path from adventures can be read
 pub fn is_on_adventure_path(path: &PathBuf) -> bool {
-    let expected_paths = user_paths!("books").map(|x| {
-        if x.is_absolute() {
-            return x;
-        } else {
-            if let Ok(p) = x.canonicalize() {
-                return p;
-            } else {
-                unreachable!()
+    let expected_paths: Vec<PathBuf> = user_data_paths("books")
+        .into_iter()
+        .filter_map(|x| {
+            if x.is_absolute() {
+                return Some(x);
             }
-        }
-    });
+            x.canonicalize().ok()
+        })
+        .collect();
     if path.is_absolute() {
         if expected_paths.iter().any(|x| path.starts_with(x)) {
             return true;
@@ -234,6 +354,70 @@ pub fn remove_adventure<P: AsRef<Path>>(path: P) {
         Err(_) => {}
     }
 }
+/// Moves a page file into the trash folder of the adventure so it can be recovered
+///
+/// path: adventure path, should be the same as stored in adventure struct
+/// name: name of the page, the extension is applied automatically
+///
+/// Older trashed versions of the page are kept, the new one gets a number appended to its name
+pub fn trash_page(path: &str, name: &str) {
+    let mut source = PathBuf::from(path);
+    source.push(name);
+    source.set_extension("txt");
+    if source.exists() == false {
+        return;
+    }
+    let mut trash = PathBuf::from(path);
+    trash.push(TRASH_FOLDER);
+    if let Err(_) = create_dir_all(&trash) {
+        println!("Path {:?} could not be created!", trash.to_str());
+        return;
+    }
+    let mut target = trash.join(format!("{}.txt", name));
+    let mut i = 2;
+    while target.exists() {
+        target = trash.join(format!("{}-{}.txt", name, i));
+        i += 1;
+    }
+    if let Err(e) = rename(&source, &target) {
+        signal_error!("Error moving the page {} to trash: {}", name, e);
+    }
+}
+/// Returns the time the page file was last modified, None if the file doesn't exist or the time isn't available
+///
+/// path: adventure path, should be the same as stored in adventure struct
+/// name: name of the page, the extension is applied automatically
+pub fn page_modified(path: &str, name: &str) -> Option<SystemTime> {
+    let mut p = PathBuf::from(path);
+    p.push(name);
+    p.set_extension("txt");
+    p.metadata().and_then(|x| x.modified()).ok()
+}
+/// Renames a page file within the adventure folder
+///
+/// path: adventure path, should be the same as stored in adventure struct
+/// old: current name of the page, the extension is applied automatically
+/// new: new name of the page
+///
+/// If a page file with the new name already exists, it is moved to trash first
+pub fn rename_page_file(path: &str, old: &str, new: &str) -> Result<(), FileError> {
+    let mut source = PathBuf::from(path);
+    source.push(old);
+    source.set_extension("txt");
+    if source.exists() == false {
+        return Err(FileError::FileNonExistent(source));
+    }
+    let mut target = PathBuf::from(path);
+    target.push(new);
+    target.set_extension("txt");
+    if target.exists() {
+        trash_page(path, new);
+    }
+    match rename(&source, &target) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(FileError::FileUnopenable(source)),
+    }
+}
 /// Writes adventure metadata into file
 ///
 /// path: adventure path, should be the same as stored in adventure struct
@@ -281,6 +465,175 @@ pub fn save_page(path: &str, file_name: String, serialized_page: String) {
         }
     }
 }
+/// Writes text into a file at the path, replacing the file if it exists
+pub fn save_text(path: &Path, text: &str) -> Result<(), FileError> {
+    let mut file = match File::create(path) {
+        Ok(f) => f,
+        Err(_) => return Err(FileError::FileUnopenable(path.to_path_buf())),
+    };
+    match file.write_all(text.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(FileError::FileUnopenable(path.to_path_buf())),
+    }
+}
+/// Bundles adventure text files and its images folder into a zip archive
+///
+/// path: adventure path, should be the same as stored in adventure struct
+/// target: path of the archive to create, an existing file is overwritten
+///
+/// Files are stored inside a folder named the same as the adventure folder
+pub fn export_adventure(path: &str, target: &Path) -> Result<(), FileError> {
+    let archive_error =
+        |e: &dyn Display| FileError::ArchiveFailure(target.to_path_buf(), e.to_string());
+    let source = PathBuf::from(path);
+    let folder = match source.file_name().and_then(|x| x.to_str()) {
+        Some(f) => f.to_string(),
+        None => return Err(FileError::CannotStringifyPathBuff(source)),
+    };
+
+    // collecting text files of the adventure followed by its images
+    let mut files = vec![(source.join("adventure.txt"), "adventure.txt".to_string())];
+    for page in capture_pages(path) {
+        let name = format!("{}.txt", page);
+        files.push((source.join(&name), name));
+    }
+    if let Ok(dir) = read_dir(source.join("images")) {
+        for image in dir.flatten() {
+            let image = image.path();
+            if image.is_file() {
+                if let Some(name) = image.file_name().and_then(|x| x.to_str()) {
+                    let name = format!("images/{}", name);
+                    files.push((image.clone(), name));
+                }
+            }
+        }
+    }
+
+    let file = match File::create(target) {
+        Ok(f) => f,
+        Err(_) => return Err(FileError::FileUnopenable(target.to_path_buf())),
+    };
+    let mut zip = zip::ZipWriter::new(file);
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (file_path, name) in files {
+        let mut input = match File::open(&file_path) {
+            Ok(f) => f,
+            Err(_) => return Err(FileError::FileUnopenable(file_path)),
+        };
+        if let Err(e) = zip.start_file(format!("{}/{}", folder, name), options) {
+            return Err(archive_error(&e));
+        }
+        if let Err(_) = io::copy(&mut input, &mut zip) {
+            return Err(FileError::LoadingFailure(file_path));
+        }
+    }
+    if let Err(e) = zip.finish() {
+        return Err(archive_error(&e));
+    }
+    Ok(())
+}
+/// Unpacks an adventure from a zip archive into a new folder within books folder
+///
+/// archive: path to the zip file
+/// books: folder in which adventures are stored
+///
+/// The archive needs adventure.txt either at its top or in a single top folder.
+/// The new folder is named after the top folder or the archive, with a number appended if a folder with that name already exists
+pub fn import_adventure(archive: &Path, books: &Path) -> Result<Adventure, FileError> {
+    let archive_error =
+        |e: &dyn Display| FileError::ArchiveFailure(archive.to_path_buf(), e.to_string());
+    let file = match File::open(archive) {
+        Ok(f) => f,
+        Err(_) => return Err(FileError::FileUnopenable(archive.to_path_buf())),
+    };
+    let mut zip = match zip::ZipArchive::new(file) {
+        Ok(z) => z,
+        Err(e) => return Err(archive_error(&e)),
+    };
+
+    // finding where in the archive the adventure metadata is
+    let mut prefix = None;
+    for name in zip.file_names() {
+        let name = Path::new(name);
+        if name.file_name().map_or(false, |x| x == "adventure.txt") == false {
+            continue;
+        }
+        match name.parent() {
+            Some(p) if p.components().count() <= 1 => {
+                prefix = Some(p.to_path_buf());
+                break;
+            }
+            _ => {}
+        }
+    }
+    let prefix = match prefix {
+        Some(p) => p,
+        None => return Err(FileError::NoAdventureOnPath(archive.to_path_buf())),
+    };
+    let folder = match prefix.to_str() {
+        Some(p) if p.len() > 0 => p.to_string(),
+        _ => match archive.file_stem().and_then(|x| x.to_str()) {
+            Some(s) => s.to_string(),
+            None => return Err(FileError::CannotStringifyPathBuff(archive.to_path_buf())),
+        },
+    };
+
+    // avoiding overwriting an existing adventure
+    let mut destination = books.join(&folder);
+    let mut i = 2;
+    while destination.exists() {
+        destination = books.join(format!("{}-{}", folder, i));
+        i += 1;
+    }
+
+    for index in 0..zip.len() {
+        let mut entry = match zip.by_index(index) {
+            Ok(e) => e,
+            Err(e) => return Err(archive_error(&e)),
+        };
+        // entries with paths leading outside of the archive are skipped
+        let name = match entry.enclosed_name() {
+            Some(n) => n.to_path_buf(),
+            None => continue,
+        };
+        let relative = match name.strip_prefix(&prefix) {
+            Ok(r) => r.to_path_buf(),
+            Err(_) => continue,
+        };
+        if entry.is_dir() || relative.as_os_str().len() == 0 {
+            continue;
+        }
+        let path = destination.join(relative);
+        if let Some(parent) = path.parent() {
+            if let Err(_) = create_dir_all(parent) {
+                remove_adventure(&destination);
+                return Err(FileError::FileUnopenable(parent.to_path_buf()));
+            }
+        }
+        let mut output = match File::create(&path) {
+            Ok(f) => f,
+            Err(_) => {
+                remove_adventure(&destination);
+                return Err(FileError::FileUnopenable(path));
+            }
+        };
+        if let Err(e) = io::copy(&mut entry, &mut output) {
+            remove_adventure(&destination);
+            return Err(archive_error(&e));
+        }
+    }
+
+    if is_adventure_on_path(&destination) == false {
+        remove_adventure(&destination);
+        return Err(FileError::NoAdventureOnPath(archive.to_path_buf()));
+    }
+    let adventure = load_adventure(destination.clone());
+    if adventure.is_err() {
+        remove_adventure(&destination);
+    }
+    adventure
+}
 /// Tests if the file name is valid
 ///
 /// there's probably a better way to do it, but for now, it saves a temporary dummy file with the name to drive, if it succeeds, it is considered valid
@@ -363,13 +716,293 @@ pub fn read_page(path: &String, name: &String) -> Result<Page, FileError> {
         Ok(p) => return Ok(p),
     }
 }
+/// Reads a page the same way as read_page, keeping it in memory so it's read from the drive only once
+///
+/// The editor changes pages, so it uses read_page directly instead
+pub fn read_page_cached(path: &String, name: &String) -> Result<Page, FileError> {
+    let key = (path.clone(), name.clone());
+    if let Some(page) = PAGE_CACHE.with(|x| x.borrow().get(&key).cloned()) {
+        return Ok(page);
+    }
+    let page = read_page(path, name)?;
+    PAGE_CACHE.with(|x| x.borrow_mut().insert(key, page.clone()));
+    Ok(page)
+}
+/// Removes pages of the adventure from memory so they're read from the drive again
+pub fn forget_cached_pages(path: &String) {
+    PAGE_CACHE.with(|x| x.borrow_mut().retain(|k, _| &k.0 != path));
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            typewriter: true,
+            roots: Vec::new(),
+            fullscreen: false,
+            speech: false,
+            language: DEFAULT_LANGUAGE.to_string(),
+            seed: Some(DEFAULT_SEED),
+        }
+    }
+}
+impl Settings {
+    /// Creates settings from text, unknown lines are ignored and missing values keep their defaults
+    pub fn parse_from_string(text: &str) -> Self {
+        let mut settings = Settings::default();
+        for line in text.lines() {
+            if line.starts_with("typewriter:") {
+                settings.typewriter = line.replacen("typewriter:", "", 1).trim() != "false";
+            } else if line.starts_with("fullscreen:") {
+                settings.fullscreen = line.replacen("fullscreen:", "", 1).trim() == "true";
+            } else if line.starts_with("speech:") {
+                settings.speech = line.replacen("speech:", "", 1).trim() == "true";
+            } else if line.starts_with("language:") {
+                let language = line.replacen("language:", "", 1).trim().to_string();
+                if language.len() > 0 {
+                    settings.language = language;
+                }
+            } else if line.starts_with("seed:") {
+                settings.seed = line.replacen("seed:", "", 1).trim().parse().ok();
+            } else if line.starts_with("root:") {
+                let root = line.replacen("root:", "", 1).trim().to_string();
+                if root.len() > 0 {
+                    settings.roots.push(PathBuf::from(root));
+                }
+            }
+        }
+        settings
+    }
+    /// Turns the settings into text that can be parsed back
+    pub fn serialize_to_string(&self) -> String {
+        let mut text = format!(
+            "typewriter: {}\nfullscreen: {}\nspeech: {}\nlanguage: {}",
+            self.typewriter, self.fullscreen, self.speech, self.language
+        );
+        // the default seed is left out, an empty one stands for a different seed each time
+        match self.seed {
+            Some(DEFAULT_SEED) => {}
+            Some(seed) => text.push_str(&format!("\nseed: {}", seed)),
+            None => text.push_str("\nseed:"),
+        }
+        for root in self.roots.iter() {
+            text.push_str(&format!("\nroot: {}", root.to_string_lossy()));
+        }
+        text
+    }
+}
+impl GlobalRecords {
+    /// Creates global records from text with one `namespace; record; value` line per record, invalid lines are ignored
+    pub fn parse_from_string(text: &str) -> Self {
+        let mut globals = GlobalRecords::default();
+        for line in text.lines() {
+            // namespace comes from the adventure title so it's the only part that could contain a separator
+            let mut args = line.rsplitn(3, ';').map(|x| x.trim());
+            let (value, name, namespace) = match (args.next(), args.next(), args.next()) {
+                (Some(v), Some(r), Some(n)) if r.len() > 0 && n.len() > 0 => (v, r, n),
+                _ => continue,
+            };
+            if let Ok(value) = value.parse() {
+                globals
+                    .values
+                    .insert((namespace.to_string(), name.to_string()), value);
+            }
+        }
+        globals
+    }
+    /// Turns the global records into text that can be parsed back
+    pub fn serialize_to_string(&self) -> String {
+        let mut keys: Vec<&(String, String)> = self.values.keys().collect();
+        keys.sort();
+        keys.iter()
+            .map(|x| format!("{}; {}; {}", x.0, x.1, self.values[*x]))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+    /// Replaces values of global records with the ones stored for the namespace
+    pub fn merge_into(&self, namespace: &str, records: &mut HashMap<String, Record>) {
+        for rec in records.values_mut().filter(|x| x.global) {
+            if let Some(value) = self.values.get(&(namespace.to_string(), rec.name.clone())) {
+                // another adventure of the series could have left a value outside of this one's bounds
+                rec.value = rec.bounded(*value);
+            }
+        }
+    }
+    /// Stores values of global records of the namespace
+    ///
+    /// Returns true if any of the stored values changed
+    pub fn update_from(&mut self, namespace: &str, records: &HashMap<String, Record>) -> bool {
+        let mut changed = false;
+        for rec in records
+            .values()
+            .filter(|x| x.global && x.is_derived() == false)
+        {
+            let old = self
+                .values
+                .insert((namespace.to_string(), rec.name.clone()), rec.value);
+            if old != Some(rec.value) {
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+impl Achievements {
+    /// Creates achievements from text with one `adventure; id` line per unlocked achievement, invalid lines are ignored
+    pub fn parse_from_string(text: &str) -> Self {
+        let mut achievements = Achievements::default();
+        for line in text.lines() {
+            // adventure title is the only part that could contain a separator
+            if let Some((adventure, id)) = line.rsplit_once(';') {
+                let (adventure, id) = (adventure.trim(), id.trim());
+                if adventure.len() > 0 && id.len() > 0 {
+                    achievements
+                        .unlocked
+                        .insert((adventure.to_string(), id.to_string()));
+                }
+            }
+        }
+        achievements
+    }
+    /// Turns the achievements into text that can be parsed back
+    pub fn serialize_to_string(&self) -> String {
+        let mut unlocked: Vec<&(String, String)> = self.unlocked.iter().collect();
+        unlocked.sort();
+        unlocked
+            .iter()
+            .map(|x| format!("{}; {}", x.0, x.1))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+    /// Marks the achievement of the adventure as unlocked
+    ///
+    /// Returns false if it was already unlocked
+    pub fn unlock(&mut self, adventure: &str, id: &str) -> bool {
+        self.unlocked.insert((adventure.to_string(), id.to_string()))
+    }
+    /// Tests if the player has unlocked the achievement of the adventure
+    pub fn is_unlocked(&self, adventure: &str, id: &str) -> bool {
+        self.unlocked
+            .contains(&(adventure.to_string(), id.to_string()))
+    }
+}
+/// Returns path to a file with progress of the player that's kept between adventures
+fn progress_path(name: &str) -> Option<PathBuf> {
+    let mut path = data_dir()?;
+    path.push(PROJECT_PATH_NAME);
+    path.push(name);
+    path.set_extension("txt");
+    Some(path)
+}
+/// Reads a file with progress of the player, returns empty text if it doesn't exist yet
+fn read_progress(name: &str) -> String {
+    let path = match progress_path(name) {
+        Some(p) => p,
+        None => return String::new(),
+    };
+    let mut text = String::new();
+    if let Ok(mut file) = File::open(&path) {
+        if let Err(e) = file.read_to_string(&mut text) {
+            println!("Could not read {:?}: {}", path, e);
+            return String::new();
+        }
+    }
+    text
+}
+/// Writes a file with progress of the player
+fn write_progress(name: &str, text: String) {
+    let path = match progress_path(name) {
+        Some(p) => p,
+        None => {
+            println!("Could not find a folder for the {} file", name);
+            return;
+        }
+    };
+    if let Some(parent) = path.parent() {
+        if parent.exists() == false {
+            if let Err(_) = create_dir_all(parent) {
+                println!("Path {:?} could not be created!", parent.to_str());
+                return;
+            }
+        }
+    }
+    if let Ok(mut file) = File::create(path) {
+        if let Err(e) = file.write(text.as_bytes()) {
+            signal_error!("Error saving the {}: {}", name.replace('_', " "), e);
+        }
+    }
+}
+/// Loads values of global records, if the file doesn't exist yet there are no values
+pub fn load_global_records() -> GlobalRecords {
+    GlobalRecords::parse_from_string(&read_progress("global_records"))
+}
+/// Writes values of global records to their file
+pub fn save_global_records(globals: &GlobalRecords) {
+    write_progress("global_records", globals.serialize_to_string());
+}
+/// Loads achievements unlocked by the player, if the file doesn't exist yet none are unlocked
+pub fn load_achievements() -> Achievements {
+    Achievements::parse_from_string(&read_progress("achievements"))
+}
+/// Writes achievements unlocked by the player to their file
+pub fn save_achievements(achievements: &Achievements) {
+    write_progress("achievements", achievements.serialize_to_string());
+}
+/// Returns path to the settings file
+fn settings_path() -> Option<PathBuf> {
+    let mut path = config_dir()?;
+    path.push(PROJECT_PATH_NAME);
+    path.push("settings");
+    path.set_extension("txt");
+    Some(path)
+}
+/// Loads player settings, if the file doesn't exist yet default settings are returned
+pub fn load_settings() -> Settings {
+    let path = match settings_path() {
+        Some(p) => p,
+        None => return Settings::default(),
+    };
+    let mut text = String::new();
+    match File::open(&path) {
+        Ok(mut file) => {
+            if let Err(e) = file.read_to_string(&mut text) {
+                println!("Could not read settings from {:?}: {}", path, e);
+                return Settings::default();
+            }
+        }
+        Err(_) => return Settings::default(),
+    }
+    Settings::parse_from_string(&text)
+}
+/// Writes player settings to the settings file
+pub fn save_settings(settings: &Settings) {
+    remember_roots(&settings.roots);
+    let path = match settings_path() {
+        Some(p) => p,
+        None => {
+            println!("Could not find a folder for the settings file");
+            return;
+        }
+    };
+    if let Some(parent) = path.parent() {
+        if parent.exists() == false {
+            if let Err(_) = create_dir_all(parent) {
+                println!("Path {:?} could not be created!", parent.to_str());
+                return;
+            }
+        }
+    }
+    if let Ok(mut file) = File::create(path) {
+        if let Err(e) = file.write(settings.serialize_to_string().as_bytes()) {
+            signal_error!("Error saving the settings: {}", e);
+        }
+    }
+}
 /// Loads image
 ///
 /// name: file name
 ///
 /// Function scans all known data paths in search of the image, supports png images only
 pub fn get_image_png(name: &str) -> Result<PngImage, String> {
-    for mut path in all_paths!("images") {
+    for mut path in data_paths("images") {
         path.push(name);
         if path.exists() {
             match PngImage::load(path) {
@@ -380,11 +1013,85 @@ pub fn get_image_png(name: &str) -> Result<PngImage, String> {
     }
     Err(format!("File {} not found", name))
 }
+/// Lists names of languages the interface can be shown in, default language comes first
+pub fn capture_languages() -> Vec<String> {
+    let mut languages = Vec::new();
+    for path in data_paths("lang") {
+        let dir = match read_dir(path) {
+            Ok(d) => d,
+            Err(_) => continue,
+        };
+        for entry in dir.flatten() {
+            let path = entry.path();
+            if path.extension().map(|x| x == "txt") != Some(true) {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|x| x.to_str()) {
+                if languages.iter().any(|x| x == name) == false {
+                    languages.push(name.to_string());
+                }
+            }
+        }
+    }
+    languages.retain(|x| x != DEFAULT_LANGUAGE);
+    languages.sort();
+    languages.insert(0, DEFAULT_LANGUAGE.to_string());
+    languages
+}
+/// Reads strings file of the language
+pub fn read_language(language: &str) -> Result<String, FileError> {
+    for mut path in data_paths("lang") {
+        path.push(language);
+        path.set_extension("txt");
+        if path.exists() {
+            let mut file = match File::open(&path) {
+                Ok(f) => f,
+                Err(_) => return Err(FileError::FileUnopenable(path)),
+            };
+            let mut text = String::new();
+            if let Err(_) = file.read_to_string(&mut text) {
+                return Err(FileError::CannotStringifyPathBuff(path));
+            }
+            return Ok(text);
+        }
+    }
+    Err(FileError::FileNonExistent(PathBuf::from(language)))
+}
+/// Word list used by the system, checked when no dictionary of the default language is found in the data folders
+#[cfg(unix)]
+const SYSTEM_DICTIONARY: Option<&str> = Some("/usr/share/dict/words");
+/// No system word list is known on other platforms
+#[cfg(not(unix))]
+const SYSTEM_DICTIONARY: Option<&str> = None;
+
+/// Reads the word list used for checking spelling in the language
+///
+/// Word lists are stored in dictionaries data folder as text files named after the language
+pub fn read_dictionary(language: &str) -> Result<String, FileError> {
+    let mut paths: Vec<PathBuf> = data_paths("dictionaries")
+        .into_iter()
+        .map(|mut x| {
+            x.push(language);
+            x.set_extension("txt");
+            x
+        })
+        .collect();
+    if language == DEFAULT_LANGUAGE {
+        paths.extend(SYSTEM_DICTIONARY.map(PathBuf::from));
+    }
+    match paths.into_iter().find(|x| x.exists()) {
+        Some(path) => match std::fs::read_to_string(&path) {
+            Ok(text) => Ok(text),
+            Err(_) => Err(FileError::FileUnopenable(path)),
+        },
+        None => Err(FileError::FileNonExistent(PathBuf::from(language))),
+    }
+}
 /// Opens a help page by name
 ///
 /// Only the name is necessary, the function will apply the extension and the path
 pub fn open_help(name: &str) {
-    for mut path in all_paths!("help") {
+    for mut path in data_paths("help") {
         path.push(name);
         path.set_extension("html");
         if path.exists() {
@@ -402,3 +1109,252 @@ pub fn open_help(name: &str) {
     }
     signal_error!("Could not find a help page: {}", name);
 }
+
+/// Creates an empty folder for a test in temporary directory
+#[cfg(test)]
+pub fn test_folder(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    let _ = remove_dir_all(&path);
+    create_dir_all(&path).unwrap();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fs::{create_dir_all, remove_dir_all, File},
+        io::Write,
+        path::PathBuf,
+    };
+
+    use crate::adventure::Record;
+
+    use super::{
+        export_adventure, find_adventure_folders, forget_cached_pages, import_adventure, load_adventure, read_page_cached,
+        rename_page_file, test_folder, Achievements, FileError, GlobalRecords, Settings, DEFAULT_SEED, TRASH_FOLDER,
+    };
+
+    #[test]
+    fn nested_adventure_folders() {
+        let root = test_folder("adventure-book-nested-test");
+        let folders = [
+            "top",
+            "author/series/first",
+            "author/series/second",
+            "author/single",
+        ];
+        for folder in folders {
+            let path = root.join(folder);
+            create_dir_all(&path).unwrap();
+            File::create(path.join("adventure.txt")).unwrap();
+        }
+        // folders inside of an adventure aren't adventures on their own
+        let inner = root.join("top/images/inner");
+        create_dir_all(&inner).unwrap();
+        File::create(inner.join("adventure.txt")).unwrap();
+        create_dir_all(root.join("empty/folder")).unwrap();
+
+        let found = find_adventure_folders(&root);
+        let _ = remove_dir_all(&root);
+
+        let expected: Vec<PathBuf> = [
+            "author/series/first",
+            "author/series/second",
+            "author/single",
+            "top",
+        ]
+        .iter()
+        .map(|x| root.join(x))
+        .collect();
+        assert_eq!(found, expected);
+    }
+    #[test]
+    fn broken_adventure_errors() {
+        let root = test_folder("adventure-book-broken-test");
+        create_dir_all(root.join("stray")).unwrap();
+        create_dir_all(root.join("broken")).unwrap();
+        std::fs::write(root.join("broken/adventure.txt"), "start: intro").unwrap();
+
+        let stray = load_adventure(root.join("stray"));
+        let broken = load_adventure(root.join("broken"));
+        let _ = remove_dir_all(&root);
+
+        assert!(matches!(stray, Err(FileError::NoAdventureOnPath(_))));
+        assert!(matches!(broken, Err(FileError::ParsingFailure(_, _))));
+    }
+    #[test]
+    fn archive_round_trip() {
+        let root = test_folder("adventure-book-archive-test");
+        let source = root.join("source/tale");
+        create_dir_all(source.join("images")).unwrap();
+        std::fs::write(source.join("adventure.txt"), "title: Tale\nstart: intro").unwrap();
+        std::fs::write(source.join("intro.txt"), "title: Intro\nstory: Once upon a time").unwrap();
+        std::fs::write(source.join("images/map.png"), [137, 80, 78, 71]).unwrap();
+        let archive = root.join("tale.zip");
+        let books = root.join("books");
+
+        export_adventure(source.to_str().unwrap(), &archive).unwrap();
+        let first = import_adventure(&archive, &books).unwrap();
+        // importing the same adventure again doesn't overwrite the first copy
+        let second = import_adventure(&archive, &books).unwrap();
+        let copies = [books.join("tale"), books.join("tale-2")];
+        let contents: Vec<_> = copies
+            .iter()
+            .map(|x| {
+                [
+                    std::fs::read(x.join("adventure.txt")).unwrap(),
+                    std::fs::read(x.join("intro.txt")).unwrap(),
+                    std::fs::read(x.join("images/map.png")).unwrap(),
+                ]
+            })
+            .collect();
+        let original = [
+            std::fs::read(source.join("adventure.txt")).unwrap(),
+            std::fs::read(source.join("intro.txt")).unwrap(),
+            std::fs::read(source.join("images/map.png")).unwrap(),
+        ];
+        let _ = remove_dir_all(&root);
+
+        assert_eq!(first.title, "Tale");
+        assert_eq!(PathBuf::from(&first.path), copies[0]);
+        assert_eq!(PathBuf::from(&second.path), copies[1]);
+        assert_eq!(contents[0], original);
+        assert_eq!(contents[1], original);
+    }
+    #[test]
+    fn archive_escaping_entries() {
+        let root = test_folder("adventure-book-archive-escape-test");
+        let archive = root.join("tale.zip");
+        let books = root.join("library/books");
+        let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+        let options = zip::write::FileOptions::default();
+        for (name, text) in [
+            ("tale/adventure.txt", "title: Tale\nstart: intro"),
+            ("tale/intro.txt", "title: Intro\nstory: Once upon a time"),
+            ("tale/../../escaped.txt", "outside"),
+            ("../escaped.txt", "outside"),
+        ] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(text.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let adventure = import_adventure(&archive, &books);
+        let escaped = [
+            root.join("escaped.txt").exists(),
+            root.join("library/escaped.txt").exists(),
+            books.join("escaped.txt").exists(),
+        ];
+        let imported = books.join("tale/intro.txt").exists();
+        let _ = remove_dir_all(&root);
+
+        assert_eq!(adventure.unwrap().title, "Tale");
+        assert!(imported);
+        assert_eq!(escaped, [false, false, false]);
+    }
+    #[test]
+    fn page_file_rename() {
+        let root = test_folder("adventure-book-rename-test");
+        let path = root.to_str().unwrap();
+        std::fs::write(root.join("old.txt"), "old page").unwrap();
+        std::fs::write(root.join("taken.txt"), "taken page").unwrap();
+
+        rename_page_file(path, "old", "new").unwrap();
+        let renamed = std::fs::read_to_string(root.join("new.txt"));
+        let old_exists = root.join("old.txt").exists();
+
+        // renaming onto an existing page keeps the replaced page in trash
+        rename_page_file(path, "new", "taken").unwrap();
+        let replaced = std::fs::read_to_string(root.join("taken.txt"));
+        let trashed = std::fs::read_to_string(root.join(TRASH_FOLDER).join("taken.txt"));
+
+        let missing = rename_page_file(path, "missing", "other");
+        let _ = remove_dir_all(&root);
+
+        assert_eq!(renamed.unwrap(), "old page");
+        assert!(old_exists == false);
+        assert_eq!(replaced.unwrap(), "old page");
+        assert_eq!(trashed.unwrap(), "taken page");
+        assert!(missing.is_err());
+    }
+    #[test]
+    fn page_cache() {
+        let root = test_folder("adventure-book-cache-test");
+        let path = root.to_str().unwrap().to_string();
+        let name = "start".to_string();
+        let page = |title: &str| {
+            format!(
+                "title: {}\nstory: text\nchoice: go {{result: go}}\nresult: go; start;",
+                title
+            )
+        };
+        std::fs::write(root.join("start.txt"), page("first")).unwrap();
+
+        let first = read_page_cached(&path, &name).map(|x| x.title);
+        // the page isn't read again so changes on the drive aren't seen until the cache is cleared
+        std::fs::write(root.join("start.txt"), page("second")).unwrap();
+        let cached = read_page_cached(&path, &name).map(|x| x.title);
+        forget_cached_pages(&path);
+        let reloaded = read_page_cached(&path, &name).map(|x| x.title);
+        let _ = remove_dir_all(&root);
+
+        assert_eq!(first.unwrap(), "first");
+        assert_eq!(cached.unwrap(), "first");
+        assert_eq!(reloaded.unwrap(), "second");
+    }
+    #[test]
+    fn global_records() {
+        let mut records = HashMap::new();
+        for text in ["renown; 0; global", "gold; 5", "ally; 0; global"] {
+            let rec = Record::parse_from_string(text.to_string()).unwrap();
+            records.insert(rec.name.clone(), rec);
+        }
+        let mut globals = GlobalRecords::parse_from_string("Saga; renown; 3\nOther; ally; 1\nbroken line");
+        globals.merge_into("Saga", &mut records);
+        assert_eq!(records["renown"].value, 3);
+        assert_eq!(records["ally"].value, 0);
+
+        records.get_mut("renown").unwrap().value = 4;
+        records.get_mut("gold").unwrap().value = 6;
+        assert!(globals.update_from("Saga", &records));
+        assert!(globals.update_from("Saga", &records) == false);
+
+        let globals = GlobalRecords::parse_from_string(&globals.serialize_to_string());
+        assert_eq!(
+            globals.serialize_to_string(),
+            "Other; ally; 1\nSaga; ally; 0\nSaga; renown; 4"
+        );
+    }
+    #[test]
+    fn achievements() {
+        let mut achievements = Achievements::parse_from_string("Damsel; rescuer\nbroken line\n; empty");
+        assert!(achievements.is_unlocked("Damsel", "rescuer"));
+        assert!(achievements.is_unlocked("Arena", "rescuer") == false);
+        assert!(achievements.unlock("Arena", "champion"));
+        assert!(achievements.unlock("Arena", "champion") == false);
+
+        let achievements = Achievements::parse_from_string(&achievements.serialize_to_string());
+        assert_eq!(
+            achievements.serialize_to_string(),
+            "Arena; champion\nDamsel; rescuer"
+        );
+    }
+    #[test]
+    fn settings_seed() {
+        let settings = Settings::parse_from_string("typewriter: false\nseed: 12345");
+        assert_eq!(settings.seed, Some(12345));
+        assert!(settings.typewriter == false);
+        assert_eq!(Settings::parse_from_string(&settings.serialize_to_string()), settings);
+
+        assert_eq!(Settings::parse_from_string("seed: -3").seed, None);
+        assert!(Settings::default().serialize_to_string().contains("seed") == false);
+        assert_eq!(Settings::parse_from_string("typewriter: false").seed, Some(DEFAULT_SEED));
+
+        let settings = Settings {
+            seed: None,
+            ..Default::default()
+        };
+        assert_eq!(Settings::parse_from_string(&settings.serialize_to_string()), settings);
+    }
+}
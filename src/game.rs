@@ -1,62 +1,489 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
 
 use crate::{
-    adventure::{Adventure, Choice, Condition, Name, Page, ParsingError, Record},
-    evaluation::{EvaluationError, Random},
-    file::{read_page, FileError},
+    adventure::{
+        input_prompt, Adventure, Choice, Condition, Name, Page, ParsingError, Record, TestRoll,
+        ACHIEVEMENT_KEYWORD,
+    },
+    evaluation::{evaluate_expression, EvaluationError, Random, Rounding},
+    file::{read_page_cached, FileError},
     window::MainWindow,
 };
 use regex::Regex;
 
+/// Headless state of a running adventure
+///
+/// Holds everything needed to play through an adventure without any UI, the game screen drives the same state
+pub struct GameState {
+    pub adventure: Adventure,
+    pub page: Page,
+    pub page_name: String,
+    pub records: HashMap<String, Record>,
+    pub names: HashMap<String, Name>,
+    /// Title of the current page with keywords replaced by their values
+    title: String,
+    story: String,
+    choices: Vec<(bool, String)>,
+    /// Indexes of the choices in the order they're shown to the player
+    choice_order: Vec<usize>,
+    /// Page names and indexes of once only choices that have already been chosen
+    used_choices: HashSet<(String, usize)>,
+    finished: bool,
+    /// Values of the test evaluated by the last choice, if it had one
+    last_roll: Option<TestRoll>,
+    /// Ids of achievements unlocked since they were last taken
+    unlocked: Vec<String>,
+    /// Result of the last choice with side effects that couldn't be evaluated, those side effects were skipped
+    misconfigured: Option<String>,
+    /// Seed the random values of the current run are generated from
+    seed: u64,
+    rand: Random,
+    /// Asks the player for values of side effects, given the question and whatever a number is expected
+    input: Option<Box<dyn FnMut(&str, bool) -> Option<String>>>,
+    /// Pages played instead of the page files, used to playtest pages the editor hasn't saved yet
+    pages: Option<HashMap<String, Page>>,
+}
+
+impl GameState {
+    /// Creates a new game state for the adventure, random values are generated from the seed
+    pub fn new(adventure: Adventure, seed: u64) -> Self {
+        let records = adventure.records.clone();
+        let names = adventure.names.clone();
+        Self {
+            adventure,
+            page: Page::default(),
+            page_name: String::new(),
+            records,
+            names,
+            title: String::new(),
+            story: String::new(),
+            choices: Vec::new(),
+            choice_order: Vec::new(),
+            used_choices: HashSet::new(),
+            finished: false,
+            last_roll: None,
+            unlocked: Vec::new(),
+            misconfigured: None,
+            seed,
+            rand: Random::new(seed),
+            input: None,
+            pages: None,
+        }
+    }
+    /// Sets the function asking the player for values of side effects that start with ask:
+    ///
+    /// It's given the question and whatever a whole number is expected, returning None leaves the keyword unchanged.
+    /// Without it, such side effects don't change anything
+    pub fn set_input(&mut self, input: impl FnMut(&str, bool) -> Option<String> + 'static) {
+        self.input = Some(Box::new(input));
+    }
+    /// Plays the pages instead of reading them from the adventure folder
+    ///
+    /// Pages that aren't in the collection can't be entered
+    pub fn set_pages(&mut self, pages: HashMap<String, Page>) {
+        self.pages = Some(pages);
+    }
+    /// Returns the seed random values of the current run are generated from
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// Resets records and names to their starting values and enters the starting page of the adventure
+    ///
+    /// # Error
+    /// Returns error if the starting page can't be read or parsed
+    pub fn start(&mut self) -> Result<(), GameError> {
+        let start = self.adventure.start.clone();
+        self.start_from(&start)
+    }
+    /// Starts the game from the first page of the chapter
+    ///
+    /// # Error
+    /// Returns error if the adventure has no such chapter or its page can't be read or parsed
+    pub fn start_chapter(&mut self, chapter: &str) -> Result<(), GameError> {
+        let page = match self.adventure.chapter_page(chapter) {
+            Some(p) => p.clone(),
+            None => return Err(GameError::ChapterNotFound(chapter.to_string())),
+        };
+        self.start_from(&page)
+    }
+    /// Starts the game from any page of the adventure, with records and names at their starting values
+    ///
+    /// # Error
+    /// Returns error if the page can't be read or parsed
+    pub fn start_at(&mut self, page: &str) -> Result<(), GameError> {
+        self.start_from(&page.to_string())
+    }
+    /// Resets the game state and enters the page
+    fn start_from(&mut self, page_name: &String) -> Result<(), GameError> {
+        self.records = self.adventure.records.clone();
+        self.names = self.adventure.names.clone();
+        self.used_choices.clear();
+        self.finished = false;
+        self.last_roll = None;
+        self.unlocked.clear();
+        self.enter_page(page_name)
+    }
+    /// Reads the page and prepares its story text and choices
+    ///
+    /// Conditions of the choices are evaluated only once, when the page is entered.
+    /// Once only choices that have already been chosen are disabled
+    fn enter_page(&mut self, page_name: &String) -> Result<(), GameError> {
+        let page = match self.pages.as_ref() {
+            Some(pages) => match pages.get(page_name) {
+                Some(p) => p.clone(),
+                None => return Err(GameError::PageNotFound(page_name.clone())),
+            },
+            None => match read_page_cached(&self.adventure.path, page_name) {
+                Ok(p) => p,
+                Err(e) => return Err(GameError::FileError(e)),
+            },
+        };
+        self.title = match page.title.trim().len() {
+            0 => humanize_page_name(page_name),
+            _ => parse_keywords(&page.title, &self.records, &self.names, self.adventure.rounding)?,
+        };
+        self.story = parse_keywords(&page.story, &self.records, &self.names, self.adventure.rounding)?;
+        self.choices = parse_choices(
+            &page.choices,
+            &page.conditions,
+            &self.records,
+            &self.names,
+            &mut self.rand,
+            self.adventure.rounding,
+        )?;
+        for (i, choice) in page.choices.iter().enumerate() {
+            if choice.once && self.used_choices.contains(&(page_name.clone(), i)) {
+                self.choices[i].0 = false;
+            }
+        }
+        self.choice_order = match page.shuffle {
+            true => shuffled_order(page.choices.len(), &mut self.rand),
+            false => (0..page.choices.len()).collect(),
+        };
+        self.page = page;
+        self.page_name = page_name.clone();
+        Ok(())
+    }
+    /// Title of the current page with keywords replaced by their values
+    ///
+    /// Pages without a title use their file name, written like a sentence
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+    /// Story text of the current page with keywords replaced by their values
+    pub fn story(&self) -> &str {
+        &self.story
+    }
+    /// Choices of the current page with their availability and text with keywords replaced by their values
+    pub fn available_choices(&self) -> Vec<(bool, String)> {
+        self.choices.clone()
+    }
+    /// Choices of the current page in the order they're shown to the player, shuffled if the page asks for it
+    pub fn displayed_choices(&self) -> Vec<(bool, String)> {
+        self.choice_order
+            .iter()
+            .map(|x| self.choices[*x].clone())
+            .collect()
+    }
+    /// Index of the choice shown to the player at the position, or None if there's no choice there
+    pub fn choice_index(&self, position: usize) -> Option<usize> {
+        self.choice_order.get(position).copied()
+    }
+    /// Applies the choice shown to the player at the position, see choose
+    ///
+    /// # Error
+    /// Returns error if there's no choice at the position or choosing it fails
+    pub fn choose_displayed(&mut self, position: usize) -> Result<(), GameError> {
+        match self.choice_index(position) {
+            Some(i) => self.choose(i),
+            None => Err(GameError::ChoiceNotFound(position)),
+        }
+    }
+    /// Tests if the adventure has been finished by choosing a game over choice
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+    /// Values of the test evaluated by the last choice, none if the choice didn't have a test
+    pub fn last_roll(&self) -> Option<&TestRoll> {
+        self.last_roll.as_ref()
+    }
+    /// Name of the result of the last choice if some of its side effects couldn't be evaluated and were skipped
+    pub fn misconfigured_result(&self) -> Option<&String> {
+        self.misconfigured.as_ref()
+    }
+    /// Applies the choice of the current page and advances to the next page
+    ///
+    /// Choosing a game over choice finishes the adventure without changing the page
+    ///
+    /// Side effects are applied one after another, each is evaluated with the changes made by the ones applied before it.
+    /// Side effects that can't be evaluated are skipped and the result is reported by misconfigured_result
+    ///
+    /// # Error
+    /// Returns error if the choice doesn't exist or isn't available, if its test, result or the next page can't be found,
+    /// or when evaluation of the test fails. Records are not changed in case of an error.
+    pub fn choose(&mut self, index: usize) -> Result<(), GameError> {
+        if self.finished {
+            return Err(GameError::AdventureFinished);
+        }
+        let choice = match self.page.choices.get(index) {
+            Some(c) => c,
+            None => return Err(GameError::ChoiceNotFound(index)),
+        };
+        if self.choices.get(index).map_or(false, |x| x.0) == false {
+            return Err(GameError::ChoiceUnavailable(index));
+        }
+        self.last_roll = None;
+        self.misconfigured = None;
+        if choice.once {
+            self.used_choices.insert((self.page_name.clone(), index));
+        }
+        if choice.is_game_over() {
+            self.finished = true;
+            return Ok(());
+        }
+        if choice.is_random() {
+            let next_page = match self.page.randoms.get(&choice.random) {
+                Some(r) => match r.pick(&mut self.rand) {
+                    Some(p) => p.clone(),
+                    None => return Err(GameError::RandomNotFound(choice.random.clone())),
+                },
+                None => return Err(GameError::RandomNotFound(choice.random.clone())),
+            };
+            return self.advance(&next_page);
+        }
+        let result_name = if choice.is_constant() {
+            &choice.result
+        } else if choice.is_switch() {
+            match self.page.switches.get(&choice.switch) {
+                Some(switch) => {
+                    match switch.evaluate(&self.records, &mut self.rand, self.adventure.rounding) {
+                        Ok((Some(r), _)) => r,
+                        Ok((None, v)) => {
+                            return Err(GameError::SwitchOutOfRange(choice.switch.clone(), v))
+                        }
+                        Err(e) => return Err(GameError::EvaluationError(e)),
+                    }
+                }
+                None => return Err(GameError::SwitchNotFound(choice.switch.clone())),
+            }
+        } else {
+            match self.page.tests.get(&choice.test) {
+                Some(test) => {
+                    match test.evaluate(&self.records, &mut self.rand, self.adventure.rounding) {
+                        Ok(v) => {
+                            self.last_roll = Some(v.1);
+                            v.0
+                        }
+                        Err(e) => return Err(GameError::EvaluationError(e)),
+                    }
+                }
+                None => return Err(GameError::TestNotFound(choice.test.clone())),
+            }
+        };
+        let result = match self.adventure.find_result(&self.page, result_name) {
+            Some(r) => r,
+            None => return Err(GameError::ResultNotFound(result_name.clone())),
+        };
+
+        // changes are made on copies so nothing changes if a side effect fails
+        let mut records = self.records.clone();
+        let mut names = self.names.clone();
+        let mut misconfigured = false;
+        let mut achievement = None;
+        for mods in result.side_effects.iter() {
+            if mods.0 == ACHIEVEMENT_KEYWORD {
+                achievement = Some(mods.1.trim().to_string());
+                continue;
+            }
+            let is_name = names.contains_key(mods.0);
+            let change = match records.get(mods.0) {
+                // a key that is both can't be told apart, so neither is changed
+                Some(_) if is_name => return Err(GameError::AmbiguousSideEffect(mods.0.clone())),
+                // derived records are computed from the others, so they can't be changed directly
+                Some(r) if r.is_derived() => None,
+                // the record is set to the number the player gives, so the change is the difference from its value
+                Some(r) if input_prompt(mods.1).is_some() => {
+                    let prompt = input_prompt(mods.1).unwrap();
+                    let answer = match self.input.as_mut() {
+                        Some(input) => input(prompt, true),
+                        None => None,
+                    };
+                    match answer {
+                        Some(answer) => match answer.trim().parse::<i32>() {
+                            Ok(v) => Some(v - r.value),
+                            Err(_) => return Err(GameError::InvalidInput(answer)),
+                        },
+                        None => None,
+                    }
+                }
+                Some(_) => match evaluate_expression(mods.1, &records, &mut self.rand, self.adventure.rounding) {
+                    Ok(v) => Some(v),
+                    // the player is asked whatever to go on, the other side effects are still applied
+                    Err(_) => {
+                        misconfigured = true;
+                        None
+                    }
+                },
+                None if is_name => {
+                    let text = match input_prompt(mods.1) {
+                        Some(prompt) => match self.input.as_mut() {
+                            Some(input) => input(prompt, false).map(|x| x.trim().to_string()),
+                            None => None,
+                        },
+                        None => Some(parse_keywords(mods.1, &records, &names, self.adventure.rounding)?),
+                    };
+                    if let (Some(text), Some(n)) = (text, names.get_mut(mods.0)) {
+                        n.value = text;
+                    }
+                    None
+                }
+                None => None,
+            };
+            if let (Some(change), Some(r)) = (change, records.get_mut(mods.0)) {
+                r.value = r.bounded(r.value + change);
+            }
+        }
+        let next_page = result.next_page.clone();
+        if misconfigured {
+            self.misconfigured = Some(result.name.clone());
+        }
+        self.records = records;
+        self.names = names;
+        if let Some(id) = achievement {
+            if self.unlocked.contains(&id) == false {
+                self.unlocked.push(id);
+            }
+        }
+        self.advance(&next_page)
+    }
+    /// Returns ids of achievements unlocked by the choices made since the last call
+    pub fn take_unlocked(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.unlocked)
+    }
+    /// Moves to the page a link in the story text leads to
+    ///
+    /// Links work like choices without a test or side effects
+    ///
+    /// # Error
+    /// Returns error if the adventure is finished or the page can't be read
+    pub fn follow_link(&mut self, page: &String) -> Result<(), GameError> {
+        if self.finished {
+            return Err(GameError::AdventureFinished);
+        }
+        self.last_roll = None;
+        self.advance(page)
+    }
+    /// Moves to the next page, increasing automatic records first so the new page sees their new values
+    fn advance(&mut self, next_page: &String) -> Result<(), GameError> {
+        self.records
+            .values_mut()
+            .filter(|x| x.is_auto())
+            .for_each(|x| x.value = x.bounded(x.value + 1));
+        self.enter_page(next_page)
+    }
+    /// Restarts the adventure with a new seed and plays the choices in order
+    ///
+    /// Returns names of all visited pages, including the starting page
+    ///
+    /// # Error
+    /// Returns the first error encountered while starting the adventure or making the choices
+    pub fn replay(&mut self, seed: u64, choices: &[usize]) -> Result<Vec<String>, GameError> {
+        self.seed = seed;
+        self.rand = Random::new(seed);
+        self.start()?;
+        let mut visited = vec![self.page_name.clone()];
+        for choice in choices.iter() {
+            self.choose(*choice)?;
+            if self.finished == false {
+                visited.push(self.page_name.clone());
+            }
+        }
+        Ok(visited)
+    }
+}
+
 /// Changes currently displayed page.
 ///
 /// It refreshes windows contents to update changes in records and fills story and choices
-pub fn render_page(
-    main_window: &mut MainWindow,
-    adventure: &Adventure,
-    page_name: &String,
-    rand: &mut Random,
-) -> Result<Page, GameError> {
-    let page = match read_page(&adventure.path, page_name) {
-        Ok(p) => p,
-        Err(e) => return Err(GameError::FileError(e)),
-    };
-    let story = parse_keywords(&page.story, &adventure.records, &adventure.names)?;
-    let choices = parse_choices(
-        &page.choices,
-        &page.conditions,
-        &adventure.records,
-        &adventure.names,
-        rand,
-    )?;
-
-    main_window.game_window.fill_choices(choices);
-    main_window.game_window.fill_records(&adventure.records);
-    main_window.game_window.display_story(&page.title, story);
-    Ok(page)
+pub fn render_page(main_window: &mut MainWindow, state: &GameState) {
+    main_window
+        .game_window
+        .fill_choices(state.displayed_choices());
+    main_window
+        .game_window
+        .fill_records(&state.records, state.adventure.rounding);
+    match state.last_roll() {
+        Some(r) if state.adventure.hide_rolls == false => {
+            main_window.game_window.display_roll(&r.to_string())
+        }
+        _ => main_window.game_window.display_roll(""),
+    }
+    main_window
+        .game_window
+        .display_story(state.title(), state.story().to_string());
+}
+/// Turns a page file name into a title, with spaces in place of dashes and underscores and the first letter capitalized
+fn humanize_page_name(name: &str) -> String {
+    let name = name.replace(['-', '_'], " ");
+    let mut chars = name.trim().chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+/// Creates a random order of indexes up to the length
+fn shuffled_order(len: usize, rand: &mut Random) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..len).collect();
+    for i in (1..len).rev() {
+        let j = rand.die(1, i as i32 + 1) as usize - 1;
+        order.swap(i, j);
+    }
+    order
 }
 /// Parses supplied text and returns string with tags replaced with their values as found in records and names maps
+///
+/// The text is parsed in a single pass, so values containing brackets are inserted as they are.
+/// Derived records are replaced with the value computed from their expression
+///
+/// # Error
+/// Returns MissingRecord error for the first keyword in the text that isn't found in either records or names
+/// and an evaluation error if a derived record can't be computed
 fn parse_keywords(
     story_text: &String,
     records: &HashMap<String, Record>,
     names: &HashMap<String, Name>,
+    rounding: Rounding,
 ) -> Result<String, GameError> {
-    let reg = Regex::new(r"\[\s*(\w+(?:\s|\w)*)\]").unwrap();
+    // links in [[target|label]] form are matched first so they're kept as they are
+    let reg = Regex::new(r"\[\[[^\]]*\]\]|\[\s*(\w+(?:\s|\w)*)\]").unwrap();
 
-    let mut res = story_text.clone();
-    while let Some(caps) = reg.captures(&res) {
+    let mut res = String::with_capacity(story_text.len());
+    let mut last = 0;
+    for caps in reg.captures_iter(story_text) {
         let whole = caps.get(0).unwrap();
-        let name = caps.get(1).unwrap();
-        if let Some(rec) = records.get(name.as_str()) {
-            res.replace_range(whole.range(), &rec.value_as_string());
-        } else if let Some(name) = names.get(name.as_str()) {
-            res.replace_range(whole.range(), &name.value);
+        let name = match caps.get(1) {
+            Some(n) => n.as_str().trim(),
+            None => continue,
+        };
+        res.push_str(&story_text[last..whole.start()]);
+        if let Some(rec) = records.get(name) {
+            match rec.current_value(records, rounding) {
+                Ok(v) => res.push_str(&v.to_string()),
+                Err(e) => return Err(GameError::EvaluationError(e)),
+            }
+        } else if let Some(name) = names.get(name) {
+            res.push_str(&name.value);
         } else {
             return Err(GameError::ParsingError(ParsingError::MissingRecord(
-                name.as_str().to_string(),
+                name.to_string(),
             )));
         }
+        last = whole.end();
     }
+    res.push_str(&story_text[last..]);
     Ok(res)
 }
 
@@ -78,13 +505,14 @@ fn parse_choices(
     records: &HashMap<String, Record>,
     names: &HashMap<String, Name>,
     rand: &mut Random,
+    rounding: Rounding,
 ) -> Result<Vec<(bool, String)>, GameError> {
     let mut res = Vec::new();
     for choice in choices.iter() {
         let enabled;
         if choice.has_condition() {
             if let Some(con) = conditions.get(&choice.condition) {
-                match con.evaluate(records, rand) {
+                match con.evaluate(records, rand, rounding) {
                     Ok(v) => enabled = v,
                     Err(e) => return Err(GameError::EvaluationError(e)),
                 }
@@ -94,7 +522,7 @@ fn parse_choices(
         } else {
             enabled = true;
         }
-        let text = parse_keywords(&choice.text, records, names)?;
+        let text = parse_keywords(&choice.text, records, names, rounding)?;
         res.push((enabled, text));
     }
 
@@ -107,6 +535,22 @@ pub enum GameError {
     ParsingError(ParsingError),
     FileError(FileError),
     ConditionNotFound(String),
+    TestNotFound(String),
+    ResultNotFound(String),
+    RandomNotFound(String),
+    SwitchNotFound(String),
+    /// Value of the switch expression isn't in any of its ranges and it has no default result
+    SwitchOutOfRange(String, i32),
+    ChoiceNotFound(usize),
+    ChoiceUnavailable(usize),
+    ChapterNotFound(String),
+    /// Page isn't among the pages played instead of the page files
+    PageNotFound(String),
+    /// Side effect changes a keyword that is both a record and a name
+    AmbiguousSideEffect(String),
+    /// The player was asked for a number but gave something else
+    InvalidInput(String),
+    AdventureFinished,
 }
 
 #[derive(Clone)]
@@ -114,11 +558,30 @@ pub enum Event {
     DisplayMainMenu,
     DisplayAdventureSelect,
     StartAdventure,
+    /// Starts the adventure being played anew, with records back at their starting values
+    RestartAdventure,
     QuitToMainMenu,
     Quit,
     SelectAdventure(String),
+    /// Shows which achievements of the selected adventure the player has unlocked
+    ShowAchievements,
     StoryChoice(usize),
+    /// Result of clicking a link in the story text, holds name of the linked page
+    StoryLink(String),
+    SetTypewriter(bool),
+    /// Turns reading the story aloud on or off
+    SetSpeech(bool),
+    /// Changes the language of the interface, holds name of the language
+    SetLanguage(String),
+    /// Sets the seed adventures are started with, None starts them with a different seed each time
+    SetSeed(Option<u64>),
+    /// Switches the window between fullscreen and its previous size
+    ToggleFullscreen,
+    /// Lets the player choose an additional folder to read adventures from
+    AddDataFolder,
     EditAdventure,
+    /// Adds a new adventure to the list of adventures, sent when the editor creates one
+    AddAdventure(Adventure),
     Editor(crate::editor::Event),
 }
 
@@ -131,21 +594,285 @@ impl Display for GameError {
             GameError::ConditionNotFound(e) => {
                 write!(f, "Condition {} have not been found in the page", e)
             }
+            GameError::TestNotFound(e) => write!(f, "Test {} have not been found in the page", e),
+            GameError::ResultNotFound(e) => {
+                write!(f, "Result {} have not been found in the page", e)
+            }
+            GameError::RandomNotFound(e) => {
+                write!(f, "Random result {} have not been found in the page", e)
+            }
+            GameError::SwitchNotFound(e) => {
+                write!(f, "Switch {} have not been found in the page", e)
+            }
+            GameError::SwitchOutOfRange(e, v) => {
+                write!(f, "Switch {} has no result for value {}", e, v)
+            }
+            GameError::ChoiceNotFound(e) => write!(f, "Choice {} doesn't exist in the page", e),
+            GameError::ChoiceUnavailable(e) => write!(f, "Choice {} is not available", e),
+            GameError::ChapterNotFound(e) => write!(f, "Chapter {} doesn't exist in the adventure", e),
+            GameError::PageNotFound(e) => write!(f, "Page {} doesn't exist in the adventure", e),
+            GameError::AmbiguousSideEffect(e) => {
+                write!(f, "Side effect changes {} which is both a record and a name", e)
+            }
+            GameError::InvalidInput(e) => write!(f, "{} is not a whole number", e),
+            GameError::AdventureFinished => write!(f, "The adventure has already finished"),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::{collections::HashMap, fs::remove_dir_all, path::Path};
 
     use crate::{
-        adventure::{Choice, Condition, Name, Record},
-        evaluation::Random,
+        adventure::{Adventure, Choice, Condition, Name, Page, ParsingError, Record, StoryResult},
+        evaluation::{EvaluationError, Random, Rounding},
+        file::test_folder,
     };
 
-    use super::{parse_choices, parse_keywords};
+    use super::{humanize_page_name, parse_choices, parse_keywords, shuffled_order, GameError, GameState};
 
+    /// Writes a small adventure into the folder and returns it
+    fn write_test_adventure(path: &Path) -> Adventure {
+        let pages = [
+            (
+                "start",
+                "title: Crossroads\nstory: You have [gold] gold.\nchoice: Go left{result: left}\nchoice: Buy a map{condition: rich}{result: map}\ncondition: rich;[gold];>;10\nresult: left;cave;gold;5\nresult: map;cave;gold;-10",
+            ),
+            (
+                "cave",
+                "title: Cave\nstory: You have [gold] gold now.\nchoice: Buy a map{condition: rich}{result: map}\nchoice: The end{result: game over}\nchoice: Get lost{once}{random: lost}\ncondition: rich;[gold];>=;10\nresult: map;start;gold;-10\nrandom: lost;start;1",
+            ),
+        ];
+        for page in pages {
+            std::fs::write(path.join(format!("{}.txt", page.0)), page.1).unwrap();
+        }
+        let mut records = HashMap::new();
+        records.insert(
+            "gold".to_string(),
+            Record {
+                category: String::new(),
+                name: "gold".to_string(),
+                value: 5,
+                hidden: false,
+                expression: String::new(),
+                global: false,
+                min: None,
+                max: None,
+            },
+        );
+        Adventure {
+            title: "Test".to_string(),
+            start: "start".to_string(),
+            path: path.to_str().unwrap().to_string(),
+            records,
+            ..Default::default()
+        }
+    }
+    #[test]
+    fn game_state_end_to_end() {
+        let path = test_folder("adventure-book-game-state");
+        let adventure = write_test_adventure(&path);
+        let mut game = GameState::new(adventure, 69420);
+        game.start().unwrap();
+        assert_eq!(game.page_name, "start");
+        assert_eq!(game.story(), "You have 5 gold.");
+        assert_eq!(
+            game.available_choices(),
+            vec![(true, "Go left".to_string()), (false, "Buy a map".to_string())]
+        );
+        match game.choose(1) {
+            Err(GameError::ChoiceUnavailable(1)) => {}
+            _ => assert!(false),
+        }
+
+        game.choose(0).unwrap();
+        assert_eq!(game.page_name, "cave");
+        assert_eq!(game.records["gold"].value, 10);
+        assert_eq!(game.story(), "You have 10 gold now.");
+        assert!(game.available_choices()[0].0);
+
+        game.choose(0).unwrap();
+        assert_eq!(game.page_name, "start");
+        assert_eq!(game.records["gold"].value, 0);
+
+        game.choose(0).unwrap();
+        game.choose(2).unwrap();
+        assert_eq!(game.page_name, "start");
+        assert_eq!(game.records["gold"].value, 5);
+
+        game.choose(0).unwrap();
+        assert_eq!(game.available_choices()[2].0, false);
+        match game.choose(2) {
+            Err(GameError::ChoiceUnavailable(2)) => {}
+            _ => assert!(false),
+        }
+        game.choose(0).unwrap();
+
+        game.choose(0).unwrap();
+        game.choose(1).unwrap();
+        assert!(game.is_finished());
+        match game.choose(0) {
+            Err(GameError::AdventureFinished) => {}
+            _ => assert!(false),
+        }
+
+        game.start().unwrap();
+        assert_eq!(game.page_name, "start");
+        assert_eq!(game.records["gold"].value, 5);
+        assert!(game.is_finished() == false);
+        let _ = remove_dir_all(&path);
+    }
+
+    #[test]
+    fn game_state_auto_record() {
+        let path = test_folder("adventure-book-auto-record");
+        let mut adventure = write_test_adventure(&path);
+        adventure.records.insert(
+            "turn".to_string(),
+            Record {
+                category: "auto".to_string(),
+                name: "turn".to_string(),
+                value: 1,
+                hidden: false,
+                expression: String::new(),
+                global: false,
+                min: None,
+                max: None,
+            },
+        );
+        let mut game = GameState::new(adventure, 69420);
+        game.start().unwrap();
+        assert_eq!(game.records["turn"].value, 1);
+
+        game.choose(0).unwrap();
+        assert_eq!(game.records["turn"].value, 2);
+        game.choose(0).unwrap();
+        assert_eq!(game.records["turn"].value, 3);
+        game.choose(0).unwrap();
+        game.choose(2).unwrap();
+        assert_eq!(game.records["turn"].value, 5);
+        assert_eq!(game.records["gold"].value, 5);
+
+        match game.choose(9) {
+            Err(GameError::ChoiceNotFound(9)) => {}
+            _ => assert!(false),
+        }
+        assert_eq!(game.records["turn"].value, 5);
+
+        game.start().unwrap();
+        assert_eq!(game.records["turn"].value, 1);
+        let _ = remove_dir_all(&path);
+    }
+    #[test]
+    fn game_state_achievement() {
+        let path = test_folder("adventure-book-achievement");
+        let adventure = write_test_adventure(&path);
+        std::fs::write(
+            path.join("cave.txt"),
+            "title: Cave\nstory: A cave.\nchoice: Buy a map{result: map}\nresult: map;start;gold;-10;achievement;cartographer",
+        )
+        .unwrap();
+        let mut game = GameState::new(adventure, 69420);
+        game.start().unwrap();
+        game.choose(0).unwrap();
+        assert!(game.take_unlocked().is_empty());
+        game.choose(0).unwrap();
+        assert_eq!(game.records["gold"].value, 0);
+        assert_eq!(game.take_unlocked(), vec!["cartographer".to_string()]);
+        assert!(game.take_unlocked().is_empty());
+        let _ = remove_dir_all(&path);
+    }
+    #[test]
+    fn game_state_name_side_effect() {
+        let path = test_folder("adventure-book-name-side-effect");
+        let mut adventure = write_test_adventure(&path);
+        std::fs::write(
+            path.join("cave.txt"),
+            "title: Cave\nstory: A cave.\nchoice: Rest{result: rest}\nresult: rest;start;camp;[gold] coins camp",
+        )
+        .unwrap();
+        adventure.names.insert(
+            "camp".to_string(),
+            Name {
+                keyword: "camp".to_string(),
+                value: "Nowhere".to_string(),
+            },
+        );
+        let mut game = GameState::new(adventure.clone(), 69420);
+        game.start().unwrap();
+        game.choose(0).unwrap();
+        game.choose(0).unwrap();
+        assert_eq!(game.names["camp"].value, "10 coins camp");
+
+        adventure.names.insert(
+            "gold".to_string(),
+            Name {
+                keyword: "gold".to_string(),
+                value: "Gold".to_string(),
+            },
+        );
+        let mut game = GameState::new(adventure, 69420);
+        game.start().unwrap();
+        match game.choose(0) {
+            Err(GameError::AmbiguousSideEffect(key)) => assert_eq!(key, "gold"),
+            _ => assert!(false),
+        }
+        assert_eq!(game.page_name, "start");
+        assert_eq!(game.records["gold"].value, 5);
+        let _ = remove_dir_all(&path);
+    }
+    #[test]
+    fn game_state_switch() {
+        let path = test_folder("adventure-book-switch");
+        let adventure = write_test_adventure(&path);
+        std::fs::write(
+            path.join("cave.txt"),
+            "title: Cave\nstory: A cave.\nchoice: Count the gold{switch: purse}\nchoice: Count again{switch: wallet}\nswitch: purse;[gold];..5;poor;6..15;fine\nswitch: wallet;[gold];..5;poor\nresult: poor;start;gold;1\nresult: fine;cave;gold;10",
+        )
+        .unwrap();
+        let mut game = GameState::new(adventure, 69420);
+        game.start().unwrap();
+        game.choose(0).unwrap();
+        assert_eq!(game.records["gold"].value, 10);
+
+        game.choose(0).unwrap();
+        assert_eq!(game.page_name, "cave");
+        assert_eq!(game.records["gold"].value, 20);
+        match game.choose(0) {
+            Err(GameError::SwitchOutOfRange(name, 20)) => assert_eq!(name, "purse"),
+            _ => assert!(false),
+        }
+        match game.choose(1) {
+            Err(GameError::SwitchOutOfRange(name, 20)) => assert_eq!(name, "wallet"),
+            _ => assert!(false),
+        }
+        assert_eq!(game.records["gold"].value, 20);
+        let _ = remove_dir_all(&path);
+    }
+    #[test]
+    fn game_state_replay() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/data/books/arena");
+        let text = std::fs::read_to_string(format!("{}/adventure.txt", path)).unwrap();
+        let adventure = Adventure::parse_from_string(text, path.to_string()).unwrap();
+        let mut game = GameState::new(adventure, 0);
+        assert_eq!(game.seed(), 0);
+
+        let visited = game.replay(69420, &[0, 0, 0]).unwrap();
+        assert_eq!(game.seed(), 69420);
+        assert_eq!(visited, vec!["start", "hard-battle", "battle-won", "recover"]);
+        assert!(game.replay(69420, &[0]).is_ok());
+        let roll = *game.last_roll().unwrap();
+        assert!(roll.success);
+        assert!(roll.left > 10);
+        assert_eq!(roll.right, 10);
+        assert!(roll.to_string().starts_with("Rolled"));
+        assert_eq!(visited, game.replay(69420, &[0, 0, 0]).unwrap());
+        match game.replay(69420, &[0, 9]) {
+            Err(GameError::ChoiceNotFound(9)) => {}
+            _ => assert!(false),
+        }
+    }
     #[test]
     fn story_text_parsing() {
         let story = "You approach castle [castle name] in hopes of finding adventure. With only [gold] gold coins in your pouch, you know you will have to accept any job you will be offered. However, you're sure you will find something good. The name of [name] should be well known in these parts.".to_string();
@@ -174,19 +901,113 @@ mod tests {
                 category: String::new(),
                 name: "gold".to_string(),
                 value: 13,
+                hidden: false,
+                expression: String::new(),
+                global: false,
+                min: None,
+                max: None,
             },
         );
 
-        let res = parse_keywords(&story, &records, &names).unwrap();
+        let res = parse_keywords(&story, &records, &names, Rounding::Truncate).unwrap();
         assert_eq!(res, expected);
     }
     #[test]
+    fn story_text_parsing_links() {
+        let story = "With [gold] gold you can go to the [[market|Market]] or [[cave]].".to_string();
+        let expected = "With 13 gold you can go to the [[market|Market]] or [[cave]].".to_string();
+
+        let mut records = HashMap::new();
+        records.insert(
+            "gold".to_string(),
+            Record {
+                category: String::new(),
+                name: "gold".to_string(),
+                value: 13,
+                hidden: false,
+                expression: String::new(),
+                global: false,
+                min: None,
+                max: None,
+            },
+        );
+
+        let res = parse_keywords(&story, &records, &HashMap::new(), Rounding::Truncate).unwrap();
+        assert_eq!(res, expected);
+    }
+    #[test]
+    fn game_state_follow_link() {
+        let path = test_folder("adventure-book-follow-link");
+        let adventure = write_test_adventure(&path);
+        let mut game = GameState::new(adventure, 69420);
+        game.start().unwrap();
+        game.follow_link(&"cave".to_string()).unwrap();
+        assert_eq!(game.page_name, "cave");
+        assert_eq!(game.records["gold"].value, 5);
+        assert!(game.last_roll().is_none());
+
+        match game.follow_link(&"missing".to_string()) {
+            Err(GameError::FileError(_)) => {}
+            _ => assert!(false),
+        }
+        assert_eq!(game.page_name, "cave");
+
+        game.choose(1).unwrap();
+        match game.follow_link(&"start".to_string()) {
+            Err(GameError::AdventureFinished) => {}
+            _ => assert!(false),
+        }
+        let _ = remove_dir_all(&path);
+    }
+    #[test]
+    fn story_text_parsing_bracketed_value() {
+        let story = "[hero] met [villain] near [ town ].".to_string();
+
+        let mut names = HashMap::new();
+        let records = HashMap::new();
+        names.insert(
+            "hero".to_string(),
+            Name {
+                keyword: "hero".to_string(),
+                value: "[hero]".to_string(),
+            },
+        );
+        names.insert(
+            "villain".to_string(),
+            Name {
+                keyword: "villain".to_string(),
+                value: "[other]".to_string(),
+            },
+        );
+        names.insert(
+            "town".to_string(),
+            Name {
+                keyword: "town".to_string(),
+                value: "Stonehill".to_string(),
+            },
+        );
+
+        let res = parse_keywords(&story, &records, &names, Rounding::Truncate).unwrap();
+        assert_eq!(res, "[hero] met [other] near Stonehill.");
+
+        let story = "[missing] and [another missing]".to_string();
+        match parse_keywords(&story, &records, &names, Rounding::Truncate) {
+            Err(GameError::ParsingError(ParsingError::MissingRecord(r))) => {
+                assert_eq!(r, "missing")
+            }
+            _ => assert!(false),
+        }
+    }
+    #[test]
     fn parsing_choices() {
         let choices = vec![Choice {
             text: "Choose".to_string(),
             condition: "con".to_string(),
             result: "res".to_string(),
             test: String::new(),
+            random: String::new(),
+            switch: String::new(),
+            once: false,
         }];
         let mut conditions = HashMap::new();
         conditions.insert(
@@ -202,7 +1023,15 @@ mod tests {
         let records = HashMap::new();
         let mut rand = Random::new(69420);
 
-        let res = parse_choices(&choices, &conditions, &records, &names, &mut rand).unwrap();
+        let res = parse_choices(
+            &choices,
+            &conditions,
+            &records,
+            &names,
+            &mut rand,
+            Rounding::Truncate,
+        )
+        .unwrap();
         for r in res {
             assert!(r.0);
             assert_eq!(r.1, "Choose".to_string());
@@ -215,6 +1044,9 @@ mod tests {
             condition: "con".to_string(),
             result: "res".to_string(),
             test: String::new(),
+            random: String::new(),
+            switch: String::new(),
+            once: false,
         }];
         let mut conditions = HashMap::new();
 
@@ -234,10 +1066,273 @@ mod tests {
         let records = HashMap::new();
         let names = HashMap::new();
 
-        let res = parse_choices(&choices, &conditions, &records, &names, &mut rand).unwrap();
+        let res = parse_choices(
+            &choices,
+            &conditions,
+            &records,
+            &names,
+            &mut rand,
+            Rounding::Truncate,
+        )
+        .unwrap();
         for r in res {
             assert_eq!(r.0, lv > rv);
             assert_eq!(r.1, "Choose".to_string());
         }
     }
+    #[test]
+    fn derived_records_in_play() {
+        let mut records = HashMap::new();
+        for text in ["armor; 3", "agility; 5", "defense; derived; [armor]+[agility]/2"] {
+            let r = Record::parse_from_string(text.to_string()).unwrap();
+            records.insert(r.name.clone(), r);
+        }
+        let names = HashMap::new();
+        let story = "Your defense is [defense].".to_string();
+        let res = parse_keywords(&story, &records, &names, Rounding::Ceil).unwrap();
+        assert_eq!(res, "Your defense is 6.");
+
+        let choices = vec![Choice {
+            text: "Block with [defense]".to_string(),
+            condition: "sturdy".to_string(),
+            ..Default::default()
+        }];
+        let mut conditions = HashMap::new();
+        conditions.insert(
+            "sturdy".to_string(),
+            Condition::parse_from_string("sturdy; [defense]; >=; 6".to_string()).unwrap(),
+        );
+        let mut rand = Random::new(69420);
+        let res = parse_choices(&choices, &conditions, &records, &names, &mut rand, Rounding::Floor).unwrap();
+        assert_eq!(res, vec![(false, "Block with 5".to_string())]);
+
+        let rec = Record::parse_from_string("armor; derived; [defense]".to_string()).unwrap();
+        records.insert(rec.name.clone(), rec);
+        match parse_keywords(&story, &records, &names, Rounding::Floor) {
+            Err(GameError::EvaluationError(EvaluationError::CircularRecord(_))) => {}
+            x => panic!("Expected a circular record error, got {:?}", x),
+        }
+    }
+    #[test]
+    fn game_state_shuffled_choices() {
+        let mut rand = Random::new(69420);
+        let mut order = shuffled_order(6, &mut rand);
+        order.sort();
+        assert_eq!(order, vec![0, 1, 2, 3, 4, 5]);
+        assert!(shuffled_order(0, &mut rand).is_empty());
+
+        let path = test_folder("adventure-book-shuffle");
+        std::fs::write(
+            path.join("start.txt"),
+            "title: Shuffled\nshuffle: true\nstory: Pick one\nchoice: a{result: a}\nchoice: b{condition: rich}{result: b}\nchoice: c{result: c}\nchoice: d{result: d}\ncondition: rich;[gold];>;10\nresult: a;a\nresult: b;b\nresult: c;c\nresult: d;d",
+        )
+        .unwrap();
+        for page in ["a", "b", "c", "d"] {
+            std::fs::write(
+                path.join(format!("{}.txt", page)),
+                format!("title: {0}\nstory: {0}\nchoice: Back{{result: start}}\nresult: start;start", page),
+            )
+            .unwrap();
+        }
+        let adventure = Adventure {
+            title: "Shuffle".to_string(),
+            start: "start".to_string(),
+            path: path.to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        let mut game = GameState::new(adventure, 69420);
+        game.start().unwrap();
+        let mut orders = Vec::new();
+        for _ in 0..10 {
+            let displayed = game.displayed_choices();
+            assert_eq!(displayed.len(), 4);
+            let order: Vec<usize> = (0..4).map(|x| game.choice_index(x).unwrap()).collect();
+            for (position, index) in order.iter().enumerate() {
+                assert_eq!(displayed[position], game.available_choices()[*index]);
+            }
+            // the unavailable choice stays unavailable wherever it's shown
+            let b = displayed.iter().position(|x| x.1 == "b").unwrap();
+            assert_eq!(displayed[b].0, false);
+            match game.choose_displayed(b) {
+                Err(GameError::ChoiceUnavailable(1)) => {}
+                _ => assert!(false),
+            }
+            let a = displayed.iter().position(|x| x.1 == "a").unwrap();
+            game.choose_displayed(a).unwrap();
+            assert_eq!(game.page_name, "a");
+            assert_eq!(game.displayed_choices(), game.available_choices());
+            game.choose_displayed(0).unwrap();
+            orders.push(order);
+        }
+        assert!(orders.iter().any(|x| *x != orders[0]));
+        match game.choose_displayed(4) {
+            Err(GameError::ChoiceNotFound(4)) => {}
+            _ => assert!(false),
+        }
+        let _ = remove_dir_all(&path);
+    }
+    #[test]
+    fn game_state_input_side_effect() {
+        let path = test_folder("adventure-book-input");
+        std::fs::write(
+            path.join("start.txt"),
+            "title: Tavern\nstory: [hero] has [gold] gold.\nchoice: Bet{result: bet}\nresult: bet;start;gold;ask: How much gold do you have?;hero;ask: What's your name?",
+        )
+        .unwrap();
+        let mut adventure = Adventure {
+            title: "Input".to_string(),
+            start: "start".to_string(),
+            path: path.to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        adventure.records.insert("gold".to_string(), Record::parse_from_string("gold; 5".to_string()).unwrap());
+        adventure.names.insert("hero".to_string(), Name::parse_from_string("hero; Hero".to_string()).unwrap());
+
+        // without a way to ask the player nothing changes
+        let mut game = GameState::new(adventure.clone(), 1);
+        game.start().unwrap();
+        game.choose(0).unwrap();
+        assert_eq!(game.story(), "Hero has 5 gold.");
+
+        let mut game = GameState::new(adventure.clone(), 1);
+        let mut answers = vec![" 12 ".to_string(), "Robin".to_string()];
+        game.set_input(move |prompt, number| {
+            assert_eq!(number, prompt.starts_with("How much"));
+            Some(answers.remove(if number { 0 } else { answers.len() - 1 }))
+        });
+        game.start().unwrap();
+        game.choose(0).unwrap();
+        assert_eq!(game.story(), "Robin has 12 gold.");
+
+        let mut game = GameState::new(adventure, 1);
+        game.set_input(|_, number| if number { Some("lots".to_string()) } else { None });
+        game.start().unwrap();
+        match game.choose(0) {
+            Err(GameError::InvalidInput(e)) => assert_eq!(e, "lots"),
+            _ => assert!(false),
+        }
+        assert_eq!(game.records["gold"].value, 5);
+        let _ = remove_dir_all(&path);
+    }
+    #[test]
+    fn game_state_page_title() {
+        assert_eq!(humanize_page_name("hard-battle"), "Hard battle");
+        assert_eq!(humanize_page_name("old_mill_2"), "Old mill 2");
+        assert_eq!(humanize_page_name(""), "");
+
+        let path = test_folder("adventure-book-title");
+        std::fs::write(
+            path.join("dark-forest.txt"),
+            "story: Trees.\nchoice: Go on{result: on}\nresult: on;camp",
+        )
+        .unwrap();
+        std::fs::write(path.join("camp.txt"), "title: [hero]'s camp\nstory: Fire.\nchoice: Rest{result: game over}").unwrap();
+        let mut adventure = Adventure {
+            title: "Title".to_string(),
+            start: "dark-forest".to_string(),
+            path: path.to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        adventure.names.insert("hero".to_string(), Name::parse_from_string("hero; Robin".to_string()).unwrap());
+        let mut game = GameState::new(adventure, 1);
+        game.start().unwrap();
+        assert_eq!(game.title(), "Dark forest");
+        game.choose(0).unwrap();
+        assert_eq!(game.title(), "Robin's camp");
+        let _ = remove_dir_all(&path);
+    }
+    #[test]
+    fn game_state_shared_results() {
+        let path = test_folder("adventure-book-shared-results");
+        std::fs::write(
+            path.join("road.txt"),
+            "story: A road.\nchoice: Go back{result: return to town}\nchoice: Rest{result: rest}\nresult: rest;road",
+        )
+        .unwrap();
+        std::fs::write(
+            path.join("town.txt"),
+            "story: A town.\nchoice: Rest{result: rest}\nchoice: Retire{result: game over}",
+        )
+        .unwrap();
+        std::fs::write(path.join("inn.txt"), "story: An inn.\nchoice: Sleep{result: game over}").unwrap();
+        let mut adventure = Adventure {
+            title: "Title".to_string(),
+            start: "road".to_string(),
+            path: path.to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        adventure.records.insert("gold".to_string(), Record::parse_from_string("gold; 0".to_string()).unwrap());
+        for text in ["return to town;town;gold;5", "rest;inn"] {
+            let result = StoryResult::parse_from_string(text.to_string()).unwrap();
+            adventure.results.insert(result.name.clone(), result);
+        }
+        let mut game = GameState::new(adventure, 1);
+        game.start().unwrap();
+        // the result of the page is used instead of the shared one
+        game.choose(1).unwrap();
+        assert_eq!(game.page_name, "road");
+        game.choose(0).unwrap();
+        assert_eq!(game.page_name, "town");
+        assert_eq!(game.records["gold"].value, 5);
+        game.choose(0).unwrap();
+        assert_eq!(game.page_name, "inn");
+        let _ = remove_dir_all(&path);
+    }
+    #[test]
+    fn game_state_playtest() {
+        let path = test_folder("adventure-book-playtest");
+        let mut adventure = write_test_adventure(&path);
+        adventure.records.get_mut("gold").unwrap().value = 20;
+        let mut pages = HashMap::new();
+        for (name, text) in [
+            ("cave", "title: Flooded cave\nstory: Water.\nchoice: Swim{result: swim}\nresult: swim;lake;gold;-5"),
+            ("lake", "title: Lake\nstory: Still water.\nchoice: Back{result: back}\nresult: back;start"),
+        ] {
+            pages.insert(name.to_string(), Page::parse_from_string(text.to_string()).unwrap());
+        }
+        let mut game = GameState::new(adventure, 1);
+        game.set_pages(pages);
+        // the page in memory is played instead of the one in the adventure folder
+        game.start_at("cave").unwrap();
+        assert_eq!(game.title(), "Flooded cave");
+        game.choose(0).unwrap();
+        assert_eq!(game.page_name, "lake");
+        assert_eq!(game.records["gold"].value, 15);
+        assert!(matches!(game.choose(0), Err(GameError::PageNotFound(p)) if p == "start"));
+        let _ = remove_dir_all(&path);
+    }
+    #[test]
+    fn game_state_misconfigured_side_effect() {
+        let path = test_folder("adventure-book-misconfigured");
+        let mut adventure = write_test_adventure(&path);
+        std::fs::write(
+            path.join("start.txt"),
+            "title: Crossroads\nstory: Go.\nchoice: Go left{result: left}\nresult: left;cave;gold;1d;silver;3",
+        )
+        .unwrap();
+        let silver = Record::parse_from_string("silver; 0".to_string()).unwrap();
+        adventure.records.insert(silver.name.clone(), silver);
+        let mut game = GameState::new(adventure, 69420);
+        game.start().unwrap();
+        game.choose(0).unwrap();
+        assert_eq!(game.page_name, "cave");
+        assert_eq!(game.misconfigured_result(), Some(&"left".to_string()));
+        assert_eq!(game.records["gold"].value, 5);
+        assert_eq!(game.records["silver"].value, 3);
+        game.choose(1).unwrap();
+        assert_eq!(game.misconfigured_result(), None);
+        let _ = remove_dir_all(&path);
+    }
+    #[test]
+    fn game_state_record_bounds() {
+        let path = test_folder("adventure-book-bounds");
+        let mut adventure = write_test_adventure(&path);
+        adventure.records.get_mut("gold").unwrap().max = Some(8);
+        let mut game = GameState::new(adventure, 69420);
+        game.start().unwrap();
+        game.choose(0).unwrap();
+        assert_eq!(game.page_name, "cave");
+        assert_eq!(game.records["gold"].value, 8);
+        let _ = remove_dir_all(&path);
+    }
 }
@@ -0,0 +1,59 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::file::read_language;
+
+/// Language used when no other is chosen, its texts are the keys used in the code
+pub const DEFAULT_LANGUAGE: &str = "English";
+
+thread_local! {
+    /// Translations of the chosen language, keyed by their english text
+    static STRINGS: RefCell<HashMap<String, &'static str>> = RefCell::new(HashMap::new());
+}
+
+/// Loads translations of the language to be used by tr
+///
+/// Default language doesn't need a file, if the language can't be loaded english is used instead
+pub fn load(language: &str) {
+    let mut strings = HashMap::new();
+    if language != DEFAULT_LANGUAGE {
+        match read_language(language) {
+            Ok(text) => strings = parse_strings(&text),
+            Err(e) => println!("Could not load {} language: {}", language, e),
+        }
+    }
+    STRINGS.with(|x| *x.borrow_mut() = strings);
+}
+/// Returns translation of the english text in the loaded language
+///
+/// Texts without translation are returned as they are
+pub fn tr(key: &'static str) -> &'static str {
+    STRINGS.with(|x| x.borrow().get(key).copied().unwrap_or(key))
+}
+/// Parses a strings file, with one `english text = translation` pair per line
+///
+/// Empty lines, lines starting with # and lines without a translation are skipped
+fn parse_strings(text: &str) -> HashMap<String, &'static str> {
+    text.lines()
+        .map(|x| x.trim())
+        .filter(|x| x.len() > 0 && x.starts_with('#') == false)
+        .filter_map(|x| x.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .filter(|(key, value)| key.len() > 0 && value.len() > 0)
+        // labels of fltk widgets have to live as long as the program, translations are loaded once so leaking them is fine
+        .map(|(key, value)| (key.to_string(), &*Box::leak(value.to_string().into_boxed_str())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_strings;
+
+    #[test]
+    fn strings_parsing() {
+        let text = "# comment\nNew Game = Nowa Gra\n\nQuit=Wyjdź\nEditor =\nno translation\n";
+        let strings = parse_strings(text);
+        assert_eq!(strings.len(), 2);
+        assert_eq!(strings["New Game"], "Nowa Gra");
+        assert_eq!(strings["Quit"], "Wyjdź");
+    }
+}
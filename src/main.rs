@@ -1,15 +1,27 @@
-use adventure::{Adventure, Page};
-use dialog::{ask_for_new_adventure, ask_to_choose_adventure, ask_to_confirm};
-use evaluation::{evaluate_expression, Random};
-use file::{capture_adventures, signal_error};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use adventure::{Adventure, Page, ParsingError};
+use dialog::{
+    ask_for_answer, ask_for_data_folder, ask_for_new_adventure, ask_to_choose_adventure, ask_to_confirm, show_report,
+};
+use file::{
+    capture_adventures, capture_adventures_with_progress, capture_languages, capture_pages, forget_cached_pages, is_valid_root,
+    load_achievements, load_adventure, load_global_records, load_settings, read_page,
+    save_achievements, save_global_records, save_settings, signal_error, FileError, DEFAULT_SEED,
+};
 use fltk::{
     app::{self, App},
     draw::Rect,
+    enums::{Color, Font, Key},
     prelude::*,
     window::Window,
 };
-use game::{render_page, Event};
-use window::MainWindow;
+use game::{render_page, Event, GameState};
+use window::{MainWindow, SplashWindow};
 
 extern crate dirs;
 extern crate fltk;
@@ -23,13 +35,50 @@ mod evaluation;
 mod file;
 mod game;
 mod icons;
+mod lang;
+mod spelling;
+mod tts;
 mod widgets;
 mod window;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|x| x == "--validate") {
+        let code = match args.get(i + 1) {
+            Some(path) => validate_adventure(path),
+            None => {
+                println!("Usage: adventure-book --validate <path to adventure folder>");
+                2
+            }
+        };
+        std::process::exit(code);
+    }
+    // timing of loading and playing adventures, meant for catching performance regressions
+    if let Some(i) = args.iter().position(|x| x == "--bench") {
+        let runs = args.get(i + 2).and_then(|x| x.parse().ok()).unwrap_or(10);
+        let code = match args.get(i + 1) {
+            Some(path) if runs > 0 => bench_adventure(path, runs),
+            _ => {
+                println!("Usage: adventure-book --bench <path to adventure folder> [runs]");
+                2
+            }
+        };
+        std::process::exit(code);
+    }
+
     let app = App::default();
     let (s, game_events) = app::channel();
-    let mut adventures = capture_adventures();
+    let mut settings = load_settings();
+    let mut global_records = load_global_records();
+    let mut achievements = load_achievements();
+    lang::load(&settings.language);
+    spelling::load(&settings.language);
+
+    // scanning a large library takes a while, the splash shows that the program is starting
+    let mut splash = SplashWindow::create();
+    let mut adventures =
+        capture_adventures_with_progress(|done, total| splash.set_progress(done, total));
+    splash.close();
 
     let window_size = Rect::new(0, 0, 1000, 750);
     let mut window = Window::new(
@@ -43,24 +92,58 @@ fn main() {
     window.set_xclass("Choose your own adventure");
 
     let mut main_window = MainWindow::create(window_size);
+    main_window.main_menu.set_typewriter(settings.typewriter);
+    main_window.game_window.set_typewriter(settings.typewriter);
+    main_window.main_menu.set_speech(settings.speech);
+    main_window.main_menu.set_seed(settings.seed);
+    main_window
+        .main_menu
+        .set_languages(&capture_languages(), &settings.language);
+    tts::set_enabled(settings.speech);
     window.end();
+    window.handle({
+        let s = s.clone();
+        move |_, ev| match ev {
+            fltk::enums::Event::KeyDown | fltk::enums::Event::Shortcut
+                if app::event_key() == Key::F11 =>
+            {
+                s.send(Event::ToggleFullscreen);
+                true
+            }
+            _ => false,
+        }
+    });
+    // closing the window goes through the same confirmation as quitting from the menu
+    window.set_callback({
+        let s = s.clone();
+        move |_| s.send(Event::Quit)
+    });
     window.show();
+    if settings.fullscreen {
+        window.fullscreen(true);
+    }
 
-    let mut selected_adventure = 0;
-    let mut active_storybook = Adventure::default();
-    let mut active_page = Page::default();
-    let mut rng = Random::new(69420);
+    // path of the adventure selected in adventure select screen
+    let mut selected_adventure = String::new();
+    let mut game: Option<GameState> = None;
+    // page the adventure being playtested from the editor was started from
+    let mut playtest: Option<String> = None;
 
     while app.wait() {
         if let Some(msg) = game_events.recv() {
             match msg {
                 Event::Quit => {
-                    app::quit();
+                    if confirm_quit(&mut main_window, &game) {
+                        app::quit();
+                    }
                 }
                 // Enters adventure select screen
                 Event::DisplayAdventureSelect => {
                     if adventures.len() > 0 {
-                        main_window.main_menu.fill_adventure_choices(&adventures);
+                        selected_adventure = main_window
+                            .main_menu
+                            .fill_adventure_choices(&adventures)
+                            .unwrap_or_default();
                         main_window.switch_to_adventure_choice();
                     } else {
                         signal_error!("Could not find any adventures!");
@@ -69,151 +152,435 @@ fn main() {
                 }
                 // Enters main menu screen
                 Event::DisplayMainMenu => main_window.switch_to_main_menu(),
-                Event::QuitToMainMenu => main_window.switch_to_adventure_choice(),
+                Event::QuitToMainMenu => {
+                    tts::stop();
+                    game = None;
+                    main_window.game_window.set_colors(None, None);
+                    main_window.game_window.set_story_theme(None, None, None);
+                    match playtest.take() {
+                        Some(_) => main_window.return_to_editor(),
+                        None => main_window.switch_to_adventure_choice(),
+                    }
+                }
                 // Changes which adventure is selected in adventure select screen
                 Event::SelectAdventure(txt) => {
-                    if let Some(find) = adventures.iter().position(|x| x.title == txt) {
-                        selected_adventure = find;
-                        let adventure = &adventures[find];
+                    if let Some(adventure) = adventures.iter().find(|x| x.title == txt) {
+                        selected_adventure = adventure.path.clone();
                         main_window.main_menu.set_adventure_preview_text(adventure);
                     }
                 }
+                Event::ShowAchievements => {
+                    if let Some(adventure) = adventures.iter().find(|x| x.path == selected_adventure) {
+                        let lines = adventure
+                            .achievements
+                            .iter()
+                            .map(|x| {
+                                let mark = match achievements.is_unlocked(&adventure.title, &x.id) {
+                                    true => "[x]",
+                                    false => "[ ]",
+                                };
+                                format!("{} {}: {}", mark, x.id, x.description)
+                            })
+                            .collect();
+                        show_report(lang::tr("Achievements"), &lines);
+                    }
+                }
 
-                // Enters gameplay screen and starts a new game
-                Event::StartAdventure => {
-                    active_storybook = adventures[selected_adventure].clone();
-                    main_window.game_window.clear_records();
-                    match render_page(
-                        &mut main_window,
-                        &active_storybook,
-                        &active_storybook.start,
-                        &mut rng,
-                    ) {
-                        Ok(v) => active_page = v,
-                        Err(_) => {
-                            signal_error!("The adventure has invalid start page");
+                // playtests restart from the page they were started from, with the pages of the editor
+                Event::RestartAdventure if playtest.is_some() => {
+                    if let (Some(state), Some(page)) = (game.as_mut(), playtest.as_ref()) {
+                        if let Err(e) = state.start_at(page) {
+                            signal_error!("Page {}: {}", page, e);
+                            s.send(Event::QuitToMainMenu);
+                            continue;
+                        }
+                        main_window.game_window.clear_records();
+                        render_page(&mut main_window, state);
+                        window.redraw();
+                    }
+                }
+                // Enters gameplay screen and starts a new game, restarting works the same without leaving the screen
+                Event::StartAdventure | Event::RestartAdventure => {
+                    let mut adventure = match adventures.iter().find(|x| x.path == selected_adventure) {
+                        Some(a) => a.clone(),
+                        None => {
+                            signal_error!("The selected adventure could not be found");
                             s.send(Event::DisplayAdventureSelect);
                             continue;
                         }
+                    };
+                    let seed = game_seed(settings.seed);
+                    // pages could have been changed since the adventure was last played
+                    forget_cached_pages(&adventure.path);
+                    // global records begin with values left by previous adventures of the series
+                    let namespace = adventure.global_namespace().to_string();
+                    global_records.merge_into(&namespace, &mut adventure.records);
+                    let mut state = GameState::new(adventure, seed);
+                    state.set_input(ask_for_answer);
+                    let started = match main_window.main_menu.selected_chapter() {
+                        Some(chapter) => state.start_chapter(&chapter),
+                        None => state.start(),
+                    };
+                    if let Err(_) = started {
+                        signal_error!("The adventure has invalid start page");
+                        s.send(Event::DisplayAdventureSelect);
+                        continue;
                     }
-                    main_window.switch_to_game();
+                    show_game(&mut main_window, &state);
+                    game = Some(state);
                 }
                 // Result of a choice button in gameplay screen, parses the choice and enters another storybook page into the screen
                 Event::StoryChoice(index) => {
-                    let choice = &active_page.choices[index];
-                    let result;
-                    if choice.is_game_over() {
-                        s.send(Event::QuitToMainMenu);
-                        continue;
-                    }
-                    if choice.is_constant() {
-                        // the choice leads to a result straight away, just switching pages
-                        if let Some(res) = active_page.results.get(&choice.result) {
-                            result = res;
-                        } else {
-                            signal_error!(
-                                "Selected result ({}) doesn't exist in the page ({})!",
-                                choice.result,
-                                active_page.title
-                            );
-                            s.send(Event::DisplayAdventureSelect);
+                    if let Some(state) = game.as_mut() {
+                        let title = state.title().to_string();
+                        if let Err(e) = state.choose_displayed(index) {
+                            signal_error!("Page {}: {}", title, e);
+                            s.send(match playtest {
+                                Some(_) => Event::QuitToMainMenu,
+                                None => Event::DisplayAdventureSelect,
+                            });
                             continue;
                         }
-                    } else {
-                        if let Some(test) = &active_page.tests.get(&choice.test) {
-                            let tres = match test.evaluate(&active_storybook.records, &mut rng) {
-                                Ok(v) => v,
-                                Err(e) => {
-                                    signal_error!("Error evaluating a test: {}", e);
-                                    s.send(Event::DisplayAdventureSelect);
-                                    continue;
-                                }
-                            };
-
-                            if let Some(res) = active_page.results.get(tres) {
-                                result = res;
-                            } else {
-                                signal_error!(
-                                    "Page {}: The result {} isn't declared",
-                                    active_page.title,
-                                    choice.result
-                                );
-                                s.send(Event::DisplayAdventureSelect);
+                        // playtests don't leave anything behind for the player
+                        if playtest.is_none()
+                            && global_records.update_from(state.adventure.global_namespace(), &state.records)
+                        {
+                            save_global_records(&global_records);
+                        }
+                        let mut unlocked = false;
+                        for id in state.take_unlocked() {
+                            unlocked |= playtest.is_none() && achievements.unlock(&state.adventure.title, &id);
+                        }
+                        if unlocked {
+                            save_achievements(&achievements);
+                        }
+                        if let Some(result) = state.misconfigured_result() {
+                            if ask_to_confirm(&format!("Misconfigured Result {} in page {}! The adventure will likely not proceed correctly, do you wish to return to main menu?", result, title)) {
+                                s.send(Event::QuitToMainMenu);
                                 continue;
                             }
-                        } else {
-                            signal_error!(
-                                "Page {}: The test {} isn't declared",
-                                active_page.title,
-                                choice.test
-                            );
-                            s.send(Event::DisplayAdventureSelect);
-                            continue;
                         }
-                    }
-
-                    for mods in result.side_effects.iter() {
-                        if active_storybook.records.contains_key(mods.0) {
-                            if let Ok(v) =
-                                evaluate_expression(mods.1, &active_storybook.records, &mut rng)
-                            {
-                                if let Some(r) = active_storybook.records.get_mut(mods.0) {
-                                    r.value += v;
-                                }
-                            } else {
-                                if ask_to_confirm(&format!("Misconfigured Result {} in page {}! The adventure will likely not proceed correctly, do you wish to return to main menu?", result.name, active_page.title)) {
-                                    s.send(Event::QuitToMainMenu);
-                                }
-                            }
+                        if state.is_finished() {
+                            s.send(Event::QuitToMainMenu);
+                            continue;
                         }
+                        render_page(&mut main_window, state);
+                        window.redraw();
                     }
-                    // now we move on to the next scene
-                    match render_page(
-                        &mut main_window,
-                        &active_storybook,
-                        &result.next_page,
-                        &mut rng,
-                    ) {
-                        Ok(v) => active_page = v,
-                        Err(e) => {
-                            signal_error!("{}", e);
-                            s.send(Event::DisplayAdventureSelect);
+                }
+                Event::StoryLink(page) => {
+                    if let Some(state) = game.as_mut() {
+                        if let Err(e) = state.follow_link(&page) {
+                            signal_error!("Page {}: {}", state.title(), e);
+                            s.send(match playtest {
+                                Some(_) => Event::QuitToMainMenu,
+                                None => Event::DisplayAdventureSelect,
+                            });
                             continue;
                         }
+                        if playtest.is_none()
+                            && global_records.update_from(state.adventure.global_namespace(), &state.records)
+                        {
+                            save_global_records(&global_records);
+                        }
+                        render_page(&mut main_window, state);
+                        window.redraw();
                     }
-
-                    window.redraw();
+                }
+                Event::SetTypewriter(typewriter) => {
+                    settings.typewriter = typewriter;
+                    main_window.game_window.set_typewriter(typewriter);
+                    save_settings(&settings);
+                }
+                Event::SetSpeech(speech) => {
+                    settings.speech = speech;
+                    tts::set_enabled(speech);
+                    save_settings(&settings);
+                }
+                Event::SetLanguage(language) => {
+                    settings.language = language;
+                    save_settings(&settings);
+                }
+                Event::SetSeed(seed) => {
+                    settings.seed = seed;
+                    save_settings(&settings);
+                }
+                Event::ToggleFullscreen => {
+                    settings.fullscreen = window.fullscreen_active() == false;
+                    window.fullscreen(settings.fullscreen);
+                    save_settings(&settings);
+                }
+                Event::AddDataFolder => {
+                    let folder = match ask_for_data_folder() {
+                        Some(f) => f,
+                        None => continue,
+                    };
+                    if is_valid_root(&folder) == false {
+                        signal_error!("The folder {:?} doesn't exist or can't be read", folder);
+                        continue;
+                    }
+                    if settings.roots.contains(&folder) {
+                        signal_error!("The folder {:?} was already added", folder);
+                        continue;
+                    }
+                    settings.roots.push(folder);
+                    save_settings(&settings);
+                    adventures = capture_adventures();
                 }
                 Event::EditAdventure => {
-                    if let Some(index) = ask_to_choose_adventure(&adventures) {
+                    if let Some(index) = ask_to_choose_adventure(&adventures, true) {
                         if let Some(ad) = adventures.get(index) {
-                            main_window.editor_window.load_adventure(&ad, index);
+                            main_window.editor_window.load_adventure(&ad);
                             main_window.switch_to_editor();
                         } else {
                             if let Some(ad) = ask_for_new_adventure() {
-                                main_window
-                                    .editor_window
-                                    .load_adventure(&ad, adventures.len());
+                                main_window.editor_window.load_adventure(&ad);
                                 adventures.push(ad);
                                 main_window.switch_to_editor();
                             }
                         }
                     }
                 }
+                Event::AddAdventure(adventure) => adventures.push(adventure),
                 Event::Editor(e) => {
-                    if e == crate::editor::Event::Save {
-                        main_window.editor_window.process(e);
-                        let ret = main_window.editor_window.get_adventure();
-                        if let Some(index) = ret.1 {
-                            adventures[index] = ret.0;
-                        } else {
-                            adventures.push(ret.0);
+                    let leaving = e == crate::editor::Event::Return;
+                    let playtesting = e == crate::editor::Event::Playtest;
+                    if main_window.editor_window.process(e) {
+                        let edited = main_window.editor_window.get_adventure();
+                        forget_cached_pages(&edited.path);
+                        // the list may have been recaptured since the adventure was loaded so it's found by its path
+                        match adventures.iter_mut().find(|x| x.path == edited.path) {
+                            Some(a) => *a = edited,
+                            None => adventures.push(edited),
                         }
-                    } else {
-                        main_window.editor_window.process(e);
                     }
+                    if leaving && main_window.editor_window.can_leave() {
+                        main_window.switch_to_main_menu();
+                    }
+                    if playtesting {
+                        let (adventure, pages, page) = match main_window.editor_window.playtest() {
+                            Some(p) => p,
+                            None => continue,
+                        };
+                        let mut state = GameState::new(adventure, game_seed(settings.seed));
+                        state.set_input(ask_for_answer);
+                        state.set_pages(pages);
+                        if let Err(e) = state.start_at(&page) {
+                            signal_error!("Page {}: {}", page, e);
+                            main_window.return_to_editor();
+                            continue;
+                        }
+                        show_game(&mut main_window, &state);
+                        game = Some(state);
+                        playtest = Some(page);
+                    }
+                }
+            }
+        }
+    }
+}
+/// Seed a new game is started with, the one chosen in the settings or one taken from the current time
+fn game_seed(seed: Option<u64>) -> u64 {
+    seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(DEFAULT_SEED, |x| x.as_secs())
+    })
+}
+/// Switches to the gameplay screen styled by the theme of the adventure and shows the page the game started on
+fn show_game(main_window: &mut MainWindow, state: &GameState) {
+    main_window.game_window.clear_records();
+    main_window.game_window.display_seed(state.seed());
+    main_window.game_window.set_story_theme(
+        state.adventure.theme_color.map(Color::from_hex),
+        state.adventure.theme_font.as_ref().map(|x| Font::by_name(x)),
+        state.adventure.theme_size,
+    );
+    main_window.game_window.set_colors(
+        state.adventure.theme_background.map(Color::from_hex),
+        state.adventure.theme_accent.map(Color::from_hex),
+    );
+    render_page(main_window, state);
+    main_window.switch_to_game();
+}
+/// Asks the player to confirm quitting when an adventure is being played or the editor has unsaved changes
+///
+/// Returns true if the program can quit
+fn confirm_quit(main_window: &mut MainWindow, game: &Option<GameState>) -> bool {
+    if game.as_ref().map_or(false, |x| x.is_finished() == false)
+        && ask_to_confirm(lang::tr("Quit and abandon current adventure?")) == false
+    {
+        return false;
+    }
+    if main_window.editor_window.has_unsaved_changes()
+        && ask_to_confirm(lang::tr("The adventure has unsaved changes, quit without saving them?")) == false
+    {
+        return false;
+    }
+    true
+}
+/// Loads the adventure from the path with all of its pages and prints any problems found to the console
+///
+/// Returns exit code for the program, 0 if the adventure has no problems
+fn validate_adventure(path: &str) -> i32 {
+    let adventure = match load_adventure(PathBuf::from(path)) {
+        Ok(a) => a,
+        Err(e) => {
+            println!("{}", e);
+            return 1;
+        }
+    };
+    let mut problems = Vec::new();
+    let mut pages = HashMap::new();
+    for name in capture_pages(&adventure.path) {
+        match read_page(&adventure.path, &name) {
+            Ok(p) => {
+                // choices of the page can lead to results that aren't in the page or the adventure
+                if p.is_playable_with(&adventure.results) == false {
+                    problems.push(format!("Page {} is incomplete", name));
+                }
+                pages.insert(name, p);
+            }
+            Err(FileError::ParsingFailure(_, ParsingError::IncomplatePage(p))) => {
+                problems.push(format!("Page {} is incomplete", name));
+                pages.insert(name, p);
+            }
+            Err(e) => problems.push(format!("Page {}: {}", name, e)),
+        }
+    }
+    problems.append(&mut adventure.validate(&pages));
+
+    if problems.len() > 0 {
+        problems.iter().for_each(|x| println!("{}", x));
+        println!("{}: {} problem(s) found", adventure.title, problems.len());
+        return 1;
+    }
+    println!("{}: no problems found", adventure.title);
+    0
+}
+/// Loads the adventure and plays through all of its reachable pages the number of times, printing how long it took
+///
+/// Each run reads and parses all pages, evaluates all of their expressions, then visits every reachable page
+/// and tries each of its available choices
+///
+/// Returns exit code for the program, 0 if all runs finished
+fn bench_adventure(path: &str, runs: usize) -> i32 {
+    let mut parse_total = Duration::ZERO;
+    let mut evaluation_total = Duration::ZERO;
+    let mut traversal_total = Duration::ZERO;
+    for run in 0..runs {
+        let timer = Instant::now();
+        forget_cached_pages(&path.to_string());
+        let adventure = match load_adventure(PathBuf::from(path)) {
+            Ok(a) => a,
+            Err(e) => {
+                println!("{}", e);
+                return 1;
+            }
+        };
+        let mut pages = HashMap::new();
+        for name in capture_pages(&adventure.path) {
+            match read_page(&adventure.path, &name) {
+                Ok(p) | Err(FileError::ParsingFailure(_, ParsingError::IncomplatePage(p))) => {
+                    pages.insert(name, p);
+                }
+                Err(e) => {
+                    println!("Page {}: {}", name, e);
+                    return 1;
                 }
             }
         }
+        let parse = timer.elapsed();
+
+        let timer = Instant::now();
+        let problems = adventure.check_expressions(&pages).len();
+        let evaluation = timer.elapsed();
+
+        let timer = Instant::now();
+        let (visited, errors) = traverse_adventure(adventure, &pages, run as u64);
+        let traversal = timer.elapsed();
+
+        println!(
+            "Run {}: parse {:.2?}, evaluation {:.2?} ({} problem(s)), traversal {:.2?} ({} page(s), {} error(s))",
+            run + 1,
+            parse,
+            evaluation,
+            problems,
+            traversal,
+            visited,
+            errors
+        );
+        parse_total += parse;
+        evaluation_total += evaluation;
+        traversal_total += traversal;
+    }
+    let runs_u32 = runs as u32;
+    println!(
+        "Average of {} run(s): parse {:.2?}, evaluation {:.2?}, traversal {:.2?}, total {:.2?}",
+        runs,
+        parse_total / runs_u32,
+        evaluation_total / runs_u32,
+        traversal_total / runs_u32,
+        (parse_total + evaluation_total + traversal_total) / runs_u32
+    );
+    0
+}
+/// Visits every page reachable from the start of the adventure and makes each available choice once
+///
+/// Pages reached by every outcome of tests and random results are visited, not only the rolled ones
+///
+/// Returns count of visited pages and count of choices that failed
+fn traverse_adventure(
+    adventure: Adventure,
+    pages: &HashMap<String, Page>,
+    seed: u64,
+) -> (usize, usize) {
+    let start = adventure.start.clone();
+    let mut state = GameState::new(adventure, seed);
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([start]);
+    let mut errors = 0;
+    while let Some(page) = queue.pop_front() {
+        if visited.insert(page.clone()) == false {
+            continue;
+        }
+        if state.is_finished() && state.start().is_err() {
+            errors += 1;
+            continue;
+        }
+        if state.follow_link(&page).is_err() {
+            errors += 1;
+            continue;
+        }
+        for (i, choice) in state.available_choices().iter().enumerate() {
+            if choice.0 == false {
+                continue;
+            }
+            let entered = match state.is_finished() {
+                true => state.start().and_then(|_| state.follow_link(&page)),
+                false => state.follow_link(&page),
+            };
+            if entered.is_err() || state.choose(i).is_err() {
+                errors += 1;
+                continue;
+            }
+            // side effects that can't be evaluated are skipped by the game, but they're still a failure
+            if state.misconfigured_result().is_some() {
+                errors += 1;
+            }
+            if state.is_finished() == false {
+                queue.push_back(state.page_name.clone());
+            }
+        }
+        if let Some(p) = pages.get(&page) {
+            p.results
+                .values()
+                .chain(p.used_shared_results(&state.adventure.results))
+                .map(|x| x.next_page.clone())
+                .chain(p.randoms.values().flat_map(|x| x.outcomes.iter().map(|x| x.0.clone())))
+                .chain(p.story_links().into_iter().map(|x| x.to_string()))
+                .for_each(|x| queue.push_back(x));
+        }
     }
+    (visited.len(), errors)
 }
@@ -0,0 +1,124 @@
+use std::{cell::RefCell, collections::HashSet, ops::Range};
+
+use crate::file::read_dictionary;
+
+thread_local! {
+    /// Lowercase words of the loaded dictionary, empty if no dictionary could be loaded
+    static WORDS: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+}
+
+/// Loads the dictionary of the language used to check spelling of story texts
+///
+/// If no dictionary is found for the language, spelling isn't checked
+pub fn load(language: &str) {
+    let words = match read_dictionary(language) {
+        Ok(text) => parse_dictionary(&text),
+        Err(e) => {
+            println!("Could not load {} dictionary: {}", language, e);
+            HashSet::new()
+        }
+    };
+    WORDS.with(|x| *x.borrow_mut() = words);
+}
+/// Tests if a dictionary is loaded and spelling can be checked
+pub fn is_available() -> bool {
+    WORDS.with(|x| x.borrow().len() > 0)
+}
+/// Finds byte ranges of words in the text that are neither in the dictionary nor in the extra words
+///
+/// Keywords in square brackets aren't checked
+pub fn misspelled(text: &str, extra: &Vec<String>) -> Vec<Range<usize>> {
+    WORDS.with(|x| misspelled_words(text, &x.borrow(), extra))
+}
+/// Returns the word the byte position is in or right after, keywords in square brackets aren't words
+pub fn word_at(text: &str, position: usize) -> Option<&str> {
+    words(text)
+        .into_iter()
+        .find(|x| x.start <= position && position <= x.end)
+        .map(|x| &text[x])
+}
+/// Parses a word list with one word per line
+///
+/// Affix flags after a / and the word count on the first line of hunspell dictionaries are ignored
+fn parse_dictionary(text: &str) -> HashSet<String> {
+    text.lines()
+        .map(|x| x.split('/').next().unwrap_or_default().trim())
+        .filter(|x| x.len() > 0 && x.chars().all(|c| c.is_ascii_digit()) == false)
+        .map(|x| x.to_lowercase())
+        .collect()
+}
+/// Finds byte ranges of words in the text, skipping keywords in square brackets
+///
+/// Words are made of letters and apostrophes between them, words containing digits are skipped
+fn words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut in_keyword = false;
+    let mut has_digit = false;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if in_keyword {
+            in_keyword = c != ']';
+            continue;
+        }
+        let next_is_letter = chars.peek().map(|x| x.1.is_alphabetic()) == Some(true);
+        if c.is_alphanumeric() || (c == '\'' && start.is_some() && next_is_letter) {
+            if start.is_none() {
+                start = Some(i);
+                has_digit = false;
+            }
+            has_digit |= c.is_numeric();
+            continue;
+        }
+        if let Some(s) = start.take() {
+            if has_digit == false {
+                words.push(s..i);
+            }
+        }
+        in_keyword = c == '[';
+    }
+    if let Some(s) = start {
+        if has_digit == false {
+            words.push(s..text.len());
+        }
+    }
+    words
+}
+/// Finds byte ranges of words in the text that aren't in the dictionary or the extra words
+fn misspelled_words(text: &str, dictionary: &HashSet<String>, extra: &Vec<String>) -> Vec<Range<usize>> {
+    if dictionary.len() == 0 {
+        return Vec::new();
+    }
+    words(text)
+        .into_iter()
+        .filter(|x| {
+            let word = &text[x.clone()];
+            let lower = word.to_lowercase();
+            dictionary.contains(&lower) == false
+                && extra.iter().any(|e| e == word || e.to_lowercase() == lower) == false
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{misspelled_words, parse_dictionary, word_at};
+
+    #[test]
+    fn spelling_check() {
+        let dictionary = parse_dictionary("3\nthe\nknight/S\ndon't\n\nentered\n");
+        assert_eq!(dictionary.len(), 4);
+        let text = "The knigt entered [castle], don't stop 3rd Eldoria";
+        let wrong: Vec<&str> = misspelled_words(text, &dictionary, &vec!["eldoria".to_string()])
+            .into_iter()
+            .map(|x| &text[x])
+            .collect();
+        assert_eq!(wrong, vec!["knigt", "stop"]);
+        assert!(misspelled_words(text, &Default::default(), &vec![]).is_empty());
+
+        assert_eq!(word_at(text, 6), Some("knigt"));
+        assert_eq!(word_at(text, 9), Some("knigt"));
+        assert_eq!(word_at(text, 21), None);
+        assert_eq!(word_at("zamek", 0), Some("zamek"));
+    }
+}
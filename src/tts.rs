@@ -0,0 +1,88 @@
+use std::{
+    io::Write,
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+/// Whether text should be read aloud, off unless the player turns it on
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Speech that is currently being read, stopped when new text is spoken
+static SPEAKING: Mutex<Option<Child>> = Mutex::new(None);
+
+/// Speech programs to try on linux, in order of preference
+#[cfg(target_os = "linux")]
+const SPEECH_COMMANDS: &[(&str, &[&str])] = &[("espeak-ng", &["--stdin"]), ("espeak", &["--stdin"])];
+/// Speech programs to try on mac
+#[cfg(target_os = "macos")]
+const SPEECH_COMMANDS: &[(&str, &[&str])] = &[("say", &[])];
+/// Speech programs to try on windows
+#[cfg(target_os = "windows")]
+const SPEECH_COMMANDS: &[(&str, &[&str])] = &[(
+    "powershell",
+    &[
+        "-NoProfile",
+        "-Command",
+        "Add-Type -AssemblyName System.Speech; (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak([Console]::In.ReadToEnd())",
+    ],
+)];
+/// No known speech programs on other platforms
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+const SPEECH_COMMANDS: &[(&str, &[&str])] = &[];
+
+/// Turns reading text aloud on or off, turning it off stops current speech
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if enabled == false {
+        stop();
+    }
+}
+/// Tests if text is read aloud
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+/// Reads the text aloud, replacing anything that is still being read
+///
+/// Does nothing if reading is turned off or no speech program is available on the system
+pub fn speak(text: &str) {
+    if is_enabled() == false {
+        return;
+    }
+    stop();
+    if text.trim().len() == 0 {
+        return;
+    }
+    for (program, args) in SPEECH_COMMANDS.iter() {
+        let mut child = match Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            if let Err(e) = stdin.write_all(text.as_bytes()) {
+                println!("Failed to pass text to {}: {}", program, e);
+            }
+            // stdin is closed here so the program knows the text is complete
+        }
+        if let Ok(mut speaking) = SPEAKING.lock() {
+            *speaking = Some(child);
+        }
+        return;
+    }
+}
+/// Stops reading text
+pub fn stop() {
+    if let Ok(mut speaking) = SPEAKING.lock() {
+        if let Some(mut child) = speaking.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
@@ -3,49 +3,139 @@ use std::{cell::RefCell, rc::Rc};
 use fltk::{
     app,
     draw::*,
-    enums::{Color, Event, FrameType},
+    enums::{Color, Event, Font, FrameType, Key},
     prelude::{WidgetBase, WidgetExt, BrowserExt},
     widget::Widget,
     widget_extends, browser::SelectBrowser,
 };
 
-/// Fancy non-interactive text renderer that allows background
+/// Fancy text renderer that allows background
+///
+/// Optionally reveals the text character by character, clicking on the text shows all of it at once.
+/// Supports **bold** text and [[target|label]] links, clicking a link triggers the link callback
 pub struct TextRenderer {
     widget: Widget,
-    text: Rc<RefCell<Vec<String>>>,
+    text: Rc<RefCell<Vec<Word>>>,
+    /// Amount of characters drawn, usize::MAX draws the whole text
+    visible: Rc<RefCell<usize>>,
+    timeout: Rc<RefCell<Option<app::TimeoutHandle>>>,
+    typewriter: bool,
+    /// Target of the last clicked link
+    clicked: Rc<RefCell<Option<String>>>,
+    style: Rc<RefCell<TextStyle>>,
+}
+/// Color, font and size of text rendered by TextRenderer, None values keep the defaults
+#[derive(Debug, Clone, Default)]
+struct TextStyle {
+    color: Option<Color>,
+    font: Option<Font>,
+    size: Option<i32>,
+}
+/// Single word of text rendered by TextRenderer, along with its style
+#[derive(Debug, Clone, PartialEq)]
+struct Word {
+    text: String,
+    bold: bool,
+    /// Target of the link if the word is part of one
+    link: Option<String>,
+}
+/// Area of a link as it was last drawn, used to find which link was clicked
+struct LinkArea {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+    target: String,
 }
 
+/// Time between revealing characters in typewriter mode
+const TYPEWRITER_DELAY: f64 = 0.02;
+/// How many characters are revealed at once in typewriter mode
+const TYPEWRITER_STEP: usize = 2;
+
 impl TextRenderer {
     /// Creates a new text renderer in specified area with text to render
     pub fn new(x: i32, y: i32, w: i32, h: i32, text: &str) -> Self {
         let mut widget = Widget::new(x, y, w, h, None);
-        let text = text
-            .split(&[' ', '\n'][..])
-            .map(|x| x.to_string())
-            .collect();
-        let text = Rc::new(RefCell::new(text));
+        let text = Rc::new(RefCell::new(parse_markup(text)));
+        let visible = Rc::new(RefCell::new(usize::MAX));
+        let timeout = Rc::new(RefCell::new(None));
+        let clicked = Rc::new(RefCell::new(None));
+        let links = Rc::new(RefCell::new(Vec::<LinkArea>::new()));
+        let style = Rc::new(RefCell::new(TextStyle::default()));
 
         widget.draw({
-            let text: Rc<RefCell<Vec<String>>> = Rc::clone(&text);
+            let text: Rc<RefCell<Vec<Word>>> = Rc::clone(&text);
+            let visible: Rc<RefCell<usize>> = Rc::clone(&visible);
+            let links: Rc<RefCell<Vec<LinkArea>>> = Rc::clone(&links);
+            let style: Rc<RefCell<TextStyle>> = Rc::clone(&style);
             move |r| {
                 let x = r.x();
                 let y = r.y();
                 let w = r.w();
                 let h = r.h();
+                let style = style.borrow();
+                if style.font.is_some() || style.size.is_some() {
+                    set_font(style.font.unwrap_or(font()), style.size.unwrap_or(size()));
+                }
+                if let Some(color) = style.color {
+                    set_draw_color(color);
+                }
                 let mut line = y + size();
                 let mut cursor_x = 0;
                 let whitespace_width = width(" ") as i32;
                 let column_start = x + whitespace_width;
+                let mut remaining = *visible.borrow();
+                let normal_font = font();
+                let font_size = size();
+                let color = get_color();
+                let mut links = links.borrow_mut();
+                links.clear();
 
                 push_clip(x, y, w, h);
                 for word in text.borrow().iter() {
-                    let width = width(&word) as i32;
+                    if remaining == 0 {
+                        break;
+                    }
+                    if word.bold {
+                        set_font(bold_font(normal_font), font_size);
+                    }
+                    // layout uses the whole word so revealed text doesn't jump between lines
+                    let width = width(&word.text) as i32;
                     if width + cursor_x + whitespace_width > w {
                         cursor_x = 0;
                         line += size() + size() / 2;
                     }
-                    draw_text(&word, cursor_x + column_start, line);
-                    if word.ends_with("\n") {
+                    let length = word.text.chars().count();
+                    let drawn = if length > remaining {
+                        let part: String = word.text.chars().take(remaining).collect();
+                        remaining = 0;
+                        part
+                    } else {
+                        remaining -= length;
+                        word.text.clone()
+                    };
+                    if let Some(target) = &word.link {
+                        let underline = fltk::draw::width(drawn.trim_end()) as i32;
+                        let start = cursor_x + column_start;
+                        set_draw_color(Color::Blue);
+                        draw_text(&drawn, start, line);
+                        draw_line(start, line + 2, start + underline, line + 2);
+                        set_draw_color(color);
+                        links.push(LinkArea {
+                            x: start,
+                            y: line - font_size,
+                            w: width,
+                            h: font_size + font_size / 2,
+                            target: target.clone(),
+                        });
+                    } else {
+                        draw_text(&drawn, cursor_x + column_start, line);
+                    }
+                    if word.bold {
+                        set_font(normal_font, font_size);
+                    }
+                    if word.text.ends_with("\n") {
                         cursor_x = 0;
                         line += size() + size() / 2;
                     }
@@ -54,26 +144,205 @@ impl TextRenderer {
                 pop_clip();
             }
         });
-        Self { widget, text }
+        widget.handle({
+            let visible: Rc<RefCell<usize>> = Rc::clone(&visible);
+            let timeout: Rc<RefCell<Option<app::TimeoutHandle>>> = Rc::clone(&timeout);
+            let clicked: Rc<RefCell<Option<String>>> = Rc::clone(&clicked);
+            let links: Rc<RefCell<Vec<LinkArea>>> = Rc::clone(&links);
+            move |wid, ev| match ev {
+                Event::Push => {
+                    if let Some(handle) = timeout.borrow_mut().take() {
+                        app::remove_timeout3(handle);
+                        *visible.borrow_mut() = usize::MAX;
+                        if let Some(mut p) = wid.parent() {
+                            p.redraw();
+                        }
+                        return true;
+                    }
+                    let (x, y) = app::event_coords();
+                    let target = links
+                        .borrow()
+                        .iter()
+                        .find(|l| x >= l.x && x < l.x + l.w && y >= l.y && y < l.y + l.h)
+                        .map(|l| l.target.clone());
+                    if let Some(target) = target {
+                        *clicked.borrow_mut() = Some(target);
+                        wid.do_callback();
+                        return true;
+                    }
+                    false
+                }
+                _ => false,
+            }
+        });
+        Self {
+            widget,
+            text,
+            visible,
+            timeout,
+            typewriter: false,
+            clicked,
+            style,
+        }
+    }
+    /// Sets color of the text, None restores the default color
+    pub fn set_color(&mut self, color: Option<Color>) {
+        self.style.borrow_mut().color = color;
+        self.widget.redraw();
+    }
+    /// Sets font of the text, None restores the default font
+    pub fn set_font(&mut self, font: Option<Font>) {
+        self.style.borrow_mut().font = font;
+        self.widget.redraw();
+    }
+    /// Sets size of the text, None restores the default size
+    pub fn set_size(&mut self, size: Option<i32>) {
+        self.style.borrow_mut().size = size;
+        self.widget.redraw();
+    }
+    /// Sets a function called with the target of a link when the player clicks on it
+    pub fn set_link_callback<F: FnMut(String) + 'static>(&mut self, mut callback: F) {
+        let clicked: Rc<RefCell<Option<String>>> = Rc::clone(&self.clicked);
+        self.widget.set_callback(move |_| {
+            if let Some(target) = clicked.borrow_mut().take() {
+                callback(target);
+            }
+        });
     }
     /// Sets new text to render
+    ///
+    /// In typewriter mode the text is revealed gradually
     pub fn set_text(&mut self, text: &str) {
-        *self.text.borrow_mut() = text
-            .split_inclusive(&[' ', '\n'][..])
-            .map(|x| x.to_string())
-            .collect();
+        let words = parse_markup(text);
+        let total = words.iter().map(|x| x.text.chars().count()).sum::<usize>();
+        *self.text.borrow_mut() = words;
+        if let Some(handle) = self.timeout.borrow_mut().take() {
+            app::remove_timeout3(handle);
+        }
+        if self.typewriter {
+            *self.visible.borrow_mut() = 0;
+            let handle = app::add_timeout3(TYPEWRITER_DELAY, {
+                let visible: Rc<RefCell<usize>> = Rc::clone(&self.visible);
+                let timeout: Rc<RefCell<Option<app::TimeoutHandle>>> = Rc::clone(&self.timeout);
+                let widget = self.widget.clone();
+                move |handle| {
+                    let shown = visible.borrow().saturating_add(TYPEWRITER_STEP);
+                    *visible.borrow_mut() = shown;
+                    if shown < total {
+                        app::repeat_timeout3(TYPEWRITER_DELAY, handle);
+                    } else {
+                        *visible.borrow_mut() = usize::MAX;
+                        *timeout.borrow_mut() = None;
+                    }
+                    if let Some(mut p) = widget.parent() {
+                        p.redraw();
+                    }
+                }
+            });
+            *self.timeout.borrow_mut() = Some(handle);
+        } else {
+            *self.visible.borrow_mut() = usize::MAX;
+        }
         if let Some(mut p) = self.widget.parent() {
             p.redraw();
         }
     }
+    /// Turns revealing the text character by character on or off
+    pub fn set_typewriter(&mut self, typewriter: bool) {
+        self.typewriter = typewriter;
+    }
 }
 widget_extends!(TextRenderer, Widget, widget);
 
+/// Returns bold variant of a built in font, other fonts are returned as they are
+fn bold_font(font: Font) -> Font {
+    // built in font families keep their bold variant at the next index
+    if font.bits() < Font::Symbol.bits() {
+        Font::by_index(font.bits() as usize | 1)
+    } else {
+        font
+    }
+}
+/// Splits text into words, applying **bold** and [[target|label]] link markup
+///
+/// A link without a label shows its target, markup that isn't closed is treated as text
+fn parse_markup(text: &str) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut bold = false;
+    let mut rest = text;
+    while rest.len() > 0 {
+        let end = [rest.find("**"), rest.find("[[")]
+            .iter()
+            .flatten()
+            .min()
+            .copied()
+            .unwrap_or(rest.len());
+        push_words(&mut words, &rest[..end], bold, None);
+        rest = &rest[end..];
+        if rest.starts_with("**") {
+            bold = !bold;
+            rest = &rest[2..];
+        } else if rest.starts_with("[[") {
+            match rest.find("]]") {
+                Some(close) => {
+                    let inner = &rest[2..close];
+                    let (target, label) = inner.split_once('|').unwrap_or((inner, inner));
+                    push_words(&mut words, label, bold, Some(target.trim()));
+                    rest = &rest[close + 2..];
+                }
+                None => {
+                    push_words(&mut words, "[[", bold, None);
+                    rest = &rest[2..];
+                }
+            }
+        }
+    }
+    words
+}
+/// Returns the text with its markup removed, as it's shown to the player
+pub fn plain_text(text: &str) -> String {
+    parse_markup(text).into_iter().map(|x| x.text).collect()
+}
+/// Escapes text so FLTK menus show it as it is instead of reading /, \, _ and & in it as menu syntax
+///
+/// A | always splits menu items so it's replaced with a similar looking ¦
+pub fn escape_menu_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '/' | '\\' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '&' => escaped.push_str("&&"),
+            '|' => escaped.push('¦'),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+/// Escapes text so FLTK tree shows it as a single item instead of splitting it into a path at /
+pub fn escape_tree_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('/', "\\/")
+}
+/// Splits text into words with the same style and adds them to the list
+fn push_words(words: &mut Vec<Word>, text: &str, bold: bool, link: Option<&str>) {
+    for word in text.split_inclusive(&[' ', '\n'][..]) {
+        words.push(Word {
+            text: word.to_string(),
+            bold,
+            link: link.map(|x| x.to_string()),
+        });
+    }
+}
+
 /// Fancy custom selector that doesn't obscure what's behind it in drawing order
 pub struct Selector {
     widget: Widget,
     options: Rc<RefCell<Vec<String>>>,
     selected: Rc<RefCell<usize>>,
+    /// Index of the first visible option
+    scroll: Rc<RefCell<usize>>,
 }
 
 impl Selector {
@@ -83,11 +352,13 @@ impl Selector {
         let options = Rc::new(RefCell::new(vec![]));
         let selected = Rc::new(RefCell::new(0));
         let highlight = Rc::new(RefCell::new(-1));
+        let scroll = Rc::new(RefCell::new(0));
 
         widget.draw({
             let options: Rc<RefCell<Vec<String>>> = Rc::clone(&options);
             let selected: Rc<RefCell<usize>> = Rc::clone(&selected);
             let highlight: Rc<RefCell<i32>> = Rc::clone(&highlight);
+            let scroll: Rc<RefCell<usize>> = Rc::clone(&scroll);
             move |wid| {
                 let x = wid.x();
                 let y = wid.y();
@@ -102,9 +373,10 @@ impl Selector {
                 let mut row = y + label_size;
                 let sel = selected.borrow();
                 let high = highlight.borrow();
+                let scroll = *scroll.borrow();
 
                 push_clip(x, y, w, h);
-                for (i, item) in opt.iter().enumerate() {
+                for (i, item) in opt.iter().enumerate().skip(scroll) {
                     if *sel == i {
                         draw_box(
                             FrameType::BorderFrame,
@@ -130,16 +402,20 @@ impl Selector {
             let options: Rc<RefCell<Vec<String>>> = Rc::clone(&options);
             let selected: Rc<RefCell<usize>> = Rc::clone(&selected);
             let highlight: Rc<RefCell<i32>> = Rc::clone(&highlight);
+            let scroll: Rc<RefCell<usize>> = Rc::clone(&scroll);
             move |wid, ev| {
                 let cursor_position = app::event_coords();
                 let cursor_position = (cursor_position.0 - wid.x(), cursor_position.1 - wid.y());
                 let elements = options.borrow().len();
                 let label_size = wid.label_size();
                 let line_size = label_size + label_size / 5;
-                let sel = cursor_position.1 / line_size;
+                let visible = usize::max(1, (wid.h() / line_size) as usize);
+                let max_scroll = elements.saturating_sub(visible);
+                let sel = cursor_position.1 / line_size + *scroll.borrow() as i32;
 
                 match ev {
                     Event::Push => {
+                        let _ = wid.take_focus();
                         if sel < elements as i32 && *selected.borrow() != sel as usize {
                             *selected.borrow_mut() = sel as usize;
                             wid.parent().unwrap().redraw();
@@ -148,6 +424,48 @@ impl Selector {
                         true
                     }
                     Event::Enter => true,
+                    Event::Focus | Event::Unfocus => true,
+                    Event::KeyDown => {
+                        let current = *selected.borrow();
+                        let key = app::event_key();
+                        let new = if key == Key::Up && current > 0 {
+                            current - 1
+                        } else if key == Key::Down && current + 1 < elements {
+                            current + 1
+                        } else if key == Key::Enter || key == Key::KPEnter {
+                            // letting the key through so window shortcuts can confirm the choice
+                            wid.do_callback();
+                            return false;
+                        } else {
+                            return key == Key::Up || key == Key::Down;
+                        };
+                        *selected.borrow_mut() = new;
+                        // keeping the selected option in view
+                        let first = *scroll.borrow();
+                        if new < first {
+                            *scroll.borrow_mut() = new;
+                        } else if new >= first + visible {
+                            *scroll.borrow_mut() = new + 1 - visible;
+                        }
+                        wid.parent().unwrap().redraw();
+                        wid.do_callback();
+                        true
+                    }
+                    Event::MouseWheel => {
+                        let first = *scroll.borrow();
+                        // FLTK reports the wheel moving down, towards later options, as Up
+                        let new = match app::event_dy() {
+                            app::MouseWheel::Up => usize::min(first + 1, max_scroll),
+                            app::MouseWheel::Down => first.saturating_sub(1),
+                            _ => first,
+                        };
+                        if new != first {
+                            *scroll.borrow_mut() = new;
+                            *highlight.borrow_mut() = -1;
+                            wid.parent().unwrap().redraw();
+                        }
+                        true
+                    }
                     Event::Leave => {
                         *highlight.borrow_mut() = -1;
                         wid.parent().unwrap().redraw();
@@ -175,11 +493,13 @@ impl Selector {
             widget,
             options,
             selected,
+            scroll,
         }
     }
     /// Clears all the elements of the selector
     pub fn clear(&mut self) {
         self.options.borrow_mut().clear();
+        *self.scroll.borrow_mut() = 0;
     }
     /// Adds a new element to the selector
     pub fn add(&mut self, choice: String) {
@@ -207,3 +527,46 @@ pub fn find_item(selector: &SelectBrowser, item: &str) -> Option<i32> {
     }
     return None;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_menu_text, escape_tree_text, parse_markup, plain_text, Word};
+
+    #[test]
+    fn markup_bold_and_links() {
+        let word = |text: &str, bold: bool, link: Option<&str>| Word {
+            text: text.to_string(),
+            bold,
+            link: link.map(|x| x.to_string()),
+        };
+        assert_eq!(
+            parse_markup("Go **to [[ cave |the cave]]** now"),
+            vec![
+                word("Go ", false, None),
+                word("to ", true, None),
+                word("the ", true, Some("cave")),
+                word("cave", true, Some("cave")),
+                word(" ", false, None),
+                word("now", false, None),
+            ]
+        );
+        assert_eq!(
+            parse_markup("[[lake]]"),
+            vec![word("lake", false, Some("lake"))]
+        );
+    }
+    #[test]
+    fn markup_unclosed() {
+        assert_eq!(plain_text("Open [[cave and **bold"), "Open [[cave and bold");
+        assert_eq!(plain_text("A [[lake|pond]] and [[cave]]."), "A pond and cave.");
+        assert_eq!(plain_text(""), "");
+    }
+    #[test]
+    fn menu_and_tree_escaping() {
+        assert_eq!(escape_menu_text("combat"), "combat");
+        assert_eq!(escape_menu_text("_act 1/2 & more|less"), "\\_act 1\\/2 && more¦less");
+        assert_eq!(escape_menu_text("a\\b"), "a\\\\b");
+        assert_eq!(escape_tree_text("act 1/2"), "act 1\\/2");
+        assert_eq!(escape_tree_text("a\\b"), "a\\\\b");
+    }
+}
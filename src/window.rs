@@ -1,25 +1,42 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 use fltk::{
     app,
-    button::Button,
-    draw::{draw_text, draw_text2, pop_clip, push_clip, Rect},
-    enums::Align,
+    button::{Button, CheckButton},
+    draw::{draw_text, draw_text2, get_color, pop_clip, push_clip, set_draw_color, Rect},
+    enums::{Align, CallbackTrigger, Color, Font, FrameType, Key, Shortcut},
     frame::Frame,
     group::{Group, Scroll},
+    input::IntInput,
+    menu::Choice,
+    misc::Progress,
     prelude::*,
     widget::Widget,
     widget_extends,
+    window::Window,
 };
 
 use crate::{
     adventure::{Adventure, Record},
+    dialog::ask_to_confirm,
     editor::EditorWindow,
+    evaluation::Rounding,
     file::get_image_png,
     game::Event,
-    widgets::{Selector, TextRenderer},
+    lang::tr,
+    tts,
+    widgets::{plain_text, Selector, TextRenderer},
 };
 
+/// Small window shown while the program starts, showing progress of loading adventures
+pub struct SplashWindow {
+    window: Window,
+    progress: Progress,
+}
 /// Main window controls, used for managing states of subwindows
 pub struct MainWindow {
     pub main_menu: MainMenu,
@@ -34,6 +51,13 @@ pub struct MainMenu {
     adventure_title: Label,
     adventure_description: TextRenderer,
     adventure_picker: Rc<RefCell<Selector>>,
+    chapter: Choice,
+    /// Seed adventures are started with, empty for a different seed each time
+    seed: IntInput,
+    achievements: Button,
+    typewriter: CheckButton,
+    speech: CheckButton,
+    language: Choice,
 }
 /// Responsible for managing the gameplay UI
 pub struct GameWindow {
@@ -41,15 +65,31 @@ pub struct GameWindow {
     records: RecordWindow,
     story: StoryWindow,
     choices: ChoiceWindow,
+    roll: Frame,
+    /// Shows the seed of the current run so the player can play it again
+    seed: Frame,
+    /// Background image of the game screen, hidden when the adventure has its own background color
+    art: Option<Widget>,
 }
 /// Subwindow of a GameWindow responsible for displaying records to the player
 struct RecordWindow {
     widget: Widget,
     categories: Rc<RefCell<HashMap<String, HashMap<String, i32>>>>,
+    /// Categories whose records are hidden by the player
+    collapsed: Rc<RefCell<HashSet<String>>>,
+    /// Vertical ranges of category headers as they were last drawn, used to find which header was clicked
+    headers: Rc<RefCell<Vec<(i32, i32, String)>>>,
+    /// Records whose values changed with the last choice, drawn highlighted
+    changed: Rc<RefCell<HashSet<String>>>,
+    timeout: Rc<RefCell<Option<app::TimeoutHandle>>>,
 }
 /// Subwindow of a GameWindow responsible for displaying interactive choices to the player
 struct ChoiceWindow {
     window: Scroll,
+    /// Height of all the choice buttons added so far, including the space between them
+    content_height: i32,
+    /// Color of the choice buttons, None uses the default color
+    accent: Option<Color>,
 }
 /// Subwindow of a GameWindow responsible for displaying story text
 struct StoryWindow {
@@ -59,6 +99,11 @@ struct StoryWindow {
 
 type Label = Frame;
 
+/// How long in seconds records that changed stay highlighted
+const CHANGE_HIGHLIGHT_TIME: f64 = 3.0;
+/// Color of records that changed with the last choice
+const CHANGE_HIGHLIGHT_COLOR: Color = Color::DarkRed;
+
 impl MainWindow {
     /// Creates a window and all the associated UI
     ///
@@ -105,6 +150,11 @@ impl MainWindow {
         self.main_menu.hide();
         self.editor_window.show();
     }
+    /// Leaves the game started from the editor, showing the editor as it was left
+    pub fn return_to_editor(&mut self) {
+        self.game_window.hide();
+        self.editor_window.resume();
+    }
 }
 impl MainMenu {
     /// Creates main game menu UI
@@ -119,13 +169,17 @@ impl MainMenu {
                 image.draw(b.x(), b.y(), b.width(), b.height());
             });
         }
-        let mut title = Frame::new(area.w / 2 - 100 + area.x, 150, 200, 40, "Adventure Book");
+        let mut title = Frame::new(area.w / 2 - 100 + area.x, 150, 200, 40, tr("Adventure Book"));
         title.set_label_size(20);
         let but_x = area.w / 2 - 50 + area.x;
         let but_y = area.h / 2 - 50 + area.y;
-        let mut new_but = Button::new(but_x, but_y, 100, 20, "New Game");
-        let mut edit_but = Button::new(but_x, but_y + 30, 100, 20, "Editor");
-        let mut quit_but = Button::new(but_x, but_y + 60, 100, 20, "Quit");
+        let mut new_but = Button::new(but_x, but_y, 100, 20, tr("New Game"));
+        let mut edit_but = Button::new(but_x, but_y + 30, 100, 20, tr("Editor"));
+        let mut folder_but = Button::new(but_x, but_y + 60, 100, 20, tr("Add Folder"));
+        let mut quit_but = Button::new(but_x, but_y + 90, 100, 20, tr("Quit"));
+        let mut typewriter = CheckButton::new(but_x, but_y + 120, 150, 20, tr("Typewriter text"));
+        let mut speech = CheckButton::new(but_x, but_y + 150, 150, 20, tr("Read aloud"));
+        let mut language = Choice::new(but_x, but_y + 180, 150, 20, None);
         main.end();
 
         let mut starting = Group::default().size_of_parent();
@@ -153,7 +207,7 @@ impl MainMenu {
             top_border,
             half_width,
             20,
-            "Select the Adventure",
+            tr("Select the Adventure"),
         );
 
         let description = TextRenderer::new(
@@ -171,9 +225,23 @@ impl MainMenu {
             bottom_border,
             100,
             20,
-            "Back",
+            tr("Back"),
         );
-        let mut accept = Button::new(area.w - 200, bottom_border, 100, 20, "Start");
+        let mut accept = Button::new(area.w - 200, bottom_border, 100, 20, tr("Start"));
+        let mut chapter = Choice::new(area.w - 360, bottom_border, 150, 20, None);
+        let mut seed = IntInput::new(area.w - 520, bottom_border, 100, 20, tr("Seed"));
+        let mut achievements = Button::new(
+            left_border + horizontal_margin + 110,
+            bottom_border,
+            100,
+            20,
+            tr("Achievements"),
+        );
+        achievements.hide();
+        chapter.set_tooltip(tr("Chapter to begin the adventure from"));
+        chapter.hide();
+        seed.set_tooltip(tr("Seed of random values, the same seed and choices play out the same way. Leave empty for a different seed each time"));
+        seed.set_trigger(CallbackTrigger::EnterKey | CallbackTrigger::Release);
 
         starting.end();
         starting.hide();
@@ -183,9 +251,36 @@ impl MainMenu {
 
         new_but.emit(send.clone(), Event::DisplayAdventureSelect);
         edit_but.emit(send.clone(), Event::EditAdventure);
+        folder_but.emit(send.clone(), Event::AddDataFolder);
+        folder_but.set_tooltip(tr("Adds a folder to read adventures from, for example one on a synced drive"));
         back.emit(send.clone(), Event::DisplayMainMenu);
+        achievements.emit(send.clone(), Event::ShowAchievements);
         quit_but.emit(send.clone(), Event::Quit);
         accept.emit(send.clone(), Event::StartAdventure);
+        accept.set_shortcut(Shortcut::from_key(Key::Enter));
+        typewriter.set_tooltip(tr("Reveals story text gradually, click on the text to show all of it"));
+        typewriter.set_callback({
+            let send = send.clone();
+            move |b| send.send(Event::SetTypewriter(b.is_checked()))
+        });
+        speech.set_tooltip(tr("Reads story text aloud, and choices as they're selected with the keyboard, if the system has a speech program"));
+        speech.set_callback({
+            let send = send.clone();
+            move |b| send.send(Event::SetSpeech(b.is_checked()))
+        });
+        seed.set_callback({
+            let send = send.clone();
+            move |i| send.send(Event::SetSeed(i.value().trim().parse().ok()))
+        });
+        language.set_tooltip(tr("Language of the interface, it's used the next time the program starts"));
+        language.set_callback({
+            let send = send.clone();
+            move |c| {
+                if let Some(l) = c.choice() {
+                    send.send(Event::SetLanguage(l));
+                }
+            }
+        });
 
         let picker = Rc::new(RefCell::new(picker));
         picker.borrow_mut().set_callback({
@@ -204,7 +299,34 @@ impl MainMenu {
             adventure_title: title,
             adventure_description: description,
             adventure_picker: picker,
+            chapter,
+            seed,
+            achievements,
+            typewriter,
+            speech,
+            language,
+        }
+    }
+    /// Sets state of the typewriter setting without triggering its event
+    pub fn set_typewriter(&mut self, typewriter: bool) {
+        self.typewriter.set_checked(typewriter);
+    }
+    /// Sets state of the read aloud setting without triggering its event
+    pub fn set_speech(&mut self, speech: bool) {
+        self.speech.set_checked(speech);
+    }
+    /// Sets the seed adventures are started with without triggering its event
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed.set_value(&seed.map_or(String::new(), |x| x.to_string()));
+    }
+    /// Fills the language list and selects the current language without triggering its event
+    pub fn set_languages(&mut self, languages: &Vec<String>, current: &str) {
+        self.language.clear();
+        for l in languages.iter() {
+            self.language.add_choice(l);
         }
+        let index = languages.iter().position(|x| x == current).unwrap_or(0);
+        self.language.set_value(index as i32);
     }
     /// Switches the screen to display main menu
     fn show_main(&mut self) {
@@ -217,6 +339,7 @@ impl MainMenu {
         self.main_manu.show();
         self.start_menu.hide();
         self.adventure_choice.show();
+        let _ = self.adventure_picker.borrow_mut().take_focus();
     }
     /// Hides active screen
     fn hide(&mut self) {
@@ -225,10 +348,56 @@ impl MainMenu {
     /// Fills adventure information preview area with supplied adventure data
     pub fn set_adventure_preview_text(&mut self, adventure: &Adventure) {
         self.adventure_title.set_label(&adventure.title);
-        self.adventure_description.set_text(&adventure.description);
+        let mut text = String::new();
+        if adventure.author.len() > 0 {
+            text.push_str(&format!("{}: {}\n", tr("Author"), adventure.author));
+        }
+        if adventure.version.len() > 0 {
+            text.push_str(&format!("{}: {}\n", tr("Version"), adventure.version));
+        }
+        if adventure.date.len() > 0 {
+            text.push_str(&format!("{}: {}\n", tr("Date"), adventure.date));
+        }
+        if text.len() > 0 {
+            text.push('\n');
+        }
+        text.push_str(&adventure.description);
+        let records = adventure.records_preview();
+        if records.len() > 0 {
+            text.push_str(&format!("\n\n**{}**\n{}", tr("Starting records"), records.join("\n")));
+        }
+        self.adventure_description.set_text(&text);
+        self.set_chapters(adventure);
+        match adventure.achievements.len() > 0 {
+            true => self.achievements.show(),
+            false => self.achievements.hide(),
+        }
+    }
+    /// Fills the chapter list with chapters of the adventure, the list is hidden if there are none
+    fn set_chapters(&mut self, adventure: &Adventure) {
+        self.chapter.clear();
+        if adventure.chapters.len() == 0 {
+            self.chapter.hide();
+            return;
+        }
+        self.chapter.add_choice(tr("Beginning"));
+        for c in adventure.chapters.iter() {
+            self.chapter.add_choice(&c.name);
+        }
+        self.chapter.set_value(0);
+        self.chapter.show();
+    }
+    /// Returns name of the chapter the player chose to begin from, None if the adventure should start from the beginning
+    pub fn selected_chapter(&self) -> Option<String> {
+        if self.chapter.visible() == false || self.chapter.value() < 1 {
+            return None;
+        }
+        self.chapter.choice()
     }
     /// Fills chooser control with adventures to choose from
-    pub fn fill_adventure_choices(&mut self, adventures: &Vec<Adventure>) {
+    ///
+    /// Returns path of the adventure that is selected by default, or None if there's no playable adventure
+    pub fn fill_adventure_choices(&mut self, adventures: &Vec<Adventure>) -> Option<String> {
         let mut picker = self.adventure_picker.borrow_mut();
         picker.clear();
         adventures
@@ -237,16 +406,145 @@ impl MainMenu {
             .for_each(|x| picker.add(x.title.clone()));
         if let Some(sel) = picker.selected_text() {
             drop(picker);
-            if let Some(find) = adventures.iter().position(|x| x.title == sel) {
-                self.set_adventure_preview_text(adventures.get(find).unwrap());
+            if let Some(find) = adventures.iter().find(|x| x.title == sel) {
+                self.set_adventure_preview_text(find);
+                return Some(find.path.clone());
             }
         }
+        None
+    }
+}
+
+impl SplashWindow {
+    /// Creates and shows the splash window
+    pub fn create() -> Self {
+        let mut window = Window::default()
+            .with_size(300, 80)
+            .with_label("Adventure Book");
+        Frame::new(10, 10, 280, 20, tr("Loading adventures"));
+        let mut progress = Progress::new(10, 40, 280, 25, None);
+        progress.set_selection_color(Color::Blue);
+        window.end();
+        window.set_pos(
+            (app::screen_size().0 as i32 - window.w()) / 2,
+            (app::screen_size().1 as i32 - window.h()) / 2,
+        );
+        window.show();
+        // drawing the window right away, before the program gets busy with loading
+        app::flush();
+        let _ = app::check();
+        SplashWindow { window, progress }
+    }
+    /// Updates the progress bar with the number of processed adventure folders out of all of them
+    pub fn set_progress(&mut self, done: usize, total: usize) {
+        self.progress.set_maximum(total as f64);
+        self.progress.set_value(done as f64);
+        self.progress.set_label(&format!("{}/{}", done, total));
+        let _ = app::check();
+    }
+    /// Hides the splash window once loading is done
+    pub fn close(&mut self) {
+        self.window.hide();
     }
 }
 
 impl GameWindow {
     /// creates UI for interacting with the story
     fn create(area: Rect) -> Self {
+        let (choice_area, record_area, story_area) = GameWindow::layout(area);
+
+        let mut game_window = Group::new(area.x, area.y, area.w, area.h, "");
+
+        let art = get_image_png("story.png").ok().map(|mut image| {
+            let mut img = Widget::default().size_of_parent();
+            img.draw(move |b| {
+                image.scale(b.width(), b.height(), false, true);
+                image.draw(b.x(), b.y(), b.width(), b.height());
+            });
+            img
+        });
+
+        let choices = ChoiceWindow::create(choice_area);
+        let records = RecordWindow::create(record_area);
+        let story = StoryWindow::create(story_area);
+
+        let mut butt = Button::new(record_area.x + 10, record_area.h - 30, 20, 20, "@<-");
+        let (s, _r) = app::channel();
+
+        butt.set_tooltip(tr("Abandon the adventure and return to the adventure choice"));
+        butt.set_callback({
+            let s = s.clone();
+            move |_| {
+                // progress isn't saved so a misclick would lose the whole run
+                if ask_to_confirm(tr("Abandon current adventure?")) {
+                    s.send(Event::QuitToMainMenu);
+                }
+            }
+        });
+        let mut restart = Button::new(record_area.x + 40, record_area.h - 30, 20, 20, "@reload");
+        restart.set_tooltip(tr("Restart the adventure from the beginning"));
+        restart.set_callback(move |_| {
+            if ask_to_confirm(tr("Restart current adventure?")) {
+                s.send(Event::RestartAdventure);
+            }
+        });
+
+        let mut roll = Frame::new(
+            record_area.x + 70,
+            record_area.h - 30,
+            record_area.w - 210,
+            20,
+            None,
+        );
+        roll.set_align(Align::Left | Align::Inside);
+        let mut seed = Frame::new(
+            record_area.x + record_area.w - 140,
+            record_area.h - 30,
+            130,
+            20,
+            None,
+        );
+        seed.set_align(Align::Right | Align::Inside);
+        seed.set_tooltip(tr("Seed of random values, enter it before starting the adventure to play with the same luck"));
+
+        game_window.end();
+
+        // the window scales everything proportionally, this brings back margins and font based sizes
+        game_window.resize_callback({
+            let mut choice_window = choices.window.clone();
+            let mut record_widget = records.widget.clone();
+            let mut title = story.title.clone();
+            let mut text = (*story.text).clone();
+            let mut butt = butt.clone();
+            let mut restart = restart.clone();
+            let mut roll = roll.clone();
+            let mut seed = seed.clone();
+            move |_, x, y, w, h| {
+                let (choice_area, record_area, story_area) =
+                    GameWindow::layout(Rect::new(x, y, w, h));
+                choice_window.resize(choice_area.x, choice_area.y, choice_area.w, choice_area.h);
+                record_widget.resize(record_area.x, record_area.y, record_area.w, record_area.h - 40);
+                StoryWindow::place(&mut title, &mut text, story_area);
+                let bottom = record_area.y + record_area.h;
+                butt.resize(record_area.x + 10, bottom - 30, 20, 20);
+                restart.resize(record_area.x + 40, bottom - 30, 20, 20);
+                roll.resize(record_area.x + 70, bottom - 30, record_area.w - 210, 20);
+                seed.resize(record_area.x + record_area.w - 140, bottom - 30, 130, 20);
+            }
+        });
+
+        Self {
+            game_window,
+            choices,
+            records,
+            story,
+            roll,
+            seed,
+            art,
+        }
+    }
+    /// Splits the area into areas for choices, records and story text, in that order
+    fn layout(area: Rect) -> (Rect, Rect, Rect) {
         let width_large = (area.w as f64 * 0.7) as i32;
         let width_small = area.w - width_large;
         let height_large = (area.h as f64 * 0.7) as i32;
@@ -277,34 +575,7 @@ impl GameWindow {
             w: width_large,
             h: height_large,
         };
-
-        let game_window = Group::new(area.x, area.y, area.w, area.h, "");
-
-        if let Ok(mut image) = get_image_png("story.png") {
-            let mut img = Widget::default().size_of_parent();
-            img.draw(move |b| {
-                image.scale(b.width(), b.height(), false, true);
-                image.draw(b.x(), b.y(), b.width(), b.height());
-            });
-        }
-
-        let choices = ChoiceWindow::create(choice_area);
-        let records = RecordWindow::create(record_area);
-        let story = StoryWindow::create(story_area);
-
-        let mut butt = Button::new(record_area.x + 10, record_area.h - 30, 20, 20, "@<-");
-        let (s, _r) = app::channel();
-
-        butt.emit(s, Event::QuitToMainMenu);
-
-        game_window.end();
-
-        Self {
-            game_window,
-            choices,
-            records,
-            story,
-        }
+        (choice_area, record_area, story_area)
     }
     /// shows the game play UI
     fn show(&mut self) {
@@ -315,9 +586,50 @@ impl GameWindow {
         self.game_window.hide();
     }
     /// fills the story window with provided text
+    ///
+    /// The text is also read aloud if the player turned it on
     pub fn display_story(&mut self, title: &str, story: String) {
         self.story.set_title(title);
         self.story.set_text(&story);
+        tts::speak(&format!("{}.\n{}", title, plain_text(&story)));
+    }
+    /// Shows result of the last test to the player, empty text clears it
+    pub fn display_roll(&mut self, roll: &str) {
+        self.roll.set_label(roll);
+    }
+    /// Shows the seed random values of the current run are generated from
+    pub fn display_seed(&mut self, seed: u64) {
+        self.seed.set_label(&format!("{} {}", tr("Seed"), seed));
+    }
+    /// Turns revealing the story text character by character on or off
+    pub fn set_typewriter(&mut self, typewriter: bool) {
+        self.story.text.set_typewriter(typewriter);
+    }
+    /// Sets color, font and size of the story text, None values restore the defaults
+    pub fn set_story_theme(&mut self, color: Option<Color>, font: Option<Font>, size: Option<i32>) {
+        self.story.set_theme(color, font, size);
+    }
+    /// Sets colors of the game screen background and the choice buttons, None values restore the defaults
+    ///
+    /// Background color replaces the background image
+    pub fn set_colors(&mut self, background: Option<Color>, accent: Option<Color>) {
+        match background {
+            Some(c) => {
+                self.game_window.set_frame(FrameType::FlatBox);
+                self.game_window.set_color(c);
+                if let Some(art) = self.art.as_mut() {
+                    art.hide();
+                }
+            }
+            None => {
+                self.game_window.set_frame(FrameType::NoBox);
+                if let Some(art) = self.art.as_mut() {
+                    art.show();
+                }
+            }
+        }
+        self.choices.accent = accent;
+        self.game_window.redraw();
     }
     /// Clears record window
     pub fn clear_records(&mut self) {
@@ -327,11 +639,25 @@ impl GameWindow {
     ///
     /// don't call more than once per game
     /// use update_records to update the screen
-    pub fn fill_records(&mut self, records: &HashMap<String, Record>) {
-        records
-            .iter()
-            .filter(|x| x.1.category != "hidden")
-            .for_each(|x| self.records.set_record(x.1));
+    ///
+    /// Records whose values changed since the last update are highlighted for a while
+    ///
+    /// Values of derived records are computed from the other records
+    pub fn fill_records(&mut self, records: &HashMap<String, Record>, rounding: Rounding) {
+        let mut changed = HashSet::new();
+        for rec in records.values().filter(|x| x.is_hidden() == false) {
+            let value = match rec.current_value(records, rounding) {
+                Ok(v) => v,
+                Err(e) => {
+                    println!("Could not evaluate derived record {}: {}", rec.name, e);
+                    0
+                }
+            };
+            if self.records.set_record(&rec.name, &rec.category, value) {
+                changed.insert(rec.name.clone());
+            }
+        }
+        self.records.highlight_changed(changed);
     }
     /// Updates choices window
     ///
@@ -355,10 +681,16 @@ impl RecordWindow {
     fn create(rect: Rect) -> Self {
         let mut widget = Widget::new(rect.x, rect.y, rect.w, rect.h - 40, None);
         let categories = Rc::new(RefCell::new(HashMap::new()));
+        let collapsed = Rc::new(RefCell::new(HashSet::new()));
+        let headers = Rc::new(RefCell::new(Vec::new()));
+        let changed = Rc::new(RefCell::new(HashSet::new()));
 
         widget.draw({
             let categories: Rc<RefCell<HashMap<String, HashMap<String, i32>>>> =
                 Rc::clone(&categories);
+            let collapsed: Rc<RefCell<HashSet<String>>> = Rc::clone(&collapsed);
+            let headers: Rc<RefCell<Vec<(i32, i32, String)>>> = Rc::clone(&headers);
+            let changed: Rc<RefCell<HashSet<String>>> = Rc::clone(&changed);
             move |wid| {
                 let x = wid.x();
                 let y = wid.y();
@@ -366,11 +698,15 @@ impl RecordWindow {
                 let h = wid.h();
                 let font_size = wid.label_size() + wid.label_size() / 4;
                 let el = categories.borrow();
+                let collapsed = collapsed.borrow();
+                let changed = changed.borrow();
+                let mut headers = headers.borrow_mut();
                 let mut offset = font_size;
+                headers.clear();
 
                 push_clip(x, y, w, h);
                 draw_text2(
-                    "Story Records",
+                    tr("Story Records"),
                     x,
                     y + offset,
                     w - w / 4,
@@ -379,11 +715,28 @@ impl RecordWindow {
                 );
                 offset += font_size * 3;
                 for e in el.iter() {
-                    draw_text(&e.0, x + 10, y + offset);
+                    let folded = collapsed.contains(e.0);
+                    let header = if folded {
+                        format!("+ {}", e.0)
+                    } else {
+                        format!("- {}", e.0)
+                    };
+                    draw_text(&header, x + 10, y + offset);
+                    headers.push((y + offset - font_size, y + offset, e.0.clone()));
                     offset += font_size;
+                    if folded {
+                        continue;
+                    }
                     for c in e.1.iter() {
                         let txt = format!("{}: {}", c.0, c.1);
-                        draw_text(&txt, x + 20, y + offset);
+                        if changed.contains(c.0) {
+                            let color = get_color();
+                            set_draw_color(CHANGE_HIGHLIGHT_COLOR);
+                            draw_text(&txt, x + 20, y + offset);
+                            set_draw_color(color);
+                        } else {
+                            draw_text(&txt, x + 20, y + offset);
+                        }
                         offset += font_size;
                     }
                 }
@@ -391,30 +744,105 @@ impl RecordWindow {
             }
         });
 
-        RecordWindow { widget, categories }
+        widget.handle({
+            let collapsed: Rc<RefCell<HashSet<String>>> = Rc::clone(&collapsed);
+            let headers: Rc<RefCell<Vec<(i32, i32, String)>>> = Rc::clone(&headers);
+            move |wid, ev| {
+                use fltk::enums::Event;
+                match ev {
+                    Event::Push => {
+                        let y = app::event_y();
+                        let clicked = headers
+                            .borrow()
+                            .iter()
+                            .find(|x| y >= x.0 && y < x.1)
+                            .map(|x| x.2.clone());
+                        if let Some(category) = clicked {
+                            let mut collapsed = collapsed.borrow_mut();
+                            if collapsed.remove(&category) == false {
+                                collapsed.insert(category);
+                            }
+                            if let Some(mut p) = wid.parent() {
+                                p.redraw();
+                            }
+                            return true;
+                        }
+                        false
+                    }
+                    _ => false,
+                }
+            }
+        });
+
+        RecordWindow {
+            widget,
+            categories,
+            collapsed,
+            headers,
+            changed,
+            timeout: Rc::new(RefCell::new(None)),
+        }
     }
     /// Removes all group and record displays
+    ///
+    /// Collapsed categories are expanded again
     fn clear(&mut self) {
         self.categories.borrow_mut().clear();
+        self.collapsed.borrow_mut().clear();
+        self.headers.borrow_mut().clear();
+        self.clear_changed();
+    }
+    /// Stops highlighting records that changed
+    fn clear_changed(&mut self) {
+        if let Some(handle) = self.timeout.borrow_mut().take() {
+            app::remove_timeout3(handle);
+        }
+        self.changed.borrow_mut().clear();
+    }
+    /// Highlights the records for a few seconds
+    fn highlight_changed(&mut self, names: HashSet<String>) {
+        self.clear_changed();
+        if names.len() == 0 {
+            return;
+        }
+        *self.changed.borrow_mut() = names;
+        let handle = app::add_timeout3(CHANGE_HIGHLIGHT_TIME, {
+            let changed: Rc<RefCell<HashSet<String>>> = Rc::clone(&self.changed);
+            let timeout: Rc<RefCell<Option<app::TimeoutHandle>>> = Rc::clone(&self.timeout);
+            let widget = self.widget.clone();
+            move |_| {
+                changed.borrow_mut().clear();
+                *timeout.borrow_mut() = None;
+                if let Some(mut p) = widget.parent() {
+                    p.redraw();
+                }
+            }
+        });
+        *self.timeout.borrow_mut() = Some(handle);
     }
     /// This will add a record into the window.
     ///
     /// Any records for categories will be created if they haven't been already
     /// Existing records will be updated
-    fn set_record(&mut self, record: &Record) {
+    ///
+    /// Returns true if the record was already shown with a different value
+    fn set_record(&mut self, name: &String, category: &String, value: i32) -> bool {
         let mut categories = self.categories.borrow_mut();
         let &mut cat;
 
         // creating a category if it haven't been created yet, otherwise we just grab it
-        if let Some(v) = categories.get_mut(&record.category) {
+        if let Some(v) = categories.get_mut(category) {
             cat = v
         } else {
             // here is group creation
             let new_group = HashMap::new();
-            categories.insert(record.category.clone(), new_group);
-            cat = categories.get_mut(&record.category).unwrap();
+            categories.insert(category.clone(), new_group);
+            cat = categories.get_mut(category).unwrap();
+        }
+        match cat.insert(name.clone(), value) {
+            Some(old) => old != value,
+            None => false,
         }
-        cat.insert(record.name.clone(), record.value);
     }
 }
 impl ChoiceWindow {
@@ -422,23 +850,40 @@ impl ChoiceWindow {
     ///
     /// Use add_choice and clear_choices to populate and clear the menu
     fn create(area: Rect) -> Self {
-        let window = Scroll::new(area.x, area.y, area.w, area.h, "");
+        let mut window = Scroll::new(area.x, area.y, area.w, area.h, "");
+        window.set_type(fltk::group::ScrollType::Vertical);
         window.end();
 
-        Self { window }
+        Self {
+            window,
+            content_height: 0,
+            accent: None,
+        }
     }
     /// Adds a button with supplied text as available choice
+    ///
+    /// The button grows to fit the text wrapped to its width
     fn add_choice(&mut self, text: &str, active: bool) {
         let count = self.window.children() - 2;
         let label = format!("{}: {}", count + 1, text);
         let mut butt = Button::new(
             self.window.x(),
-            self.window.y() + count * 30,
+            self.window.y() - self.window.yposition() + self.content_height,
             self.window.width(),
             25,
             "",
         );
         butt.set_label(&label);
+        butt.set_align(Align::Wrap | Align::Inside);
+        if let Some(color) = self.accent {
+            butt.set_color(color);
+            butt.set_selection_color(color.darker());
+        }
+        // leaving some space on the sides so the text doesn't touch the button edges
+        let lines = wrapped_lines(&label, butt.w() - 10, butt.label_font(), butt.label_size());
+        let height = i32::max(25, lines * (butt.label_size() + 4) + 8);
+        butt.set_size(butt.w(), height);
+        self.content_height += height + 5;
 
         let (s, _r) = app::channel();
         butt.set_callback(move |_| {
@@ -446,12 +891,17 @@ impl ChoiceWindow {
         });
         butt.handle(|wid, ev| {
             use fltk::enums::Event;
-            if let Event::Resize = ev {
-                let parent = wid.parent().unwrap();
-                let w = parent.w();
-                let h = wid.h();
-                wid.set_size(w, h);
-                wid.redraw();
+            match ev {
+                Event::Resize => {
+                    let parent = wid.parent().unwrap();
+                    let w = parent.w();
+                    let h = wid.h();
+                    wid.set_size(w, h);
+                    wid.redraw();
+                }
+                // focus only moves between choices when they're selected with the keyboard
+                Event::Focus if app::visible_focus() => tts::speak(&wid.label()),
+                _ => {}
             }
             // returning false because otherwise only the first button gets redrawn properly on resize
             false
@@ -467,19 +917,49 @@ impl ChoiceWindow {
     /// Removes all choice buttons from the menu
     fn clear_choices(&mut self) {
         self.window.clear();
+        self.window.scroll_to(0, 0);
+        self.content_height = 0;
     }
 }
+/// Counts lines the text takes when wrapped at word boundaries to fit the width
+fn wrapped_lines(text: &str, width: i32, font: Font, size: i32) -> i32 {
+    fltk::draw::set_font(font, size);
+    let space = fltk::draw::width(" ") as i32;
+    let mut lines = 1;
+    let mut line_width = 0;
+    for word in text.split_whitespace() {
+        let word_width = fltk::draw::width(word) as i32;
+        if line_width > 0 && line_width + space + word_width > width {
+            lines += 1;
+            line_width = word_width;
+        } else if line_width > 0 {
+            line_width += space + word_width;
+        } else {
+            line_width = word_width;
+        }
+    }
+    lines
+}
 impl StoryWindow {
     /// Creates empty story area
     ///
     /// The story window is where the main story events are displayed
     fn create(area: Rect) -> Self {
-        let text = TextRenderer::new(area.x + 30, area.y + 100, area.w - 80, area.h - 100, "");
-        let mut title = Frame::new(area.x + 30, area.y + 40, area.w - 80, 21, None);
+        let mut text = TextRenderer::new(area.x, area.y, area.w, area.h, "");
+        let mut title = Frame::new(area.x, area.y, area.w, 21, None);
         title.set_label_size(21);
+        StoryWindow::place(&mut title, &mut text, area);
+
+        let (sender, _) = app::channel();
+        text.set_link_callback(move |page| sender.send(Event::StoryLink(page)));
 
         StoryWindow { title, text }
     }
+    /// Positions title and story text within the story area
+    fn place(title: &mut Frame, text: &mut Widget, area: Rect) {
+        title.resize(area.x + 30, area.y + 40, area.w - 80, 21);
+        text.resize(area.x + 30, area.y + 100, area.w - 80, area.h - 100);
+    }
     /// Sets page's title
     fn set_title(&mut self, text: &str) {
         self.title.set_label(text);
@@ -488,4 +968,10 @@ impl StoryWindow {
     fn set_text(&mut self, text: &str) {
         self.text.set_text(text);
     }
+    /// Sets color, font and size of the story text
+    fn set_theme(&mut self, color: Option<Color>, font: Option<Font>, size: Option<i32>) {
+        self.text.set_color(color);
+        self.text.set_font(font);
+        self.text.set_size(size);
+    }
 }